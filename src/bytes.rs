@@ -0,0 +1,299 @@
+use serde::{Deserialize, Serialize};
+
+use crate::parallel::sort_unstable_by;
+use crate::unsorted::{antimodes_on_sorted, mode_on_sorted, modes_on_sorted, ANTIMODES_LIMIT};
+use crate::{Commute, Parallelism};
+
+/// A commutative data structure for byte strings, stored in a single arena.
+///
+/// This is the byte-slice counterpart of `Unsorted<Vec<u8>>`. Values are
+/// copied into one contiguous buffer instead of being allocated one by one,
+/// so fields read from a CSV `ByteRecord` can be added as `&[u8]` without
+/// converting them to `String`s or `Vec`s first.
+///
+/// Values are ordered bytewise, which for UTF-8 is the same as ordering by
+/// code point. Results borrow from the arena.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(try_from = "UnsortedBytesParts")]
+pub struct UnsortedBytes {
+    buf: Vec<u8>,
+    spans: Vec<(usize, usize)>,
+    sorted: bool,
+    #[serde(default)]
+    parallelism: Parallelism,
+}
+
+/// The serialized form of an `UnsortedBytes`, which is checked before use.
+#[derive(Deserialize)]
+struct UnsortedBytesParts {
+    buf: Vec<u8>,
+    spans: Vec<(usize, usize)>,
+    sorted: bool,
+    #[serde(default)]
+    parallelism: Parallelism,
+}
+
+impl TryFrom<UnsortedBytesParts> for UnsortedBytes {
+    type Error = String;
+
+    fn try_from(v: UnsortedBytesParts) -> Result<UnsortedBytes, String> {
+        let in_buf = |&(start, len): &(usize, usize)| {
+            start.checked_add(len).is_some_and(|end| end <= v.buf.len())
+        };
+        if !v.spans.iter().all(in_buf) {
+            return Err("a value lies outside the byte buffer".to_owned());
+        }
+        Ok(UnsortedBytes {
+            buf: v.buf,
+            spans: v.spans,
+            sorted: v.sorted,
+            parallelism: v.parallelism,
+        })
+    }
+}
+
+impl UnsortedBytes {
+    /// Create initial empty state.
+    #[must_use]
+    pub fn new() -> UnsortedBytes {
+        Default::default()
+    }
+
+    /// Add a new value, copying it into the arena.
+    #[inline]
+    pub fn add(&mut self, v: &[u8]) {
+        if self.sorted {
+            self.sorted = self.spans.last().map_or(true, |&s| self.get(s) <= v);
+        }
+        let start = self.buf.len();
+        self.buf.extend_from_slice(v);
+        self.spans.push((start, v.len()));
+    }
+
+    /// Set whether sorting runs on the rayon thread pool. The default,
+    /// `Parallelism::Auto`, only goes parallel on large inputs.
+    #[inline]
+    pub fn set_parallelism(&mut self, parallelism: Parallelism) {
+        self.parallelism = parallelism;
+    }
+
+    /// Returns the parallelism setting.
+    #[inline]
+    #[must_use]
+    pub const fn parallelism(&self) -> Parallelism {
+        self.parallelism
+    }
+
+    #[inline]
+    fn get(&self, (start, len): (usize, usize)) -> &[u8] {
+        &self.buf[start..start + len]
+    }
+
+    /// Returns the number of values.
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.spans.len()
+    }
+
+    /// Returns true if there are no values.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+
+    /// Returns the number of bytes allocated by this state.
+    #[inline]
+    #[must_use]
+    pub fn mem_usage(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.buf.capacity()
+            + self.spans.capacity() * std::mem::size_of::<(usize, usize)>()
+    }
+
+    #[inline]
+    fn sort(&mut self) {
+        if self.sorted {
+            return;
+        }
+        let buf = &self.buf;
+        sort_unstable_by(&mut self.spans, self.parallelism, |&(s1, l1), &(s2, l2)| {
+            buf[s1..s1 + l1].cmp(&buf[s2..s2 + l2])
+        });
+        self.sorted = true;
+    }
+
+    /// Sorts the values if needed and returns an iterator over them.
+    fn sorted_iter(&mut self) -> impl Iterator<Item = &[u8]> {
+        self.sort();
+        self.spans.iter().map(|&s| self.get(s))
+    }
+
+    /// Returns the smallest value, without sorting.
+    #[must_use]
+    pub fn min(&self) -> Option<&[u8]> {
+        if self.sorted {
+            return self.spans.first().map(|&s| self.get(s));
+        }
+        self.spans.iter().map(|&s| self.get(s)).min()
+    }
+
+    /// Returns the largest value, without sorting.
+    #[must_use]
+    pub fn max(&self) -> Option<&[u8]> {
+        if self.sorted {
+            return self.spans.last().map(|&s| self.get(s));
+        }
+        self.spans.iter().map(|&s| self.get(s)).max()
+    }
+
+    /// Returns the number of distinct values.
+    pub fn cardinality(&mut self) -> usize {
+        let mut it = self.sorted_iter();
+        let Some(mut prev) = it.next() else {
+            return 0;
+        };
+        let mut count = 1;
+        for v in it {
+            if v != prev {
+                count += 1;
+                prev = v;
+            }
+        }
+        count
+    }
+
+    /// Returns the mode of the data. See `Unsorted::mode`.
+    pub fn mode(&mut self) -> Option<&[u8]> {
+        mode_on_sorted(self.sorted_iter())
+    }
+
+    /// Returns the modes of the data. See `Unsorted::modes`.
+    pub fn modes(&mut self) -> (Vec<&[u8]>, usize, u64) {
+        let size = self.len();
+        modes_on_sorted(self.sorted_iter(), size)
+    }
+
+    /// Returns the antimodes of the data. See `Unsorted::antimodes`.
+    pub fn antimodes(&mut self) -> (Vec<&[u8]>, usize, u64) {
+        let size = self.len();
+        antimodes_on_sorted(self.sorted_iter(), size, ANTIMODES_LIMIT)
+    }
+}
+
+impl Commute for UnsortedBytes {
+    #[inline]
+    fn merge(&mut self, v: UnsortedBytes) {
+        if v.is_empty() {
+            return;
+        }
+        let offset = self.buf.len();
+        self.sorted = self.is_empty() && v.sorted;
+        self.buf.extend(v.buf);
+        self.spans.extend(
+            v.spans
+                .into_iter()
+                .map(|(start, len)| (start + offset, len)),
+        );
+    }
+}
+
+impl Default for UnsortedBytes {
+    #[inline]
+    fn default() -> UnsortedBytes {
+        UnsortedBytes {
+            buf: Vec::new(),
+            spans: Vec::new(),
+            sorted: true, // empty is sorted
+            parallelism: Parallelism::Auto,
+        }
+    }
+}
+
+impl<S: AsRef<[u8]>> FromIterator<S> for UnsortedBytes {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = S>>(it: I) -> UnsortedBytes {
+        let mut v = UnsortedBytes::new();
+        v.extend(it);
+        v
+    }
+}
+
+impl<S: AsRef<[u8]>> Extend<S> for UnsortedBytes {
+    #[inline]
+    fn extend<I: IntoIterator<Item = S>>(&mut self, it: I) {
+        for v in it {
+            self.add(v.as_ref());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::UnsortedBytes;
+    use crate::{Commute, Parallelism, Unsorted};
+
+    #[test]
+    fn matches_unsorted() {
+        let data = ["b", "a", "c", "b", "", "a", "b", "é"];
+        let mut bytes: UnsortedBytes = data.into_iter().collect();
+        let mut unsorted: Unsorted<Vec<u8>> = data.iter().map(|s| s.as_bytes().to_vec()).collect();
+        assert_eq!(bytes.min(), Some(&b""[..]));
+        assert_eq!(bytes.max(), Some("é".as_bytes()));
+        assert_eq!(bytes.cardinality(), unsorted.cardinality());
+        assert_eq!(bytes.mode(), unsorted.mode().as_deref());
+        let (modes, count, occurrences) = unsorted.modes();
+        assert_eq!(
+            bytes.modes(),
+            (
+                modes.iter().map(Vec::as_slice).collect(),
+                count,
+                occurrences
+            )
+        );
+        let (antimodes, count, occurrences) = unsorted.antimodes();
+        assert_eq!(
+            bytes.antimodes(),
+            (
+                antimodes.iter().map(Vec::as_slice).collect(),
+                count,
+                occurrences
+            )
+        );
+    }
+
+    #[test]
+    fn merge_arenas() {
+        let mut b1: UnsortedBytes = ["x", "y"].into_iter().collect();
+        let b2: UnsortedBytes = ["a", "y"].into_iter().collect();
+        b1.merge(b2);
+        b1.merge(UnsortedBytes::new());
+        assert_eq!(b1.len(), 4);
+        assert_eq!(b1.min(), Some(&b"a"[..]));
+        assert_eq!(b1.mode(), Some(&b"y"[..]));
+        assert_eq!(b1.cardinality(), 3);
+
+        let json = serde_json::to_string(&b1).unwrap();
+        assert!(serde_json::from_str::<UnsortedBytes>(&json).unwrap() == b1);
+        for invalid in [
+            r#"{"buf":[97,98],"spans":[[0,1],[1,2]],"sorted":false}"#,
+            r#"{"buf":[97],"spans":[[18446744073709551615,2]],"sorted":false}"#,
+        ] {
+            assert!(serde_json::from_str::<UnsortedBytes>(invalid).is_err());
+        }
+    }
+
+    #[test]
+    fn parallel_sort() {
+        let data: Vec<String> = (0..1000).map(|i| ((i * 7919) % 101).to_string()).collect();
+        let mut sequential: UnsortedBytes = data.iter().collect();
+        sequential.set_parallelism(Parallelism::Sequential);
+        let mut parallel: UnsortedBytes = data.iter().collect();
+        parallel.set_parallelism(Parallelism::Force);
+        assert_eq!(parallel.parallelism(), Parallelism::Force);
+        assert_eq!(parallel.cardinality(), 101);
+        assert_eq!(sequential.cardinality(), 101);
+        assert_eq!(parallel.modes(), sequential.modes());
+    }
+}
@@ -0,0 +1,317 @@
+use std::collections::HashSet;
+use std::hash::Hash;
+
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::hashing::stable_hash;
+use crate::Commute;
+
+/// The default number of distinct values counted exactly.
+const DEFAULT_EXACT_LIMIT: usize = 10_000;
+
+/// The default HyperLogLog precision, for a relative error of about 0.8%.
+const DEFAULT_PRECISION: u8 = 14;
+
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq)]
+enum State<T: Eq + Hash> {
+    Exact(HashSet<T>),
+    Approx(Vec<u8>),
+}
+
+/// A distinct-value counter that is exact for small columns and switches
+/// to a HyperLogLog sketch for large ones.
+///
+/// Values are kept in a set until there are more than `exact_limit` of
+/// them. From then on, the set is replaced by a HyperLogLog sketch with
+/// `2^precision` one-byte registers, so memory stays bounded and the count
+/// becomes an estimate with a relative standard error of about
+/// `1.04 / sqrt(2^precision)`. `is_exact` reports which is the case.
+///
+/// Sketches hash values with a stable hash, so they can be merged across
+/// threads and runs. Merging requires the same precision.
+#[derive(Clone, Deserialize, PartialEq, Eq)]
+#[serde(
+    try_from = "CardinalityParts<T>",
+    bound(deserialize = "T: Eq + Hash + Deserialize<'de>")
+)]
+pub struct Cardinality<T: Eq + Hash> {
+    exact_limit: usize,
+    precision: u8,
+    state: State<T>,
+}
+
+/// The serialized form of a `Cardinality`, which is checked before use.
+#[derive(Deserialize)]
+struct CardinalityParts<T: Eq + Hash> {
+    exact_limit: usize,
+    precision: u8,
+    state: State<T>,
+    // derived from the above when serializing, so it is ignored here
+    #[serde(default, rename = "relative_error")]
+    _relative_error: f64,
+}
+
+impl<T: Eq + Hash + Serialize> Serialize for Cardinality<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Cardinality", 4)?;
+        state.serialize_field("exact_limit", &self.exact_limit)?;
+        state.serialize_field("precision", &self.precision)?;
+        state.serialize_field("state", &self.state)?;
+        state.serialize_field("relative_error", &self.relative_error())?;
+        state.end()
+    }
+}
+
+impl<T: Eq + Hash> TryFrom<CardinalityParts<T>> for Cardinality<T> {
+    type Error = String;
+
+    fn try_from(v: CardinalityParts<T>) -> Result<Cardinality<T>, String> {
+        if !(4..=18).contains(&v.precision) {
+            return Err(format!(
+                "cardinality precision must be in 4..=18, not {}",
+                v.precision
+            ));
+        }
+        if let State::Approx(registers) = &v.state {
+            if registers.len() != 1 << v.precision {
+                return Err(format!(
+                    "cardinality sketch of precision {} has {} registers",
+                    v.precision,
+                    registers.len()
+                ));
+            }
+        }
+        Ok(Cardinality {
+            exact_limit: v.exact_limit,
+            precision: v.precision,
+            state: v.state,
+        })
+    }
+}
+
+impl<T: Eq + Hash> Cardinality<T> {
+    /// Create initial empty state that counts up to 10,000 distinct values
+    /// exactly, and then estimates with a relative error of about 0.8%.
+    #[must_use]
+    pub fn new() -> Cardinality<T> {
+        Cardinality::with_limit(DEFAULT_EXACT_LIMIT, DEFAULT_PRECISION)
+    }
+
+    /// Create initial empty state that counts up to `exact_limit` distinct
+    /// values exactly, and then switches to a sketch with `2^precision`
+    /// registers.
+    ///
+    /// # Panics
+    ///
+    /// If `precision` is not in `4..=18`.
+    #[must_use]
+    pub fn with_limit(exact_limit: usize, precision: u8) -> Cardinality<T> {
+        assert!((4..=18).contains(&precision), "precision must be in 4..=18");
+        Cardinality {
+            exact_limit,
+            precision,
+            state: State::Exact(HashSet::new()),
+        }
+    }
+
+    /// Add a value.
+    #[inline]
+    pub fn add(&mut self, value: T) {
+        match &mut self.state {
+            State::Exact(set) => {
+                set.insert(value);
+                if set.len() > self.exact_limit {
+                    self.switch_to_sketch();
+                }
+            }
+            State::Approx(registers) => insert_hash(registers, self.precision, &value),
+        }
+    }
+
+    fn switch_to_sketch(&mut self) {
+        let mut registers = vec![0; 1 << self.precision];
+        if let State::Exact(set) = &self.state {
+            for value in set {
+                insert_hash(&mut registers, self.precision, value);
+            }
+        }
+        self.state = State::Approx(registers);
+    }
+
+    /// Returns the number of distinct values, exact or estimated.
+    #[must_use]
+    pub fn count(&self) -> u64 {
+        match &self.state {
+            State::Exact(set) => set.len() as u64,
+            State::Approx(registers) => estimate(registers).round() as u64,
+        }
+    }
+
+    /// Returns true if `count` is exact, i.e. there have been no more than
+    /// `exact_limit` distinct values.
+    #[inline]
+    #[must_use]
+    pub const fn is_exact(&self) -> bool {
+        matches!(self.state, State::Exact(_))
+    }
+
+    /// Returns the relative standard error of `count`, which is `0` while
+    /// it is exact.
+    #[inline]
+    #[must_use]
+    pub fn relative_error(&self) -> f64 {
+        if self.is_exact() {
+            0.0
+        } else {
+            1.04 / f64::from(1u32 << self.precision).sqrt()
+        }
+    }
+}
+
+/// Updates the HyperLogLog `registers` with the hash of `value`.
+#[inline]
+fn insert_hash<T: Hash + ?Sized>(registers: &mut [u8], precision: u8, value: &T) {
+    let hash = stable_hash(value);
+    let index = (hash >> (64 - precision)) as usize;
+    // the sentinel bit caps the rank at `65 - precision`
+    let rest = (hash << precision) | (1 << (precision - 1));
+    let rank = rest.leading_zeros() as u8 + 1;
+    registers[index] = registers[index].max(rank);
+}
+
+/// Returns the HyperLogLog estimate for `registers`, with the linear
+/// counting correction for small cardinalities.
+fn estimate(registers: &[u8]) -> f64 {
+    let m = registers.len() as f64;
+    let alpha = 0.7213 / (1.0 + 1.079 / m);
+    let sum: f64 = registers.iter().map(|&r| (-f64::from(r)).exp2()).sum();
+    let raw = alpha * m * m / sum;
+    let zeros = registers.iter().filter(|&&r| r == 0).count();
+    if raw <= 2.5 * m && zeros > 0 {
+        m * (m / zeros as f64).ln()
+    } else {
+        raw
+    }
+}
+
+impl<T: Eq + Hash> Commute for Cardinality<T> {
+    /// # Panics
+    ///
+    /// If the accumulators have different precisions, and so different
+    /// numbers of registers.
+    fn merge(&mut self, v: Cardinality<T>) {
+        assert!(
+            self.precision == v.precision,
+            "cannot merge cardinalities with different precisions"
+        );
+        match v.state {
+            State::Exact(set) => {
+                for value in set {
+                    self.add(value);
+                }
+            }
+            State::Approx(other) => {
+                if self.is_exact() {
+                    self.switch_to_sketch();
+                }
+                if let State::Approx(registers) = &mut self.state {
+                    assert_eq!(registers.len(), other.len(), "register counts differ");
+                    for (r, o) in registers.iter_mut().zip(other) {
+                        *r = (*r).max(o);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<T: Eq + Hash> Default for Cardinality<T> {
+    #[inline]
+    fn default() -> Cardinality<T> {
+        Cardinality::new()
+    }
+}
+
+impl<T: Eq + Hash> FromIterator<T> for Cardinality<T> {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = T>>(it: I) -> Cardinality<T> {
+        let mut v = Cardinality::new();
+        v.extend(it);
+        v
+    }
+}
+
+impl<T: Eq + Hash> Extend<T> for Cardinality<T> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
+        for value in it {
+            self.add(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Cardinality;
+    use crate::Commute;
+
+    #[test]
+    fn exact_then_approximate() {
+        let mut card = Cardinality::with_limit(1000, 12);
+        card.extend((0..500u32).chain(0..500));
+        assert!(card.is_exact());
+        assert_eq!(card.count(), 500);
+        assert_eq!(card.relative_error(), 0.0);
+
+        card.extend(0..100_000u32);
+        assert!(!card.is_exact());
+        let error = (card.count() as f64 - 100_000.0).abs() / 100_000.0;
+        assert!(error < 3.0 * card.relative_error());
+    }
+
+    #[test]
+    fn merge_mixed() {
+        let mut a: Cardinality<String> = Cardinality::with_limit(100, 10);
+        let mut b = Cardinality::with_limit(100, 10);
+        a.extend((0..80).map(|i| i.to_string()));
+        b.extend((40..120).map(|i| i.to_string()));
+        let mut exact = a.clone();
+        exact.merge(b.clone());
+        // the union of 120 values is over the limit
+        assert!(!exact.is_exact());
+        assert!((exact.count() as i64 - 120).abs() <= 5);
+
+        let mut big: Cardinality<String> = Cardinality::with_limit(10, 10);
+        big.extend((0..5000).map(|i| i.to_string()));
+        a.merge(big);
+        assert!(!a.is_exact());
+        let error = (a.count() as f64 - 5000.0).abs() / 5000.0;
+        assert!(error < 0.1);
+    }
+
+    #[test]
+    fn deserialize_checks_sketch() {
+        let mut card: Cardinality<u32> = Cardinality::with_limit(2, 4);
+        card.extend(0..10);
+        let json = serde_json::to_string(&card).unwrap();
+        assert!(json.ends_with(r#","relative_error":0.26}"#), "{json}");
+        assert!(serde_json::from_str::<Cardinality<u32>>(&json).unwrap() == card);
+
+        let registers = "0,".repeat(15);
+        for bad in [
+            json.replace(r#""precision":4"#, r#""precision":3"#),
+            json.replace(r#""precision":4"#, r#""precision":19"#),
+            // one register short of 2^4
+            format!(
+                r#"{{"exact_limit":2,"precision":4,"state":{{"Approx":[{}]}}}}"#,
+                registers.trim_end_matches(',')
+            ),
+        ] {
+            assert!(
+                serde_json::from_str::<Cardinality<u32>>(&bad).is_err(),
+                "{bad}"
+            );
+        }
+    }
+}
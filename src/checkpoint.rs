@@ -0,0 +1,160 @@
+use std::io::{self, Read, Write};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Identifies a checkpoint written by `save_state`.
+const MAGIC: [u8; 4] = *b"QSVS";
+
+/// The version of the framing, bumped if the header layout changes.
+const FRAME_VERSION: u8 = 1;
+
+/// Saving and restoring accumulator state, so that a long-running job can
+/// resume after an interruption without re-reading its input.
+///
+/// Every accumulator that implements serde's `Serialize` and `Deserialize`
+/// gets this trait for free. The state is written as a small header (magic
+/// bytes, framing version, payload length and a CRC-32 of the payload)
+/// followed by the `bincode` encoded accumulator. `load_state` rejects
+/// truncated or corrupted checkpoints with an `InvalidData` error instead
+/// of restoring garbage.
+///
+/// The payload uses the accumulator's own serde layout, so checkpoints are
+/// meant to be restored by the same crate version. Use the versioned
+/// snapshot types (e.g. `OnlineStatsSnapshotV2`) for state that has to
+/// survive upgrades.
+pub trait Checkpoint: Sized {
+    /// Writes the current state to `writer`.
+    fn save_state<W: Write>(&self, writer: W) -> io::Result<()>;
+
+    /// Reads a state written by `save_state` from `reader`.
+    fn load_state<R: Read>(reader: R) -> io::Result<Self>;
+}
+
+impl<T: Serialize + DeserializeOwned> Checkpoint for T {
+    fn save_state<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let payload = bincode::serialize(self).map_err(invalid_data)?;
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&[FRAME_VERSION])?;
+        writer.write_all(&(payload.len() as u64).to_le_bytes())?;
+        writer.write_all(&crc32(&payload).to_le_bytes())?;
+        writer.write_all(&payload)?;
+        writer.flush()
+    }
+
+    fn load_state<R: Read>(mut reader: R) -> io::Result<T> {
+        let mut header = [0u8; 17];
+        reader.read_exact(&mut header)?;
+        if header[..4] != MAGIC {
+            return Err(invalid_data("not a qsv-stats checkpoint"));
+        }
+        if header[4] != FRAME_VERSION {
+            return Err(invalid_data(format!(
+                "unsupported checkpoint version {}",
+                header[4]
+            )));
+        }
+        let len = u64::from_le_bytes(header[5..13].try_into().unwrap());
+        let checksum = u32::from_le_bytes(header[13..].try_into().unwrap());
+
+        let mut payload = Vec::new();
+        reader.take(len).read_to_end(&mut payload)?;
+        if payload.len() as u64 != len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        if crc32(&payload) != checksum {
+            return Err(invalid_data("checkpoint checksum mismatch"));
+        }
+        bincode::deserialize(&payload).map_err(invalid_data)
+    }
+}
+
+fn invalid_data<E>(e: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+/// Returns the CRC-32 (IEEE) of `data`.
+fn crc32(data: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut c = i as u32;
+            let mut k = 0;
+            while k < 8 {
+                c = if c & 1 == 1 {
+                    0xEDB8_8320 ^ (c >> 1)
+                } else {
+                    c >> 1
+                };
+                k += 1;
+            }
+            table[i] = c;
+            i += 1;
+        }
+        table
+    };
+    !data.iter().fold(!0u32, |crc, &b| {
+        TABLE[((crc ^ u32::from(b)) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::ErrorKind;
+
+    use super::{crc32, Checkpoint};
+    use crate::{DDSketch, MinMax, OnlineStats, StringStats, Unsorted};
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut buf = Vec::new();
+        let stats = OnlineStats::from_slice(&[1, 5, 9]);
+        let minmax: MinMax<String> = ["b", "a"].iter().map(|s| s.to_string()).collect();
+        let unsorted: Unsorted<f64> = [3.0, 1.0, 2.0].into_iter().collect();
+        let strings: StringStats = ["x", "yz"].into_iter().collect();
+        let mut sketch = DDSketch::with_max_buckets(0.01, 8);
+        sketch.extend([1.0, -3.0, 0.0]);
+        stats.save_state(&mut buf).unwrap();
+        minmax.save_state(&mut buf).unwrap();
+        unsorted.save_state(&mut buf).unwrap();
+        strings.save_state(&mut buf).unwrap();
+        sketch.save_state(&mut buf).unwrap();
+
+        let mut reader = &buf[..];
+        assert_eq!(OnlineStats::load_state(&mut reader).unwrap(), stats);
+        assert_eq!(MinMax::<String>::load_state(&mut reader).unwrap(), minmax);
+        let mut restored = Unsorted::<f64>::load_state(&mut reader).unwrap();
+        assert_eq!(restored.median(), Some(2.0));
+        assert!(StringStats::load_state(&mut reader).unwrap() == strings);
+        assert_eq!(DDSketch::load_state(&mut reader).unwrap(), sketch);
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn rejects_corruption() {
+        let mut buf = Vec::new();
+        OnlineStats::from_slice(&[1, 2])
+            .save_state(&mut buf)
+            .unwrap();
+
+        let mut corrupted = buf.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        let err = OnlineStats::<f64>::load_state(&corrupted[..]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        let err = OnlineStats::<f64>::load_state(&buf[..buf.len() - 1]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+
+        let err = OnlineStats::<f64>::load_state(&b"not a checkpoint!!"[..]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+}
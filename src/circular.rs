@@ -0,0 +1,199 @@
+use std::f64::consts::TAU;
+
+use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+
+use crate::Commute;
+
+/// Online state for statistics of angles and other periodic data, such as
+/// compass bearings, times of day or days of the year.
+///
+/// Each value is treated as a direction on a circle of circumference
+/// `period`, so the mean of 359° and 1° is 0°, not 180°. The statistics
+/// come from the mean resultant vector of the directions, as unit vectors.
+///
+/// Merging requires both accumulators to have the same period.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct CircularStats {
+    period: f64,
+    sin: f64,
+    cos: f64,
+    len: u64,
+}
+
+impl CircularStats {
+    /// Create initial empty state for values that wrap around at `period`.
+    ///
+    /// # Panics
+    ///
+    /// If `period` is not positive and finite.
+    #[must_use]
+    pub fn new(period: f64) -> CircularStats {
+        assert!(
+            period > 0.0 && period.is_finite(),
+            "period must be positive and finite"
+        );
+        CircularStats {
+            period,
+            sin: 0.0,
+            cos: 0.0,
+            len: 0,
+        }
+    }
+
+    /// Create initial empty state for angles in degrees.
+    #[must_use]
+    pub fn degrees() -> CircularStats {
+        CircularStats::new(360.0)
+    }
+
+    /// Create initial empty state for angles in radians.
+    #[must_use]
+    pub fn radians() -> CircularStats {
+        CircularStats::new(TAU)
+    }
+
+    /// Create initial empty state for hours of the day.
+    #[must_use]
+    pub fn hours() -> CircularStats {
+        CircularStats::new(24.0)
+    }
+
+    /// Add a new sample.
+    #[inline]
+    pub fn add<T: ToPrimitive>(&mut self, sample: &T) {
+        let theta = sample.to_f64().unwrap() / self.period * TAU;
+        let (sin, cos) = theta.sin_cos();
+        self.sin += sin;
+        self.cos += cos;
+        self.len += 1;
+    }
+
+    /// Returns the mean direction, in `[0, period)`.
+    ///
+    /// `None` is returned if there are no samples, or the directions cancel
+    /// out so there is no mean direction (e.g. 0° and 180°).
+    #[must_use]
+    pub fn mean(&self) -> Option<f64> {
+        if self.mean_resultant_length() < 1e-12 {
+            return None;
+        }
+        let theta = self.sin.atan2(self.cos).rem_euclid(TAU);
+        // `rem_euclid` can round up to the period itself
+        Some((theta / TAU * self.period) % self.period)
+    }
+
+    /// Returns the mean resultant length, from `0` (directions spread out
+    /// evenly) to `1` (all directions the same), or `0` if there are no
+    /// samples.
+    #[must_use]
+    pub fn mean_resultant_length(&self) -> f64 {
+        if self.len == 0 {
+            return 0.0;
+        }
+        self.sin.hypot(self.cos) / self.len as f64
+    }
+
+    /// Returns the circular variance, `1 - mean_resultant_length()`, from
+    /// `0` (all directions the same) to `1`.
+    #[inline]
+    #[must_use]
+    pub fn variance(&self) -> f64 {
+        1.0 - self.mean_resultant_length()
+    }
+
+    /// Returns the circular standard deviation, `sqrt(-2 ln R)` converted
+    /// to the units of the period, where `R` is the mean resultant length.
+    ///
+    /// For concentrated data this is close to the ordinary standard
+    /// deviation. It is infinite if the directions cancel out.
+    #[must_use]
+    pub fn stddev(&self) -> f64 {
+        let r = self.mean_resultant_length();
+        (-2.0 * r.ln()).sqrt() / TAU * self.period
+    }
+
+    /// Returns the period.
+    #[inline]
+    #[must_use]
+    pub const fn period(&self) -> f64 {
+        self.period
+    }
+
+    /// Returns the number of samples.
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns true if there are no samples.
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Commute for CircularStats {
+    /// # Panics
+    ///
+    /// If the accumulators have different periods.
+    #[inline]
+    fn merge(&mut self, v: CircularStats) {
+        assert!(
+            self.period == v.period,
+            "cannot merge circular statistics with different periods"
+        );
+        self.sin += v.sin;
+        self.cos += v.cos;
+        self.len += v.len;
+    }
+}
+
+impl<T: ToPrimitive> Extend<T> for CircularStats {
+    #[inline]
+    fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
+        for sample in it {
+            self.add(&sample);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CircularStats;
+    use crate::Commute;
+
+    #[test]
+    fn wraps_around() {
+        let mut angles = CircularStats::degrees();
+        angles.extend([359.0, 1.0]);
+        assert!(angles.mean().unwrap().abs() < 1e-9);
+        assert!(angles.mean_resultant_length() > 0.999);
+        assert!((angles.stddev() - 1.0).abs() < 1e-3);
+
+        let mut hours = CircularStats::hours();
+        hours.extend([23, 1, 2]);
+        assert!((hours.mean().unwrap() - 0.675_48).abs() < 1e-5);
+
+        let mut opposite = CircularStats::degrees();
+        opposite.extend([0, 180]);
+        assert_eq!(opposite.mean(), None);
+        assert!((opposite.variance() - 1.0).abs() < 1e-12);
+        assert_eq!(CircularStats::radians().mean(), None);
+    }
+
+    #[test]
+    fn merge() {
+        let mut a = CircularStats::degrees();
+        a.extend([350, 10]);
+        let mut b = CircularStats::degrees();
+        b.extend([20]);
+        a.merge(b);
+        assert_eq!(a.len(), 3);
+        let mut whole = CircularStats::degrees();
+        whole.extend([350, 10, 20]);
+        assert!((a.mean().unwrap() - whole.mean().unwrap()).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,222 @@
+use std::io::Read;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Commute, OnlineSummary, StringStats};
+
+/// The inferred type of a CSV column.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColumnType {
+    /// Every value is empty.
+    Empty,
+    /// Every non-empty value parses as a number.
+    Numeric,
+    /// At least one non-empty value is not a number.
+    Text,
+}
+
+/// A commutative summary of one CSV column, with its type auto-detected.
+///
+/// Every value contributes to the text statistics. Values that parse as
+/// an `f64` (after trimming whitespace) also contribute to the numeric
+/// statistics, and empty values are counted as NULLs.
+#[derive(Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ColumnSummary {
+    name: Option<Vec<u8>>,
+    nulls: u64,
+    non_numeric: u64,
+    numeric: OnlineSummary,
+    text: StringStats,
+}
+
+impl ColumnSummary {
+    /// Create initial empty state.
+    #[must_use]
+    pub fn new() -> ColumnSummary {
+        Default::default()
+    }
+
+    /// Create initial empty state for the column named `name`.
+    #[must_use]
+    pub fn with_name(name: &[u8]) -> ColumnSummary {
+        ColumnSummary {
+            name: Some(name.to_vec()),
+            ..Default::default()
+        }
+    }
+
+    /// Add a raw field value.
+    #[inline]
+    pub fn add(&mut self, field: &[u8]) {
+        self.text.add(field);
+        if field.is_empty() {
+            self.nulls += 1;
+            return;
+        }
+        match std::str::from_utf8(field)
+            .ok()
+            .and_then(|s| s.trim().parse::<f64>().ok())
+            // "NaN", "inf" and "infinity" parse, but are text in a CSV
+            .filter(|x| x.is_finite())
+        {
+            Some(x) => self.numeric.add(&x),
+            None => self.non_numeric += 1,
+        }
+    }
+
+    /// Returns the column name, if the CSV data has headers.
+    #[inline]
+    #[must_use]
+    pub fn name(&self) -> Option<&[u8]> {
+        self.name.as_deref()
+    }
+
+    /// Returns the inferred type of the column.
+    #[inline]
+    #[must_use]
+    pub const fn column_type(&self) -> ColumnType {
+        if self.non_numeric > 0 {
+            ColumnType::Text
+        } else if self.numeric.is_empty() {
+            ColumnType::Empty
+        } else {
+            ColumnType::Numeric
+        }
+    }
+
+    /// Returns the number of empty values.
+    #[inline]
+    #[must_use]
+    pub const fn null_count(&self) -> u64 {
+        self.nulls
+    }
+
+    /// Returns the statistics of the values that parsed as numbers.
+    #[inline]
+    #[must_use]
+    pub const fn numeric(&self) -> &OnlineSummary {
+        &self.numeric
+    }
+
+    /// Returns the statistics of all values as text.
+    #[inline]
+    #[must_use]
+    pub const fn text(&self) -> &StringStats {
+        &self.text
+    }
+
+    /// Returns the number of values, including empty ones.
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> u64 {
+        self.text.len()
+    }
+
+    /// Returns true if there are no values.
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+}
+
+impl Commute for ColumnSummary {
+    #[inline]
+    fn merge(&mut self, v: ColumnSummary) {
+        if self.name.is_none() {
+            self.name = v.name;
+        }
+        self.nulls += v.nulls;
+        self.non_numeric += v.non_numeric;
+        self.numeric.merge(v.numeric);
+        self.text.merge(v.text);
+    }
+}
+
+/// Reads every record of `rdr` and returns a summary of each column.
+///
+/// If the reader has headers, they are used as the column names. Records
+/// with more fields than the first record add new columns, so ragged data
+/// is summarized as far as it goes (when the reader is `flexible`).
+pub fn summarize_csv<R: Read>(rdr: &mut csv::Reader<R>) -> csv::Result<Vec<ColumnSummary>> {
+    let mut columns: Vec<ColumnSummary> = if rdr.has_headers() {
+        rdr.byte_headers()?
+            .iter()
+            .map(ColumnSummary::with_name)
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let mut record = csv::ByteRecord::new();
+    while rdr.read_byte_record(&mut record)? {
+        if columns.len() < record.len() {
+            columns.resize_with(record.len(), ColumnSummary::new);
+        }
+        for (column, field) in columns.iter_mut().zip(record.iter()) {
+            column.add(field);
+        }
+    }
+    Ok(columns)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{summarize_csv, ColumnSummary, ColumnType};
+    use crate::Commute;
+
+    #[test]
+    fn summarize() {
+        let data = "id,name,score,notes\n1,ann, 3.5,\n2,bob,,\n3,cy,-1,\n";
+        let mut rdr = csv::Reader::from_reader(data.as_bytes());
+        let columns = summarize_csv(&mut rdr).unwrap();
+        assert_eq!(columns.len(), 4);
+
+        let types: Vec<_> = columns.iter().map(ColumnSummary::column_type).collect();
+        assert_eq!(
+            types,
+            [
+                ColumnType::Numeric,
+                ColumnType::Text,
+                ColumnType::Numeric,
+                ColumnType::Empty
+            ]
+        );
+        assert_eq!(columns[0].name(), Some(&b"id"[..]));
+        assert_eq!(columns[0].numeric().mean(), 2.0);
+        assert_eq!(columns[1].text().max_length(), Some(3));
+        assert_eq!(columns[2].null_count(), 1);
+        assert_eq!(columns[2].numeric().min(), Some(-1.0));
+        assert_eq!(columns[3].len(), 3);
+    }
+
+    #[test]
+    fn headerless_merge() {
+        let mut rdr = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader("a,1\nb,2\n".as_bytes());
+        let mut columns = summarize_csv(&mut rdr).unwrap();
+        assert_eq!(columns[0].name(), None);
+
+        let mut rdr = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader("c,x\n".as_bytes());
+        let more = summarize_csv(&mut rdr).unwrap();
+        for (col, other) in columns.iter_mut().zip(more) {
+            col.merge(other);
+        }
+        assert_eq!(columns[0].len(), 3);
+        assert_eq!(columns[1].column_type(), ColumnType::Text);
+        assert_eq!(columns[1].numeric().max(), Some(2.0));
+    }
+
+    #[test]
+    fn non_finite_is_text() {
+        let data = "a,b\nNaN,1\ninf,infinity\n";
+        let mut rdr = csv::Reader::from_reader(data.as_bytes());
+        let columns = summarize_csv(&mut rdr).unwrap();
+        assert_eq!(columns[0].column_type(), ColumnType::Text);
+        assert_eq!(columns[0].numeric().len(), 0);
+        assert_eq!(columns[1].column_type(), ColumnType::Text);
+        assert_eq!(columns[1].numeric().len(), 1);
+    }
+}
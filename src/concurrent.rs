@@ -0,0 +1,117 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+
+use crate::Commute;
+
+static NEXT_SLOT: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    // Each thread is assigned a slot once, round-robin, so that threads are
+    // spread evenly over the shards and keep hitting the same one.
+    static THREAD_SLOT: usize = NEXT_SLOT.fetch_add(1, Ordering::Relaxed);
+}
+
+/// A thread-safe wrapper around a commutative accumulator.
+///
+/// Samples are pushed into one of several independently locked shards,
+/// chosen per thread, so that multiple ingestion threads rarely contend.
+/// Once ingestion is done, `collapse` merges all shards into one value.
+pub struct Concurrent<T> {
+    shards: Vec<Mutex<T>>,
+}
+
+impl<T: Commute + Default> Concurrent<T> {
+    /// Create a new wrapper with one shard per available CPU.
+    #[must_use]
+    pub fn new() -> Concurrent<T> {
+        let shards = thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get);
+        Concurrent::with_shards(shards)
+    }
+
+    /// Create a new wrapper with `shards` independently locked accumulators.
+    ///
+    /// A value of `0` is treated as `1`.
+    #[must_use]
+    pub fn with_shards(shards: usize) -> Concurrent<T> {
+        Concurrent {
+            shards: (0..shards.max(1)).map(|_| Mutex::default()).collect(),
+        }
+    }
+}
+
+impl<T: Commute> Concurrent<T> {
+    /// Returns the number of shards.
+    #[inline]
+    #[must_use]
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Apply `f` to the accumulator of the current thread's shard.
+    ///
+    /// This is how samples are added, e.g. `c.update(|s| s.add(&x))`.
+    #[inline]
+    pub fn update<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> R {
+        let slot = THREAD_SLOT.with(|slot| *slot) % self.shards.len();
+        let mut shard = self.shards[slot]
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        f(&mut shard)
+    }
+
+    /// Merge all shards into a single accumulator.
+    #[must_use]
+    pub fn collapse(self) -> T {
+        let mut shards = self
+            .shards
+            .into_iter()
+            .map(|m| m.into_inner().unwrap_or_else(PoisonError::into_inner));
+        let mut merged = shards.next().unwrap();
+        merged.consume(shards);
+        merged
+    }
+}
+
+impl<T: Commute + Default> Default for Concurrent<T> {
+    #[inline]
+    fn default() -> Concurrent<T> {
+        Concurrent::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+
+    use super::Concurrent;
+    use crate::{MinMax, OnlineStats};
+
+    #[test]
+    fn concurrent_ingestion() {
+        let stats: Concurrent<OnlineStats> = Concurrent::with_shards(3);
+        let minmax: Concurrent<MinMax<u64>> = Concurrent::new();
+        thread::scope(|s| {
+            for t in 0..8u64 {
+                let (stats, minmax) = (&stats, &minmax);
+                s.spawn(move || {
+                    for i in 0..1000 {
+                        let x = t * 1000 + i;
+                        stats.update(|acc| acc.add(&x));
+                        minmax.update(|acc| acc.add(x));
+                    }
+                });
+            }
+        });
+
+        let expected = OnlineStats::from_slice(&(0..8000u64).collect::<Vec<_>>());
+        let stats = stats.collapse();
+        assert_eq!(stats.len(), 8000);
+        assert!((stats.mean() - expected.mean()).abs() < 1e-9);
+        assert!((stats.variance() - expected.variance()).abs() < 1e-6);
+
+        let minmax = minmax.collapse();
+        assert_eq!(minmax.min(), Some(&0));
+        assert_eq!(minmax.max(), Some(&7999));
+    }
+}
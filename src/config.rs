@@ -0,0 +1,533 @@
+use std::ops::{BitOr, BitOrAssign, Sub};
+
+use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+
+use crate::unsorted::ANTIMODES_LIMIT;
+#[cfg(feature = "ostree")]
+use crate::OrderStatTree;
+use crate::{Commute, Describe, OnlineStats, Parallelism, QuantileMethod, Unsorted};
+
+/// How samples that convert to `NaN` are handled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NanPolicy {
+    /// Add `NaN` like any other sample, so the mean and variance are `NaN`
+    /// and its position in sorted order is arbitrary.
+    #[default]
+    Include,
+    /// Drop `NaN` samples, and count them separately.
+    Skip,
+    /// Count `NaN` samples as NULLs.
+    Null,
+}
+
+impl NanPolicy {
+    /// Returns true if `sample` is `NaN`, i.e. not comparable even to
+    /// itself, and this policy keeps it out of the statistics.
+    #[inline]
+    pub(crate) fn excludes<T: PartialOrd>(self, sample: &T) -> bool {
+        self != NanPolicy::Include && sample.partial_cmp(sample).is_none()
+    }
+}
+
+/// An accumulator that applies a `NanPolicy` to the samples added to it,
+/// as created by `StatsConfig::unsorted` and `StatsConfig::online_stats`.
+///
+/// `NaN` samples are added as usual under `NanPolicy::Include`, dropped
+/// and counted under `NanPolicy::Skip`, and added to the NULL count of the
+/// accumulator under `NanPolicy::Null`. The statistics are read through
+/// `get` and `get_mut`.
+///
+/// Merging keeps the policy of `self`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NanFiltered<A> {
+    inner: A,
+    policy: NanPolicy,
+    nans: u64,
+}
+
+impl<A> NanFiltered<A> {
+    /// Wraps `inner`, applying `policy` to the samples added from now on.
+    #[must_use]
+    pub const fn new(inner: A, policy: NanPolicy) -> NanFiltered<A> {
+        NanFiltered {
+            inner,
+            policy,
+            nans: 0,
+        }
+    }
+
+    /// Returns the accumulator.
+    #[inline]
+    #[must_use]
+    pub const fn get(&self) -> &A {
+        &self.inner
+    }
+
+    /// Returns the accumulator mutably, e.g. to compute order statistics.
+    /// Samples added to it directly bypass the policy.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut A {
+        &mut self.inner
+    }
+
+    /// Returns the accumulator, dropping the policy.
+    #[inline]
+    #[must_use]
+    pub fn into_inner(self) -> A {
+        self.inner
+    }
+
+    /// Returns how `NaN` samples are handled.
+    #[inline]
+    #[must_use]
+    pub const fn policy(&self) -> NanPolicy {
+        self.policy
+    }
+
+    /// Returns the number of `NaN` samples dropped under `NanPolicy::Skip`.
+    #[inline]
+    #[must_use]
+    pub const fn nan_count(&self) -> u64 {
+        self.nans
+    }
+}
+
+impl<T: PartialOrd> NanFiltered<Unsorted<T>> {
+    /// Add a new sample, applying the `NaN` policy.
+    #[inline]
+    pub fn add(&mut self, sample: T) {
+        if !self.policy.excludes(&sample) {
+            self.inner.add(sample);
+        } else if self.policy == NanPolicy::Null {
+            self.inner.extend([None]);
+        } else {
+            self.nans += 1;
+        }
+    }
+}
+
+impl NanFiltered<OnlineStats> {
+    /// Add a new sample, applying the `NaN` policy.
+    #[inline]
+    pub fn add<T: ToPrimitive>(&mut self, sample: &T) {
+        let x = sample.to_f64().unwrap();
+        if !self.policy.excludes(&x) {
+            self.inner.add(&x);
+        } else if self.policy == NanPolicy::Null {
+            self.inner.extend_options([None::<f64>]);
+        } else {
+            self.nans += 1;
+        }
+    }
+}
+
+impl<A: Commute> Commute for NanFiltered<A> {
+    #[inline]
+    fn merge(&mut self, v: NanFiltered<A>) {
+        self.inner.merge(v.inner);
+        self.nans += v.nans;
+    }
+}
+
+impl<T: PartialOrd> Extend<T> for NanFiltered<Unsorted<T>> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
+        for sample in it {
+            self.add(sample);
+        }
+    }
+}
+
+impl<T: ToPrimitive> Extend<T> for NanFiltered<OnlineStats> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
+        for sample in it {
+            self.add(&sample);
+        }
+    }
+}
+
+/// Where `Describe` keeps the values it computes quantiles from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QuantileBackend {
+    /// Buffer the values in an `Unsorted`, which sorts them when they are
+    /// queried. This is the fastest way to describe the data once.
+    #[default]
+    Sort,
+    /// Keep the values in an `OrderStatTree`, which keeps them sorted as
+    /// they are added, for workloads that describe the data between adds.
+    #[cfg(feature = "ostree")]
+    Tree,
+}
+
+/// A set of the statistics that `Describe` computes, so that callers
+/// profiling many columns can skip the expensive ones.
+///
+/// Sets are combined like bit flags:
+///
+/// ```
+/// use stats::Metrics;
+///
+/// let cheap = Metrics::ALL - Metrics::MAD - Metrics::CARDINALITY;
+/// assert!(cheap.contains(Metrics::MODES));
+/// assert!(!cheap.contains(Metrics::MAD));
+/// assert_eq!(Metrics::MOMENTS | Metrics::MINMAX, Metrics::BASIC);
+/// ```
+///
+/// `MOMENTS` and `MINMAX` are tracked as the values are added. Every other
+/// metric needs the values to be buffered and sorted, so with only `BASIC`
+/// selected, `Describe` runs in constant memory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Metrics(u32);
+
+impl Metrics {
+    /// No statistics beyond the counts.
+    pub const NONE: Metrics = Metrics(0);
+    /// The mean and standard deviation.
+    pub const MOMENTS: Metrics = Metrics(1);
+    /// The minimum and maximum.
+    pub const MINMAX: Metrics = Metrics(1 << 1);
+    /// The quartiles and the median.
+    pub const QUARTILES: Metrics = Metrics(1 << 2);
+    /// The median absolute deviation.
+    pub const MAD: Metrics = Metrics(1 << 3);
+    /// The modes.
+    pub const MODES: Metrics = Metrics(1 << 4);
+    /// The antimodes.
+    pub const ANTIMODES: Metrics = Metrics(1 << 5);
+    /// The number of distinct values.
+    pub const CARDINALITY: Metrics = Metrics(1 << 6);
+    /// The moments and the minimum and maximum.
+    pub const BASIC: Metrics = Metrics(Metrics::MOMENTS.0 | Metrics::MINMAX.0);
+    /// Every statistic.
+    pub const ALL: Metrics = Metrics((1 << 7) - 1);
+
+    /// Returns true if every metric in `other` is in this set.
+    #[inline]
+    #[must_use]
+    pub const fn contains(self, other: Metrics) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns true if this set has no metrics.
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns true if any metric in this set needs the values buffered.
+    #[inline]
+    pub(crate) const fn needs_values(self) -> bool {
+        self.0 & !Metrics::BASIC.0 != 0
+    }
+}
+
+impl Default for Metrics {
+    #[inline]
+    fn default() -> Metrics {
+        Metrics::ALL
+    }
+}
+
+impl BitOr for Metrics {
+    type Output = Metrics;
+
+    #[inline]
+    fn bitor(self, rhs: Metrics) -> Metrics {
+        Metrics(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Metrics {
+    #[inline]
+    fn bitor_assign(&mut self, rhs: Metrics) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl Sub for Metrics {
+    type Output = Metrics;
+
+    /// Returns the metrics in `self` that are not in `rhs`.
+    #[inline]
+    fn sub(self, rhs: Metrics) -> Metrics {
+        Metrics(self.0 & !rhs.0)
+    }
+}
+
+/// A builder for the settings of the accumulators that have any, so they
+/// can be chosen in one place and applied consistently.
+///
+/// ```
+/// use stats::{NanPolicy, Parallelism, StatsConfig};
+///
+/// let config = StatsConfig::new()
+///     .with_nan_policy(NanPolicy::Skip)
+///     .with_parallelism(Parallelism::Sequential)
+///     .with_capacity(1000);
+/// let mut describe = config.describe::<f64>();
+/// describe.extend([1.0, f64::NAN, 3.0]);
+/// assert_eq!(describe.describe().mean, Some(2.0));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatsConfig {
+    nan_policy: NanPolicy,
+    parallelism: Parallelism,
+    quantile_method: QuantileMethod,
+    antimode_limit: usize,
+    capacity: usize,
+    stable: bool,
+    #[serde(default)]
+    metrics: Metrics,
+    #[serde(default)]
+    quantile_backend: QuantileBackend,
+}
+
+impl StatsConfig {
+    /// Create the default configuration, which matches the defaults of the
+    /// individual accumulators.
+    #[must_use]
+    pub const fn new() -> StatsConfig {
+        StatsConfig {
+            nan_policy: NanPolicy::Include,
+            parallelism: Parallelism::Auto,
+            quantile_method: QuantileMethod::Linear,
+            antimode_limit: ANTIMODES_LIMIT,
+            capacity: 0,
+            stable: false,
+            metrics: Metrics::ALL,
+            quantile_backend: QuantileBackend::Sort,
+        }
+    }
+
+    /// Sets how `NaN` samples are handled.
+    #[must_use]
+    pub const fn with_nan_policy(mut self, policy: NanPolicy) -> StatsConfig {
+        self.nan_policy = policy;
+        self
+    }
+
+    /// Sets when work runs on the rayon thread pool.
+    #[must_use]
+    pub const fn with_parallelism(mut self, parallelism: Parallelism) -> StatsConfig {
+        self.parallelism = parallelism;
+        self
+    }
+
+    /// Sets how quantiles between two data points are computed.
+    #[must_use]
+    pub const fn with_quantile_method(mut self, method: QuantileMethod) -> StatsConfig {
+        self.quantile_method = method;
+        self
+    }
+
+    /// Sets the maximum number of antimodes reported.
+    #[must_use]
+    pub const fn with_antimode_limit(mut self, limit: usize) -> StatsConfig {
+        self.antimode_limit = limit;
+        self
+    }
+
+    /// Sets where `Describe` keeps the values for the quartiles.
+    #[must_use]
+    pub const fn with_quantile_backend(mut self, backend: QuantileBackend) -> StatsConfig {
+        self.quantile_backend = backend;
+        self
+    }
+
+    /// Sets the number of samples to allocate space for up front.
+    #[must_use]
+    pub const fn with_capacity(mut self, capacity: usize) -> StatsConfig {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Sets whether sorts are stable; see `Unsorted::set_stable`.
+    #[must_use]
+    pub const fn with_stable(mut self, stable: bool) -> StatsConfig {
+        self.stable = stable;
+        self
+    }
+
+    /// Sets the statistics that `Describe` computes.
+    #[must_use]
+    pub const fn with_metrics(mut self, metrics: Metrics) -> StatsConfig {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Returns how `NaN` samples are handled.
+    #[inline]
+    #[must_use]
+    pub const fn nan_policy(&self) -> NanPolicy {
+        self.nan_policy
+    }
+
+    /// Returns when work runs on the rayon thread pool.
+    #[inline]
+    #[must_use]
+    pub const fn parallelism(&self) -> Parallelism {
+        self.parallelism
+    }
+
+    /// Returns how quantiles between two data points are computed.
+    #[inline]
+    #[must_use]
+    pub const fn quantile_method(&self) -> QuantileMethod {
+        self.quantile_method
+    }
+
+    /// Returns the maximum number of antimodes reported.
+    #[inline]
+    #[must_use]
+    pub const fn antimode_limit(&self) -> usize {
+        self.antimode_limit
+    }
+
+    /// Returns where `Describe` keeps the values for the quartiles.
+    #[inline]
+    #[must_use]
+    pub const fn quantile_backend(&self) -> QuantileBackend {
+        self.quantile_backend
+    }
+
+    /// Returns true if `Describe` computes the quartiles with an
+    /// `OrderStatTree`.
+    #[inline]
+    pub(crate) fn tree_quartiles(&self) -> bool {
+        #[cfg(feature = "ostree")]
+        {
+            self.quantile_backend == QuantileBackend::Tree
+                && self.metrics.contains(Metrics::QUARTILES)
+        }
+        #[cfg(not(feature = "ostree"))]
+        {
+            false
+        }
+    }
+
+    /// Returns true if `Describe` buffers the values in an `Unsorted`.
+    #[inline]
+    pub(crate) fn buffers_values(&self) -> bool {
+        if self.tree_quartiles() {
+            (self.metrics - Metrics::QUARTILES).needs_values()
+        } else {
+            self.metrics.needs_values()
+        }
+    }
+
+    /// Returns the number of samples to allocate space for up front.
+    #[inline]
+    #[must_use]
+    pub const fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the statistics that `Describe` computes.
+    #[inline]
+    #[must_use]
+    pub const fn metrics(&self) -> Metrics {
+        self.metrics
+    }
+
+    /// Returns whether sorts are stable.
+    #[inline]
+    #[must_use]
+    pub const fn stable(&self) -> bool {
+        self.stable
+    }
+
+    /// Creates an empty `Unsorted` with this capacity, parallelism and
+    /// sort stability, wrapped to apply this `NaN` policy.
+    #[must_use]
+    pub fn unsorted<T: PartialOrd>(&self) -> NanFiltered<Unsorted<T>> {
+        let mut unsorted = Unsorted::with_capacity(self.capacity);
+        unsorted.set_parallelism(self.parallelism);
+        unsorted.set_stable(self.stable);
+        NanFiltered::new(unsorted, self.nan_policy)
+    }
+
+    /// Creates an empty `OrderStatTree` with this quantile method, for
+    /// workloads that query quantiles between adds.
+    #[cfg(feature = "ostree")]
+    #[must_use]
+    pub fn order_stat_tree<T: PartialOrd>(&self) -> OrderStatTree<T> {
+        let mut tree = OrderStatTree::new();
+        tree.set_quantile_method(self.quantile_method);
+        tree
+    }
+
+    /// Creates an empty `OnlineStats`, wrapped to apply this `NaN` policy.
+    ///
+    /// With `NanPolicy::Null`, `NaN` samples are counted in `null_count`.
+    #[must_use]
+    pub fn online_stats(&self) -> NanFiltered<OnlineStats> {
+        NanFiltered::new(OnlineStats::new(), self.nan_policy)
+    }
+
+    /// Creates an empty `Describe` that uses this configuration.
+    #[must_use]
+    pub fn describe<T: PartialOrd + ToPrimitive + Clone>(&self) -> Describe<T> {
+        Describe::with_config(*self)
+    }
+}
+
+impl Default for StatsConfig {
+    #[inline]
+    fn default() -> StatsConfig {
+        StatsConfig::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{NanPolicy, StatsConfig};
+    use crate::{Commute, Parallelism, QuantileMethod};
+
+    #[test]
+    fn configured_accumulators() {
+        let config = StatsConfig::new()
+            .with_parallelism(Parallelism::Sequential)
+            .with_quantile_method(QuantileMethod::Lower)
+            .with_capacity(64);
+        assert_eq!(config.antimode_limit(), 10);
+        let unsorted = config.unsorted::<u32>();
+        assert!(unsorted.get().mem_usage() >= 64 * 4);
+
+        let data = [1.0, f64::NAN, 3.0];
+        let mut include = config.online_stats();
+        include.extend(data);
+        assert!(include.get().mean().is_nan());
+        let mut skip = config.with_nan_policy(NanPolicy::Skip).online_stats();
+        skip.extend(data);
+        let stats = skip.get();
+        assert_eq!((stats.len(), stats.mean(), stats.null_count()), (2, 2.0, 0));
+        assert_eq!(skip.nan_count(), 1);
+        let mut null = config.with_nan_policy(NanPolicy::Null).online_stats();
+        null.extend(data);
+        assert_eq!((null.get().len(), null.get().null_count()), (2, 1));
+    }
+
+    #[test]
+    fn nan_filtered_unsorted() {
+        let config = StatsConfig::new().with_nan_policy(NanPolicy::Skip);
+        let mut unsorted = config.unsorted();
+        unsorted.extend([3.0, f64::NAN, 1.0]);
+        let mut other = config.unsorted();
+        other.extend([f64::NAN, 2.0]);
+        unsorted.merge(other);
+        assert_eq!(unsorted.nan_count(), 2);
+        assert_eq!(unsorted.get_mut().median(), Some(2.0));
+
+        let mut null = config.with_nan_policy(NanPolicy::Null).unsorted();
+        null.extend([f64::NAN, 5.0]);
+        assert_eq!((null.get().len(), null.get().null_count()), (1, 1));
+        assert_eq!(null.nan_count(), 0);
+
+        let mut include = StatsConfig::new().unsorted();
+        include.add(f64::NAN);
+        assert_eq!(include.get().len(), 1);
+    }
+}
@@ -0,0 +1,237 @@
+use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+
+use crate::Commute;
+
+/// A commutative accumulator of all pairwise Pearson correlations between
+/// the columns of numeric records.
+///
+/// The means and the co-moments are updated online (a multivariate form of
+/// Welford's algorithm), and shards are combined with the pairwise formula
+/// of Chan et al. Since the co-moment matrix is symmetric, only its upper
+/// triangle (including the diagonal) is stored, i.e. `k * (k + 1) / 2`
+/// values for `k` columns.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct CorrelationMatrix {
+    len: u64,
+    means: Vec<f64>,
+    comoments: Vec<f64>,
+}
+
+impl CorrelationMatrix {
+    /// Create initial empty state for records of `columns` values.
+    #[must_use]
+    pub fn new(columns: usize) -> CorrelationMatrix {
+        CorrelationMatrix {
+            len: 0,
+            means: vec![0.0; columns],
+            comoments: vec![0.0; columns * (columns + 1) / 2],
+        }
+    }
+
+    /// Position of the co-moment of columns `i <= j` in the upper triangle.
+    #[inline]
+    fn index(&self, i: usize, j: usize) -> usize {
+        let (i, j) = if i <= j { (i, j) } else { (j, i) };
+        i * self.means.len() - i * i.saturating_sub(1) / 2 - i + j
+    }
+
+    /// Add a record.
+    ///
+    /// # Panics
+    ///
+    /// If the record does not have exactly one value per column.
+    #[inline]
+    pub fn add<T: ToPrimitive>(&mut self, record: &[T]) {
+        let k = self.means.len();
+        assert_eq!(record.len(), k, "record length must match the columns");
+        self.len += 1;
+        let n = self.len as f64;
+        let x: Vec<f64> = record.iter().map(|v| v.to_f64().unwrap()).collect();
+        let mut deltas = Vec::with_capacity(k);
+        for (mean, &xi) in self.means.iter_mut().zip(&x) {
+            let delta = xi - *mean;
+            *mean += delta / n;
+            deltas.push(delta);
+        }
+        let mut comoments = self.comoments.iter_mut();
+        for (i, di) in deltas.iter().enumerate() {
+            for (xj, mj) in x[i..].iter().zip(&self.means[i..]) {
+                *comoments.next().unwrap() += di * (xj - mj);
+            }
+        }
+    }
+
+    /// Returns the Pearson correlation of columns `i` and `j`.
+    ///
+    /// `None` is returned if there are fewer than two records or if either
+    /// column is constant.
+    ///
+    /// # Panics
+    ///
+    /// If `i` or `j` is not a column.
+    #[must_use]
+    pub fn correlation(&self, i: usize, j: usize) -> Option<f64> {
+        let k = self.means.len();
+        assert!(i < k && j < k, "column out of bounds");
+        let vi = self.comoments[self.index(i, i)];
+        let vj = self.comoments[self.index(j, j)];
+        if self.len < 2 || vi <= 0.0 || vj <= 0.0 {
+            return None;
+        }
+        let r = self.comoments[self.index(i, j)] / (vi * vj).sqrt();
+        Some(r.clamp(-1.0, 1.0))
+    }
+
+    /// Returns the population covariance of columns `i` and `j`.
+    ///
+    /// `None` is returned if there are no records.
+    ///
+    /// # Panics
+    ///
+    /// If `i` or `j` is not a column.
+    #[must_use]
+    pub fn covariance(&self, i: usize, j: usize) -> Option<f64> {
+        let k = self.means.len();
+        assert!(i < k && j < k, "column out of bounds");
+        if self.len == 0 {
+            return None;
+        }
+        Some(self.comoments[self.index(i, j)] / self.len as f64)
+    }
+
+    /// Returns the full correlation matrix, row by row.
+    ///
+    /// Undefined correlations (see `correlation`) are `NaN`.
+    #[must_use]
+    pub fn to_matrix(&self) -> Vec<Vec<f64>> {
+        let k = self.means.len();
+        (0..k)
+            .map(|i| {
+                (0..k)
+                    .map(|j| self.correlation(i, j).unwrap_or(f64::NAN))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Returns the mean of each column.
+    #[inline]
+    #[must_use]
+    pub fn means(&self) -> &[f64] {
+        &self.means
+    }
+
+    /// Returns the number of columns.
+    #[inline]
+    #[must_use]
+    pub fn columns(&self) -> usize {
+        self.means.len()
+    }
+
+    /// Returns the number of records.
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns true if there are no records.
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Commute for CorrelationMatrix {
+    /// # Panics
+    ///
+    /// If the two matrices have a different number of columns.
+    #[inline]
+    fn merge(&mut self, v: CorrelationMatrix) {
+        assert_eq!(self.columns(), v.columns());
+        if v.len == 0 {
+            return;
+        }
+        if self.len == 0 {
+            *self = v;
+            return;
+        }
+        let (n1, n2) = (self.len as f64, v.len as f64);
+        let n = n1 + n2;
+        let deltas: Vec<f64> = v
+            .means
+            .iter()
+            .zip(&self.means)
+            .map(|(b, a)| b - a)
+            .collect();
+        let mut comoments = self.comoments.iter_mut().zip(&v.comoments);
+        for (i, di) in deltas.iter().enumerate() {
+            for dj in &deltas[i..] {
+                let (c1, c2) = comoments.next().unwrap();
+                *c1 += c2 + di * dj * n1 * n2 / n;
+            }
+        }
+        for (mean, delta) in self.means.iter_mut().zip(deltas) {
+            *mean += delta * n2 / n;
+        }
+        self.len += v.len;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CorrelationMatrix;
+    use crate::Commute;
+
+    fn records() -> Vec<[f64; 3]> {
+        (0..20)
+            .map(|i| {
+                let x = f64::from(i);
+                [x, 3.0f64.mul_add(x, 1.0), (x * 1.7).sin()]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn pearson() {
+        let mut m = CorrelationMatrix::new(3);
+        for r in records() {
+            m.add(&r);
+        }
+        assert_eq!(m.len(), 20);
+        assert!((m.correlation(0, 1).unwrap() - 1.0).abs() < 1e-12);
+        assert_eq!(m.correlation(2, 2), Some(1.0));
+        assert_eq!(m.correlation(0, 2), m.correlation(2, 0));
+        assert!((m.covariance(0, 0).unwrap() - 33.25).abs() < 1e-9);
+
+        let mut constant = CorrelationMatrix::new(2);
+        constant.add(&[1, 5]);
+        constant.add(&[2, 5]);
+        assert_eq!(constant.correlation(0, 1), None);
+        assert!(constant.to_matrix()[1][0].is_nan());
+    }
+
+    #[test]
+    fn merge_matches_sequential() {
+        let mut all = CorrelationMatrix::new(3);
+        let (mut m1, mut m2) = (CorrelationMatrix::new(3), CorrelationMatrix::new(3));
+        for (i, r) in records().iter().enumerate() {
+            all.add(r);
+            if i % 3 == 0 { &mut m1 } else { &mut m2 }.add(r);
+        }
+        m1.merge(m2);
+        m1.merge(CorrelationMatrix::new(3));
+        for i in 0..3 {
+            assert!((m1.means()[i] - all.means()[i]).abs() < 1e-12);
+            for j in 0..3 {
+                let (a, b) = (
+                    m1.correlation(i, j).unwrap(),
+                    all.correlation(i, j).unwrap(),
+                );
+                assert!((a - b).abs() < 1e-12);
+            }
+        }
+    }
+}
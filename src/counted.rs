@@ -0,0 +1,533 @@
+use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+
+use crate::unsorted::{median_with, quartiles_with};
+use crate::{Commute, Partial, Unsorted};
+
+/// A commutative data structure storing each distinct value once, with
+/// its number of occurrences (i.e. a run-length encoding of the sorted data).
+///
+/// The distinct values are kept sorted, so adding a new distinct value
+/// takes time linear in the cardinality.
+///
+/// This supports the same exact statistics as `Unsorted`, but uses space
+/// proportional to the cardinality rather than the number of samples,
+/// which makes it much cheaper for booleans, enums, codes and other
+/// low-cardinality data.
+///
+/// Note that this works on types that do not define a total ordering like
+/// `f32` and `f64`. When an ordering is not defined, an arbitrary order
+/// is returned.
+#[derive(Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Counted<T> {
+    data: Vec<(Partial<T>, u64)>,
+    len: u64,
+    #[serde(default)]
+    nulls: u64,
+}
+
+impl<T: PartialOrd> Counted<T> {
+    /// Create initial empty state.
+    #[inline]
+    #[must_use]
+    pub fn new() -> Counted<T> {
+        Default::default()
+    }
+
+    /// Add a new element to the set.
+    #[inline]
+    pub fn add(&mut self, v: T) {
+        self.add_count(v, 1);
+    }
+
+    /// Add `count` occurrences of an element to the set.
+    #[inline]
+    pub fn add_count(&mut self, v: T, count: u64) {
+        if count == 0 {
+            return;
+        }
+        self.len += count;
+        let v = Partial(v);
+        match self.data.binary_search_by(|(k, _)| k.cmp(&v)) {
+            Ok(idx) => self.data[idx].1 += count,
+            Err(idx) => self.data.insert(idx, (v, count)),
+        }
+    }
+
+    /// Return the number of data points.
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns true if there are no data points.
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Return the number of `None` values ingested via `Extend<Option<T>>`.
+    ///
+    /// NULLs are not part of the data and do not affect any statistic.
+    #[inline]
+    #[must_use]
+    pub const fn null_count(&self) -> u64 {
+        self.nulls
+    }
+
+    /// Return the number of distinct values.
+    #[inline]
+    #[must_use]
+    pub fn cardinality(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns an iterator of the distinct values and their counts, in
+    /// ascending order.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (&T, u64)> {
+        self.data.iter().map(|(k, c)| (&k.0, *c))
+    }
+
+    /// Returns the `i`th smallest value (0-based).
+    fn nth(&self, i: usize) -> Option<&T> {
+        let mut seen = 0;
+        for &(ref k, c) in &self.data {
+            seen += c as usize;
+            if i < seen {
+                return Some(&k.0);
+            }
+        }
+        None
+    }
+}
+
+impl<T: PartialOrd + Clone> Counted<T> {
+    /// Returns the mode of the data.
+    ///
+    /// As with `Unsorted::mode`, `None` is returned if there is a tie for the
+    /// most frequent value, or if no value occurs more than once.
+    #[must_use]
+    pub fn mode(&self) -> Option<T> {
+        let (mut mode, mut highest, mut tied) = (None, 0, false);
+        for &(ref k, c) in &self.data {
+            if c > highest {
+                (mode, highest, tied) = (Some(k), c, false);
+            } else if c == highest {
+                tied = true;
+            }
+        }
+        if tied || highest < 2 {
+            return None;
+        }
+        mode.map(|p| p.0.clone())
+    }
+
+    /// Returns the modes of the data.
+    ///
+    /// This has the same semantics as `Unsorted::modes`.
+    #[must_use]
+    pub fn modes(&self) -> (Vec<T>, usize, u64) {
+        let highest = self.data.iter().map(|&(_, c)| c).max().unwrap_or(0);
+        if highest < 2 {
+            return (vec![], 0, 0);
+        }
+        let modes: Vec<T> = self
+            .data
+            .iter()
+            .filter(|&&(_, c)| c == highest)
+            .map(|(k, _)| k.0.clone())
+            .collect();
+        let count = modes.len();
+        (modes, count, highest)
+    }
+}
+
+impl<T: PartialOrd + ToPrimitive> Counted<T> {
+    /// Returns the median of the data.
+    #[must_use]
+    pub fn median(&self) -> Option<f64> {
+        median_with(self.len as usize, |i| {
+            self.nth(i).map(|v| v.to_f64().unwrap())
+        })
+    }
+
+    /// Returns the quartiles of the data.
+    #[must_use]
+    pub fn quartiles(&self) -> Option<(f64, f64, f64)> {
+        quartiles_with(self.len as usize, |i| {
+            self.nth(i).map(|v| v.to_f64().unwrap())
+        })
+    }
+}
+
+impl<T: PartialOrd> Commute for Counted<T> {
+    #[inline]
+    fn merge(&mut self, v: Counted<T>) {
+        self.nulls += v.nulls;
+        for (k, c) in v.data {
+            self.add_count(k.0, c);
+        }
+    }
+}
+
+impl<T: PartialOrd> Default for Counted<T> {
+    #[inline]
+    fn default() -> Counted<T> {
+        Counted {
+            data: Vec::new(),
+            len: 0,
+            nulls: 0,
+        }
+    }
+}
+
+impl<T: PartialOrd> FromIterator<T> for Counted<T> {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = T>>(it: I) -> Counted<T> {
+        let mut v = Counted::new();
+        v.extend(it);
+        v
+    }
+}
+
+impl<T: PartialOrd> Extend<T> for Counted<T> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
+        for sample in it {
+            self.add(sample);
+        }
+    }
+}
+
+impl<T: PartialOrd> Extend<Option<T>> for Counted<T> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = Option<T>>>(&mut self, it: I) {
+        for sample in it {
+            match sample {
+                Some(v) => self.add(v),
+                None => self.nulls += 1,
+            }
+        }
+    }
+}
+
+impl<T: PartialOrd> From<Unsorted<T>> for Counted<T> {
+    fn from(unsorted: Unsorted<T>) -> Counted<T> {
+        let mut counted = Counted::new();
+        counted.nulls = unsorted.null_count();
+        let mut run: Option<(Partial<T>, u64)> = None;
+        for v in unsorted.into_sorted_partials() {
+            run = match run {
+                Some((prev, c)) if prev == v => Some((prev, c + 1)),
+                Some((prev, c)) => {
+                    counted.add_count(prev.0, c);
+                    Some((v, 1))
+                }
+                None => Some((v, 1)),
+            };
+        }
+        if let Some((prev, c)) = run {
+            counted.add_count(prev.0, c);
+        }
+        counted
+    }
+}
+
+/// The default number of buffered samples after which `AdaptiveUnsorted`
+/// first checks whether to switch to counted storage.
+const DEFAULT_CHECK_LEN: usize = 10_000;
+
+/// The default maximum ratio of distinct values to samples at which
+/// `AdaptiveUnsorted` switches to counted storage.
+const DEFAULT_MAX_DISTINCT_RATIO: f64 = 0.1;
+
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
+enum Storage<T> {
+    Raw(Unsorted<T>),
+    Counted(Counted<T>),
+}
+
+/// A commutative data structure that starts out as an `Unsorted` buffer and
+/// switches to `Counted` storage once the data turns out to have many
+/// duplicates.
+///
+/// The check happens each time the buffer doubles in size, starting at
+/// `check_len` samples. If the ratio of distinct values to samples is at
+/// most `max_distinct_ratio`, the buffer is run-length encoded and all
+/// further samples are counted.
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
+pub struct AdaptiveUnsorted<T> {
+    storage: Storage<T>,
+    next_check: usize,
+    max_distinct_ratio: f64,
+}
+
+impl<T: PartialOrd> AdaptiveUnsorted<T> {
+    /// Create initial empty state with the default thresholds.
+    #[inline]
+    #[must_use]
+    pub fn new() -> AdaptiveUnsorted<T> {
+        Default::default()
+    }
+
+    /// Create initial empty state, first checking for duplicates at
+    /// `check_len` samples and switching at or below `max_distinct_ratio`.
+    #[inline]
+    #[must_use]
+    pub fn with_threshold(check_len: usize, max_distinct_ratio: f64) -> AdaptiveUnsorted<T> {
+        AdaptiveUnsorted {
+            storage: Storage::Raw(Unsorted::new()),
+            next_check: check_len.max(1),
+            max_distinct_ratio,
+        }
+    }
+
+    /// Add a new element to the set.
+    #[inline]
+    pub fn add(&mut self, v: T) {
+        match self.storage {
+            Storage::Raw(ref mut raw) => {
+                raw.add(v);
+                if raw.len() >= self.next_check {
+                    self.check();
+                }
+            }
+            Storage::Counted(ref mut counted) => counted.add(v),
+        }
+    }
+
+    /// Add a NULL value, which is counted but not otherwise used.
+    #[inline]
+    pub fn add_null(&mut self) {
+        match self.storage {
+            Storage::Raw(ref mut raw) => raw.extend([None]),
+            Storage::Counted(ref mut counted) => counted.extend([None]),
+        }
+    }
+
+    /// Returns true if the data is stored as distinct values and counts.
+    #[inline]
+    #[must_use]
+    pub const fn is_counted(&self) -> bool {
+        matches!(self.storage, Storage::Counted(_))
+    }
+
+    /// Return the number of data points.
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> u64 {
+        match self.storage {
+            Storage::Raw(ref raw) => raw.len() as u64,
+            Storage::Counted(ref counted) => counted.len(),
+        }
+    }
+
+    /// Returns true if there are no data points.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Return the number of NULL values added.
+    #[inline]
+    #[must_use]
+    pub const fn null_count(&self) -> u64 {
+        match self.storage {
+            Storage::Raw(ref raw) => raw.null_count(),
+            Storage::Counted(ref counted) => counted.null_count(),
+        }
+    }
+
+    /// Switch to counted storage if the raw buffer has enough duplicates.
+    fn check(&mut self) {
+        let Storage::Raw(ref mut raw) = self.storage else {
+            return;
+        };
+        let len = raw.len();
+        let data = raw.sorted_data();
+        let distinct = 1 + data.windows(2).filter(|w| w[0] != w[1]).count();
+        if distinct as f64 <= self.max_distinct_ratio * len as f64 {
+            let raw = std::mem::take(raw);
+            self.storage = Storage::Counted(raw.into());
+        } else {
+            self.next_check = len.saturating_mul(2);
+        }
+    }
+}
+
+impl<T: PartialOrd + Eq + Clone> AdaptiveUnsorted<T> {
+    /// Return the number of distinct values.
+    #[inline]
+    pub fn cardinality(&mut self) -> usize {
+        match self.storage {
+            Storage::Raw(ref mut raw) => raw.cardinality(),
+            Storage::Counted(ref counted) => counted.cardinality(),
+        }
+    }
+}
+
+impl<T: PartialOrd + Clone> AdaptiveUnsorted<T> {
+    /// Returns the mode of the data.
+    #[inline]
+    pub fn mode(&mut self) -> Option<T> {
+        match self.storage {
+            Storage::Raw(ref mut raw) => raw.mode(),
+            Storage::Counted(ref counted) => counted.mode(),
+        }
+    }
+
+    /// Returns the modes of the data.
+    #[inline]
+    pub fn modes(&mut self) -> (Vec<T>, usize, u64) {
+        match self.storage {
+            Storage::Raw(ref mut raw) => raw.modes(),
+            Storage::Counted(ref counted) => counted.modes(),
+        }
+    }
+}
+
+impl<T: PartialOrd + ToPrimitive> AdaptiveUnsorted<T> {
+    /// Returns the median of the data.
+    #[inline]
+    pub fn median(&mut self) -> Option<f64> {
+        match self.storage {
+            Storage::Raw(ref mut raw) => raw.median(),
+            Storage::Counted(ref counted) => counted.median(),
+        }
+    }
+
+    /// Returns the quartiles of the data.
+    #[inline]
+    pub fn quartiles(&mut self) -> Option<(f64, f64, f64)> {
+        match self.storage {
+            Storage::Raw(ref mut raw) => raw.quartiles(),
+            Storage::Counted(ref counted) => counted.quartiles(),
+        }
+    }
+}
+
+impl<T: PartialOrd> Commute for AdaptiveUnsorted<T> {
+    #[inline]
+    fn merge(&mut self, v: AdaptiveUnsorted<T>) {
+        let storage = std::mem::replace(&mut self.storage, Storage::Counted(Counted::new()));
+        self.storage = match (storage, v.storage) {
+            (Storage::Raw(mut a), Storage::Raw(b)) => {
+                a.merge(b);
+                Storage::Raw(a)
+            }
+            (Storage::Counted(mut a), Storage::Counted(b)) => {
+                a.merge(b);
+                Storage::Counted(a)
+            }
+            // once either side is counted, the merged data is counted too
+            (Storage::Raw(raw), Storage::Counted(mut counted))
+            | (Storage::Counted(mut counted), Storage::Raw(raw)) => {
+                counted.merge(raw.into());
+                Storage::Counted(counted)
+            }
+        };
+        if !self.is_counted() && self.len() as usize >= self.next_check {
+            self.check();
+        }
+    }
+}
+
+impl<T: PartialOrd> Default for AdaptiveUnsorted<T> {
+    #[inline]
+    fn default() -> AdaptiveUnsorted<T> {
+        AdaptiveUnsorted::with_threshold(DEFAULT_CHECK_LEN, DEFAULT_MAX_DISTINCT_RATIO)
+    }
+}
+
+impl<T: PartialOrd> FromIterator<T> for AdaptiveUnsorted<T> {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = T>>(it: I) -> AdaptiveUnsorted<T> {
+        let mut v = AdaptiveUnsorted::new();
+        v.extend(it);
+        v
+    }
+}
+
+impl<T: PartialOrd> Extend<T> for AdaptiveUnsorted<T> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
+        for sample in it {
+            self.add(sample);
+        }
+    }
+}
+
+impl<T: PartialOrd> Extend<Option<T>> for AdaptiveUnsorted<T> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = Option<T>>>(&mut self, it: I) {
+        for sample in it {
+            match sample {
+                Some(v) => self.add(v),
+                None => self.add_null(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AdaptiveUnsorted, Counted};
+    use crate::{Commute, Unsorted};
+
+    #[test]
+    fn counted_matches_unsorted() {
+        let data = vec![3usize, 1, 2, 2, 3, 3, 1, 3, 2, 3, 5];
+        let counted: Counted<usize> = data.iter().copied().collect();
+        let mut unsorted: Unsorted<usize> = data.iter().copied().collect();
+        assert_eq!(counted.len(), 11);
+        assert_eq!(counted.cardinality(), 4);
+        assert_eq!(counted.median(), unsorted.median());
+        assert_eq!(counted.quartiles(), unsorted.quartiles());
+        assert_eq!(counted.mode(), unsorted.mode());
+        assert_eq!(counted.modes(), unsorted.modes());
+        assert!(Counted::from(unsorted) == counted);
+    }
+
+    #[test]
+    fn counted_modes_ties() {
+        let counted: Counted<usize> = vec![1usize, 1, 2, 2, 3].into_iter().collect();
+        assert_eq!(counted.mode(), None);
+        assert_eq!(counted.modes(), (vec![1, 2], 2, 2));
+        let unique: Counted<usize> = vec![1usize, 2, 3].into_iter().collect();
+        assert_eq!(unique.mode(), None);
+        // as `Unsorted::modes`, so both backends of `AdaptiveUnsorted` agree
+        assert_eq!(unique.modes(), (vec![], 0, 0));
+        let mut raw: Unsorted<usize> = vec![1usize, 2, 3].into_iter().collect();
+        assert_eq!(unique.modes(), raw.modes());
+    }
+
+    #[test]
+    fn adaptive_switches() {
+        let mut low: AdaptiveUnsorted<u8> = AdaptiveUnsorted::with_threshold(100, 0.1);
+        low.extend((0..1000u32).map(|i| (i % 3) as u8));
+        assert!(low.is_counted());
+        assert_eq!(low.len(), 1000);
+        assert_eq!(low.median(), Some(1.0));
+        assert_eq!(low.cardinality(), 3);
+
+        let mut high: AdaptiveUnsorted<u32> = AdaptiveUnsorted::with_threshold(100, 0.1);
+        high.extend(0..1000u32);
+        assert!(!high.is_counted());
+        assert_eq!(high.median(), Some(499.5));
+
+        // merging raw data into counted data keeps it counted
+        low.merge(AdaptiveUnsorted::with_threshold(100, 0.1));
+        let mut raw: AdaptiveUnsorted<u8> = AdaptiveUnsorted::new();
+        raw.extend([Some(7u8), None, Some(7), Some(7)]);
+        low.add_null();
+        low.merge(raw);
+        assert!(low.is_counted());
+        assert_eq!(low.len(), 1003);
+        assert_eq!(low.null_count(), 2);
+        assert_eq!(low.cardinality(), 4);
+    }
+}
@@ -0,0 +1,306 @@
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::hashing::{nth_index, stable_hash};
+use crate::Commute;
+
+/// A Count-Min sketch for approximate per-value frequency counts.
+///
+/// The sketch is a `depth x width` table of counters. Each value increments
+/// one counter per row, and its estimated count is the smallest of those
+/// counters. Estimates never undercount, and with `width = ceil(e / eps)`
+/// and `depth = ceil(ln(1 / delta))` they overcount by at most `eps` times
+/// the total count with probability `1 - delta` (see `with_error`).
+///
+/// Memory is fixed at `width * depth` counters, regardless of the number
+/// of distinct values. Sketches can only be merged with sketches of the
+/// same dimensions.
+#[derive(Clone, Deserialize, PartialEq, Eq)]
+#[serde(try_from = "CountMinParts")]
+pub struct CountMin<T: ?Sized> {
+    width: usize,
+    depth: usize,
+    len: u64,
+    counters: Vec<u64>,
+    #[serde(skip)]
+    _marker: PhantomData<fn(&T)>,
+}
+
+/// The serialized form of a `CountMin`, which is checked before use.
+#[derive(Deserialize)]
+struct CountMinParts {
+    width: usize,
+    depth: usize,
+    len: u64,
+    counters: Vec<u64>,
+    // derived from the above when serializing, so they are ignored here
+    #[serde(default, rename = "relative_error")]
+    _relative_error: f64,
+    #[serde(default, rename = "error_bound")]
+    _error_bound: u64,
+    #[serde(default, rename = "failure_probability")]
+    _failure_probability: f64,
+}
+
+impl<T: Hash + ?Sized> Serialize for CountMin<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("CountMin", 7)?;
+        state.serialize_field("width", &self.width)?;
+        state.serialize_field("depth", &self.depth)?;
+        state.serialize_field("len", &self.len)?;
+        state.serialize_field("counters", &self.counters)?;
+        state.serialize_field("relative_error", &self.relative_error())?;
+        state.serialize_field("error_bound", &self.error_bound())?;
+        state.serialize_field("failure_probability", &self.failure_probability())?;
+        state.end()
+    }
+}
+
+impl<T: ?Sized> TryFrom<CountMinParts> for CountMin<T> {
+    type Error = String;
+
+    fn try_from(v: CountMinParts) -> Result<CountMin<T>, String> {
+        if v.width == 0 || v.depth == 0 {
+            return Err("Count-Min sketch dimensions must be positive".to_owned());
+        }
+        if v.width.checked_mul(v.depth) != Some(v.counters.len()) {
+            return Err(format!(
+                "Count-Min sketch of {} x {} has {} counters",
+                v.depth,
+                v.width,
+                v.counters.len()
+            ));
+        }
+        Ok(CountMin {
+            width: v.width,
+            depth: v.depth,
+            len: v.len,
+            counters: v.counters,
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<T: Hash + ?Sized> CountMin<T> {
+    /// Create an empty sketch with `depth` rows of `width` counters.
+    ///
+    /// Values of `0` are treated as `1`.
+    ///
+    /// # Panics
+    ///
+    /// If `width * depth` overflows a `usize`.
+    #[must_use]
+    pub fn new(width: usize, depth: usize) -> CountMin<T> {
+        let (width, depth) = (width.max(1), depth.max(1));
+        let size = width
+            .checked_mul(depth)
+            .expect("Count-Min sketch dimensions overflow usize");
+        CountMin {
+            width,
+            depth,
+            len: 0,
+            counters: vec![0; size],
+            _marker: PhantomData,
+        }
+    }
+
+    /// Create an empty sketch whose estimates overcount by at most `eps`
+    /// times the total count, with probability at least `1 - delta`.
+    ///
+    /// # Panics
+    ///
+    /// If `eps` or `delta` is not in `(0, 1)`, or if they are so small that
+    /// the dimensions overflow, as for `new`.
+    #[must_use]
+    pub fn with_error(eps: f64, delta: f64) -> CountMin<T> {
+        assert!(eps > 0.0 && eps < 1.0, "eps must be in (0, 1)");
+        assert!(delta > 0.0 && delta < 1.0, "delta must be in (0, 1)");
+        let width = (std::f64::consts::E / eps).ceil() as usize;
+        let depth = delta.recip().ln().ceil() as usize;
+        CountMin::new(width, depth)
+    }
+
+    /// Add one occurrence of `value`.
+    #[inline]
+    pub fn add(&mut self, value: &T) {
+        self.add_count(value, 1);
+    }
+
+    /// Add `count` occurrences of `value`.
+    #[inline]
+    pub fn add_count(&mut self, value: &T, count: u64) {
+        let hash = stable_hash(value);
+        for row in 0..self.depth {
+            let i = row * self.width + nth_index(hash, row, self.width);
+            self.counters[i] = self.counters[i].saturating_add(count);
+        }
+        self.len = self.len.saturating_add(count);
+    }
+
+    /// Returns the estimated number of occurrences of `value`.
+    ///
+    /// This is never less than the true count.
+    #[inline]
+    #[must_use]
+    pub fn estimate(&self, value: &T) -> u64 {
+        let hash = stable_hash(value);
+        (0..self.depth)
+            .map(|row| self.counters[row * self.width + nth_index(hash, row, self.width)])
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Returns the number of counters per row.
+    #[inline]
+    #[must_use]
+    pub const fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Returns the number of rows.
+    #[inline]
+    #[must_use]
+    pub const fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns the error of estimates relative to the total count, i.e.
+    /// the `eps` of `with_error`, which is `e / width`.
+    #[inline]
+    #[must_use]
+    pub fn relative_error(&self) -> f64 {
+        std::f64::consts::E / self.width as f64
+    }
+
+    /// Returns the largest overcount of any estimate, given the current
+    /// total count, which holds with probability `1 - failure_probability()`.
+    #[inline]
+    #[must_use]
+    pub fn error_bound(&self) -> u64 {
+        (self.relative_error() * self.len as f64).ceil() as u64
+    }
+
+    /// Returns the probability that an estimate exceeds `error_bound`,
+    /// i.e. the `delta` of `with_error`, which is `exp(-depth)`.
+    #[inline]
+    #[must_use]
+    pub fn failure_probability(&self) -> f64 {
+        (-(self.depth as f64)).exp()
+    }
+
+    /// Returns the total number of occurrences added.
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns true if nothing has been added.
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<T: ?Sized> Commute for CountMin<T> {
+    /// # Panics
+    ///
+    /// If the sketches have different dimensions.
+    #[inline]
+    fn merge(&mut self, v: CountMin<T>) {
+        assert!(
+            self.width == v.width && self.depth == v.depth,
+            "cannot merge Count-Min sketches of different dimensions"
+        );
+        self.len = self.len.saturating_add(v.len);
+        for (c1, c2) in self.counters.iter_mut().zip(v.counters) {
+            *c1 = c1.saturating_add(c2);
+        }
+    }
+}
+
+impl<T: Hash> Extend<T> for CountMin<T> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
+        for value in it {
+            self.add(&value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CountMin;
+    use crate::{Commute, Frequencies};
+
+    #[test]
+    fn never_undercounts() {
+        let data: Vec<u32> = (0..5000).map(|i| (i * i) % 97).collect();
+        let mut sketch = CountMin::with_error(0.01, 0.01);
+        assert_eq!((sketch.width(), sketch.depth()), (272, 5));
+        sketch.extend(data.iter().copied());
+        let exact: Frequencies<u32> = data.iter().copied().collect();
+        for v in 0..100 {
+            let (est, count) = (sketch.estimate(&v), exact.count(&v));
+            assert!(est >= count);
+            assert!(est - count <= sketch.error_bound());
+        }
+        assert_eq!(sketch.len(), 5000);
+        assert_eq!(sketch.error_bound(), 50);
+        assert!(sketch.relative_error() <= 0.01);
+        assert!(sketch.failure_probability() <= 0.01);
+    }
+
+    #[test]
+    fn merge_and_str() {
+        let mut s1: CountMin<str> = CountMin::new(64, 4);
+        let mut s2 = CountMin::new(64, 4);
+        s1.add("yes");
+        s2.add_count("yes", 4);
+        s2.add("no");
+        s1.merge(s2);
+        assert_eq!(s1.estimate("yes"), 5);
+        assert_eq!(s1.estimate("no"), 1);
+        assert_eq!(s1.estimate("maybe"), 0);
+        assert_eq!(s1.len(), 6);
+    }
+
+    #[test]
+    fn saturates() {
+        let mut s1: CountMin<u8> = CountMin::new(8, 2);
+        s1.add_count(&1, u64::MAX - 1);
+        s1.add_count(&2, 5);
+        assert_eq!(s1.len(), u64::MAX);
+        let mut s2 = CountMin::new(8, 2);
+        s2.add_count(&1, 3);
+        s1.merge(s2);
+        assert_eq!(s1.len(), u64::MAX);
+        assert_eq!(s1.estimate(&1), u64::MAX);
+    }
+
+    #[test]
+    fn deserialize_checks_dimensions() {
+        let mut sketch: CountMin<u8> = CountMin::new(3, 2);
+        sketch.add(&1);
+        let json = serde_json::to_string(&sketch).unwrap();
+        assert!(json.contains(r#""error_bound":1,"#));
+        let back: CountMin<u8> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.estimate(&1), 1);
+        assert!(back == sketch);
+
+        let short = r#"{"width":3,"depth":2,"len":0,"counters":[0,0,0]}"#;
+        assert!(serde_json::from_str::<CountMin<u8>>(short).is_err());
+        let empty = r#"{"width":0,"depth":2,"len":0,"counters":[]}"#;
+        assert!(serde_json::from_str::<CountMin<u8>>(empty).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "dimensions overflow")]
+    fn dimensions_overflow() {
+        let _ = CountMin::<u8>::new(usize::MAX, 2);
+    }
+}
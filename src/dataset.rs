@@ -0,0 +1,172 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Commute;
+
+/// A commutative container of per-column accumulators for tabular data.
+///
+/// Each record is split across the columns, so one `add_record` call
+/// updates every column's accumulator. Columns can be addressed by index
+/// or, if the set was created with `with_names`, by name. Shards of the
+/// same data set built on different threads are combined with `merge`.
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
+pub struct ColumnSet<S> {
+    names: Vec<String>,
+    columns: Vec<S>,
+    records: u64,
+}
+
+impl<S: Commute + Default> ColumnSet<S> {
+    /// Create a set of `n` unnamed columns.
+    #[must_use]
+    pub fn with_columns(n: usize) -> ColumnSet<S> {
+        ColumnSet {
+            names: Vec::new(),
+            columns: (0..n).map(|_| S::default()).collect(),
+            records: 0,
+        }
+    }
+
+    /// Create a set with one column per name, e.g. from a CSV header.
+    #[must_use]
+    pub fn with_names<N: Into<String>, I: IntoIterator<Item = N>>(names: I) -> ColumnSet<S> {
+        let names: Vec<String> = names.into_iter().map(Into::into).collect();
+        let mut set = ColumnSet::with_columns(names.len());
+        set.names = names;
+        set
+    }
+
+    /// Add one record, giving the `i`th field to the `i`th column.
+    ///
+    /// A record with more fields than there are columns adds unnamed
+    /// columns. Columns beyond the end of a short record are left as is.
+    #[inline]
+    pub fn add_record<V, I>(&mut self, record: I)
+    where
+        S: Extend<V>,
+        I: IntoIterator<Item = V>,
+    {
+        self.records += 1;
+        for (i, field) in record.into_iter().enumerate() {
+            if i == self.columns.len() {
+                self.columns.push(S::default());
+            }
+            self.columns[i].extend(Some(field));
+        }
+    }
+
+    /// Returns the accumulator of column `i`.
+    #[inline]
+    #[must_use]
+    pub fn column(&self, i: usize) -> Option<&S> {
+        self.columns.get(i)
+    }
+
+    /// Returns the accumulator of the column named `name`.
+    #[inline]
+    #[must_use]
+    pub fn column_by_name(&self, name: &str) -> Option<&S> {
+        let i = self.names.iter().position(|n| n == name)?;
+        self.columns.get(i)
+    }
+
+    /// Returns the accumulators of all columns, in order.
+    #[inline]
+    #[must_use]
+    pub fn columns(&self) -> &[S] {
+        &self.columns
+    }
+
+    /// Returns the column names, empty if the columns are unnamed.
+    #[inline]
+    #[must_use]
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Returns the number of records added.
+    #[inline]
+    #[must_use]
+    pub const fn records(&self) -> u64 {
+        self.records
+    }
+
+    /// Returns the number of columns.
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.columns.len()
+    }
+
+    /// Returns true if there are no columns.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+
+    /// Consumes the set, returning the accumulators of all columns.
+    #[inline]
+    #[must_use]
+    pub fn into_columns(self) -> Vec<S> {
+        self.columns
+    }
+}
+
+impl<S: Commute + Default> Commute for ColumnSet<S> {
+    /// Merges column by column. If `v` has more columns, they are added;
+    /// if `self` has no names, it takes those of `v`.
+    #[inline]
+    fn merge(&mut self, v: ColumnSet<S>) {
+        if self.names.is_empty() {
+            self.names = v.names;
+        }
+        self.records += v.records;
+        let mut other = v.columns.into_iter();
+        for (c1, c2) in self.columns.iter_mut().zip(&mut other) {
+            c1.merge(c2);
+        }
+        self.columns.extend(other);
+    }
+}
+
+impl<S: Commute + Default> Default for ColumnSet<S> {
+    #[inline]
+    fn default() -> ColumnSet<S> {
+        ColumnSet::with_columns(0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ColumnSet;
+    use crate::{Commute, MinMax, OnlineStats};
+
+    #[test]
+    fn records_by_column() {
+        let mut set: ColumnSet<OnlineStats> = ColumnSet::with_names(["x", "y"]);
+        set.add_record([1.0, 10.0]);
+        set.add_record([3.0, 30.0]);
+        let mut shard = ColumnSet::with_columns(2);
+        shard.add_record([5, 50]);
+        set.merge(shard);
+
+        assert_eq!(set.records(), 3);
+        assert_eq!(set.column(0).unwrap().mean(), 3.0);
+        assert_eq!(set.column_by_name("y").unwrap().mean(), 30.0);
+        assert!(set.column_by_name("z").is_none());
+        assert_eq!(set.names(), ["x", "y"]);
+    }
+
+    #[test]
+    fn ragged_records() {
+        let mut set: ColumnSet<MinMax<&str>> = ColumnSet::default();
+        set.add_record(["a", "b"]);
+        set.add_record(["c"]);
+        set.add_record(["d", "e", "f"]);
+        assert_eq!(set.len(), 3);
+        let columns = set.into_columns();
+        assert_eq!(columns[0].max(), Some(&"d"));
+        assert_eq!(columns[1].len(), 2);
+        assert_eq!(columns[2].min(), Some(&"f"));
+    }
+}
@@ -0,0 +1,408 @@
+use std::collections::BTreeMap;
+
+use num_traits::ToPrimitive;
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::Commute;
+
+/// A DDSketch, a mergeable quantile sketch with relative-error guarantees.
+///
+/// Values are counted in logarithmically sized buckets, so every quantile
+/// estimate is within a relative error of `alpha` of the true value (e.g.
+/// 1% with `alpha = 0.01`). Unlike sketches with rank-error guarantees,
+/// this keeps high quantiles such as p99 and p999 of long-tailed data like
+/// latencies accurate. The number of buckets grows with the logarithm of
+/// the range of the data, not with the number of values.
+///
+/// With `with_max_buckets`, memory is bounded by merging the buckets of the
+/// smallest magnitudes, which keeps the guarantee for the upper quantiles.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(try_from = "DDSketchParts")]
+pub struct DDSketch {
+    alpha: f64,
+    gamma_ln: f64,
+    max_buckets: Option<usize>,
+    positive: BTreeMap<i32, u64>,
+    negative: BTreeMap<i32, u64>,
+    zeros: u64,
+    len: u64,
+    min: f64,
+    max: f64,
+}
+
+/// The serialized form of a `DDSketch`, which is checked before use.
+#[derive(Deserialize)]
+struct DDSketchParts {
+    alpha: f64,
+    // derived from `alpha`, so they are recomputed rather than used
+    #[serde(default, rename = "gamma_ln")]
+    _gamma_ln: f64,
+    max_buckets: Option<usize>,
+    positive: BTreeMap<i32, u64>,
+    negative: BTreeMap<i32, u64>,
+    zeros: u64,
+    len: u64,
+    min: f64,
+    max: f64,
+    #[serde(default, rename = "relative_error")]
+    _relative_error: f64,
+}
+
+impl TryFrom<DDSketchParts> for DDSketch {
+    type Error = String;
+
+    fn try_from(v: DDSketchParts) -> Result<DDSketch, String> {
+        if !(v.alpha > 0.0 && v.alpha < 1.0) {
+            return Err(format!("DDSketch alpha must be in (0, 1), not {}", v.alpha));
+        }
+        if v.max_buckets == Some(0) {
+            return Err("DDSketch must allow at least one bucket".to_owned());
+        }
+        if let Some(max) = v.max_buckets {
+            let buckets = v.positive.len().max(v.negative.len());
+            if buckets > max {
+                return Err(format!(
+                    "DDSketch has {buckets} buckets for one sign, more than its maximum of {max}"
+                ));
+            }
+        }
+        let counted = v
+            .positive
+            .values()
+            .chain(v.negative.values())
+            .try_fold(v.zeros, |sum, &count| sum.checked_add(count));
+        if counted != Some(v.len) {
+            return Err(format!(
+                "DDSketch of {} values has buckets that count a different number",
+                v.len
+            ));
+        }
+        Ok(DDSketch {
+            max_buckets: v.max_buckets,
+            positive: v.positive,
+            negative: v.negative,
+            zeros: v.zeros,
+            len: v.len,
+            min: v.min,
+            max: v.max,
+            ..DDSketch::new(v.alpha)
+        })
+    }
+}
+
+impl Serialize for DDSketch {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("DDSketch", 10)?;
+        state.serialize_field("alpha", &self.alpha)?;
+        state.serialize_field("gamma_ln", &self.gamma_ln)?;
+        state.serialize_field("max_buckets", &self.max_buckets)?;
+        state.serialize_field("positive", &self.positive)?;
+        state.serialize_field("negative", &self.negative)?;
+        state.serialize_field("zeros", &self.zeros)?;
+        state.serialize_field("len", &self.len)?;
+        state.serialize_field("min", &self.min)?;
+        state.serialize_field("max", &self.max)?;
+        state.serialize_field("relative_error", &self.relative_error())?;
+        state.end()
+    }
+}
+
+impl DDSketch {
+    /// Create an empty sketch with relative accuracy `alpha`.
+    ///
+    /// # Panics
+    ///
+    /// If `alpha` is not in `(0, 1)`.
+    #[must_use]
+    pub fn new(alpha: f64) -> DDSketch {
+        assert!(alpha > 0.0 && alpha < 1.0, "alpha must be in (0, 1)");
+        DDSketch {
+            alpha,
+            gamma_ln: ((1.0 + alpha) / (1.0 - alpha)).ln(),
+            max_buckets: None,
+            positive: BTreeMap::new(),
+            negative: BTreeMap::new(),
+            zeros: 0,
+            len: 0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    /// Create an empty sketch with relative accuracy `alpha` that keeps at
+    /// most `max_buckets` buckets for each sign.
+    ///
+    /// # Panics
+    ///
+    /// If `alpha` is not in `(0, 1)`.
+    #[must_use]
+    pub fn with_max_buckets(alpha: f64, max_buckets: usize) -> DDSketch {
+        DDSketch {
+            max_buckets: Some(max_buckets.max(1)),
+            ..DDSketch::new(alpha)
+        }
+    }
+
+    /// Returns the bucket of a positive magnitude `x`.
+    #[inline]
+    fn key(&self, x: f64) -> i32 {
+        (x.ln() / self.gamma_ln).ceil() as i32
+    }
+
+    /// Returns the representative value of bucket `key`, which is within
+    /// `alpha` of every value in the bucket.
+    #[inline]
+    fn value(&self, key: i32) -> f64 {
+        (f64::from(key) * self.gamma_ln).exp() * 2.0 / (1.0 + (self.gamma_ln).exp())
+    }
+
+    /// Add a sample. `NaN`s are ignored.
+    #[inline]
+    pub fn add<T: ToPrimitive>(&mut self, sample: &T) {
+        let x = sample.to_f64().unwrap();
+        if x.is_nan() {
+            return;
+        }
+        self.len += 1;
+        self.min = self.min.min(x);
+        self.max = self.max.max(x);
+        // magnitudes too small to index are counted as zero
+        if x.abs() < f64::MIN_POSITIVE {
+            self.zeros += 1;
+        } else if x > 0.0 {
+            *self.positive.entry(self.key(x)).or_insert(0) += 1;
+            self.collapse();
+        } else {
+            *self.negative.entry(self.key(-x)).or_insert(0) += 1;
+            self.collapse();
+        }
+    }
+
+    /// Merges the buckets of the smallest magnitudes until each store has
+    /// at most `max_buckets` buckets.
+    fn collapse(&mut self) {
+        let Some(max) = self.max_buckets else {
+            return;
+        };
+        for store in [&mut self.positive, &mut self.negative] {
+            while store.len() > max {
+                let (_, count) = store.pop_first().unwrap();
+                *store.first_entry().unwrap().get_mut() += count;
+            }
+        }
+    }
+
+    /// Returns the estimated `q`th quantile, for `q` in `[0, 1]`.
+    ///
+    /// The estimate is within a relative error of `alpha` of the value of
+    /// rank `q * (len - 1)`. The minimum and maximum are exact.
+    ///
+    /// `None` is returned if the sketch is empty or `q` is not in `[0, 1]`.
+    #[must_use]
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        if self.len == 0 || !(0.0..=1.0).contains(&q) {
+            return None;
+        }
+        let rank = (q * (self.len - 1) as f64).floor() as u64;
+        if rank == 0 {
+            return Some(self.min);
+        }
+        if rank == self.len - 1 {
+            return Some(self.max);
+        }
+        let mut seen = 0;
+        // negative values in ascending order have descending magnitudes
+        for (&key, &count) in self.negative.iter().rev() {
+            seen += count;
+            if seen > rank {
+                return Some(self.clamp(-self.value(key)));
+            }
+        }
+        seen += self.zeros;
+        if seen > rank {
+            return Some(self.clamp(0.0));
+        }
+        for (&key, &count) in &self.positive {
+            seen += count;
+            if seen > rank {
+                return Some(self.clamp(self.value(key)));
+            }
+        }
+        Some(self.max)
+    }
+
+    #[inline]
+    fn clamp(&self, x: f64) -> f64 {
+        x.clamp(self.min, self.max)
+    }
+
+    /// Returns the relative accuracy of the sketch.
+    #[inline]
+    #[must_use]
+    pub const fn alpha(&self) -> f64 {
+        self.alpha
+    }
+
+    /// Returns the relative error guaranteed for quantiles, i.e. `alpha`.
+    ///
+    /// Every quantile is within `relative_error() * |x|` of the exact
+    /// value `x`, except the lowest quantiles once buckets have been
+    /// collapsed to stay within `max_buckets`.
+    #[inline]
+    #[must_use]
+    pub const fn relative_error(&self) -> f64 {
+        self.alpha
+    }
+
+    /// Returns the absolute error bound of `quantile(q)`, or `None` if the
+    /// sketch is empty or `q` is not in `[0, 1]`.
+    #[must_use]
+    pub fn error_bound(&self, q: f64) -> Option<f64> {
+        self.quantile(q).map(|x| x.abs() * self.alpha)
+    }
+
+    /// Returns the number of buckets in use.
+    #[inline]
+    #[must_use]
+    pub fn buckets(&self) -> usize {
+        self.positive.len() + self.negative.len() + usize::from(self.zeros > 0)
+    }
+
+    /// Returns the smallest value, or `None` if the sketch is empty.
+    #[inline]
+    #[must_use]
+    pub fn min(&self) -> Option<f64> {
+        (self.len > 0).then_some(self.min)
+    }
+
+    /// Returns the largest value, or `None` if the sketch is empty.
+    #[inline]
+    #[must_use]
+    pub fn max(&self) -> Option<f64> {
+        (self.len > 0).then_some(self.max)
+    }
+
+    /// Returns the number of values.
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns true if there are no values.
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Commute for DDSketch {
+    /// # Panics
+    ///
+    /// If the sketches have different relative accuracies.
+    #[inline]
+    fn merge(&mut self, v: DDSketch) {
+        assert!(
+            self.alpha == v.alpha,
+            "cannot merge DDSketches of different accuracies"
+        );
+        for (store, other) in [
+            (&mut self.positive, v.positive),
+            (&mut self.negative, v.negative),
+        ] {
+            for (key, count) in other {
+                *store.entry(key).or_insert(0) += count;
+            }
+        }
+        self.zeros += v.zeros;
+        self.len += v.len;
+        self.min = self.min.min(v.min);
+        self.max = self.max.max(v.max);
+        self.max_buckets = match (self.max_buckets, v.max_buckets) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        self.collapse();
+    }
+}
+
+impl<T: ToPrimitive> Extend<T> for DDSketch {
+    #[inline]
+    fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
+        for sample in it {
+            self.add(&sample);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DDSketch;
+    use crate::Commute;
+
+    #[test]
+    fn relative_error() {
+        let data: Vec<f64> = (1..=10_000).map(|i| f64::from(i).powf(1.5)).collect();
+        let mut sketch = DDSketch::new(0.01);
+        sketch.extend(data.iter().copied());
+        for q in [0.0, 0.01, 0.5, 0.9, 0.99, 0.999, 1.0] {
+            let exact = data[(q * 9999.0) as usize];
+            let est = sketch.quantile(q).unwrap();
+            assert!((est - exact).abs() <= 0.01 * exact, "q={q}");
+        }
+        assert!(sketch.buckets() < 500);
+        assert_eq!(sketch.quantile(1.5), None);
+        assert_eq!(sketch.relative_error(), 0.01);
+        let bound = sketch.error_bound(0.5).unwrap();
+        assert!((bound - sketch.quantile(0.5).unwrap() * 0.01).abs() < 1e-12);
+
+        let json = serde_json::to_string(&sketch).unwrap();
+        assert!(json.ends_with(r#""relative_error":0.01}"#));
+        assert_eq!(serde_json::from_str::<DDSketch>(&json).unwrap(), sketch);
+    }
+
+    #[test]
+    fn deserialize_checks_parameters() {
+        let mut sketch = DDSketch::with_max_buckets(0.01, 4);
+        sketch.extend([1.0, 10.0, 100.0]);
+        let json = serde_json::to_string(&sketch).unwrap();
+        // a stale gamma_ln is recomputed from alpha
+        let stale = json.replace(r#""gamma_ln":"#, r#""gamma_ln":1e9,"_":"#);
+        assert_eq!(serde_json::from_str::<DDSketch>(&stale).unwrap(), sketch);
+        for bad in [
+            json.replace(r#""alpha":0.01"#, r#""alpha":0.0"#),
+            json.replace(r#""alpha":0.01"#, r#""alpha":1.5"#),
+            json.replace(r#""max_buckets":4"#, r#""max_buckets":0"#),
+            json.replace(r#""max_buckets":4"#, r#""max_buckets":2"#),
+            json.replace(r#""len":3"#, r#""len":4"#),
+        ] {
+            assert!(serde_json::from_str::<DDSketch>(&bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn signs_and_merge() {
+        let mut s1 = DDSketch::new(0.02);
+        s1.extend([-8.0, -2.0, 0.0]);
+        let mut s2 = DDSketch::new(0.02);
+        s2.extend([1.0, 4.0]);
+        s1.merge(s2);
+        assert_eq!(s1.len(), 5);
+        assert_eq!(s1.quantile(0.0), Some(-8.0));
+        assert!((s1.quantile(0.25).unwrap() + 2.0).abs() <= 0.04);
+        assert_eq!(s1.quantile(0.5), Some(0.0));
+        assert!((s1.quantile(0.75).unwrap() - 1.0).abs() <= 0.02 + 1e-12);
+        assert_eq!(s1.max(), Some(4.0));
+    }
+
+    #[test]
+    fn bounded_buckets() {
+        let mut sketch = DDSketch::with_max_buckets(0.01, 50);
+        sketch.extend((1..=100_000).map(f64::from));
+        assert_eq!(sketch.buckets(), 50);
+        let p99 = sketch.quantile(0.99).unwrap();
+        assert!((p99 - 99_000.0).abs() <= 0.01 * 99_000.0);
+    }
+}
@@ -0,0 +1,203 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::{Commute, MinMax, Unsorted};
+
+/// Online state for exact statistics on decimal data, such as money.
+///
+/// Converting to `f64` loses precision for values like `0.1`, so this
+/// keeps a running `Decimal` sum instead. Fixed-point integers (e.g. cents
+/// stored as `i128`) can be added with `add_scaled`.
+///
+/// If the sum overflows the range of `Decimal`, the sum and mean are no
+/// longer available, but min/max and the count still are.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DecimalStats {
+    sum: Option<Decimal>,
+    minmax: MinMax<Decimal>,
+}
+
+impl DecimalStats {
+    /// Create initial empty state.
+    #[must_use]
+    pub fn new() -> DecimalStats {
+        Default::default()
+    }
+
+    /// Add a new sample.
+    #[inline]
+    pub fn add(&mut self, sample: Decimal) {
+        self.sum = self.sum.and_then(|sum| sum.checked_add(sample));
+        self.minmax.add(sample);
+    }
+
+    /// Add a new fixed-point sample, `mantissa * 10^-scale`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value cannot be represented as a `Decimal`, i.e. if
+    /// `scale > 28` or the mantissa needs more than 96 bits.
+    #[inline]
+    pub fn add_scaled(&mut self, mantissa: i128, scale: u32) {
+        self.add(Decimal::from_i128_with_scale(mantissa, scale));
+    }
+
+    /// Returns the number of data points.
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.minmax.len()
+    }
+
+    /// Returns true if there are no data points.
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.minmax.is_empty()
+    }
+
+    /// Returns the exact sum of the data.
+    ///
+    /// `None` is returned if the sum overflowed.
+    #[inline]
+    #[must_use]
+    pub const fn sum(&self) -> Option<Decimal> {
+        self.sum
+    }
+
+    /// Returns the mean of the data, rounded to the precision of `Decimal`.
+    ///
+    /// `None` is returned if there is no data or the sum overflowed.
+    #[inline]
+    #[must_use]
+    pub fn mean(&self) -> Option<Decimal> {
+        if self.is_empty() {
+            return None;
+        }
+        self.sum?.checked_div(Decimal::from(self.len()))
+    }
+
+    /// Returns the minimum of the data.
+    #[inline]
+    #[must_use]
+    pub const fn min(&self) -> Option<&Decimal> {
+        self.minmax.min()
+    }
+
+    /// Returns the maximum of the data.
+    #[inline]
+    #[must_use]
+    pub const fn max(&self) -> Option<&Decimal> {
+        self.minmax.max()
+    }
+}
+
+impl Commute for DecimalStats {
+    #[inline]
+    fn merge(&mut self, v: DecimalStats) {
+        self.sum = match (self.sum, v.sum) {
+            (Some(a), Some(b)) => a.checked_add(b),
+            _ => None,
+        };
+        self.minmax.merge(v.minmax);
+    }
+}
+
+impl Default for DecimalStats {
+    #[inline]
+    fn default() -> DecimalStats {
+        DecimalStats {
+            sum: Some(Decimal::ZERO),
+            minmax: MinMax::new(),
+        }
+    }
+}
+
+impl FromIterator<Decimal> for DecimalStats {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = Decimal>>(it: I) -> DecimalStats {
+        let mut v = DecimalStats::new();
+        v.extend(it);
+        v
+    }
+}
+
+impl Extend<Decimal> for DecimalStats {
+    #[inline]
+    fn extend<I: IntoIterator<Item = Decimal>>(&mut self, it: I) {
+        for sample in it {
+            self.add(sample);
+        }
+    }
+}
+
+impl Unsorted<Decimal> {
+    /// Returns the exact median of the data, without converting to `f64`.
+    ///
+    /// For an even number of values, the mean of the two middle values is
+    /// returned.
+    #[must_use]
+    pub fn decimal_median(&mut self) -> Option<Decimal> {
+        let data = self.as_sorted_slice();
+        let len = data.len();
+        if len == 0 {
+            return None;
+        }
+        if len % 2 == 1 {
+            return Some(data[len / 2]);
+        }
+        let (a, b) = (data[len / 2 - 1], data[len / 2]);
+        Some(match a.checked_add(b) {
+            Some(sum) => sum / Decimal::TWO,
+            None => a / Decimal::TWO + b / Decimal::TWO,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rust_decimal::Decimal;
+
+    use super::DecimalStats;
+    use crate::{Commute, Unsorted};
+
+    fn dec(s: &str) -> Decimal {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn exact_sum() {
+        let mut stats: DecimalStats = std::iter::repeat(dec("0.1")).take(10).collect();
+        assert_eq!(stats.sum(), Some(dec("1.0")));
+        assert_eq!(stats.mean(), Some(dec("0.1")));
+
+        stats.add_scaled(-250, 2);
+        assert_eq!(stats.len(), 11);
+        assert_eq!(stats.sum(), Some(dec("-1.5")));
+        assert_eq!(stats.min(), Some(&dec("-2.5")));
+        assert_eq!(stats.max(), Some(&dec("0.1")));
+
+        assert_eq!(DecimalStats::default().sum(), Some(Decimal::ZERO));
+        assert_eq!(DecimalStats::new().mean(), None);
+    }
+
+    #[test]
+    fn overflow_and_merge() {
+        let mut s1: DecimalStats = [Decimal::MAX].into_iter().collect();
+        let s2: DecimalStats = [Decimal::ONE].into_iter().collect();
+        s1.merge(s2);
+        assert_eq!(s1.len(), 2);
+        assert_eq!(s1.sum(), None);
+        assert_eq!(s1.mean(), None);
+        assert_eq!(s1.min(), Some(&Decimal::ONE));
+    }
+
+    #[test]
+    fn median() {
+        let mut v: Unsorted<Decimal> = ["0.3", "0.1", "0.2", "0.4"].into_iter().map(dec).collect();
+        assert_eq!(v.decimal_median(), Some(dec("0.25")));
+        v.add(dec("0.5"));
+        assert_eq!(v.decimal_median(), Some(dec("0.3")));
+        assert_eq!(Unsorted::<Decimal>::new().decimal_median(), None);
+    }
+}
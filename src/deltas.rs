@@ -0,0 +1,245 @@
+use std::fmt;
+
+use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+
+use crate::round::{RoundPolicy, RoundedDisplay};
+use crate::{Commute, MinMax, OnlineStats};
+
+/// Online statistics of the differences between consecutive values of an
+/// ordered stream, such as a timestamp or counter column.
+///
+/// The mean, spread and extremes of the differences describe how regularly
+/// the stream was sampled, and the share of negative differences shows how
+/// close it is to being monotonic.
+///
+/// Since the differences depend on the order of the values, this does not
+/// implement `Commute`. Chunks of a stream can instead be combined, in
+/// stream order, with `append`.
+#[derive(Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Deltas {
+    first: Option<f64>,
+    last: Option<f64>,
+    stats: OnlineStats,
+    minmax: MinMax<f64>,
+    negative: u64,
+    zero: u64,
+}
+
+impl Deltas {
+    /// Create initial empty state.
+    #[must_use]
+    pub fn new() -> Deltas {
+        Default::default()
+    }
+
+    /// Add the next value of the stream.
+    #[inline]
+    pub fn add<T: ToPrimitive>(&mut self, sample: &T) {
+        let x = sample.to_f64().unwrap();
+        match self.last {
+            Some(last) => self.add_delta(x - last),
+            None => self.first = Some(x),
+        }
+        self.last = Some(x);
+    }
+
+    #[inline]
+    fn add_delta(&mut self, d: f64) {
+        self.stats.add(&d);
+        self.minmax.add(d);
+        if d < 0.0 {
+            self.negative += 1;
+        } else if d == 0.0 {
+            self.zero += 1;
+        }
+    }
+
+    /// Appends the state of the chunk of the stream that immediately
+    /// follows this one, adding the difference across the boundary.
+    pub fn append(&mut self, v: Deltas) {
+        let (Some(last), Some(next)) = (self.last, v.first) else {
+            if self.first.is_none() {
+                *self = v;
+            }
+            return;
+        };
+        self.add_delta(next - last);
+        self.stats.merge(v.stats);
+        self.minmax.merge(v.minmax);
+        self.negative += v.negative;
+        self.zero += v.zero;
+        self.last = v.last;
+    }
+
+    /// Returns the mean difference.
+    #[inline]
+    #[must_use]
+    pub const fn mean(&self) -> f64 {
+        self.stats.mean()
+    }
+
+    /// Returns the standard deviation of the differences.
+    #[inline]
+    #[must_use]
+    pub fn stddev(&self) -> f64 {
+        self.stats.stddev()
+    }
+
+    /// Returns the smallest difference, or `None` if there are fewer than
+    /// two values.
+    #[inline]
+    #[must_use]
+    pub fn min(&self) -> Option<f64> {
+        self.minmax.min().copied()
+    }
+
+    /// Returns the largest difference, or `None` if there are fewer than
+    /// two values.
+    #[inline]
+    #[must_use]
+    pub fn max(&self) -> Option<f64> {
+        self.minmax.max().copied()
+    }
+
+    /// Returns the number of negative differences.
+    #[inline]
+    #[must_use]
+    pub const fn negative_count(&self) -> u64 {
+        self.negative
+    }
+
+    /// Returns the number of zero differences, i.e. repeated values.
+    #[inline]
+    #[must_use]
+    pub const fn zero_count(&self) -> u64 {
+        self.zero
+    }
+
+    /// Returns the fraction of differences that are negative, or `None` if
+    /// there are fewer than two values.
+    #[inline]
+    #[must_use]
+    pub fn negative_fraction(&self) -> Option<f64> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(self.negative as f64 / self.len() as f64)
+        }
+    }
+
+    /// Returns true if no difference is negative.
+    #[inline]
+    #[must_use]
+    pub const fn is_non_decreasing(&self) -> bool {
+        self.negative == 0
+    }
+
+    /// Returns true if no difference is positive.
+    #[inline]
+    #[must_use]
+    pub const fn is_non_increasing(&self) -> bool {
+        self.negative + self.zero == self.stats.len() as u64
+    }
+
+    /// Returns the number of differences, i.e. one less than the number of
+    /// values.
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.stats.len()
+    }
+
+    /// Returns true if there are no differences.
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.stats.is_empty()
+    }
+}
+
+impl fmt::Debug for Deltas {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "deltas {:?} in [{:?}, {:?}]",
+            self.stats,
+            self.min(),
+            self.max()
+        )
+    }
+}
+
+/// Shows the statistics of the differences and their range.
+impl fmt::Display for Deltas {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_rounded(&RoundPolicy::new(), f)
+    }
+}
+
+impl RoundedDisplay for Deltas {
+    fn fmt_rounded(&self, policy: &RoundPolicy, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "deltas {}, range {}",
+            policy.apply(&self.stats),
+            policy.apply(&self.minmax)
+        )
+    }
+}
+
+impl<T: ToPrimitive> FromIterator<T> for Deltas {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = T>>(it: I) -> Deltas {
+        let mut v = Deltas::new();
+        v.extend(it);
+        v
+    }
+}
+
+impl<T: ToPrimitive> Extend<T> for Deltas {
+    #[inline]
+    fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
+        for sample in it {
+            self.add(&sample);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Deltas;
+
+    #[test]
+    fn timestamps() {
+        let deltas: Deltas = [100, 110, 120, 120, 135, 130].into_iter().collect();
+        assert_eq!(deltas.len(), 5);
+        assert_eq!(deltas.mean(), 6.0);
+        assert_eq!((deltas.min(), deltas.max()), (Some(-5.0), Some(15.0)));
+        assert_eq!((deltas.negative_count(), deltas.zero_count()), (1, 1));
+        assert_eq!(deltas.negative_fraction(), Some(0.2));
+        assert!(!deltas.is_non_decreasing() && !deltas.is_non_increasing());
+
+        let single: Deltas = [7].into_iter().collect();
+        assert!(single.is_empty() && single.is_non_decreasing());
+        assert_eq!(single.negative_fraction(), None);
+    }
+
+    #[test]
+    fn append_chunks() {
+        let data = [1.0, 3.0, 2.0, 2.0, 8.0, 9.0, 4.0];
+        let whole: Deltas = data.iter().copied().collect();
+        let mut chunked = Deltas::new();
+        for chunk in [&data[..0], &data[..1], &data[1..4], &data[4..]] {
+            chunked.append(chunk.iter().copied().collect());
+        }
+        assert_eq!(chunked.len(), 6);
+        assert!((chunked.mean() - whole.mean()).abs() < 1e-12);
+        assert!((chunked.stddev() - whole.stddev()).abs() < 1e-12);
+        assert_eq!((chunked.min(), chunked.max()), (whole.min(), whole.max()));
+        assert_eq!(chunked.negative_count(), 2);
+        assert!(Deltas::from_iter([5, 4, 4]).is_non_increasing());
+    }
+}
@@ -0,0 +1,373 @@
+use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "ostree")]
+use crate::OrderStatTree;
+use crate::{Commute, ExactSum, Metrics, MinMax, NanPolicy, OnlineStats, StatsConfig, Unsorted};
+
+/// The statistics of a column, as reported by `Describe::describe`.
+///
+/// Statistics that are undefined for the data (e.g. the median of no
+/// values) or were not selected in the configured `Metrics` are `None`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Description<T> {
+    /// The number of values.
+    pub len: usize,
+    /// The number of NULLs, including `NaN` samples under
+    /// `NanPolicy::Null`.
+    pub nulls: u64,
+    /// The number of `NaN` samples dropped under `NanPolicy::Skip`.
+    pub nans: u64,
+    /// The number of values beyond `MAX_SAFE_INTEGER`, which may have been
+    /// rounded, so that the mean, standard deviation and quantiles may be
+    /// imprecise.
+    pub imprecise: u64,
+    /// The mean.
+    pub mean: Option<f64>,
+    /// The population standard deviation.
+    pub stddev: Option<f64>,
+    /// The sum, as `OnlineSummary::sum`.
+    pub sum: Option<f64>,
+    /// The exact sum, if every value is an integer and the sum fits in an
+    /// `i128`.
+    pub exact_sum: Option<i128>,
+    /// The smallest value.
+    pub min: Option<T>,
+    /// The largest value.
+    pub max: Option<T>,
+    /// The first quartile, by the configured quantile method.
+    pub q1: Option<f64>,
+    /// The median, by the configured quantile method.
+    pub median: Option<f64>,
+    /// The third quartile, by the configured quantile method.
+    pub q3: Option<f64>,
+    /// The median absolute deviation around the median.
+    pub mad: Option<f64>,
+    /// The most frequent values, which are empty if no value repeats.
+    pub modes: Option<Vec<T>>,
+    /// The least frequent values, up to the configured limit.
+    pub antimodes: Option<Vec<T>>,
+    /// The number of distinct values.
+    pub cardinality: Option<usize>,
+}
+
+/// A commutative accumulator for the usual summary of a numeric column:
+/// moments, order statistics, modes and cardinality, in one place.
+///
+/// The values are buffered in an `Unsorted`, so this uses `O(n)` memory,
+/// unless the configured `Metrics` only select the moments and the minimum
+/// and maximum, which are tracked as the values are added. Settings such
+/// as the `NaN` policy, the quantile method and the `QuantileBackend` come
+/// from a `StatsConfig`.
+///
+/// Merging keeps the configuration of `self`.
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
+#[serde(bound(deserialize = "T: Deserialize<'de> + PartialOrd"))]
+pub struct Describe<T> {
+    config: StatsConfig,
+    stats: OnlineStats,
+    sum: ExactSum,
+    minmax: MinMax<T>,
+    unsorted: Unsorted<T>,
+    #[cfg(feature = "ostree")]
+    tree: OrderStatTree<T>,
+    nulls: u64,
+    nans: u64,
+}
+
+impl<T: PartialOrd + ToPrimitive + Clone> Describe<T> {
+    /// Create initial empty state with the default configuration.
+    #[must_use]
+    pub fn new() -> Describe<T> {
+        Describe::with_config(StatsConfig::new())
+    }
+
+    /// Create initial empty state with the given configuration.
+    #[must_use]
+    pub fn with_config(config: StatsConfig) -> Describe<T> {
+        Describe {
+            config,
+            stats: OnlineStats::new(),
+            sum: ExactSum::new(),
+            minmax: MinMax::new(),
+            unsorted: config.unsorted().into_inner(),
+            #[cfg(feature = "ostree")]
+            tree: config.order_stat_tree(),
+            nulls: 0,
+            nans: 0,
+        }
+    }
+
+    /// Add a new sample, applying the `NaN` policy.
+    #[inline]
+    pub fn add(&mut self, sample: T) {
+        let policy = self.config.nan_policy();
+        if policy.excludes(&sample) {
+            match policy {
+                NanPolicy::Null => self.nulls += 1,
+                _ => self.nans += 1,
+            }
+            return;
+        }
+        self.stats.add(&sample);
+        self.sum.add_numeric(&sample);
+        let metrics = self.config.metrics();
+        if metrics.contains(Metrics::MINMAX) {
+            self.minmax.add(sample.clone());
+        }
+        #[cfg(feature = "ostree")]
+        if self.config.tree_quartiles() {
+            self.tree.add(sample.clone());
+        }
+        if self.config.buffers_values() {
+            self.unsorted.add(sample);
+        }
+    }
+
+    /// Add a NULL value, which is counted but not otherwise used.
+    #[inline]
+    pub fn add_null(&mut self) {
+        self.nulls += 1;
+    }
+
+    /// Returns the configuration.
+    #[inline]
+    #[must_use]
+    pub const fn config(&self) -> &StatsConfig {
+        &self.config
+    }
+
+    /// Returns the number of values, excluding NULLs and dropped `NaN`s.
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.stats.len()
+    }
+
+    /// Returns true if there are no values.
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.stats.is_empty()
+    }
+
+    /// Computes the selected statistics. This sorts the buffered values
+    /// once, if any are selected that need them.
+    pub fn describe(&mut self) -> Description<T> {
+        let metrics = self.config.metrics();
+        let method = self.config.quantile_method();
+        let limit = self.config.antimode_limit();
+        let nonempty = !self.is_empty();
+        let unsorted = &mut self.unsorted;
+        let quartiles = metrics.contains(Metrics::QUARTILES);
+        #[cfg(feature = "ostree")]
+        let tree = self.config.tree_quartiles().then_some(&self.tree);
+        let mut quantile = |q| {
+            if !quartiles {
+                return None;
+            }
+            #[cfg(feature = "ostree")]
+            if let Some(tree) = tree {
+                return tree.quantile(q);
+            }
+            unsorted.quantile_with(q, method)
+        };
+        let (q1, median, q3) = (quantile(0.25), quantile(0.5), quantile(0.75));
+        let moments = metrics.contains(Metrics::MOMENTS) && nonempty;
+        Description {
+            len: self.stats.len(),
+            nulls: self.nulls,
+            nans: self.nans,
+            imprecise: self.stats.imprecise_count(),
+            mean: moments.then(|| self.stats.mean()),
+            stddev: moments.then(|| self.stats.stddev()),
+            sum: moments.then(|| {
+                self.sum
+                    .sum()
+                    .map_or_else(|| self.stats.mean() * self.stats.len() as f64, |s| s as f64)
+            }),
+            exact_sum: self.sum.sum().filter(|_| moments),
+            min: self.minmax.min().cloned(),
+            max: self.minmax.max().cloned(),
+            q1,
+            median,
+            q3,
+            mad: metrics
+                .contains(Metrics::MAD)
+                .then(|| unsorted.mad(None))
+                .flatten(),
+            modes: metrics.contains(Metrics::MODES).then(|| unsorted.modes().0),
+            antimodes: metrics
+                .contains(Metrics::ANTIMODES)
+                .then(|| unsorted.antimodes_with_limit(limit).0),
+            cardinality: metrics.contains(Metrics::CARDINALITY).then(|| {
+                unsorted
+                    .duplicate_stats()
+                    .map_or(0, |d| d.distinct as usize)
+            }),
+        }
+    }
+}
+
+impl<T: PartialOrd> Commute for Describe<T> {
+    #[inline]
+    fn merge(&mut self, v: Describe<T>) {
+        self.stats.merge(v.stats);
+        self.sum.merge(v.sum);
+        self.minmax.merge(v.minmax);
+        if self.config.buffers_values() {
+            self.unsorted.merge(v.unsorted);
+        }
+        #[cfg(feature = "ostree")]
+        if self.config.tree_quartiles() {
+            self.tree.merge(v.tree);
+        }
+        self.nulls += v.nulls;
+        self.nans += v.nans;
+    }
+}
+
+impl<T: PartialOrd + ToPrimitive + Clone> Default for Describe<T> {
+    #[inline]
+    fn default() -> Describe<T> {
+        Describe::new()
+    }
+}
+
+impl<T: PartialOrd + ToPrimitive + Clone> FromIterator<T> for Describe<T> {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = T>>(it: I) -> Describe<T> {
+        let mut v = Describe::new();
+        v.extend(it);
+        v
+    }
+}
+
+impl<T: PartialOrd + ToPrimitive + Clone> Extend<T> for Describe<T> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
+        for sample in it {
+            self.add(sample);
+        }
+    }
+}
+
+impl<T: PartialOrd + ToPrimitive + Clone> Extend<Option<T>> for Describe<T> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = Option<T>>>(&mut self, it: I) {
+        for sample in it {
+            match sample {
+                Some(v) => self.add(v),
+                None => self.add_null(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Describe;
+    use crate::{Commute, Metrics, NanPolicy, QuantileMethod, StatsConfig};
+
+    #[test]
+    fn describe() {
+        let mut d: Describe<i32> = [5, 1, 4, 4, 2, 3, 8].into_iter().collect();
+        d.extend([None, Some(4)]);
+        let desc = d.describe();
+        assert_eq!((desc.len, desc.nulls, desc.nans), (8, 1, 0));
+        assert!((desc.mean.unwrap() - 3.875).abs() < 1e-12);
+        assert_eq!((desc.min, desc.max), (Some(1), Some(8)));
+        assert_eq!(desc.median, Some(4.0));
+        assert_eq!((desc.q1, desc.q3), (Some(2.75), Some(4.25)));
+        assert_eq!(desc.mad, Some(1.0));
+        assert_eq!(desc.modes, Some(vec![4]));
+        assert_eq!(desc.antimodes, Some(vec![1, 2, 3, 5, 8]));
+        assert_eq!(desc.cardinality, Some(6));
+        assert_eq!((desc.sum, desc.exact_sum), (Some(31.0), Some(31)));
+        assert_eq!(desc.imprecise, 0);
+        let mut big: Describe<u64> = [1 << 60, 1].into_iter().collect();
+        assert_eq!(big.describe().imprecise, 1);
+
+        let empty = Describe::<f64>::new().describe();
+        assert_eq!(
+            (empty.mean, empty.median, empty.cardinality),
+            (None, None, Some(0))
+        );
+    }
+
+    #[test]
+    fn configured() {
+        let config = StatsConfig::new()
+            .with_nan_policy(NanPolicy::Skip)
+            .with_quantile_method(QuantileMethod::Lower)
+            .with_antimode_limit(1);
+        let mut d = config.describe();
+        d.extend([3.0, f64::NAN, 1.0, 2.0, 4.0]);
+        let mut other = config.describe();
+        other.extend([f64::NAN]);
+        d.merge(other);
+        let desc = d.describe();
+        assert_eq!((desc.len, desc.nans), (4, 2));
+        assert_eq!(desc.mean, Some(2.5));
+        assert_eq!(desc.median, Some(2.0));
+        assert_eq!(desc.antimodes, Some(vec![1.0]));
+
+        let mut nulls = config.with_nan_policy(NanPolicy::Null).describe::<f64>();
+        nulls.extend([f64::NAN, 1.0]);
+        assert_eq!(nulls.describe().nulls, 1);
+    }
+
+    #[test]
+    fn selected_metrics() {
+        let config = StatsConfig::new().with_metrics(Metrics::ALL - Metrics::MAD - Metrics::MODES);
+        let mut d = config.describe();
+        d.extend([3, 1, 2, 2]);
+        let desc = d.describe();
+        assert_eq!((desc.median, desc.mad, desc.modes), (Some(2.0), None, None));
+        assert_eq!(desc.cardinality, Some(3));
+
+        let mut moments = StatsConfig::new().with_metrics(Metrics::MOMENTS).describe();
+        moments.extend([3, 1, 2, 2]);
+        let desc = moments.describe();
+        assert_eq!((desc.len, desc.mean), (4, Some(2.0)));
+        assert_eq!(
+            (desc.min, desc.median, desc.cardinality),
+            (None, None, None)
+        );
+        assert_eq!(moments.unsorted.len(), 0);
+
+        let mut basic = StatsConfig::new().with_metrics(Metrics::BASIC).describe();
+        basic.extend([3, 1, 2, 2]);
+        basic.merge([0, 5].into_iter().collect());
+        let desc = basic.describe();
+        assert_eq!((desc.min, desc.max, desc.median), (Some(0), Some(5), None));
+        assert_eq!(basic.unsorted.len(), 0);
+    }
+
+    #[cfg(feature = "ostree")]
+    #[test]
+    fn tree_backend() {
+        use crate::QuantileBackend;
+
+        let data = [5, 1, 4, 4, 2, 3, 8, 7];
+        let config = StatsConfig::new().with_quantile_method(QuantileMethod::Midpoint);
+        let mut sorted = config.describe();
+        let tree_config = config.with_quantile_backend(QuantileBackend::Tree);
+        let mut tree = tree_config.describe();
+        for (i, &x) in data.iter().enumerate() {
+            sorted.add(x);
+            tree.add(x);
+            let (a, b) = (sorted.describe(), tree.describe());
+            assert_eq!((a.q1, a.median, a.q3), (b.q1, b.median, b.q3), "{i}");
+            assert_eq!(a, b);
+        }
+        tree.merge(tree_config.describe());
+        assert_eq!(tree.describe().median, Some(4.0));
+
+        let quartiles_only = tree_config.with_metrics(Metrics::BASIC | Metrics::QUARTILES);
+        let mut d = quartiles_only.describe();
+        d.extend(data);
+        assert_eq!(d.describe().median, Some(4.0));
+        assert_eq!((d.unsorted.len(), d.tree.len()), (0, 8));
+    }
+}
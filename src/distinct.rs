@@ -0,0 +1,400 @@
+use std::hash::Hash;
+use std::iter;
+use std::marker::PhantomData;
+
+use ahash::AHashSet;
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::hashing::{nth_index, stable_hash};
+use crate::Commute;
+
+/// A Bloom filter that flags values which have (probably) been seen
+/// before, for detecting duplicate-heavy columns in a single pass.
+///
+/// A value reported as new is certainly new. A value reported as seen is a
+/// duplicate, except with the false positive probability the filter was
+/// sized for, so the duplicate count may slightly overcount once the filter
+/// holds about as many distinct values as it was sized for.
+///
+/// Merging filters (of the same size) combines the sets of values seen, but
+/// a value added to both shards was a new value in each of them, so the
+/// merged `duplicate_count` misses such cross-shard duplicates. Use
+/// `estimated_distinct`, which is computed from the filter itself, to
+/// count the distinct values of merged filters.
+#[derive(Clone, Deserialize, PartialEq, Eq)]
+#[serde(try_from = "DistinctFilterParts")]
+pub struct DistinctFilter<T: ?Sized> {
+    bits: Vec<u64>,
+    num_bits: usize,
+    hashes: usize,
+    len: u64,
+    duplicates: u64,
+    #[serde(skip)]
+    _marker: PhantomData<fn(&T)>,
+}
+
+/// The serialized form of a `DistinctFilter`, which is checked before use.
+#[derive(Deserialize)]
+struct DistinctFilterParts {
+    bits: Vec<u64>,
+    num_bits: usize,
+    hashes: usize,
+    len: u64,
+    duplicates: u64,
+    // derived from the above when serializing, so it is ignored here
+    #[serde(default, rename = "error_bound")]
+    _error_bound: f64,
+}
+
+impl<T: Hash + ?Sized> Serialize for DistinctFilter<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("DistinctFilter", 6)?;
+        state.serialize_field("bits", &self.bits)?;
+        state.serialize_field("num_bits", &self.num_bits)?;
+        state.serialize_field("hashes", &self.hashes)?;
+        state.serialize_field("len", &self.len)?;
+        state.serialize_field("duplicates", &self.duplicates)?;
+        state.serialize_field("error_bound", &self.error_bound())?;
+        state.end()
+    }
+}
+
+impl<T: ?Sized> TryFrom<DistinctFilterParts> for DistinctFilter<T> {
+    type Error = String;
+
+    fn try_from(v: DistinctFilterParts) -> Result<DistinctFilter<T>, String> {
+        if v.num_bits == 0 || v.hashes == 0 {
+            return Err("Bloom filter size and hash count must be positive".to_owned());
+        }
+        if v.bits.len() != v.num_bits.div_ceil(64) {
+            return Err(format!(
+                "Bloom filter of {} bits has {} words",
+                v.num_bits,
+                v.bits.len()
+            ));
+        }
+        Ok(DistinctFilter {
+            bits: v.bits,
+            num_bits: v.num_bits,
+            hashes: v.hashes,
+            len: v.len,
+            duplicates: v.duplicates,
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<T: Hash + ?Sized> DistinctFilter<T> {
+    /// Create a filter sized for `expected` distinct values with a false
+    /// positive rate of `fp_rate`.
+    ///
+    /// # Panics
+    ///
+    /// If `fp_rate` is not in `(0, 1)`.
+    #[must_use]
+    pub fn new(expected: usize, fp_rate: f64) -> DistinctFilter<T> {
+        assert!(fp_rate > 0.0 && fp_rate < 1.0, "fp_rate must be in (0, 1)");
+        let ln2 = std::f64::consts::LN_2;
+        let n = expected.max(1) as f64;
+        let num_bits = ((-n * fp_rate.ln() / (ln2 * ln2)).ceil() as usize).max(64);
+        let hashes = ((num_bits as f64 / n * ln2).round() as usize).max(1);
+        DistinctFilter {
+            bits: vec![0; num_bits.div_ceil(64)],
+            num_bits,
+            hashes,
+            len: 0,
+            duplicates: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Add a value, returning true if it has (probably) been seen before.
+    #[inline]
+    pub fn add(&mut self, value: &T) -> bool {
+        let hash = stable_hash(value);
+        let mut seen = true;
+        for i in 0..self.hashes {
+            let bit = nth_index(hash, i, self.num_bits);
+            let (word, mask) = (bit / 64, 1u64 << (bit % 64));
+            seen &= self.bits[word] & mask != 0;
+            self.bits[word] |= mask;
+        }
+        self.len += 1;
+        self.duplicates += u64::from(seen);
+        seen
+    }
+
+    /// Returns true if `value` has (probably) been added.
+    #[inline]
+    #[must_use]
+    pub fn contains(&self, value: &T) -> bool {
+        let hash = stable_hash(value);
+        (0..self.hashes).all(|i| {
+            let bit = nth_index(hash, i, self.num_bits);
+            self.bits[bit / 64] & (1u64 << (bit % 64)) != 0
+        })
+    }
+
+    /// Returns the number of values that were flagged as duplicates.
+    #[inline]
+    #[must_use]
+    pub const fn duplicate_count(&self) -> u64 {
+        self.duplicates
+    }
+
+    /// Returns the fraction of values that were flagged as duplicates, or
+    /// `None` if there are no values.
+    #[inline]
+    #[must_use]
+    pub fn duplicate_fraction(&self) -> Option<f64> {
+        if self.len == 0 {
+            None
+        } else {
+            Some(self.duplicates as f64 / self.len as f64)
+        }
+    }
+
+    /// Returns an estimate of the number of distinct values added, based on
+    /// the fraction of bits set (Swamidass and Baldi).
+    #[must_use]
+    pub fn estimated_distinct(&self) -> f64 {
+        let set: u32 = self.bits.iter().map(|w| w.count_ones()).sum();
+        let (m, k) = (self.num_bits as f64, self.hashes as f64);
+        if set as usize >= self.num_bits {
+            return f64::INFINITY;
+        }
+        -m / k * (-f64::from(set) / m).ln_1p()
+    }
+
+    /// Returns the current false positive rate, i.e. the probability that a
+    /// new value is flagged as a duplicate, given the bits set so far.
+    ///
+    /// This grows as values are added, and passes the rate the filter was
+    /// sized for once it holds more than the expected number of values.
+    #[must_use]
+    pub fn error_bound(&self) -> f64 {
+        let set: u32 = self.bits.iter().map(|w| w.count_ones()).sum();
+        (f64::from(set) / self.num_bits as f64).powi(self.hashes as i32)
+    }
+
+    /// Returns the number of values added.
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns true if no values have been added.
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<T: ?Sized> Commute for DistinctFilter<T> {
+    /// # Panics
+    ///
+    /// If the filters have different sizes.
+    #[inline]
+    fn merge(&mut self, v: DistinctFilter<T>) {
+        assert!(
+            self.num_bits == v.num_bits && self.hashes == v.hashes,
+            "cannot merge Bloom filters of different sizes"
+        );
+        for (w1, w2) in self.bits.iter_mut().zip(v.bits) {
+            *w1 |= w2;
+        }
+        self.len += v.len;
+        self.duplicates += v.duplicates;
+    }
+}
+
+impl<T: Hash> Extend<T> for DistinctFilter<T> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
+        for value in it {
+            self.add(&value);
+        }
+    }
+}
+
+/// An adapter that feeds only the first occurrence of each value to an
+/// inner accumulator, e.g. for the mean of the distinct prices in a column.
+///
+/// `Deduped<T, S>` keeps an exact set of the values seen, so it needs
+/// memory for every distinct value, and forwards each new value to `S`,
+/// which can be any accumulator that implements `Extend<T>` (such as
+/// `OnlineStats`, `MinMax` or `Unsorted`).
+///
+/// Merging is exact: only the values of the other shard that this one has
+/// not seen are forwarded, and the other shard's inner accumulator is
+/// dropped.
+#[derive(Clone)]
+pub struct Deduped<T, S> {
+    seen: AHashSet<T>,
+    inner: S,
+    duplicates: u64,
+}
+
+impl<T: Eq + Hash + Clone, S: Extend<T> + Default> Deduped<T, S> {
+    /// Create initial empty state, with an empty inner accumulator.
+    #[must_use]
+    pub fn new() -> Deduped<T, S> {
+        Deduped {
+            seen: AHashSet::new(),
+            inner: S::default(),
+            duplicates: 0,
+        }
+    }
+
+    /// Add a value, forwarding it to the inner accumulator if it has not
+    /// been seen before. Returns true if the value is new.
+    #[inline]
+    pub fn add(&mut self, value: T) -> bool {
+        if self.seen.contains(&value) {
+            self.duplicates += 1;
+            return false;
+        }
+        self.seen.insert(value.clone());
+        self.inner.extend(iter::once(value));
+        true
+    }
+
+    /// Returns the inner accumulator, which has seen each distinct value
+    /// once.
+    #[inline]
+    #[must_use]
+    pub const fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Returns the inner accumulator, dropping the set of seen values.
+    #[inline]
+    #[must_use]
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    /// Returns the number of distinct values.
+    #[inline]
+    #[must_use]
+    pub fn distinct_count(&self) -> usize {
+        self.seen.len()
+    }
+
+    /// Returns the number of values that were not forwarded because they
+    /// had been seen before.
+    #[inline]
+    #[must_use]
+    pub const fn duplicate_count(&self) -> u64 {
+        self.duplicates
+    }
+}
+
+impl<T: Eq + Hash + Clone, S: Extend<T> + Default> Commute for Deduped<T, S> {
+    #[inline]
+    fn merge(&mut self, v: Deduped<T, S>) {
+        self.duplicates += v.duplicates;
+        for value in v.seen {
+            self.add(value);
+        }
+    }
+}
+
+impl<T: Eq + Hash + Clone, S: Extend<T> + Default> Default for Deduped<T, S> {
+    #[inline]
+    fn default() -> Deduped<T, S> {
+        Deduped::new()
+    }
+}
+
+impl<T: Eq + Hash + Clone, S: Extend<T> + Default> FromIterator<T> for Deduped<T, S> {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = T>>(it: I) -> Deduped<T, S> {
+        let mut v = Deduped::new();
+        v.extend(it);
+        v
+    }
+}
+
+impl<T: Eq + Hash + Clone, S: Extend<T> + Default> Extend<T> for Deduped<T, S> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
+        for value in it {
+            self.add(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Deduped, DistinctFilter};
+    use crate::{Commute, MinMax, OnlineStats};
+
+    #[test]
+    fn deduped_stats() {
+        let mut prices: Deduped<u32, OnlineStats> = [10, 20, 10, 30, 20].into_iter().collect();
+        assert_eq!(prices.distinct_count(), 3);
+        assert_eq!(prices.duplicate_count(), 2);
+        assert_eq!(prices.inner().mean(), 20.0);
+
+        let other: Deduped<u32, OnlineStats> = [30, 60, 60].into_iter().collect();
+        prices.merge(other);
+        // 30 was seen by both shards, so only 60 is new
+        assert_eq!(prices.distinct_count(), 4);
+        assert_eq!(prices.duplicate_count(), 4);
+        assert_eq!(prices.inner().len(), 4);
+        assert_eq!(prices.into_inner().mean(), 30.0);
+
+        let minmax: Deduped<&str, MinMax<&str>> = ["b", "a", "b"].into_iter().collect();
+        assert_eq!(minmax.inner().len(), 2);
+    }
+
+    #[test]
+    fn deserialize_checks_size() {
+        let mut filter: DistinctFilter<u8> = DistinctFilter::new(10, 0.1);
+        filter.add(&3);
+        let json = serde_json::to_string(&filter).unwrap();
+        assert!(json.contains(r#""error_bound":"#));
+        let back: DistinctFilter<u8> = serde_json::from_str(&json).unwrap();
+        assert!(back.contains(&3));
+        assert!(back == filter);
+
+        let short = r#"{"bits":[0],"num_bits":128,"hashes":3,"len":0,"duplicates":0}"#;
+        assert!(serde_json::from_str::<DistinctFilter<u8>>(short).is_err());
+        let no_bits = r#"{"bits":[],"num_bits":0,"hashes":3,"len":0,"duplicates":0}"#;
+        assert!(serde_json::from_str::<DistinctFilter<u8>>(no_bits).is_err());
+    }
+
+    #[test]
+    fn flags_duplicates() {
+        let mut filter = DistinctFilter::new(1000, 0.01);
+        assert!(!filter.add(&17u32));
+        assert!(filter.add(&17u32));
+        filter.extend((0..2000u32).map(|i| i % 500));
+        assert_eq!(filter.len(), 2002);
+        // 1501 true duplicates, plus maybe a few false positives
+        assert!((1501..1520).contains(&filter.duplicate_count()));
+        assert!((filter.estimated_distinct() - 500.0).abs() < 25.0);
+        assert!(filter.contains(&499) && !filter.contains(&100_000));
+        // half full, so well under the rate it was sized for
+        assert!(filter.error_bound() < 0.001);
+        assert_eq!(DistinctFilter::<u32>::new(10, 0.1).error_bound(), 0.0);
+    }
+
+    #[test]
+    fn merge_filters() {
+        let mut f1: DistinctFilter<str> = DistinctFilter::new(100, 0.01);
+        let mut f2 = DistinctFilter::new(100, 0.01);
+        f1.add("a");
+        f2.add("a");
+        f2.add("b");
+        f1.merge(f2);
+        assert_eq!(f1.len(), 3);
+        assert_eq!(f1.duplicate_count(), 0);
+        assert!((f1.estimated_distinct() - 2.0).abs() < 0.1);
+        assert_eq!(f1.duplicate_fraction(), Some(0.0));
+    }
+}
@@ -0,0 +1,265 @@
+use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+
+/// Online state for the exponentially weighted moving mean and variance.
+///
+/// Each new sample gets weight `alpha` and the weight of all earlier
+/// samples decays by `1 - alpha`, so the statistics follow recent values.
+/// A larger `alpha` reacts faster; `2 / (span + 1)` gives a smoothing
+/// window of roughly `span` samples.
+///
+/// Samples that are `NaN` or infinite are skipped and counted, since one of
+/// them would otherwise stay in the mean and variance forever.
+///
+/// Since the weights depend on the order of the samples, this does not
+/// implement `Commute`.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Ewma {
+    alpha: f64,
+    mean: f64,
+    variance: f64,
+    len: u64,
+    #[serde(default)]
+    skipped: u64,
+}
+
+impl Ewma {
+    /// Create initial empty state with smoothing factor `alpha`.
+    ///
+    /// # Panics
+    ///
+    /// If `alpha` is not in `(0, 1]`.
+    #[must_use]
+    pub fn new(alpha: f64) -> Ewma {
+        assert!(alpha > 0.0 && alpha <= 1.0, "alpha must be in (0, 1]");
+        Ewma {
+            alpha,
+            mean: 0.0,
+            variance: 0.0,
+            len: 0,
+            skipped: 0,
+        }
+    }
+
+    /// Add a new sample. Non-finite samples are skipped.
+    #[inline]
+    pub fn add<T: ToPrimitive>(&mut self, sample: &T) {
+        let x = sample.to_f64().unwrap();
+        if !x.is_finite() {
+            self.skipped += 1;
+            return;
+        }
+        if self.len == 0 {
+            self.mean = x;
+        } else {
+            let diff = x - self.mean;
+            let incr = self.alpha * diff;
+            self.mean += incr;
+            self.variance = (1.0 - self.alpha) * diff.mul_add(incr, self.variance);
+        }
+        self.len += 1;
+    }
+
+    /// Returns the smoothing factor.
+    #[inline]
+    #[must_use]
+    pub const fn alpha(&self) -> f64 {
+        self.alpha
+    }
+
+    /// Returns the weighted mean, or `NaN` if there are no samples.
+    #[inline]
+    #[must_use]
+    pub fn mean(&self) -> f64 {
+        if self.len == 0 {
+            f64::NAN
+        } else {
+            self.mean
+        }
+    }
+
+    /// Returns the weighted variance.
+    #[inline]
+    #[must_use]
+    pub const fn variance(&self) -> f64 {
+        self.variance
+    }
+
+    /// Returns the weighted standard deviation.
+    #[inline]
+    #[must_use]
+    pub fn stddev(&self) -> f64 {
+        self.variance.sqrt()
+    }
+
+    /// Returns the number of samples, not counting skipped ones.
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns the number of samples that were skipped because they were
+    /// `NaN` or infinite.
+    #[inline]
+    #[must_use]
+    pub const fn skipped_count(&self) -> u64 {
+        self.skipped
+    }
+
+    /// Returns true if there are no samples.
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<T: ToPrimitive> Extend<T> for Ewma {
+    #[inline]
+    fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
+        for sample in it {
+            self.add(&sample);
+        }
+    }
+}
+
+/// An EWMA control chart that flags anomalous samples as they are added.
+///
+/// A sample is anomalous if it falls outside the band of `k` weighted
+/// standard deviations around the weighted mean of the samples before it.
+/// The first `warmup` samples are never flagged, so the band can settle.
+/// Every sample, anomalous or not, then updates the moving statistics.
+/// Non-finite samples are skipped as in `Ewma` and never flagged.
+///
+/// `add` numbers samples by their position, starting at `0`, counting
+/// skipped samples. Use `add_at`
+/// to record another index, such as a timestamp, for each sample.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct EwmaChart {
+    ewma: Ewma,
+    k: f64,
+    warmup: u64,
+    anomalies: Vec<u64>,
+}
+
+impl EwmaChart {
+    /// Create a chart with smoothing factor `alpha` and a band of `k`
+    /// standard deviations, which starts flagging after `warmup` samples.
+    ///
+    /// # Panics
+    ///
+    /// If `alpha` is not in `(0, 1]`.
+    #[must_use]
+    pub fn new(alpha: f64, k: f64, warmup: u64) -> EwmaChart {
+        EwmaChart {
+            ewma: Ewma::new(alpha),
+            k,
+            warmup,
+            anomalies: Vec::new(),
+        }
+    }
+
+    /// Add a new sample, returning true if it was flagged as anomalous.
+    #[inline]
+    pub fn add<T: ToPrimitive>(&mut self, sample: &T) -> bool {
+        let index = self.ewma.len() + self.ewma.skipped_count();
+        self.add_at(sample, index)
+    }
+
+    /// Add a new sample recorded at `index`, returning true if it was
+    /// flagged as anomalous.
+    pub fn add_at<T: ToPrimitive>(&mut self, sample: &T, index: u64) -> bool {
+        let x = sample.to_f64().unwrap();
+        let anomalous = x.is_finite()
+            && self.ewma.len() >= self.warmup.max(1)
+            && (x - self.ewma.mean()).abs() > self.k * self.ewma.stddev();
+        if anomalous {
+            self.anomalies.push(index);
+        }
+        self.ewma.add(&x);
+        anomalous
+    }
+
+    /// Returns the current band as `(lower, upper)`, or `None` if there
+    /// are no samples.
+    #[must_use]
+    pub fn limits(&self) -> Option<(f64, f64)> {
+        if self.ewma.is_empty() {
+            return None;
+        }
+        let (mean, width) = (self.ewma.mean(), self.k * self.ewma.stddev());
+        Some((mean - width, mean + width))
+    }
+
+    /// Returns the indices of the samples flagged as anomalous.
+    #[inline]
+    #[must_use]
+    pub fn anomalies(&self) -> &[u64] {
+        &self.anomalies
+    }
+
+    /// Returns the moving statistics.
+    #[inline]
+    #[must_use]
+    pub const fn ewma(&self) -> &Ewma {
+        &self.ewma
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Ewma, EwmaChart};
+
+    #[test]
+    fn moving_stats() {
+        let mut ewma = Ewma::new(0.5);
+        assert!(ewma.mean().is_nan());
+        ewma.extend([2, 4, 8]);
+        // means 2, 3, 5.5 and variances 0, 0.5 * 2 * 1, 0.5 * (1 + 5 * 2.5)
+        assert_eq!(ewma.mean(), 5.5);
+        assert_eq!(ewma.variance(), 6.75);
+        assert_eq!(ewma.len(), 3);
+
+        let mut last_only = Ewma::new(1.0);
+        last_only.extend([3.0, 9.0]);
+        assert_eq!((last_only.mean(), last_only.variance()), (9.0, 0.0));
+
+        let mut gappy = Ewma::new(0.5);
+        gappy.extend([2.0, f64::NAN, 4.0, f64::INFINITY, 8.0]);
+        assert_eq!((gappy.mean(), gappy.variance()), (5.5, 6.75));
+        assert_eq!((gappy.len(), gappy.skipped_count()), (3, 2));
+    }
+
+    #[test]
+    fn control_chart() {
+        let mut chart = EwmaChart::new(0.2, 3.0, 10);
+        let data = (0..100).map(|i| {
+            let noise = f64::from(i % 5) - 2.0;
+            match i {
+                40 => 60.0,
+                70 => -40.0,
+                _ => 10.0 + noise,
+            }
+        });
+        for x in data {
+            chart.add(&x);
+        }
+        assert_eq!(chart.anomalies(), &[40, 70]);
+        let (lower, upper) = chart.limits().unwrap();
+        assert!(lower < 10.0 && upper > 10.0);
+
+        let mut stamped = EwmaChart::new(0.2, 3.0, 1);
+        stamped.add_at(&1.0, 1_700_000_000);
+        assert!(!stamped.add_at(&1.0, 1_700_000_060));
+        assert!(stamped.add_at(&5.0, 1_700_000_120));
+        assert_eq!(stamped.anomalies(), &[1_700_000_120]);
+
+        let mut gappy = EwmaChart::new(0.5, 3.0, 1);
+        assert!(!gappy.add(&1.0));
+        assert!(!gappy.add(&f64::NEG_INFINITY));
+        assert!(gappy.add(&5.0));
+        assert_eq!(gappy.anomalies(), &[2]);
+        assert_eq!(gappy.limits().map(|(lo, _)| lo.is_finite()), Some(true));
+    }
+}
@@ -0,0 +1,79 @@
+use std::hash::Hash;
+
+use num_traits::ToPrimitive;
+
+use crate::{Frequencies, MinMax, OnlineStats, OnlineSummary, Unsorted};
+
+/// Extension methods that collect any iterator into an accumulator.
+///
+/// These mirror the `median` and `mode` free functions, but return the
+/// accumulator so that further statistics can be read from it or it can be
+/// merged with others, e.g. `data.into_iter().online_stats().stddev()`.
+pub trait StatsExt: Iterator + Sized {
+    /// Collects the items into an `OnlineStats`.
+    #[inline]
+    fn online_stats(self) -> OnlineStats
+    where
+        Self::Item: ToPrimitive,
+    {
+        self.collect()
+    }
+
+    /// Collects the items into an `OnlineSummary`, i.e. mean, variance,
+    /// minimum and maximum in one pass.
+    #[inline]
+    fn stats_summary(self) -> OnlineSummary
+    where
+        Self::Item: ToPrimitive,
+    {
+        self.collect()
+    }
+
+    /// Collects the items into a `MinMax`.
+    ///
+    /// This is not named `minmax` so that it does not collide with
+    /// `Itertools::minmax` when both traits are in scope.
+    #[inline]
+    fn minmax_stats(self) -> MinMax<Self::Item>
+    where
+        Self::Item: PartialOrd + Clone,
+    {
+        self.collect()
+    }
+
+    /// Collects the items into an `Unsorted`, for order statistics.
+    #[inline]
+    fn unsorted(self) -> Unsorted<Self::Item>
+    where
+        Self::Item: PartialOrd,
+    {
+        self.collect()
+    }
+
+    /// Collects the items into a `Frequencies`.
+    #[inline]
+    fn frequencies(self) -> Frequencies<Self::Item>
+    where
+        Self::Item: Eq + Hash,
+    {
+        self.collect()
+    }
+}
+
+impl<I: Iterator> StatsExt for I {}
+
+#[cfg(test)]
+mod test {
+    use super::StatsExt;
+
+    #[test]
+    fn collect_accumulators() {
+        let data = [3u32, 1, 4, 1, 5];
+        assert_eq!(data.into_iter().online_stats().mean(), 2.8);
+        let summary = data.into_iter().stats_summary();
+        assert_eq!((summary.min(), summary.max()), (Some(1.0), Some(5.0)));
+        assert_eq!(data.iter().minmax_stats().max(), Some(&&5));
+        assert_eq!(data.into_iter().unsorted().median(), Some(3.0));
+        assert_eq!(data.iter().frequencies().count(&&1), 2);
+    }
+}
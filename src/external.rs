@@ -0,0 +1,347 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+
+use num_traits::ToPrimitive;
+
+use crate::unsorted::{median_with, quartiles_with};
+use crate::Commute;
+
+/// The default number of values held in memory before spilling to disk
+/// (64MB worth of `f64`s).
+const DEFAULT_BUFFER_LEN: usize = 8 * 1024 * 1024;
+
+/// A sorted run of values spilled to a temporary file.
+struct Run {
+    file: File,
+    len: u64,
+}
+
+/// Reads `f64`s back from a spilled run.
+struct RunReader {
+    reader: BufReader<File>,
+    remaining: u64,
+}
+
+impl RunReader {
+    fn new(run: &Run) -> io::Result<RunReader> {
+        let mut file = run.file.try_clone()?;
+        file.seek(SeekFrom::Start(0))?;
+        Ok(RunReader {
+            reader: BufReader::new(file),
+            remaining: run.len,
+        })
+    }
+
+    fn next(&mut self) -> io::Result<Option<f64>> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        let mut buf = [0u8; 8];
+        self.reader.read_exact(&mut buf)?;
+        Ok(Some(f64::from_le_bytes(buf)))
+    }
+}
+
+/// An entry of the k-way merge heap, ordered so the smallest value is on top.
+struct Head {
+    value: f64,
+    source: usize,
+}
+
+impl PartialEq for Head {
+    fn eq(&self, other: &Head) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Head {}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Head) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Head {
+    fn cmp(&self, other: &Head) -> Ordering {
+        other.value.total_cmp(&self.value)
+    }
+}
+
+/// A disk-backed alternative to `Unsorted` for exact order statistics on
+/// data sets larger than memory.
+///
+/// Values are buffered in memory as `f64`s. Once the buffer is full, it is
+/// sorted and spilled to an anonymous temporary file as a sorted run.
+/// Statistics are then computed with an external k-way merge of the runs,
+/// so memory use stays bounded by the buffer size.
+///
+/// Every method that may touch the disk returns an `io::Result`. Values are
+/// ordered with `f64::total_cmp`, so `NaN`s sort after all other values.
+pub struct ExternalUnsorted {
+    buffer: Vec<f64>,
+    max_buffer_len: usize,
+    runs: Vec<Run>,
+    len: u64,
+}
+
+impl ExternalUnsorted {
+    /// Create initial empty state, spilling every 8M values (64MB).
+    #[must_use]
+    pub fn new() -> ExternalUnsorted {
+        ExternalUnsorted::with_buffer_len(DEFAULT_BUFFER_LEN)
+    }
+
+    /// Create initial empty state, spilling every `max_buffer_len` values.
+    ///
+    /// A value of `0` is treated as `1`.
+    #[must_use]
+    pub fn with_buffer_len(max_buffer_len: usize) -> ExternalUnsorted {
+        ExternalUnsorted {
+            buffer: Vec::new(),
+            max_buffer_len: max_buffer_len.max(1),
+            runs: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// Add a new sample, spilling the buffer to disk if it is full.
+    #[inline]
+    pub fn add<T: ToPrimitive>(&mut self, sample: &T) -> io::Result<()> {
+        self.buffer.push(sample.to_f64().unwrap());
+        self.len += 1;
+        if self.buffer.len() >= self.max_buffer_len {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// Returns the number of data points.
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns true if there are no data points.
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of sorted runs spilled to disk so far.
+    #[inline]
+    #[must_use]
+    pub fn spilled_runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    /// Sort the in-memory buffer and write it to a temporary file.
+    fn spill(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        self.buffer.sort_unstable_by(f64::total_cmp);
+        let file = tempfile::tempfile()?;
+        let mut writer = BufWriter::new(file);
+        for v in &self.buffer {
+            writer.write_all(&v.to_le_bytes())?;
+        }
+        let file = writer
+            .into_inner()
+            .map_err(io::IntoInnerError::into_error)?;
+        self.runs.push(Run {
+            file,
+            len: self.buffer.len() as u64,
+        });
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// Returns the values at the (0-based) sorted positions in `ranks`.
+    ///
+    /// This performs a single k-way merge over all runs and the buffer,
+    /// stopping as soon as the largest requested rank is reached.
+    fn select(&mut self, ranks: &[usize]) -> io::Result<Vec<(usize, f64)>> {
+        let mut wanted = ranks.to_vec();
+        wanted.sort_unstable();
+        wanted.dedup();
+        let mut found = Vec::with_capacity(wanted.len());
+        if wanted.is_empty() {
+            return Ok(found);
+        }
+
+        self.buffer.sort_unstable_by(f64::total_cmp);
+        let mut readers = self
+            .runs
+            .iter()
+            .map(RunReader::new)
+            .collect::<io::Result<Vec<_>>>()?;
+        // the in-memory buffer is the last source
+        let buffer_source = readers.len();
+        let mut buffer_pos = 0;
+
+        let mut heap = BinaryHeap::with_capacity(readers.len() + 1);
+        for (source, reader) in readers.iter_mut().enumerate() {
+            if let Some(value) = reader.next()? {
+                heap.push(Head { value, source });
+            }
+        }
+        if let Some(&value) = self.buffer.first() {
+            heap.push(Head {
+                value,
+                source: buffer_source,
+            });
+            buffer_pos = 1;
+        }
+
+        let mut rank = 0;
+        let mut next_wanted = wanted.iter().peekable();
+        while let Some(Head { value, source }) = heap.pop() {
+            if next_wanted.peek() == Some(&&rank) {
+                found.push((rank, value));
+                next_wanted.next();
+                if next_wanted.peek().is_none() {
+                    break;
+                }
+            }
+            rank += 1;
+            let next = if source == buffer_source {
+                let next = self.buffer.get(buffer_pos).copied();
+                buffer_pos += 1;
+                next
+            } else {
+                readers[source].next()?
+            };
+            if let Some(value) = next {
+                heap.push(Head { value, source });
+            }
+        }
+        Ok(found)
+    }
+
+    /// Evaluates `stat`, which asks for sorted values by position, using a
+    /// single external merge to fetch only the positions it needs.
+    fn order_statistic<R, F>(&mut self, stat: F) -> io::Result<Option<R>>
+    where
+        F: Fn(&mut dyn FnMut(usize) -> Option<f64>) -> Option<R>,
+    {
+        let mut ranks = Vec::new();
+        stat(&mut |i| {
+            ranks.push(i);
+            Some(0.0)
+        });
+        let values = self.select(&ranks)?;
+        Ok(stat(&mut |i| {
+            values.iter().find(|&&(r, _)| r == i).map(|&(_, v)| v)
+        }))
+    }
+
+    /// Returns the median of the data.
+    pub fn median(&mut self) -> io::Result<Option<f64>> {
+        let len = self.len as usize;
+        self.order_statistic(|get| median_with(len, get))
+    }
+
+    /// Returns the quartiles of the data.
+    pub fn quartiles(&mut self) -> io::Result<Option<(f64, f64, f64)>> {
+        let len = self.len as usize;
+        self.order_statistic(|get| quartiles_with(len, get))
+    }
+
+    /// Returns the MAD of the data.
+    ///
+    /// The absolute deviations are themselves sorted externally, with the
+    /// same buffer size, so this needs up to twice the disk space.
+    pub fn mad(&mut self, existing_median: Option<f64>) -> io::Result<Option<f64>> {
+        let median = match existing_median {
+            Some(median) => median,
+            None => match self.median()? {
+                Some(median) => median,
+                None => return Ok(None),
+            },
+        };
+
+        let mut deviations = ExternalUnsorted::with_buffer_len(self.max_buffer_len);
+        for run in &self.runs {
+            let mut reader = RunReader::new(run)?;
+            while let Some(v) = reader.next()? {
+                deviations.add(&(median - v).abs())?;
+            }
+        }
+        for v in &self.buffer {
+            deviations.add(&(median - v).abs())?;
+        }
+        deviations.median()
+    }
+}
+
+impl Commute for ExternalUnsorted {
+    #[inline]
+    fn merge(&mut self, v: ExternalUnsorted) {
+        self.len += v.len;
+        self.runs.extend(v.runs);
+        // `merge` cannot fail, so an oversized buffer is spilled on the next `add`
+        self.buffer.extend(v.buffer);
+    }
+}
+
+impl Default for ExternalUnsorted {
+    #[inline]
+    fn default() -> ExternalUnsorted {
+        ExternalUnsorted::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ExternalUnsorted;
+    use crate::{mad, median, quartiles, Commute};
+
+    fn external(data: &[usize], buffer_len: usize) -> ExternalUnsorted {
+        let mut ext = ExternalUnsorted::with_buffer_len(buffer_len);
+        for v in data {
+            ext.add(v).unwrap();
+        }
+        ext
+    }
+
+    #[test]
+    fn matches_unsorted() {
+        let data = vec![
+            86usize, 60, 95, 39, 49, 12, 56, 82, 92, 24, 33, 28, 46, 34, 100, 39, 100, 38, 50, 61,
+            39, 88, 5, 13, 64,
+        ];
+        for buffer_len in [1, 4, 7, 100] {
+            let mut ext = external(&data, buffer_len);
+            assert_eq!(ext.len(), 25);
+            assert_eq!(ext.median().unwrap(), median(data.iter().copied()));
+            assert_eq!(ext.quartiles().unwrap(), quartiles(data.iter().copied()));
+            assert_eq!(ext.mad(None).unwrap(), mad(data.iter().copied(), None));
+        }
+        assert_eq!(external(&data, 4).spilled_runs(), 6);
+    }
+
+    #[test]
+    fn merge_spilled() {
+        let mut ext1 = external(&[3, 5, 7], 2);
+        let ext2 = external(&[9, 12], 2);
+        ext1.merge(ext2);
+        assert_eq!(ext1.len(), 5);
+        assert_eq!(ext1.median().unwrap(), Some(7.0));
+        assert_eq!(ext1.quartiles().unwrap(), Some((4., 7., 10.5)));
+    }
+
+    #[test]
+    fn empty() {
+        let mut ext = ExternalUnsorted::new();
+        assert!(ext.is_empty());
+        assert_eq!(ext.median().unwrap(), None);
+        assert_eq!(ext.quartiles().unwrap(), None);
+        assert_eq!(ext.mad(None).unwrap(), None);
+    }
+}
@@ -0,0 +1,130 @@
+use std::hash::Hash;
+
+use serde::{Deserialize, Serialize};
+
+use crate::hashing::{mix64, stable_hash};
+use crate::Commute;
+
+/// An order-insensitive fingerprint of a multiset of values, for cheaply
+/// checking whether two runs saw the same data.
+///
+/// Each value is hashed with a stable hash, and the hashes (and a second,
+/// remixed copy of them) are summed with wrapping arithmetic. Sums do not
+/// depend on order, so any sharding of the same data, merged in any
+/// order, produces the same fingerprint, while repeating a value changes
+/// it. As with any hash, different data collides with a probability of
+/// about `2^-64`.
+///
+/// The hash does not depend on the run, the byte order or the width of
+/// `usize`, so fingerprints can be compared across machines. It follows
+/// `Hash`, though, so the values must have the same type in both runs:
+/// `1u32` and `1u64` have different fingerprints.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct Fingerprint {
+    len: u64,
+    sum: u64,
+    mixed_sum: u64,
+}
+
+impl Fingerprint {
+    /// Create initial empty state.
+    #[must_use]
+    pub fn new() -> Fingerprint {
+        Default::default()
+    }
+
+    /// Add a value.
+    #[inline]
+    pub fn add<T: Hash + ?Sized>(&mut self, value: &T) {
+        let hash = stable_hash(value);
+        self.len += 1;
+        self.sum = self.sum.wrapping_add(hash);
+        self.mixed_sum = self
+            .mixed_sum
+            .wrapping_add(mix64(hash ^ 0x9E37_79B9_7F4A_7C15));
+    }
+
+    /// Returns the fingerprint of the values added so far.
+    #[inline]
+    #[must_use]
+    pub const fn value(&self) -> u64 {
+        mix64(self.sum ^ mix64(self.mixed_sum ^ mix64(self.len)))
+    }
+
+    /// Returns the number of values.
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns true if there are no values.
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Commute for Fingerprint {
+    #[inline]
+    fn merge(&mut self, v: Fingerprint) {
+        self.len += v.len;
+        self.sum = self.sum.wrapping_add(v.sum);
+        self.mixed_sum = self.mixed_sum.wrapping_add(v.mixed_sum);
+    }
+}
+
+impl<T: Hash> FromIterator<T> for Fingerprint {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = T>>(it: I) -> Fingerprint {
+        let mut v = Fingerprint::new();
+        v.extend(it);
+        v
+    }
+}
+
+impl<T: Hash> Extend<T> for Fingerprint {
+    #[inline]
+    fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
+        for value in it {
+            self.add(&value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Fingerprint;
+    use crate::{merge_all, Commute};
+
+    #[test]
+    fn order_insensitive() {
+        let whole: Fingerprint = ["a", "b", "c", "b"].into_iter().collect();
+        let shards = vec![
+            Fingerprint::from_iter(["b", "c"]),
+            Fingerprint::from_iter(["b"]),
+            Fingerprint::from_iter(["a"]),
+        ];
+        let merged = merge_all(shards.into_iter()).unwrap();
+        assert_eq!(merged.value(), whole.value());
+        assert_eq!(merged.len(), 4);
+
+        let mut fewer: Fingerprint = ["a", "b", "c"].into_iter().collect();
+        assert_ne!(fewer.value(), whole.value());
+        fewer.merge(Fingerprint::from_iter(["d"]));
+        assert_ne!(fewer.value(), whole.value());
+        assert_ne!(
+            Fingerprint::new().value(),
+            Fingerprint::from_iter([0u8]).value()
+        );
+        assert_eq!(
+            Fingerprint::from_iter([1usize, 2]).value(),
+            Fingerprint::from_iter([1u64, 2]).value()
+        );
+        assert_ne!(
+            Fingerprint::from_iter([1u32]).value(),
+            Fingerprint::from_iter([1u64]).value()
+        );
+    }
+}
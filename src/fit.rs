@@ -0,0 +1,272 @@
+//! Fitting parametric distributions to streamed data.
+//!
+//! Parameters are the maximum likelihood estimates computed from the
+//! moments of an `OnlineStats`, so no raw data is needed. For the
+//! lognormal distribution, pass the moments of the logarithms of the data,
+//! e.g. an `OnlineStats` fed with `x.ln()`.
+//!
+//! Every fit reports its log-likelihood and AIC, which can be compared
+//! between candidate distributions for the same data (lower AIC is
+//! better). When the data is at hand in an `Unsorted`, `ks_test` measures
+//! the goodness of fit directly.
+
+use std::f64::consts::{PI, SQRT_2};
+
+use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+
+use crate::special::{kolmogorov_survival, normal_cdf};
+use crate::{OnlineStats, TestResult, Unsorted};
+
+/// A parametric probability distribution.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub enum Distribution {
+    /// The normal distribution.
+    Normal {
+        /// The mean.
+        mean: f64,
+        /// The standard deviation.
+        stddev: f64,
+    },
+    /// The lognormal distribution, whose logarithm is normal.
+    LogNormal {
+        /// The mean of the logarithm.
+        mu: f64,
+        /// The standard deviation of the logarithm.
+        sigma: f64,
+    },
+    /// The exponential distribution.
+    Exponential {
+        /// The rate, i.e. the reciprocal of the mean.
+        rate: f64,
+    },
+}
+
+impl Distribution {
+    /// Returns the probability density at `x`.
+    #[must_use]
+    pub fn pdf(&self, x: f64) -> f64 {
+        match *self {
+            Distribution::Normal { mean, stddev } => normal_pdf((x - mean) / stddev) / stddev,
+            Distribution::LogNormal { mu, sigma } => {
+                if x <= 0.0 {
+                    0.0
+                } else {
+                    normal_pdf((x.ln() - mu) / sigma) / (x * sigma)
+                }
+            }
+            Distribution::Exponential { rate } => {
+                if x < 0.0 {
+                    0.0
+                } else {
+                    rate * (-rate * x).exp()
+                }
+            }
+        }
+    }
+
+    /// Returns the probability densities at each of `xs`, e.g. to plot the
+    /// fitted curve over a histogram.
+    #[must_use]
+    pub fn pdf_points(&self, xs: &[f64]) -> Vec<f64> {
+        xs.iter().map(|&x| self.pdf(x)).collect()
+    }
+
+    /// Returns the cumulative probability at `x`.
+    #[must_use]
+    pub fn cdf(&self, x: f64) -> f64 {
+        match *self {
+            Distribution::Normal { mean, stddev } => normal_cdf((x - mean) / stddev),
+            Distribution::LogNormal { mu, sigma } => {
+                if x <= 0.0 {
+                    0.0
+                } else {
+                    normal_cdf((x.ln() - mu) / sigma)
+                }
+            }
+            Distribution::Exponential { rate } => {
+                if x < 0.0 {
+                    0.0
+                } else {
+                    -(-rate * x).exp_m1()
+                }
+            }
+        }
+    }
+
+    /// Performs a one-sample Kolmogorov-Smirnov test of whether `data`
+    /// follows this distribution.
+    ///
+    /// The statistic is the largest distance between the empirical and the
+    /// fitted distribution functions. When the parameters were fitted to
+    /// the same data, the p-value is conservative (too large).
+    ///
+    /// `None` is returned if `data` is empty.
+    pub fn ks_test<T: PartialOrd + ToPrimitive>(
+        &self,
+        data: &mut Unsorted<T>,
+    ) -> Option<TestResult> {
+        let sorted = data.as_sorted_slice();
+        if sorted.is_empty() {
+            return None;
+        }
+        let n = sorted.len() as f64;
+        let d = sorted
+            .iter()
+            .enumerate()
+            .map(|(i, x)| {
+                let f = self.cdf(x.to_f64().unwrap());
+                (f - i as f64 / n).max((i + 1) as f64 / n - f)
+            })
+            .fold(0.0, f64::max);
+        let sqrt_n = n.sqrt();
+        Some(TestResult {
+            statistic: d,
+            p_value: kolmogorov_survival((sqrt_n + 0.12 + 0.11 / sqrt_n) * d),
+        })
+    }
+}
+
+#[inline]
+fn normal_pdf(z: f64) -> f64 {
+    (-0.5 * z * z).exp() / (SQRT_2 * PI.sqrt())
+}
+
+/// A fitted distribution together with its goodness-of-fit metrics.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Fit {
+    /// The fitted distribution.
+    pub distribution: Distribution,
+    /// The log-likelihood of the data under the fitted distribution.
+    pub log_likelihood: f64,
+    /// Akaike's information criterion, `2k - 2 * log_likelihood` for a
+    /// distribution with `k` parameters.
+    pub aic: f64,
+}
+
+impl Fit {
+    fn new(distribution: Distribution, params: f64, log_likelihood: f64) -> Fit {
+        Fit {
+            distribution,
+            log_likelihood,
+            aic: 2.0f64.mul_add(params, -2.0 * log_likelihood),
+        }
+    }
+}
+
+/// Fits a normal distribution to data with the given moments.
+///
+/// `None` is returned if there are no samples or they are all equal.
+#[must_use]
+pub fn normal(stats: &OnlineStats) -> Option<Fit> {
+    let (n, mean, variance) = (stats.len() as f64, stats.mean(), stats.variance());
+    if stats.is_empty() || variance.is_nan() || variance <= 0.0 {
+        return None;
+    }
+    let ll = -n / 2.0 * ((2.0 * PI * variance).ln() + 1.0);
+    Some(Fit::new(
+        Distribution::Normal {
+            mean,
+            stddev: variance.sqrt(),
+        },
+        2.0,
+        ll,
+    ))
+}
+
+/// Fits a lognormal distribution to data, given the moments of the
+/// logarithms of the data.
+///
+/// `None` is returned if there are no samples or they are all equal.
+#[must_use]
+pub fn lognormal(log_stats: &OnlineStats) -> Option<Fit> {
+    let fit = normal(log_stats)?;
+    let Distribution::Normal { mean, stddev } = fit.distribution else {
+        unreachable!()
+    };
+    // the density of x has an extra factor of 1 / x, i.e. -sum(ln x)
+    let ll = (log_stats.len() as f64).mul_add(-mean, fit.log_likelihood);
+    Some(Fit::new(
+        Distribution::LogNormal {
+            mu: mean,
+            sigma: stddev,
+        },
+        2.0,
+        ll,
+    ))
+}
+
+/// Fits an exponential distribution to data with the given moments.
+///
+/// `None` is returned if there are no samples or the mean is not positive.
+#[must_use]
+pub fn exponential(stats: &OnlineStats) -> Option<Fit> {
+    let (n, mean) = (stats.len() as f64, stats.mean());
+    if stats.is_empty() || mean.is_nan() || mean <= 0.0 {
+        return None;
+    }
+    let rate = mean.recip();
+    Some(Fit::new(
+        Distribution::Exponential { rate },
+        1.0,
+        n * (rate.ln() - 1.0),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{exponential, lognormal, normal, Distribution};
+    use crate::{OnlineStats, Unsorted};
+
+    #[test]
+    fn fit_normal() {
+        let data = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+        let fit = normal(&OnlineStats::from_slice(&data)).unwrap();
+        assert_eq!(
+            fit.distribution,
+            Distribution::Normal {
+                mean: 5.0,
+                stddev: 2.0
+            }
+        );
+        // the log-likelihood is the sum of the log densities
+        let ll: f64 = data.iter().map(|&x| fit.distribution.pdf(x).ln()).sum();
+        assert!((fit.log_likelihood - ll).abs() < 1e-9);
+        assert!((fit.aic - (4.0 - 2.0 * ll)).abs() < 1e-9);
+        assert!((fit.distribution.cdf(5.0) - 0.5).abs() < 1e-6);
+        assert!(normal(&OnlineStats::from_slice(&[3, 3])).is_none());
+    }
+
+    #[test]
+    fn fit_lognormal_exponential() {
+        let data = [0.5, 1.0, 1.5, 2.0, 4.0, 8.0];
+        let log_stats: OnlineStats = data.iter().map(|x: &f64| x.ln()).collect();
+        let fit = lognormal(&log_stats).unwrap();
+        let ll: f64 = data.iter().map(|&x| fit.distribution.pdf(x).ln()).sum();
+        assert!((fit.log_likelihood - ll).abs() < 1e-9);
+
+        let fit = exponential(&OnlineStats::from_slice(&data)).unwrap();
+        let ll: f64 = data.iter().map(|&x| fit.distribution.pdf(x).ln()).sum();
+        assert!((fit.log_likelihood - ll).abs() < 1e-9);
+        assert_eq!(fit.distribution.pdf_points(&[-1.0]), [0.0]);
+        assert!(exponential(&OnlineStats::from_slice(&[-1.0, 0.5])).is_none());
+    }
+
+    #[test]
+    fn goodness_of_fit() {
+        let dist = Distribution::Exponential { rate: 1.0 };
+        // the exponential quantiles at (i + 0.5) / n fit almost perfectly
+        let mut data: Unsorted<f64> = (0..100)
+            .map(|i| -(1.0 - (f64::from(i) + 0.5) / 100.0).ln())
+            .collect();
+        let r = dist.ks_test(&mut data).unwrap();
+        assert!((r.statistic - 0.005).abs() < 1e-6);
+        assert!(r.p_value > 0.99);
+
+        let shifted = Distribution::Normal {
+            mean: 10.0,
+            stddev: 1.0,
+        };
+        assert!(shifted.ks_test(&mut data).unwrap().p_value < 1e-6);
+    }
+}
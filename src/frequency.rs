@@ -5,14 +5,14 @@ use std::hash::Hash;
 
 use rayon::prelude::*;
 
-use crate::Commute;
+use crate::parallel::in_pool;
+use crate::{Commute, Normalize, TextNormalization};
 /// A commutative data structure for exact frequency counts.
 #[derive(Clone)]
 pub struct Frequencies<T> {
     data: AHashMap<T, u64>,
 }
 
-#[cfg(debug_assertions)]
 impl<T: fmt::Debug + Eq + Hash> fmt::Debug for Frequencies<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{:?}", self.data)
@@ -39,6 +39,16 @@ impl<T: Eq + Hash> Frequencies<T> {
         }
     }
 
+    /// Add a sample to the frequency table after normalizing it, e.g.
+    /// trimming whitespace and ignoring case.
+    #[inline]
+    pub fn add_normalized(&mut self, v: T, how: TextNormalization)
+    where
+        T: Normalize,
+    {
+        self.add(v.normalize(how));
+    }
+
     /// Return the number of occurrences of `v` in the data.
     #[inline]
     #[must_use]
@@ -83,7 +93,7 @@ impl<T: Eq + Hash> Frequencies<T> {
                 (k, v)
             })
             .collect();
-        counts.sort_unstable_by(|&(_, c1), &(_, c2)| c2.cmp(&c1));
+        counts.sort_unstable_by_key(|&(_, c)| std::cmp::Reverse(c));
         (counts, total_count)
     }
 
@@ -101,7 +111,7 @@ impl<T: Eq + Hash> Frequencies<T> {
                 (k, v)
             })
             .collect();
-        counts.sort_unstable_by(|&(_, c1), &(_, c2)| c1.cmp(&c2));
+        counts.sort_unstable_by_key(|&(_, c)| c);
         (counts, total_count)
     }
 
@@ -126,21 +136,24 @@ impl<T: Eq + Hash> Frequencies<T> {
         // sort by counts asc/desc
         // if counts are equal, sort by values lexicographically
         // We need to do this because otherwise the values are not guaranteed to be in order for equal counts
-        if least {
-            // return counts in ascending order
-            counts.par_sort_unstable_by(|&(v1, c1), &(v2, c2)| {
-                let cmp = c1.cmp(&c2);
-                if cmp == std::cmp::Ordering::Equal {
-                    v1.cmp(v2)
-                } else {
-                    cmp
-                }
-            });
-        } else {
-            // return counts in descending order
-            counts
-                .par_sort_unstable_by(|&(v1, c1), &(v2, c2)| c2.cmp(&c1).then_with(|| v1.cmp(v2)));
-        }
+        in_pool(|| {
+            if least {
+                // return counts in ascending order
+                counts.par_sort_unstable_by(|&(v1, c1), &(v2, c2)| {
+                    let cmp = c1.cmp(&c2);
+                    if cmp == std::cmp::Ordering::Equal {
+                        v1.cmp(v2)
+                    } else {
+                        cmp
+                    }
+                });
+            } else {
+                // return counts in descending order
+                counts.par_sort_unstable_by(|&(v1, c1), &(v2, c2)| {
+                    c2.cmp(&c1).then_with(|| v1.cmp(v2))
+                });
+            }
+        });
         (counts, total_count)
     }
 
@@ -150,15 +163,32 @@ impl<T: Eq + Hash> Frequencies<T> {
         self.data.len()
     }
 
+    /// Returns an estimate of the number of bytes allocated by this table.
+    ///
+    /// This counts the table capacity (one entry plus one control byte per
+    /// slot), but does not follow heap allocations owned by the keys
+    /// themselves (e.g. the contents of a `String`).
+    #[must_use]
+    pub fn mem_usage(&self) -> usize {
+        std::mem::size_of::<Self>() + self.data.capacity() * (std::mem::size_of::<(T, u64)>() + 1)
+    }
+
     /// Returns true if there is no frequency/cardinality data.
     #[must_use]
     pub fn is_empty(&self) -> bool {
         self.data.is_empty()
     }
 
+    /// Returns an iterator over the unique values and their counts, in
+    /// arbitrary order.
+    #[inline]
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&T, u64)> {
+        self.data.iter().map(|(v, &c)| (v, c))
+    }
+
     /// Return an iterator over the unique values of the data.
     #[must_use]
-    pub fn unique_values(&self) -> UniqueValues<T> {
+    pub fn unique_values(&self) -> UniqueValues<'_, T> {
         UniqueValues {
             data_keys: self.data.keys(),
         }
@@ -221,6 +251,8 @@ impl<'a, K> Iterator for UniqueValues<'a, K> {
 }
 
 #[cfg(test)]
+// the tests predate this lint and are kept as written
+#[allow(clippy::useless_conversion)]
 mod test {
     use super::Frequencies;
     use std::iter::FromIterator;
@@ -257,4 +289,13 @@ mod test {
         unique.sort_unstable();
         assert_eq!(unique, vec![1, 2, 3, 4, 5, 6, 7, 8]);
     }
+
+    #[test]
+    fn mem_usage() {
+        let freqs: Frequencies<u64> = Frequencies::new();
+        let empty = freqs.mem_usage();
+        assert!(empty > std::mem::size_of::<Frequencies<u64>>());
+        let freqs: Frequencies<u64> = (0..100_000).collect();
+        assert!(freqs.mem_usage() > empty);
+    }
 }
@@ -0,0 +1,188 @@
+use ahash::RandomState;
+use std::collections::hash_map::{self, HashMap};
+use std::hash::Hash;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Commute;
+
+/// A commutative container keeping one accumulator per group key.
+///
+/// This is the building block for "statistics per category" reports: each
+/// sample is added to the accumulator of its group, which is created on
+/// first use. Shards built on different threads can be combined with
+/// `merge`, which merges the accumulators of matching groups.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "K: Serialize, S: Serialize",
+    deserialize = "K: Deserialize<'de> + Eq + Hash, S: Deserialize<'de>"
+))]
+pub struct Grouped<K, S> {
+    groups: HashMap<K, S, RandomState>,
+}
+
+impl<K: Eq + Hash, S: Commute + Default> Grouped<K, S> {
+    /// Create a new container with no groups.
+    #[must_use]
+    pub fn new() -> Grouped<K, S> {
+        Default::default()
+    }
+
+    /// Add `sample` to the accumulator of the group `key`.
+    ///
+    /// Any accumulator that can be extended with samples works, e.g.
+    /// `Grouped<String, OnlineStats>` with `f64` samples.
+    #[inline]
+    pub fn add<V>(&mut self, key: K, sample: V)
+    where
+        S: Extend<V>,
+    {
+        self.group_mut(key).extend(Some(sample));
+    }
+
+    /// Apply `f` to the accumulator of the group `key`, e.g. to call an
+    /// accumulator method other than `add`.
+    #[inline]
+    pub fn update<R, F: FnOnce(&mut S) -> R>(&mut self, key: K, f: F) -> R {
+        f(self.group_mut(key))
+    }
+
+    #[inline]
+    fn group_mut(&mut self, key: K) -> &mut S {
+        self.groups.entry(key).or_default()
+    }
+
+    /// Returns the accumulator of the group `key`, if it has any samples.
+    #[inline]
+    #[must_use]
+    pub fn get(&self, key: &K) -> Option<&S> {
+        self.groups.get(key)
+    }
+
+    /// Returns an iterator over the `(key, accumulator)` pairs, in no
+    /// particular order.
+    #[inline]
+    pub fn iter(&self) -> hash_map::Iter<'_, K, S> {
+        self.groups.iter()
+    }
+
+    /// Returns the number of groups.
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.groups.len()
+    }
+
+    /// Returns true if there are no groups.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+}
+
+impl<K: Eq + Hash, S: Commute> Commute for Grouped<K, S> {
+    #[inline]
+    fn merge(&mut self, v: Grouped<K, S>) {
+        self.groups.merge(v.groups);
+    }
+}
+
+impl<K, S> Default for Grouped<K, S> {
+    #[inline]
+    fn default() -> Grouped<K, S> {
+        Grouped {
+            groups: HashMap::default(),
+        }
+    }
+}
+
+impl<K: Eq + Hash, S: Commute + Default, V> FromIterator<(K, V)> for Grouped<K, S>
+where
+    S: Extend<V>,
+{
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(it: I) -> Grouped<K, S> {
+        let mut v = Grouped::new();
+        v.extend(it);
+        v
+    }
+}
+
+impl<K: Eq + Hash, S: Commute + Default, V> Extend<(K, V)> for Grouped<K, S>
+where
+    S: Extend<V>,
+{
+    #[inline]
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, it: I) {
+        for (key, sample) in it {
+            self.add(key, sample);
+        }
+    }
+}
+
+impl<K, S> IntoIterator for Grouped<K, S> {
+    type Item = (K, S);
+    type IntoIter = hash_map::IntoIter<K, S>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.groups.into_iter()
+    }
+}
+
+impl<'a, K, S> IntoIterator for &'a Grouped<K, S> {
+    type Item = (&'a K, &'a S);
+    type IntoIter = hash_map::Iter<'a, K, S>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.groups.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Grouped;
+    use crate::{Commute, MinMax, OnlineStats};
+
+    #[test]
+    fn per_group() {
+        let mut g1: Grouped<&str, OnlineStats> =
+            [("a", 1.0), ("b", 10.0), ("a", 3.0)].into_iter().collect();
+        let mut g2 = Grouped::new();
+        g2.add("b", 20.0);
+        g2.add("c", 5.0);
+        g1.merge(g2);
+
+        assert_eq!(g1.len(), 3);
+        assert_eq!(g1.get(&"a").unwrap().mean(), 2.0);
+        assert_eq!(g1.get(&"b").unwrap().mean(), 15.0);
+        assert!(g1.get(&"z").is_none());
+        g1.update("c", OnlineStats::add_null);
+        assert_eq!(g1.get(&"c").unwrap().len(), 2);
+
+        let mut sizes: Vec<_> = g1.iter().map(|(k, s)| (*k, s.len())).collect();
+        sizes.sort_unstable();
+        assert_eq!(sizes, [("a", 2), ("b", 2), ("c", 2)]);
+    }
+
+    #[test]
+    fn grouped_minmax() {
+        let g: Grouped<u8, MinMax<&str>> = [(1, "x"), (2, "q"), (1, "b")].into_iter().collect();
+        let mut groups: Vec<_> = g.into_iter().collect();
+        groups.sort_unstable_by_key(|(k, _)| *k);
+        assert_eq!(groups[0].1.min(), Some(&"b"));
+        assert_eq!(groups[1].1.len(), 1);
+    }
+
+    #[test]
+    fn serde_roundtrip() {
+        let g: Grouped<String, OnlineStats> = [("a".to_owned(), 1.0), ("a".to_owned(), 3.0)]
+            .into_iter()
+            .collect();
+        let json = serde_json::to_string(&g).unwrap();
+        let back: Grouped<String, OnlineStats> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.get(&"a".to_owned()).unwrap().mean(), 2.0);
+    }
+}
@@ -0,0 +1,103 @@
+//! Stable hashing for sketches.
+//!
+//! Sketches are merged and persisted, so every instance must hash a value
+//! to the same bits, on every platform and in every run. `std`'s and
+//! `ahash`'s default hashers are randomly seeded (and `ahash` also depends
+//! on the CPU), so sketches use this FNV-1a hasher instead, with a
+//! SplitMix64 finalizer to spread FNV's weak low bits.
+
+use std::hash::{Hash, Hasher};
+
+const FNV_OFFSET: u64 = 0xCBF2_9CE4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+
+struct StableHasher(u64);
+
+impl Hasher for StableHasher {
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 = (self.0 ^ u64::from(b)).wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    // `Hasher` writes integers in native byte order by default, so they are
+    // spelled out here in little-endian order, which keeps the hashes of
+    // little-endian platforms
+    #[inline]
+    fn write_u8(&mut self, i: u8) {
+        self.write(&[i]);
+    }
+
+    #[inline]
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    #[inline]
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    #[inline]
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    #[inline]
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    // also used for the lengths of slices and collections, so it must not
+    // depend on the pointer width
+    #[inline]
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    #[inline]
+    fn finish(&self) -> u64 {
+        mix64(self.0)
+    }
+}
+
+/// The SplitMix64 finalizer, a bijection that spreads every input bit over
+/// the whole output.
+#[inline]
+pub(crate) const fn mix64(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Returns a 64-bit hash of `value` that is the same in every run.
+#[inline]
+pub(crate) fn stable_hash<T: Hash + ?Sized>(value: &T) -> u64 {
+    let mut hasher = StableHasher(FNV_OFFSET);
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Returns the `i`th of a family of hashes of `value` reduced to `0..n`,
+/// derived from one 64-bit hash by double hashing (Kirsch-Mitzenmacher).
+#[inline]
+pub(crate) fn nth_index(hash: u64, i: usize, n: usize) -> usize {
+    let (h1, h2) = (hash & 0xFFFF_FFFF, (hash >> 32) | 1);
+    (h1.wrapping_add((i as u64).wrapping_mul(h2)) % n as u64) as usize
+}
+
+#[cfg(test)]
+mod test {
+    use super::stable_hash;
+
+    #[test]
+    fn platform_independent() {
+        // pinned, so a change of the hashes (which would break persisted
+        // sketches) is noticed
+        assert_eq!(stable_hash(&0x0102_0304u32), 8_240_969_181_103_592_730);
+        assert_eq!(stable_hash(&7usize), stable_hash(&7u64));
+        assert_eq!(stable_hash(&-7isize), stable_hash(&-7i64));
+        assert_eq!(stable_hash("ab"), stable_hash(&String::from("ab")));
+    }
+}
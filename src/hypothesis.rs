@@ -0,0 +1,294 @@
+use std::cmp::Ordering;
+use std::hash::Hash;
+
+use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+
+use crate::special::{f_survival, kolmogorov_survival, normal_two_sided_p};
+use crate::{merge_all, Grouped, OnlineStats, Partial, Unsorted};
+
+/// The outcome of a statistical hypothesis test.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct TestResult {
+    /// The test statistic.
+    pub statistic: f64,
+    /// The (approximate) probability of a statistic at least as extreme,
+    /// if the null hypothesis holds.
+    pub p_value: f64,
+}
+
+/// The outcome of a one-way analysis of variance.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Anova {
+    /// The sum of squares between groups.
+    pub ss_between: f64,
+    /// The sum of squares within groups.
+    pub ss_within: f64,
+    /// The degrees of freedom between groups, i.e. groups minus one.
+    pub df_between: u64,
+    /// The degrees of freedom within groups, i.e. samples minus groups.
+    pub df_within: u64,
+    /// The F statistic and its p-value.
+    pub test: TestResult,
+}
+
+impl<K: Eq + Hash> Grouped<K, OnlineStats> {
+    /// Performs a one-way analysis of variance of whether all groups have
+    /// the same mean.
+    ///
+    /// Only the per-group moments are needed, so this works on merged
+    /// streaming state without the raw data. Groups without samples are
+    /// ignored.
+    ///
+    /// `None` is returned if there are fewer than two groups with samples,
+    /// or no more samples than groups.
+    #[must_use]
+    pub fn anova(&self) -> Option<Anova> {
+        let groups: Vec<&OnlineStats> = self
+            .iter()
+            .map(|(_, s)| s)
+            .filter(|s| !s.is_empty())
+            .collect();
+        let k = groups.len() as u64;
+        let total = merge_all(groups.iter().copied().copied())?;
+        let n = total.len() as u64;
+        if k < 2 || n <= k {
+            return None;
+        }
+        let grand_mean = total.mean();
+        let ss_between: f64 = groups
+            .iter()
+            .map(|s| s.len() as f64 * (s.mean() - grand_mean).powi(2))
+            .sum();
+        let ss_within: f64 = groups.iter().map(|s| s.m2()).sum();
+        let (df_between, df_within) = (k - 1, n - k);
+        let f = (ss_between / df_between as f64) / (ss_within / df_within as f64);
+        let p_value = if f.is_nan() {
+            // every value is the same, so there is no evidence against equal means
+            1.0
+        } else {
+            f_survival(f, df_between as f64, df_within as f64)
+        };
+        Some(Anova {
+            ss_between,
+            ss_within,
+            df_between,
+            df_within,
+            test: TestResult {
+                statistic: f,
+                p_value,
+            },
+        })
+    }
+}
+
+impl<T: PartialOrd + ToPrimitive> Unsorted<T> {
+    /// Performs a two-sample Kolmogorov-Smirnov test of whether this data
+    /// and `other` come from the same distribution, e.g. to detect a
+    /// distribution shift between two versions of a data set.
+    ///
+    /// The statistic is `D`, the largest distance between the two empirical
+    /// distribution functions. The p-value uses the asymptotic Kolmogorov
+    /// distribution with the effective sample size correction of Stephens,
+    /// which is accurate once each sample has a few dozen values.
+    ///
+    /// Incomparable values, such as NaN, are ignored. `None` is returned if
+    /// either sample has no other values.
+    pub fn ks_test(&mut self, other: &mut Unsorted<T>) -> Option<TestResult> {
+        let a = comparable(self.sorted_data());
+        let b = comparable(other.sorted_data());
+        if a.is_empty() || b.is_empty() {
+            return None;
+        }
+        let (n, m) = (a.len() as f64, b.len() as f64);
+        let (mut i, mut j, mut d) = (0, 0, 0.0f64);
+        while i < a.len() && j < b.len() {
+            let x = if a[i] <= b[j] { a[i] } else { b[j] };
+            // step past every value tied with `x` in both samples at once
+            while i < a.len() && a[i] <= x {
+                i += 1;
+            }
+            while j < b.len() && b[j] <= x {
+                j += 1;
+            }
+            d = d.max((i as f64 / n - j as f64 / m).abs());
+        }
+
+        let ne = (n * m / (n + m)).sqrt();
+        Some(TestResult {
+            statistic: d,
+            p_value: kolmogorov_survival((ne + 0.12 + 0.11 / ne) * d),
+        })
+    }
+
+    /// Performs a Mann-Whitney U test (Wilcoxon rank-sum test) of whether
+    /// values of this data tend to be larger or smaller than those of
+    /// `other`. Unlike a t-test, it does not assume normal distributions.
+    ///
+    /// The statistic is `U` for this sample, i.e. the number of pairs in
+    /// which this sample's value is larger, counting ties as one half. The
+    /// two-sided p-value uses the normal approximation with a continuity
+    /// correction and a correction of the variance for ties, which is
+    /// accurate once each sample has more than about 20 values.
+    ///
+    /// Incomparable values, such as NaN, are ignored. `None` is returned if
+    /// either sample has no other values.
+    pub fn mann_whitney(&mut self, other: &mut Unsorted<T>) -> Option<TestResult> {
+        let a = comparable(self.sorted_data());
+        let b = comparable(other.sorted_data());
+        if a.is_empty() || b.is_empty() {
+            return None;
+        }
+        let (n1, n2) = (a.len() as f64, b.len() as f64);
+        let (mut i, mut j) = (0, 0);
+        // sum of the ranks of `a`, and sum of `t^3 - t` over tie groups
+        let (mut rank_sum, mut ties) = (0.0, 0.0);
+        while i < a.len() || j < b.len() {
+            let x = match (a.get(i), b.get(j)) {
+                (Some(&u), Some(&v)) => {
+                    if u <= v {
+                        u
+                    } else {
+                        v
+                    }
+                }
+                (Some(&u), None) => u,
+                (None, Some(&v)) => v,
+                (None, None) => unreachable!(),
+            };
+            let (i0, j0) = (i, j);
+            while i < a.len() && a[i] <= x {
+                i += 1;
+            }
+            while j < b.len() && b[j] <= x {
+                j += 1;
+            }
+            let t = (i - i0 + j - j0) as f64;
+            // the group occupies ranks `i0 + j0 + 1 ..= i0 + j0 + t`
+            let mid_rank = (i0 + j0) as f64 + (t + 1.0) / 2.0;
+            rank_sum += (i - i0) as f64 * mid_rank;
+            ties += t * t * t - t;
+        }
+
+        let u = n1.mul_add(-(n1 + 1.0) / 2.0, rank_sum);
+        let n = n1 + n2;
+        let variance = n1 * n2 / 12.0 * ((n + 1.0) - ties / (n * (n - 1.0)));
+        let diff = (u - n1 * n2 / 2.0).abs();
+        let p_value = if variance > 0.0 {
+            normal_two_sided_p((diff - 0.5).max(0.0) / variance.sqrt())
+        } else {
+            1.0
+        };
+        Some(TestResult {
+            statistic: u,
+            p_value,
+        })
+    }
+}
+
+/// Returns the values of `sorted` that are comparable with themselves,
+/// i.e. everything except NaN-like values, in order.
+///
+/// Skipping over a NaN would never advance the merge loops above, and with
+/// one present the sort may have left the other values out of order.
+fn comparable<T: PartialOrd>(sorted: &[Partial<T>]) -> Vec<&T> {
+    let mut values: Vec<&T> = sorted
+        .iter()
+        .map(|x| &x.0)
+        .filter(|x| x.partial_cmp(x).is_some())
+        .collect();
+    if values.len() < sorted.len() {
+        values.sort_unstable_by(|x, y| x.partial_cmp(y).unwrap_or(Ordering::Equal));
+    }
+    values
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Grouped, OnlineStats, Unsorted};
+
+    #[test]
+    fn one_way_anova() {
+        let data = [
+            ("a", [6.0, 8.0, 4.0, 5.0, 3.0, 4.0]),
+            ("b", [8.0, 12.0, 9.0, 11.0, 6.0, 8.0]),
+            ("c", [13.0, 9.0, 11.0, 8.0, 7.0, 12.0]),
+        ];
+        let mut groups: Grouped<&str, OnlineStats> = Grouped::new();
+        for (k, values) in data {
+            for v in values {
+                groups.add(k, v);
+            }
+        }
+        groups.add("empty", 0.0);
+        groups.update("empty", |s| *s = OnlineStats::new());
+
+        let r = groups.anova().unwrap();
+        assert!((r.ss_between - 84.0).abs() < 1e-9);
+        assert!((r.ss_within - 68.0).abs() < 1e-9);
+        assert_eq!((r.df_between, r.df_within), (2, 15));
+        assert!((r.test.statistic - 9.264_705_882).abs() < 1e-6);
+        assert!((r.test.p_value - 0.002_398_777).abs() < 1e-6);
+
+        let single: Grouped<u8, OnlineStats> = [(1, 2.0), (1, 3.0)].into_iter().collect();
+        assert!(single.anova().is_none());
+    }
+
+    #[test]
+    fn mann_whitney_ties() {
+        // U counts the pairs (x, y) with x > y, plus one half per tie
+        let mut a: Unsorted<u32> = [1, 2, 2, 3, 4, 5, 5, 6, 7, 9].into_iter().collect();
+        let mut b: Unsorted<u32> = [3, 5, 6, 7, 7, 8, 9, 10, 11, 12, 13].into_iter().collect();
+        let r = a.mann_whitney(&mut b).unwrap();
+        assert_eq!(r.statistic, 17.5);
+        assert!((r.p_value - 0.008_905_2).abs() < 1e-6);
+        let r = b.mann_whitney(&mut a).unwrap();
+        assert_eq!(r.statistic, 92.5);
+
+        let mut same: Unsorted<u32> = [4, 4, 4].into_iter().collect();
+        let mut other: Unsorted<u32> = [4, 4].into_iter().collect();
+        assert_eq!(same.mann_whitney(&mut other).unwrap().p_value, 1.0);
+    }
+
+    #[test]
+    fn mann_whitney_ignores_nan() {
+        let mut a: Unsorted<f64> = [1.0, 2.0, f64::NAN].into_iter().collect();
+        let mut b: Unsorted<f64> = [1.5, 2.5].into_iter().collect();
+        // only (2.0, 1.5) has the value of `a` larger
+        assert_eq!(a.mann_whitney(&mut b).unwrap().statistic, 1.0);
+        assert_eq!(b.mann_whitney(&mut a).unwrap().statistic, 3.0);
+        let mut nan: Unsorted<f64> = [f64::NAN].into_iter().collect();
+        assert!(a.mann_whitney(&mut nan).is_none());
+    }
+
+    #[test]
+    fn ks_same_distribution() {
+        let mut a: Unsorted<u32> = (0..200).map(|i| (i * 7) % 200).collect();
+        let mut b: Unsorted<u32> = (0..100).map(|i| i * 2 + 1).collect();
+        let r = a.ks_test(&mut b).unwrap();
+        assert!(r.statistic <= 0.01);
+        assert!(r.p_value > 0.99);
+    }
+
+    #[test]
+    fn ks_shifted() {
+        let mut a: Unsorted<f64> = (0..50).map(f64::from).collect();
+        let mut b: Unsorted<f64> = (0..50).map(|i| f64::from(i) + 25.0).collect();
+        let r = a.ks_test(&mut b).unwrap();
+        assert_eq!(r.statistic, 0.5);
+        assert!(r.p_value < 1e-4);
+        // the statistic is symmetric
+        assert_eq!(b.ks_test(&mut a).unwrap().statistic, 0.5);
+        assert!(a.ks_test(&mut Unsorted::new()).is_none());
+    }
+
+    #[test]
+    fn ks_ignores_nan() {
+        let mut a: Unsorted<f64> = [1.0, 2.0, f64::NAN].into_iter().collect();
+        let mut b: Unsorted<f64> = [1.5, 2.5].into_iter().collect();
+        let r = a.ks_test(&mut b).unwrap();
+        assert_eq!(r.statistic, 0.5);
+        let mut nan: Unsorted<f64> = [f64::NAN].into_iter().collect();
+        assert!(a.ks_test(&mut nan).is_none());
+    }
+}
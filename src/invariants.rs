@@ -0,0 +1,467 @@
+//! Assertions of the formal guarantees made by each estimator.
+//!
+//! These functions panic (like `assert!`) with a descriptive message when a
+//! guarantee does not hold, so they can be called directly from downstream
+//! property tests (e.g. with `quickcheck` or `proptest` generated inputs).
+//!
+//! Every estimator in this crate implements `Commute`, and the central
+//! guarantee is that merging the states of two partitions of the data gives
+//! the same answer as computing on the whole data in a single pass. The
+//! approximate estimators also stay within the error they report. For
+//! `CountMin` and `Cardinality` that bound only holds with high
+//! probability, so their assertions can fail spuriously, as documented on
+//! each function.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::hash::Hash;
+
+use num_traits::ToPrimitive;
+
+use crate::{
+    Cardinality, Commute, CompensatedStats, CountMin, DDSketch, DistinctFilter, Frequencies,
+    MinMax, OnlineStats, Unsorted,
+};
+
+/// Returns true if `a` and `b` are equal within the relative tolerance `tol`.
+fn approx_eq(a: f64, b: f64, tol: f64) -> bool {
+    if a == b || (a.is_nan() && b.is_nan()) {
+        return true;
+    }
+    (a - b).abs() <= tol * a.abs().max(b.abs()).max(1.0)
+}
+
+/// Asserts that merging `OnlineStats` of `left` and `right` agrees with a
+/// single pass over both, within the relative tolerance `tol`.
+pub fn assert_online_merge_equivalent<T: ToPrimitive>(left: &[T], right: &[T], tol: f64) {
+    let mut merged = OnlineStats::from_slice(left);
+    merged.merge(OnlineStats::from_slice(right));
+    let mut single = OnlineStats::from_slice(left);
+    single.extend(right.iter().map(|x| x.to_f64().unwrap()));
+
+    assert_eq!(merged.len(), single.len(), "merged length differs");
+    assert_eq!(
+        merged.sign_counts(),
+        single.sign_counts(),
+        "merged sign counts differ"
+    );
+    assert_eq!(
+        merged.imprecise_count(),
+        single.imprecise_count(),
+        "merged imprecise count differs"
+    );
+    assert!(
+        approx_eq(merged.mean(), single.mean(), tol),
+        "merged mean {} != single-pass mean {}",
+        merged.mean(),
+        single.mean()
+    );
+    assert!(
+        approx_eq(merged.variance(), single.variance(), tol),
+        "merged variance {} != single-pass variance {}",
+        merged.variance(),
+        single.variance()
+    );
+}
+
+/// Asserts that merging `CompensatedStats` of `left` and `right` agrees
+/// with a single pass over both, within the relative tolerance `tol`.
+///
+/// This includes the harmonic and geometric means, which must both be
+/// `NaN` if either side has a sample that is not positive.
+pub fn assert_compensated_merge_equivalent<T: ToPrimitive>(left: &[T], right: &[T], tol: f64) {
+    let mut merged: CompensatedStats = left.iter().map(|x| x.to_f64().unwrap()).collect();
+    merged.merge(right.iter().map(|x| x.to_f64().unwrap()).collect());
+    let single: CompensatedStats = left
+        .iter()
+        .chain(right)
+        .map(|x| x.to_f64().unwrap())
+        .collect();
+
+    assert_eq!(merged.len(), single.len(), "merged length differs");
+    assert_eq!(
+        merged.non_positive_count(),
+        single.non_positive_count(),
+        "merged non-positive count differs"
+    );
+    for (name, m, s) in [
+        ("mean", merged.mean(), single.mean()),
+        ("variance", merged.variance(), single.variance()),
+        (
+            "harmonic mean",
+            merged.harmonic_mean(),
+            single.harmonic_mean(),
+        ),
+        (
+            "geometric mean",
+            merged.geometric_mean(),
+            single.geometric_mean(),
+        ),
+    ] {
+        assert!(
+            approx_eq(m, s, tol),
+            "merged {name} {m} != single-pass {name} {s}"
+        );
+    }
+}
+
+/// Asserts that `OnlineStats` of `data` has a non-negative variance and a
+/// mean within the range of the data.
+pub fn assert_online_bounds<T: ToPrimitive>(data: &[T]) {
+    if data.is_empty() {
+        return;
+    }
+    let stats = OnlineStats::from_slice(data);
+    let minmax: MinMax<f64> = data.iter().map(|x| x.to_f64().unwrap()).collect();
+    let (min, max) = (*minmax.min().unwrap(), *minmax.max().unwrap());
+    // allow for rounding of the running mean at the ends of the range
+    let slack = f64::EPSILON * min.abs().max(max.abs()) * data.len() as f64;
+    assert!(
+        stats.mean() >= min - slack && stats.mean() <= max + slack,
+        "mean {} is outside [{min}, {max}]",
+        stats.mean()
+    );
+    assert!(
+        stats.variance() >= 0.0,
+        "variance {} is negative",
+        stats.variance()
+    );
+}
+
+/// Asserts that merging `MinMax` of `left` and `right` is exactly equal to
+/// a single pass over both.
+pub fn assert_minmax_merge_equivalent<T>(left: &[T], right: &[T])
+where
+    T: PartialOrd + Clone + Debug,
+{
+    let mut merged: MinMax<T> = left.iter().cloned().collect();
+    merged.merge(right.iter().cloned().collect());
+    let single: MinMax<T> = left.iter().chain(right).cloned().collect();
+
+    assert_eq!(merged.len(), single.len(), "merged length differs");
+    assert_eq!(merged.min(), single.min(), "merged min differs");
+    assert_eq!(merged.max(), single.max(), "merged max differs");
+}
+
+/// Asserts that exact quantiles of `data` are monotonic,
+/// i.e. `min <= q1 <= median <= q3 <= max`, and that Q2 is the median.
+pub fn assert_quartiles_monotonic<T>(data: &[T])
+where
+    T: PartialOrd + ToPrimitive + Clone,
+{
+    let mut unsorted: Unsorted<T> = data.iter().cloned().collect();
+    let Some((q1, q2, q3)) = unsorted.quartiles() else {
+        return;
+    };
+    let minmax: MinMax<f64> = data.iter().map(|x| x.to_f64().unwrap()).collect();
+    let (min, max) = (*minmax.min().unwrap(), *minmax.max().unwrap());
+
+    assert!(
+        min <= q1 && q1 <= q2 && q2 <= q3 && q3 <= max,
+        "quartiles are not monotonic: {min} <= {q1} <= {q2} <= {q3} <= {max}"
+    );
+    assert_eq!(unsorted.median(), Some(q2), "Q2 differs from the median");
+}
+
+/// Asserts that merging `Unsorted` of `left` and `right` yields the same
+/// median and quartiles as a single pass over both, in either merge order.
+pub fn assert_unsorted_merge_equivalent<T>(left: &[T], right: &[T])
+where
+    T: PartialOrd + ToPrimitive + Clone,
+{
+    let mut single: Unsorted<T> = left.iter().chain(right).cloned().collect();
+
+    let mut lr: Unsorted<T> = left.iter().cloned().collect();
+    lr.merge(right.iter().cloned().collect());
+    let mut rl: Unsorted<T> = right.iter().cloned().collect();
+    rl.merge(left.iter().cloned().collect());
+
+    for merged in [&mut lr, &mut rl] {
+        assert_eq!(merged.len(), single.len(), "merged length differs");
+        assert_eq!(merged.median(), single.median(), "merged median differs");
+        assert_eq!(
+            merged.quartiles(),
+            single.quartiles(),
+            "merged quartiles differ"
+        );
+    }
+}
+
+/// Asserts that merging `Frequencies` of `left` and `right` counts every
+/// value exactly as a single pass over both.
+pub fn assert_frequencies_merge_equivalent<T>(left: &[T], right: &[T])
+where
+    T: Eq + Hash + Clone + Debug,
+{
+    let mut merged: Frequencies<T> = left.iter().cloned().collect();
+    merged.merge(right.iter().cloned().collect());
+    let single: Frequencies<T> = left.iter().chain(right).cloned().collect();
+
+    assert_eq!(
+        merged.cardinality(),
+        single.cardinality(),
+        "merged cardinality differs"
+    );
+    for v in single.unique_values() {
+        assert_eq!(merged.count(v), single.count(v), "count of {v:?} differs");
+    }
+}
+
+/// Asserts that merging `DDSketch`es of `left` and `right` with relative
+/// accuracy `alpha` equals a single pass over both, and that its quantiles
+/// are within `alpha` of the exact ones, relative to their magnitude.
+///
+/// `NaN`s are ignored, as by the sketch. Magnitudes below
+/// `f64::MIN_POSITIVE`, which the sketch counts as zero, are allowed to be
+/// off by that much.
+pub fn assert_ddsketch_relative_error<T: ToPrimitive>(left: &[T], right: &[T], alpha: f64) {
+    let sketch = |data: &[T]| {
+        let mut sketch = DDSketch::new(alpha);
+        sketch.extend(data.iter().map(|x| x.to_f64().unwrap()));
+        sketch
+    };
+    let mut merged = sketch(left);
+    merged.merge(sketch(right));
+    let mut single = sketch(left);
+    single.extend(right.iter().map(|x| x.to_f64().unwrap()));
+    assert_eq!(merged, single, "merged sketch differs");
+
+    let mut exact: Vec<f64> = left
+        .iter()
+        .chain(right)
+        .map(|x| x.to_f64().unwrap())
+        .filter(|x| !x.is_nan())
+        .collect();
+    exact.sort_unstable_by(f64::total_cmp);
+    for q in [
+        0.0, 0.001, 0.01, 0.1, 0.25, 0.5, 0.75, 0.9, 0.99, 0.999, 1.0,
+    ] {
+        let Some(estimate) = merged.quantile(q) else {
+            assert!(exact.is_empty(), "no quantile {q} of a non-empty sketch");
+            continue;
+        };
+        // the rank that `DDSketch::quantile` estimates
+        let x = exact[(q * (exact.len() - 1) as f64).floor() as usize];
+        // allow for the rounding of the bucket boundaries
+        let bound = (alpha * x.abs()).mul_add(1.0 + 1e-9, f64::MIN_POSITIVE);
+        assert!(
+            (estimate - x).abs() <= bound,
+            "quantile {q}: estimate {estimate} is not within {alpha} of {x}"
+        );
+    }
+}
+
+/// Asserts that merging `CountMin` sketches of `left` and `right`, created
+/// with `CountMin::with_error(eps, delta)`, equals a single pass over both,
+/// that no estimate is below the true count, and that none exceeds it by
+/// more than `error_bound`.
+///
+/// The last guarantee only holds with probability `1 - delta` per value, so
+/// this fails spuriously with a probability of at most `delta` times the
+/// number of distinct values. Use a small `delta` (e.g. `1e-9`) in property
+/// tests.
+pub fn assert_countmin_bounds<T>(left: &[T], right: &[T], eps: f64, delta: f64)
+where
+    T: Eq + Hash + Debug,
+{
+    let sketch = |data: &[T]| {
+        let mut sketch = CountMin::with_error(eps, delta);
+        for v in data {
+            sketch.add(v);
+        }
+        sketch
+    };
+    let mut merged = sketch(left);
+    merged.merge(sketch(right));
+    let mut single = sketch(left);
+    for v in right {
+        single.add(v);
+    }
+    assert!(merged == single, "merged sketch differs");
+
+    let mut counts: HashMap<&T, u64> = HashMap::new();
+    for v in left.iter().chain(right) {
+        *counts.entry(v).or_insert(0) += 1;
+    }
+    assert_eq!(
+        merged.len(),
+        (left.len() + right.len()) as u64,
+        "length differs"
+    );
+    let bound = merged.error_bound();
+    for (v, count) in counts {
+        let estimate = merged.estimate(v);
+        assert!(
+            estimate >= count,
+            "estimate {estimate} of {v:?} is below its count {count}"
+        );
+        assert!(
+            estimate - count <= bound,
+            "estimate {estimate} of {v:?} exceeds its count {count} by more than {bound}"
+        );
+    }
+}
+
+/// Asserts that merging `Cardinality` counters of `left` and `right`,
+/// created with `Cardinality::with_limit(exact_limit, precision)`, equals a
+/// single pass over both, and that the count is exact while `is_exact` and
+/// otherwise within `k` times `relative_error` of the true count.
+///
+/// `relative_error` is a standard error, so with `k = 4` spurious failures
+/// are rare: about one input in 15,000 if the error is normally
+/// distributed.
+pub fn assert_cardinality_within<T>(
+    left: &[T],
+    right: &[T],
+    exact_limit: usize,
+    precision: u8,
+    k: f64,
+) where
+    T: Eq + Hash + Clone + Debug,
+{
+    let counter = |data: &[T]| {
+        let mut counter = Cardinality::with_limit(exact_limit, precision);
+        counter.extend(data.iter().cloned());
+        counter
+    };
+    let mut merged = counter(left);
+    merged.merge(counter(right));
+    let mut single = counter(left);
+    single.extend(right.iter().cloned());
+    assert_eq!(
+        merged.is_exact(),
+        single.is_exact(),
+        "merged exactness differs"
+    );
+    assert_eq!(merged.count(), single.count(), "merged count differs");
+
+    let distinct = left.iter().chain(right).collect::<HashSet<_>>().len() as f64;
+    let count = merged.count() as f64;
+    if merged.is_exact() {
+        assert_eq!(count, distinct, "exact count {count} != {distinct}");
+    } else {
+        let bound = k * merged.relative_error() * distinct;
+        assert!(
+            (count - distinct).abs() <= bound,
+            "estimate {count} is not within {bound} of {distinct}"
+        );
+    }
+}
+
+/// Asserts that a `DistinctFilter` sized with `DistinctFilter::new(expected,
+/// fp_rate)` has no false negatives: every repeated value is flagged as
+/// seen, and the filters of `left` and `right`, merged, contain every
+/// value of both.
+pub fn assert_distinct_filter_no_false_negatives<T>(
+    left: &[T],
+    right: &[T],
+    expected: usize,
+    fp_rate: f64,
+) where
+    T: Eq + Hash + Debug,
+{
+    let mut seen = HashSet::new();
+    let mut single = DistinctFilter::new(expected, fp_rate);
+    for v in left.iter().chain(right) {
+        let flagged = single.add(v);
+        assert!(
+            flagged || seen.insert(v),
+            "repeated value {v:?} was not flagged"
+        );
+    }
+
+    let filter = |data: &[T]| {
+        let mut filter = DistinctFilter::new(expected, fp_rate);
+        for v in data {
+            filter.add(v);
+        }
+        filter
+    };
+    let mut merged = filter(left);
+    merged.merge(filter(right));
+    for v in left.iter().chain(right) {
+        assert!(merged.contains(v), "merged filter misses {v:?}");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rng::SplitMix64;
+
+    fn random_vec(rng: &mut SplitMix64, len: usize, scale: f64) -> Vec<f64> {
+        (0..len)
+            .map(|_| (rng.next_f64() - 0.5) * scale)
+            .collect::<Vec<_>>()
+    }
+
+    #[test]
+    fn numeric_invariants() {
+        let mut rng = SplitMix64::new(0x5EED);
+        for (len, scale) in [(0, 1.0), (1, 1.0), (7, 10.0), (100, 1e6), (1000, 1e-3)] {
+            let left = random_vec(&mut rng, len, scale);
+            let right = random_vec(&mut rng, len / 2 + 3, scale);
+            assert_online_merge_equivalent(&left, &right, 1e-9);
+            assert_compensated_merge_equivalent(&left, &right, 1e-9);
+            assert_online_bounds(&left);
+            assert_minmax_merge_equivalent(&left, &right);
+            assert_quartiles_monotonic(&left);
+            assert_unsorted_merge_equivalent(&left, &right);
+        }
+    }
+
+    #[test]
+    fn merge_special_values() {
+        let positive = [1.0, 2.5, 4.0];
+        // a zero, a negative or a NaN in either shard
+        for bad in [0.0, -3.0, f64::NAN] {
+            let shard = [5.0, bad];
+            assert_online_merge_equivalent(&positive, &shard, 1e-12);
+            assert_online_merge_equivalent(&shard, &positive, 1e-12);
+            assert_compensated_merge_equivalent(&positive, &shard, 1e-12);
+            assert_compensated_merge_equivalent(&shard, &positive, 1e-12);
+
+            let mut merged: CompensatedStats = positive.into_iter().collect();
+            merged.merge(shard.into_iter().collect());
+            assert!(merged.geometric_mean().is_nan());
+            assert!(merged.harmonic_mean().is_nan());
+        }
+        assert_compensated_merge_equivalent(&positive, &[], 1e-12);
+        assert_compensated_merge_equivalent::<f64>(&[], &positive, 1e-12);
+    }
+
+    #[test]
+    fn frequency_invariants() {
+        let mut rng = SplitMix64::new(7);
+        let left: Vec<u64> = (0..500).map(|_| rng.next_u64() % 17).collect();
+        let right: Vec<u64> = (0..300).map(|_| rng.next_u64() % 23).collect();
+        assert_frequencies_merge_equivalent(&left, &right);
+        assert_minmax_merge_equivalent(&left, &right);
+    }
+
+    #[test]
+    fn sketch_invariants() {
+        let mut rng = SplitMix64::new(11);
+        for (len, scale) in [(0, 1.0), (1, 1.0), (200, 1e6), (5000, 1e-3)] {
+            let left = random_vec(&mut rng, len, scale);
+            let right = random_vec(&mut rng, len / 2 + 3, scale);
+            assert_ddsketch_relative_error(&left, &right, 0.01);
+            assert_ddsketch_relative_error(&left, &right, 0.1);
+        }
+        assert_ddsketch_relative_error(&[0.0, f64::NAN, -2.0], &[1e-310, 5.0], 0.02);
+
+        let left: Vec<u64> = (0..3000).map(|_| rng.next_u64() % 500).collect();
+        let right: Vec<u64> = (0..2000).map(|_| rng.next_u64() % 800).collect();
+        assert_countmin_bounds(&left, &right, 0.01, 1e-9);
+        assert_countmin_bounds::<u64>(&[], &[], 0.1, 0.1);
+        assert_distinct_filter_no_false_negatives(&left, &right, 800, 0.01);
+        // an undersized filter has more false positives, but no false
+        // negatives
+        assert_distinct_filter_no_false_negatives(&left, &right, 10, 0.01);
+        assert_cardinality_within(&left, &right, 100, 10, 4.0);
+        assert_cardinality_within(&left, &right, 10_000, 10, 4.0);
+
+        let wide: Vec<u64> = (0..50_000).map(|_| rng.next_u64()).collect();
+        assert_cardinality_within(&wide[..20_000], &wide[20_000..], 1000, 14, 4.0);
+    }
+}
@@ -1,3 +1,17 @@
+//! Computing summary statistics on streams.
+//!
+//! # Accuracy of sketches
+//!
+//! The approximate estimators trade exactness for bounded memory, and
+//! report how far off their results may be: `DDSketch::relative_error`,
+//! `Cardinality::relative_error`, `CountMin::error_bound` (along with its
+//! `relative_error` and `failure_probability`) and
+//! `DistinctFilter::error_bound`, its current false positive rate. Their
+//! serialized forms carry the same values under the same names, so a
+//! consumer reading a persisted sketch, e.g. from JSON, knows the accuracy
+//! of its results without recomputing it. These fields are derived from
+//! the sketch and ignored when it is deserialized.
+
 #![allow(unconditional_recursion)]
 #![allow(clippy::default_trait_access)]
 #![allow(clippy::cast_precision_loss)]
@@ -8,20 +22,79 @@
 
 use num_traits::ToPrimitive;
 use std::cmp::Ordering;
+use std::collections::hash_map::{Entry, HashMap};
+use std::collections::{btree_map, BTreeMap};
 use std::hash;
 
 use serde::{Deserialize, Serialize};
 
+pub use bytes::UnsortedBytes;
+pub use cardinality::Cardinality;
+#[cfg(feature = "checkpoint")]
+pub use checkpoint::Checkpoint;
+pub use circular::CircularStats;
+#[cfg(feature = "csv")]
+pub use columns::{summarize_csv, ColumnSummary, ColumnType};
+pub use concurrent::Concurrent;
+pub use config::{Metrics, NanFiltered, NanPolicy, QuantileBackend, StatsConfig};
+pub use correlation::CorrelationMatrix;
+pub use counted::{AdaptiveUnsorted, Counted};
+pub use countmin::CountMin;
+pub use dataset::ColumnSet;
+pub use ddsketch::DDSketch;
+#[cfg(feature = "decimal")]
+pub use decimal::DecimalStats;
+pub use deltas::Deltas;
+pub use describe::{Describe, Description};
+pub use distinct::{Deduped, DistinctFilter};
+pub use ewma::{Ewma, EwmaChart};
+pub use ext::StatsExt;
+#[cfg(feature = "spill")]
+pub use external::ExternalUnsorted;
+pub use fingerprint::Fingerprint;
 pub use frequency::{Frequencies, UniqueValues};
-pub use minmax::MinMax;
-pub use online::{mean, stddev, variance, OnlineStats};
-pub use unsorted::{antimodes, mad, median, mode, modes, quartiles, Unsorted};
+pub use grouped::Grouped;
+pub use hypothesis::{Anova, TestResult};
+pub use logstats::{LogPolicy, LogStats};
+pub use minmax::{minmax_by_key, IndexedMinMax, MinMax, MinMaxBy};
+pub use moments::Moments;
+pub use numformat::{DecimalPrecision, NumberFormat, NumberKind};
+pub use online::{
+    mean, stddev, variance, CompensatedStats, OnlineStats, OnlineSummary, MAX_SAFE_INTEGER,
+};
+#[cfg(feature = "ostree")]
+pub use ostree::OrderStatTree;
+pub use parallel::{set_thread_pool, Parallelism};
+#[cfg(feature = "parquet")]
+pub use parquet_stats::ToParquetStatistics;
+pub use proportion::Proportion;
+pub use quality::{Capability, ControlLimits};
+pub use resampling::{Jackknife, PermutationStatistic};
+pub use round::{RoundMode, RoundPolicy, Rounded, RoundedDisplay};
+pub use runs::Runs;
+pub use sampling::{sample_stratified, sample_systematic, Estimate, SampledStats, SamplingMethod};
+pub use snapshot::{
+    MinMaxSnapshotV1, OnlineStatsSnapshotV1, OnlineStatsSnapshotV2, UnsortedSnapshotV1,
+};
+pub use standardize::Standardizer;
+pub use sum::ExactSum;
+pub use text::{Normalize, StringStats, TextNormalization};
+pub use timeseries::{autocorrelation, Monotonicity, Sortedness};
+pub use topn::TopN;
+pub use unsorted::{
+    antimodes, mad, median, mode, modes, quartiles, DuplicateStats, FrequencyStrategy, Gaps,
+    MappedStats, QuantileMethod, Quantiles, RankMethod, RobustSummary, Tolerance, Unsorted,
+};
+pub use weighted::WeightedUnsorted;
 
 /// Partial wraps a type that satisfies `PartialOrd` and implements `Ord`.
 ///
 /// This allows types like `f64` to be used in data structures that require
 /// `Ord`. When an ordering is not defined, an arbitrary order is returned.
+///
+/// This is `repr(transparent)`, so a `[Partial<T>]` can be viewed as a `[T]`.
 #[derive(Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[repr(transparent)]
 struct Partial<T>(pub T);
 
 impl<T: PartialEq> Eq for Partial<T> {}
@@ -176,15 +249,131 @@ impl<T: Commute> Commute for Vec<T> {
     }
 }
 
+/// Values under the same key are merged; keys present only in `other` are
+/// moved over.
+impl<K, T, S> Commute for HashMap<K, T, S>
+where
+    K: Eq + hash::Hash,
+    T: Commute,
+    S: hash::BuildHasher,
+{
+    #[inline]
+    fn merge(&mut self, other: HashMap<K, T, S>) {
+        for (k, v2) in other {
+            match self.entry(k) {
+                Entry::Occupied(mut e) => e.get_mut().merge(v2),
+                Entry::Vacant(e) => {
+                    e.insert(v2);
+                }
+            }
+        }
+    }
+}
+
+/// Values under the same key are merged; keys present only in `other` are
+/// moved over.
+///
+/// `BTreeMap` has an unstable inherent `merge`, so call this as
+/// `Commute::merge(&mut map, other)` to avoid the name collision.
+impl<K: Ord, T: Commute> Commute for BTreeMap<K, T> {
+    #[inline]
+    fn merge(&mut self, other: BTreeMap<K, T>) {
+        for (k, v2) in other {
+            match self.entry(k) {
+                btree_map::Entry::Occupied(mut e) => e.get_mut().merge(v2),
+                btree_map::Entry::Vacant(e) => {
+                    e.insert(v2);
+                }
+            }
+        }
+    }
+}
+
+macro_rules! commute_tuple {
+    ($($name:ident $idx:tt),+) => {
+        impl<$($name: Commute),+> Commute for ($($name,)+) {
+            #[inline]
+            fn merge(&mut self, other: ($($name,)+)) {
+                $(self.$idx.merge(other.$idx);)+
+            }
+        }
+    };
+}
+
+commute_tuple!(A 0);
+commute_tuple!(A 0, B 1);
+commute_tuple!(A 0, B 1, C 2);
+commute_tuple!(A 0, B 1, C 2, D 3);
+commute_tuple!(A 0, B 1, C 2, D 3, E 4);
+commute_tuple!(A 0, B 1, C 2, D 3, E 4, F 5);
+commute_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+commute_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+
+mod bytes;
+mod cardinality;
+#[cfg(feature = "checkpoint")]
+mod checkpoint;
+mod circular;
+#[cfg(feature = "csv")]
+mod columns;
+mod concurrent;
+mod config;
+mod correlation;
+mod counted;
+mod countmin;
+mod dataset;
+mod ddsketch;
+#[cfg(feature = "decimal")]
+mod decimal;
+mod deltas;
+mod describe;
+mod distinct;
+mod ewma;
+mod ext;
+#[cfg(feature = "spill")]
+mod external;
+mod fingerprint;
+pub mod fit;
 mod frequency;
+mod grouped;
+mod hashing;
+mod hypothesis;
+pub mod invariants;
+mod logstats;
 mod minmax;
+mod moments;
+mod numformat;
 mod online;
+#[cfg(feature = "ostree")]
+mod ostree;
+mod parallel;
+#[cfg(feature = "parquet")]
+mod parquet_stats;
+mod proportion;
+mod quality;
+mod resampling;
+mod rng;
+mod round;
+mod runs;
+mod sampling;
+mod simd;
+mod snapshot;
+mod special;
+pub mod sql;
+mod standardize;
+mod sum;
+mod text;
+mod timeseries;
+mod topn;
 mod unsorted;
+mod weighted;
 
 #[cfg(test)]
 mod test {
+    use std::collections::{BTreeMap, HashMap};
+
     use crate::unsorted::Unsorted;
-    use crate::Commute;
+    use crate::{Commute, MinMax, OnlineStats};
 
     #[test]
     fn options() {
@@ -194,4 +383,26 @@ mod test {
         merged.merge(Some(v2));
         assert_eq!(merged.unwrap().mode(), Some(5));
     }
+
+    #[test]
+    fn maps_and_tuples() {
+        let stats = |data: &[u32]| -> (OnlineStats, MinMax<u32>) {
+            (
+                data.iter().copied().collect(),
+                data.iter().copied().collect(),
+            )
+        };
+        let mut m1 = HashMap::from([("a", stats(&[1, 2])), ("b", stats(&[7]))]);
+        let m2 = HashMap::from([("a", stats(&[6])), ("c", stats(&[0]))]);
+        m1.merge(m2);
+        assert_eq!(m1.len(), 3);
+        let (online, minmax) = &m1["a"];
+        assert_eq!(online.mean(), 3.0);
+        assert_eq!(minmax.max(), Some(&6));
+
+        let mut b1 = BTreeMap::from([(1, Some(stats(&[4])))]);
+        Commute::merge(&mut b1, BTreeMap::from([(1, None), (2, Some(stats(&[9])))]));
+        assert_eq!(b1[&1].as_ref().unwrap().0.len(), 1);
+        assert_eq!(b1.len(), 2);
+    }
 }
@@ -0,0 +1,254 @@
+use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+
+use crate::fit::{self, Fit};
+use crate::special::normal_critical_value;
+use crate::{Commute, Estimate, OnlineStats};
+
+/// How `LogStats` handles values whose logarithm is undefined.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum LogPolicy {
+    /// Skip zero and negative values, and count them.
+    #[default]
+    Skip,
+    /// Add this offset to every value before taking the logarithm, e.g.
+    /// `Shift(1.0)` for `ln(1 + x)` over counts that may be zero. Values
+    /// that are still not positive are skipped. Back-transformed results
+    /// subtract the offset again.
+    Shift(f64),
+}
+
+/// Online state for statistics of the logarithms of positive, skewed
+/// data, such as durations, sizes or prices.
+///
+/// Such data is often close to lognormal, so its logarithms are close to
+/// normal. This tracks the mean and standard deviation of `ln(x)`, which
+/// are the `mu` and `sigma` of a lognormal fit, and transforms results
+/// back to the original scale, where the mean of the logarithms becomes
+/// the geometric mean (the median of a lognormal distribution).
+///
+/// Merging requires both accumulators to have the same policy.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct LogStats {
+    policy: LogPolicy,
+    logs: OnlineStats,
+    skipped: u64,
+}
+
+impl LogStats {
+    /// Create initial empty state, skipping zero and negative values.
+    #[must_use]
+    pub fn new() -> LogStats {
+        Default::default()
+    }
+
+    /// Create initial empty state with the given policy for values whose
+    /// logarithm is undefined.
+    #[must_use]
+    pub fn with_policy(policy: LogPolicy) -> LogStats {
+        LogStats {
+            policy,
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    const fn offset(&self) -> f64 {
+        match self.policy {
+            LogPolicy::Skip => 0.0,
+            LogPolicy::Shift(offset) => offset,
+        }
+    }
+
+    /// Add a new sample.
+    #[inline]
+    pub fn add<T: ToPrimitive>(&mut self, sample: &T) {
+        let x = sample.to_f64().unwrap() + self.offset();
+        if x > 0.0 {
+            self.logs.add(&x.ln());
+        } else {
+            self.skipped += 1;
+        }
+    }
+
+    /// Returns the mean of the logarithms, i.e. the `mu` of a lognormal
+    /// fit.
+    #[inline]
+    #[must_use]
+    pub const fn mu(&self) -> f64 {
+        self.logs.mean()
+    }
+
+    /// Returns the population standard deviation of the logarithms, i.e.
+    /// the maximum likelihood `sigma` of a lognormal fit, as used by `fit`.
+    ///
+    /// `NaN` is returned if there are no samples.
+    #[inline]
+    #[must_use]
+    pub fn sigma(&self) -> f64 {
+        self.logs.stddev()
+    }
+
+    /// Returns the geometric mean, which estimates the median of lognormal
+    /// data, or `None` if there are no samples.
+    #[must_use]
+    pub fn geometric_mean(&self) -> Option<f64> {
+        if self.logs.is_empty() {
+            None
+        } else {
+            Some(self.mu().exp() - self.offset())
+        }
+    }
+
+    /// Returns the geometric mean with a confidence interval at the given
+    /// `confidence` level, e.g. `0.95`.
+    ///
+    /// The interval is computed for `mu` with the normal approximation,
+    /// using the sample standard deviation of the logarithms, and
+    /// transformed back, so it is asymmetric around the geometric mean.
+    ///
+    /// `None` is returned if there are fewer than two samples.
+    #[must_use]
+    pub fn geometric_mean_ci(&self, confidence: f64) -> Option<Estimate> {
+        let n = self.logs.len();
+        if n < 2 {
+            return None;
+        }
+        let half =
+            normal_critical_value(confidence) * self.logs.sample_stddev() / (n as f64).sqrt();
+        let offset = self.offset();
+        Some(Estimate {
+            value: self.mu().exp() - offset,
+            lower: (self.mu() - half).exp() - offset,
+            upper: (self.mu() + half).exp() - offset,
+        })
+    }
+
+    /// Returns the geometric standard deviation, `exp(sigma)`, a factor by
+    /// which values typically differ from the geometric mean.
+    #[inline]
+    #[must_use]
+    pub fn geometric_stddev(&self) -> f64 {
+        self.sigma().exp()
+    }
+
+    /// Fits a lognormal distribution to the (shifted) values.
+    ///
+    /// See `fit::lognormal`.
+    #[must_use]
+    pub fn fit(&self) -> Option<Fit> {
+        fit::lognormal(&self.logs)
+    }
+
+    /// Returns the mean and variance state of the logarithms.
+    #[inline]
+    #[must_use]
+    pub const fn logs(&self) -> &OnlineStats {
+        &self.logs
+    }
+
+    /// Returns the policy for values whose logarithm is undefined.
+    #[inline]
+    #[must_use]
+    pub const fn policy(&self) -> LogPolicy {
+        self.policy
+    }
+
+    /// Returns the number of values that were skipped because their
+    /// logarithm is undefined.
+    #[inline]
+    #[must_use]
+    pub const fn skipped_count(&self) -> u64 {
+        self.skipped
+    }
+
+    /// Returns the number of values whose logarithm was taken.
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.logs.len()
+    }
+
+    /// Returns true if no logarithm was taken.
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.logs.is_empty()
+    }
+}
+
+impl Commute for LogStats {
+    /// # Panics
+    ///
+    /// If the accumulators have different policies.
+    #[inline]
+    fn merge(&mut self, v: LogStats) {
+        assert!(
+            self.policy == v.policy,
+            "cannot merge log statistics with different policies"
+        );
+        self.logs.merge(v.logs);
+        self.skipped += v.skipped;
+    }
+}
+
+impl<T: ToPrimitive> FromIterator<T> for LogStats {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = T>>(it: I) -> LogStats {
+        let mut v = LogStats::new();
+        v.extend(it);
+        v
+    }
+}
+
+impl<T: ToPrimitive> Extend<T> for LogStats {
+    #[inline]
+    fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
+        for sample in it {
+            self.add(&sample);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{LogPolicy, LogStats};
+    use crate::fit::Distribution;
+    use crate::Commute;
+
+    #[test]
+    fn geometric() {
+        let stats: LogStats = [1.0, 10.0, 100.0, 0.0, -5.0].into_iter().collect();
+        assert_eq!(stats.len(), 3);
+        assert_eq!(stats.skipped_count(), 2);
+        assert!((stats.geometric_mean().unwrap() - 10.0).abs() < 1e-12);
+        let sigma = 10f64.ln() * (2.0f64 / 3.0).sqrt();
+        assert!((stats.sigma() - sigma).abs() < 1e-12);
+        assert!((stats.geometric_stddev() - sigma.exp()).abs() < 1e-9);
+
+        let ci = stats.geometric_mean_ci(0.95).unwrap();
+        assert!(ci.lower < 10.0 && ci.upper > 10.0);
+        // symmetric on the log scale
+        assert!((ci.lower * ci.upper - 100.0).abs() < 1e-9);
+        let fit = stats.fit().unwrap();
+        assert!(matches!(
+            fit.distribution,
+            Distribution::LogNormal { sigma: s, .. } if (s - sigma).abs() < 1e-12
+        ));
+        assert_eq!(LogStats::new().geometric_mean(), None);
+    }
+
+    #[test]
+    fn shifted() {
+        let mut stats = LogStats::with_policy(LogPolicy::Shift(1.0));
+        stats.extend([0, 3, 15]);
+        assert_eq!(stats.skipped_count(), 0);
+        // the geometric mean of 1, 4 and 16 is 4
+        assert!((stats.geometric_mean().unwrap() - 3.0).abs() < 1e-12);
+
+        let mut other = LogStats::with_policy(LogPolicy::Shift(1.0));
+        other.add(&-1);
+        stats.merge(other);
+        assert_eq!((stats.len(), stats.skipped_count()), (3, 1));
+    }
+}
@@ -1,8 +1,11 @@
+use std::cmp::Ordering;
 use std::fmt;
 
+use num_traits::{Float, ToPrimitive};
 use serde::{Deserialize, Serialize};
 
-use crate::Commute;
+use crate::round::{RoundPolicy, RoundedDisplay};
+use crate::{Commute, MinMaxSnapshotV1, Normalize, TextNormalization};
 
 /// A commutative data structure for tracking minimum and maximum values.
 ///
@@ -10,6 +13,8 @@ use crate::Commute;
 #[derive(Clone, Deserialize, Serialize, Eq, PartialEq)]
 pub struct MinMax<T> {
     len: u64,
+    #[serde(default)]
+    nulls: u64,
     min: Option<T>,
     max: Option<T>,
 }
@@ -33,6 +38,16 @@ impl<T: PartialOrd + Clone> MinMax<T> {
         }
     }
 
+    /// Add a sample to the data after normalizing it, e.g. trimming
+    /// whitespace and ignoring case.
+    #[inline]
+    pub fn add_normalized(&mut self, sample: T, how: TextNormalization)
+    where
+        T: Normalize,
+    {
+        self.add(sample.normalize(how));
+    }
+
     /// Returns the minimum of the data set.
     ///
     /// `None` is returned if and only if the number of samples is `0`.
@@ -64,12 +79,81 @@ impl<T: PartialOrd + Clone> MinMax<T> {
     pub const fn is_empty(&self) -> bool {
         self.len == 0
     }
+
+    /// Returns the number of bytes used by this state.
+    ///
+    /// This does not follow heap allocations owned by the min and max
+    /// values themselves (e.g. the contents of a `String`).
+    #[inline]
+    #[must_use]
+    pub const fn mem_usage(&self) -> usize {
+        std::mem::size_of::<Self>()
+    }
+
+    /// Returns the number of `None` values ingested via `Extend<Option<T>>`.
+    ///
+    /// NULLs are not counted as data points.
+    #[inline]
+    #[must_use]
+    pub const fn null_count(&self) -> u64 {
+        self.nulls
+    }
+}
+
+impl<T: PartialOrd + Clone + ToPrimitive> MinMax<T> {
+    /// Returns the range of the data, `max - min`.
+    ///
+    /// `None` is returned if there are no data points.
+    #[inline]
+    #[must_use]
+    pub fn range(&self) -> Option<f64> {
+        let (min, max) = self.bounds_f64()?;
+        Some(max - min)
+    }
+
+    /// Returns the midrange of the data, `(min + max) / 2`.
+    ///
+    /// `None` is returned if there are no data points.
+    #[inline]
+    #[must_use]
+    pub fn midrange(&self) -> Option<f64> {
+        let (min, max) = self.bounds_f64()?;
+        // halve first, so the sum of two large values cannot overflow
+        Some(min / 2.0 + max / 2.0)
+    }
+
+    #[inline]
+    fn bounds_f64(&self) -> Option<(f64, f64)> {
+        Some((self.min()?.to_f64()?, self.max()?.to_f64()?))
+    }
+}
+
+impl MinMax<f64> {
+    /// Initializes state from a slice of `f64`s, using vectorized kernels.
+    ///
+    /// With the `simd` feature, AVX2 is used on CPUs that support it.
+    /// Unlike `add`, `NaN`s are never taken as the minimum or maximum,
+    /// unless every value is `NaN`.
+    #[must_use]
+    pub fn from_f64_slice(samples: &[f64]) -> MinMax<f64> {
+        let (min, max) = match crate::simd::min_max(samples) {
+            Some((min, max)) => (Some(min), Some(max)),
+            None => (samples.first().copied(), samples.first().copied()),
+        };
+        MinMax {
+            len: samples.len() as u64,
+            nulls: 0,
+            min,
+            max,
+        }
+    }
 }
 
 impl<T: PartialOrd> Commute for MinMax<T> {
     #[inline]
     fn merge(&mut self, v: MinMax<T>) {
         self.len += v.len;
+        self.nulls += v.nulls;
         if self.min.is_none() || (v.min.is_some() && v.min < self.min) {
             self.min = v.min;
         }
@@ -79,18 +163,40 @@ impl<T: PartialOrd> Commute for MinMax<T> {
     }
 }
 
+impl<T> From<MinMax<T>> for MinMaxSnapshotV1<T> {
+    fn from(v: MinMax<T>) -> MinMaxSnapshotV1<T> {
+        MinMaxSnapshotV1 {
+            len: v.len,
+            nulls: v.nulls,
+            min: v.min,
+            max: v.max,
+        }
+    }
+}
+
+impl<T> From<MinMaxSnapshotV1<T>> for MinMax<T> {
+    fn from(v: MinMaxSnapshotV1<T>) -> MinMax<T> {
+        MinMax {
+            len: v.len,
+            nulls: v.nulls,
+            min: v.min,
+            max: v.max,
+        }
+    }
+}
+
 impl<T: PartialOrd> Default for MinMax<T> {
     #[inline]
     fn default() -> MinMax<T> {
         MinMax {
             len: 0,
+            nulls: 0,
             min: None,
             max: None,
         }
     }
 }
 
-#[cfg(debug_assertions)]
 impl<T: fmt::Debug> fmt::Debug for MinMax<T> {
     #[inline]
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -104,6 +210,30 @@ impl<T: fmt::Debug> fmt::Debug for MinMax<T> {
     }
 }
 
+impl<T: fmt::Display> fmt::Display for MinMax<T> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.min, &self.max) {
+            (Some(min), Some(max)) => write!(f, "[{min}, {max}]"),
+            _ => write!(f, "N/A"),
+        }
+    }
+}
+
+impl<T: Float> RoundedDisplay for MinMax<T> {
+    fn fmt_rounded(&self, policy: &RoundPolicy, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.min, &self.max) {
+            (Some(min), Some(max)) => write!(
+                f,
+                "[{}, {}]",
+                policy.format(min.to_f64().unwrap()),
+                policy.format(max.to_f64().unwrap())
+            ),
+            _ => write!(f, "N/A"),
+        }
+    }
+}
+
 impl<T: PartialOrd + Clone> FromIterator<T> for MinMax<T> {
     #[inline]
     fn from_iter<I: IntoIterator<Item = T>>(it: I) -> MinMax<T> {
@@ -122,10 +252,333 @@ impl<T: PartialOrd + Clone> Extend<T> for MinMax<T> {
     }
 }
 
+impl<T: PartialOrd + Clone> FromIterator<Option<T>> for MinMax<T> {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = Option<T>>>(it: I) -> MinMax<T> {
+        let mut v = MinMax::new();
+        v.extend(it);
+        v
+    }
+}
+
+impl<T: PartialOrd + Clone> Extend<Option<T>> for MinMax<T> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = Option<T>>>(&mut self, it: I) {
+        for sample in it {
+            match sample {
+                Some(v) => self.add(v),
+                None => self.nulls += 1,
+            }
+        }
+    }
+}
+
+/// A commutative data structure for tracking the minimum and maximum values
+/// together with the positions where they occurred.
+///
+/// This answers "which record has the maximum?" without a second scan of
+/// the data. `add` numbers samples by their position in this accumulator,
+/// starting at `0`. When data is split into chunks that are later merged,
+/// use `add_at` with global record numbers instead, since positions are
+/// kept as-is by `merge`.
+///
+/// If the minimum or maximum occurs more than once, the smallest position
+/// is kept.
+#[derive(Clone, Deserialize, Serialize, Eq, PartialEq)]
+pub struct IndexedMinMax<T> {
+    len: u64,
+    min: Option<(T, u64)>,
+    max: Option<(T, u64)>,
+}
+
+impl<T: PartialOrd + Clone> IndexedMinMax<T> {
+    /// Create an empty state where min and max values do not exist.
+    #[must_use]
+    pub fn new() -> IndexedMinMax<T> {
+        Default::default()
+    }
+
+    /// Add a sample at the next position, i.e. the number of samples
+    /// added so far.
+    #[inline]
+    pub fn add(&mut self, sample: T) {
+        self.add_at(sample, self.len);
+    }
+
+    /// Add a sample found at `index` (e.g. a record number).
+    #[inline]
+    pub fn add_at(&mut self, sample: T, index: u64) {
+        self.len += 1;
+        if is_better(&self.min, &sample, index, |a, b| a < b) {
+            self.min = Some((sample.clone(), index));
+        }
+        if is_better(&self.max, &sample, index, |a, b| a > b) {
+            self.max = Some((sample, index));
+        }
+    }
+
+    /// Returns the minimum of the data set.
+    #[inline]
+    #[must_use]
+    pub fn min(&self) -> Option<&T> {
+        self.min.as_ref().map(|(v, _)| v)
+    }
+
+    /// Returns the maximum of the data set.
+    #[inline]
+    #[must_use]
+    pub fn max(&self) -> Option<&T> {
+        self.max.as_ref().map(|(v, _)| v)
+    }
+
+    /// Returns the position of the minimum.
+    #[inline]
+    #[must_use]
+    pub fn argmin(&self) -> Option<u64> {
+        self.min.as_ref().map(|&(_, i)| i)
+    }
+
+    /// Returns the position of the maximum.
+    #[inline]
+    #[must_use]
+    pub fn argmax(&self) -> Option<u64> {
+        self.max.as_ref().map(|&(_, i)| i)
+    }
+
+    /// Returns the number of data points.
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    /// Returns true if there are no data points.
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Returns true if `sample` at `index` should replace `current`, i.e. it
+/// is strictly `better`, or equal but found earlier.
+#[inline]
+fn is_better<T: PartialOrd, F: Fn(&T, &T) -> bool>(
+    current: &Option<(T, u64)>,
+    sample: &T,
+    index: u64,
+    better: F,
+) -> bool {
+    match current {
+        None => true,
+        Some((v, i)) => better(sample, v) || (sample == v && index < *i),
+    }
+}
+
+impl<T: PartialOrd> Commute for IndexedMinMax<T> {
+    #[inline]
+    fn merge(&mut self, v: IndexedMinMax<T>) {
+        self.len += v.len;
+        if let Some((sample, index)) = v.min {
+            if is_better(&self.min, &sample, index, |a, b| a < b) {
+                self.min = Some((sample, index));
+            }
+        }
+        if let Some((sample, index)) = v.max {
+            if is_better(&self.max, &sample, index, |a, b| a > b) {
+                self.max = Some((sample, index));
+            }
+        }
+    }
+}
+
+impl<T: PartialOrd> Default for IndexedMinMax<T> {
+    #[inline]
+    fn default() -> IndexedMinMax<T> {
+        IndexedMinMax {
+            len: 0,
+            min: None,
+            max: None,
+        }
+    }
+}
+
+impl<T: PartialOrd + Clone> FromIterator<T> for IndexedMinMax<T> {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = T>>(it: I) -> IndexedMinMax<T> {
+        let mut v = IndexedMinMax::new();
+        v.extend(it);
+        v
+    }
+}
+
+impl<T: PartialOrd + Clone> Extend<T> for IndexedMinMax<T> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
+        for sample in it {
+            self.add(sample);
+        }
+    }
+}
+
+/// A commutative data structure for tracking minimum and maximum values,
+/// ordered by a caller-supplied comparator.
+///
+/// This is `MinMax` for orderings other than the natural one, e.g. the
+/// shortest and longest string, or dates compared after parsing. Ties keep
+/// the first value seen.
+///
+/// Only accumulators with the same comparator type can be merged, and every
+/// closure has its own type. To profile shards in parallel, create one
+/// accumulator and start each shard with `empty_like`:
+///
+/// ```
+/// use stats::{minmax_by_key, Commute};
+///
+/// let mut shortest_longest = minmax_by_key(|s: &&str| s.len());
+/// let mut shard = shortest_longest.empty_like();
+/// shortest_longest.extend(["pear", "fig"]);
+/// shard.extend(["banana", "kiwi"]);
+/// shortest_longest.merge(shard);
+/// assert_eq!(shortest_longest.min(), Some(&"fig"));
+/// assert_eq!(shortest_longest.max(), Some(&"banana"));
+/// ```
+#[derive(Clone)]
+pub struct MinMaxBy<T, F> {
+    len: u64,
+    min: Option<T>,
+    max: Option<T>,
+    cmp: F,
+}
+
+impl<T: Clone, F: Fn(&T, &T) -> Ordering> MinMaxBy<T, F> {
+    /// Create an empty state that orders samples with `cmp`.
+    #[must_use]
+    pub fn new(cmp: F) -> MinMaxBy<T, F> {
+        MinMaxBy {
+            len: 0,
+            min: None,
+            max: None,
+            cmp,
+        }
+    }
+
+    /// Add a sample to the data.
+    #[inline]
+    pub fn add(&mut self, sample: T) {
+        self.len += 1;
+        if self
+            .min
+            .as_ref()
+            .map_or(true, |v| (self.cmp)(&sample, v) == Ordering::Less)
+        {
+            self.min = Some(sample.clone());
+        }
+        if self
+            .max
+            .as_ref()
+            .map_or(true, |v| (self.cmp)(&sample, v) == Ordering::Greater)
+        {
+            self.max = Some(sample);
+        }
+    }
+
+    /// Returns the minimum of the data set.
+    #[inline]
+    #[must_use]
+    pub const fn min(&self) -> Option<&T> {
+        self.min.as_ref()
+    }
+
+    /// Returns the maximum of the data set.
+    #[inline]
+    #[must_use]
+    pub const fn max(&self) -> Option<&T> {
+        self.max.as_ref()
+    }
+
+    /// Returns the number of data points.
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    /// Returns true if there are no data points.
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<T: Clone, F: Fn(&T, &T) -> Ordering + Clone> MinMaxBy<T, F> {
+    /// Create an empty state with the same comparator, e.g. for another
+    /// shard of the data that will be merged into this one.
+    #[must_use]
+    pub fn empty_like(&self) -> MinMaxBy<T, F> {
+        MinMaxBy::new(self.cmp.clone())
+    }
+}
+
+/// Create an empty `MinMaxBy` that orders samples by the key `f` extracts,
+/// e.g. `minmax_by_key(|s: &String| s.len())`.
+#[must_use]
+pub fn minmax_by_key<T, K, G>(f: G) -> MinMaxBy<T, impl Fn(&T, &T) -> Ordering + Clone>
+where
+    T: Clone,
+    K: Ord,
+    G: Fn(&T) -> K + Clone,
+{
+    MinMaxBy::new(move |a: &T, b: &T| f(a).cmp(&f(b)))
+}
+
+impl<T, F: Fn(&T, &T) -> Ordering> Commute for MinMaxBy<T, F> {
+    #[inline]
+    fn merge(&mut self, v: MinMaxBy<T, F>) {
+        self.len += v.len;
+        if let Some(min) = v.min {
+            if self
+                .min
+                .as_ref()
+                .map_or(true, |m| (self.cmp)(&min, m) == Ordering::Less)
+            {
+                self.min = Some(min);
+            }
+        }
+        if let Some(max) = v.max {
+            if self
+                .max
+                .as_ref()
+                .map_or(true, |m| (self.cmp)(&max, m) == Ordering::Greater)
+            {
+                self.max = Some(max);
+            }
+        }
+    }
+}
+
+impl<T: Clone, F: Fn(&T, &T) -> Ordering> Extend<T> for MinMaxBy<T, F> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
+        for sample in it {
+            self.add(sample);
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use super::MinMax;
-    use crate::Commute;
+    use super::{minmax_by_key, IndexedMinMax, MinMax, MinMaxBy};
+    use crate::{Commute, MinMaxSnapshotV1};
+
+    #[test]
+    fn display() {
+        let minmax: MinMax<f64> = [2.5, -1.0, 4.0].into_iter().collect();
+        assert_eq!(format!("{minmax}"), "[-1, 4]");
+        assert_eq!(format!("{minmax:?}"), "[-1.0, 4.0]");
+        assert_eq!(MinMax::<u8>::new().to_string(), "N/A");
+    }
 
     #[test]
     fn minmax() {
@@ -150,4 +603,93 @@ mod test {
         assert_eq!(mx1.min(), Some(&1u32));
         assert_eq!(mx1.max(), Some(&10u32));
     }
+
+    #[test]
+    fn minmax_options() {
+        let mut mx1: MinMax<u32> = vec![Some(4u32), None, Some(2)].into_iter().collect();
+        let mx2: MinMax<u32> = vec![None, Some(10u32)].into_iter().collect();
+        mx1.merge(mx2);
+        assert_eq!(mx1.min(), Some(&2u32));
+        assert_eq!(mx1.max(), Some(&10u32));
+        assert_eq!(mx1.len(), 3);
+        assert_eq!(mx1.null_count(), 2);
+
+        let old: MinMax<u32> = serde_json::from_str(r#"{"len":1,"min":3,"max":3}"#).unwrap();
+        assert_eq!((old.len(), old.null_count(), old.max()), (1, 0, Some(&3)));
+    }
+
+    #[test]
+    fn minmax_f64_slice() {
+        let data: Vec<f64> = (0..37).map(|i| f64::from(i * 7 % 37) - 3.5).collect();
+        let expected: MinMax<f64> = data.iter().copied().collect();
+        let got = MinMax::from_f64_slice(&data);
+        assert_eq!(got.len(), 37);
+        assert_eq!(got.min(), expected.min());
+        assert_eq!(got.max(), expected.max());
+        assert!(MinMax::from_f64_slice(&[]).is_empty());
+    }
+
+    #[test]
+    fn indexed_minmax() {
+        let mx: IndexedMinMax<u32> = vec![4, 1, 9, 1, 9, 3].into_iter().collect();
+        assert_eq!(mx.min(), Some(&1));
+        assert_eq!(mx.argmin(), Some(1));
+        assert_eq!(mx.max(), Some(&9));
+        assert_eq!(mx.argmax(), Some(2));
+        assert!(IndexedMinMax::<u32>::new().argmin().is_none());
+    }
+
+    #[test]
+    fn indexed_minmax_merge_chunks() {
+        let (mut mx1, mut mx2) = (IndexedMinMax::new(), IndexedMinMax::new());
+        for (i, v) in [5, 2, 8].into_iter().enumerate() {
+            mx2.add_at(v, i as u64 + 3);
+        }
+        for (i, v) in [7, 2, 6].into_iter().enumerate() {
+            mx1.add_at(v, i as u64);
+        }
+        // merge in the "wrong" order: the earlier tied minimum still wins
+        mx2.merge(mx1);
+        assert_eq!(mx2.len(), 6);
+        assert_eq!((mx2.min(), mx2.argmin()), (Some(&2), Some(1)));
+        assert_eq!((mx2.max(), mx2.argmax()), (Some(&8), Some(5)));
+    }
+
+    #[test]
+    fn minmax_snapshot_v1() {
+        let mx: MinMax<&str> = vec!["b", "a", "c"].into_iter().collect();
+        let snap = MinMaxSnapshotV1::from(mx.clone());
+        assert_eq!((snap.len, snap.min, snap.max), (3, Some("a"), Some("c")));
+        assert_eq!(MinMax::from(snap), mx);
+    }
+
+    #[test]
+    fn minmax_range() {
+        let mx: MinMax<i32> = vec![-3, 7, 2].into_iter().collect();
+        assert_eq!(mx.range(), Some(10.0));
+        assert_eq!(mx.midrange(), Some(2.0));
+        let big: MinMax<f64> = vec![f64::MAX, f64::MAX].into_iter().collect();
+        assert_eq!(big.midrange(), Some(f64::MAX));
+        assert_eq!(MinMax::<u8>::new().range(), None);
+    }
+
+    #[test]
+    fn minmax_by() {
+        let words = ["pear", "fig", "banana", "kiwi", "plum", "cherry"].map(String::from);
+        let mut by_len = minmax_by_key(|s: &String| s.len());
+        let mut rest = by_len.empty_like();
+        by_len.extend(words[..3].iter().cloned());
+        rest.extend(words[3..].iter().cloned());
+        assert_eq!(rest.max().map(String::as_str), Some("cherry"));
+        by_len.merge(rest);
+        assert_eq!(by_len.len(), 6);
+        assert_eq!(by_len.min().map(String::as_str), Some("fig"));
+        // ties keep the first value seen
+        assert_eq!(by_len.max().map(String::as_str), Some("banana"));
+
+        let mut reversed = MinMaxBy::new(|a: &i32, b: &i32| b.cmp(a));
+        reversed.extend([3, 1, 2]);
+        assert_eq!((reversed.min(), reversed.max()), (Some(&3), Some(&1)));
+        assert_eq!(reversed.len(), 3);
+    }
 }
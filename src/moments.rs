@@ -0,0 +1,317 @@
+use std::fmt;
+
+use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+
+use crate::round::{RoundPolicy, RoundedDisplay};
+use crate::special::normal_quantile;
+use crate::{Commute, TestResult};
+
+/// Online state for the first four central moments: mean, variance,
+/// skewness and kurtosis.
+///
+/// The moments are updated with the one-pass formulas of Terriberry and
+/// Pébay, and merged with their pairwise generalization, so the results
+/// match a two-pass computation up to floating-point rounding. This costs
+/// a few more operations per sample than `OnlineStats`, which only tracks
+/// the mean and variance unless created with
+/// `OnlineStats::with_higher_moments`.
+#[derive(Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct Moments {
+    len: u64,
+    mean: f64,
+    m2: f64,
+    m3: f64,
+    m4: f64,
+}
+
+impl Moments {
+    /// Create initial empty state.
+    #[must_use]
+    pub fn new() -> Moments {
+        Default::default()
+    }
+
+    /// Restores state from the sample size, the mean and the sums of the
+    /// squared, cubed and fourth-power deviations from the mean.
+    pub(crate) const fn from_sums(len: u64, mean: f64, m2: f64, m3: f64, m4: f64) -> Moments {
+        Moments {
+            len,
+            mean,
+            m2,
+            m3,
+            m4,
+        }
+    }
+
+    /// Returns the sums of the cubed and fourth-power deviations from the
+    /// mean.
+    pub(crate) const fn sums(&self) -> (f64, f64) {
+        (self.m3, self.m4)
+    }
+
+    /// Add a new sample.
+    #[inline]
+    pub fn add<T: ToPrimitive>(&mut self, sample: &T) {
+        let x = sample.to_f64().unwrap();
+        let n1 = self.len as f64;
+        self.len += 1;
+        let n = self.len as f64;
+        let delta = x - self.mean;
+        let delta_n = delta / n;
+        let delta_n2 = delta_n * delta_n;
+        let term1 = delta * delta_n * n1;
+        self.mean += delta_n;
+        self.m4 += term1 * delta_n2 * (n * n - 3.0 * n + 3.0) + 6.0 * delta_n2 * self.m2
+            - 4.0 * delta_n * self.m3;
+        self.m3 += term1 * delta_n * (n - 2.0) - 3.0 * delta_n * self.m2;
+        self.m2 += term1;
+    }
+
+    /// Returns the mean.
+    #[inline]
+    #[must_use]
+    pub const fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// Returns the population variance.
+    #[inline]
+    #[must_use]
+    pub fn variance(&self) -> f64 {
+        self.m2 / self.len as f64
+    }
+
+    /// Returns the population skewness, `g1`.
+    ///
+    /// `NaN` is returned if there are no samples or they are all equal.
+    #[must_use]
+    pub fn skewness(&self) -> f64 {
+        if self.m2 == 0.0 {
+            return f64::NAN;
+        }
+        (self.len as f64).sqrt() * self.m3 / self.m2.powf(1.5)
+    }
+
+    /// Returns the population excess kurtosis, `g2`, which is `0` for a
+    /// normal distribution.
+    ///
+    /// `NaN` is returned if there are no samples or they are all equal.
+    #[must_use]
+    pub fn kurtosis(&self) -> f64 {
+        if self.m2 == 0.0 {
+            return f64::NAN;
+        }
+        self.len as f64 * self.m4 / (self.m2 * self.m2) - 3.0
+    }
+
+    /// Returns an approximation of the `q`th quantile, for `q` in `(0, 1)`,
+    /// from the first four moments alone.
+    ///
+    /// This uses the Cornish-Fisher expansion, which adjusts the quantile
+    /// of a normal distribution for the skewness and kurtosis of the data.
+    /// It costs nothing beyond the moments, but is only a rough estimate:
+    /// it is accurate for moderately skewed, unimodal data and can be far
+    /// off for heavy tails, multimodal data or extreme quantiles. Keep a
+    /// quantile sketch (e.g. `DDSketch`) when accuracy matters.
+    ///
+    /// `None` is returned if there are no samples or `q` is not in `(0, 1)`.
+    /// Constant data returns its value for every `q`.
+    #[must_use]
+    pub fn approx_quantile(&self, q: f64) -> Option<f64> {
+        if self.len == 0 || q <= 0.0 || q >= 1.0 {
+            return None;
+        }
+        if self.m2 == 0.0 {
+            return Some(self.mean);
+        }
+        let (s, k) = (self.skewness(), self.kurtosis());
+        let z = normal_quantile(q);
+        let (z2, z3) = (z * z, z * z * z);
+        let w = z + (z2 - 1.0) * s / 6.0 + (z3 - 3.0 * z) * k / 24.0
+            - (2.0 * z3 - 5.0 * z) * s * s / 36.0;
+        Some(self.variance().sqrt().mul_add(w, self.mean))
+    }
+
+    /// Performs the Jarque-Bera test of whether the data is normally
+    /// distributed, based on its skewness and kurtosis.
+    ///
+    /// The p-value uses the asymptotic chi-squared distribution with two
+    /// degrees of freedom, which overstates the evidence against normality
+    /// in small samples; prefer at least a few hundred values.
+    ///
+    /// `None` is returned if there are fewer than two distinct values.
+    #[must_use]
+    pub fn jarque_bera(&self) -> Option<TestResult> {
+        jarque_bera(self.len, self.skewness(), self.kurtosis())
+    }
+
+    /// Returns true if the Jarque-Bera test rejects normality at the
+    /// significance level `alpha`, e.g. `0.05`.
+    ///
+    /// Data without a test result, i.e. constant data, is not reported.
+    #[must_use]
+    pub fn is_clearly_non_normal(&self, alpha: f64) -> bool {
+        self.jarque_bera().is_some_and(|r| r.p_value < alpha)
+    }
+
+    /// Returns the number of data points.
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    /// Returns if empty.
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// The Jarque-Bera test from the sample size, skewness and excess kurtosis.
+pub(crate) fn jarque_bera(len: u64, skewness: f64, kurtosis: f64) -> Option<TestResult> {
+    if skewness.is_nan() || kurtosis.is_nan() {
+        return None;
+    }
+    let statistic = len as f64 / 6.0 * kurtosis.mul_add(kurtosis / 4.0, skewness * skewness);
+    Some(TestResult {
+        statistic,
+        // the survival function of chi-squared with 2 degrees of freedom
+        p_value: (-statistic / 2.0).exp(),
+    })
+}
+
+impl Commute for Moments {
+    #[inline]
+    fn merge(&mut self, v: Moments) {
+        if v.len == 0 {
+            return;
+        }
+        if self.len == 0 {
+            *self = v;
+            return;
+        }
+        let (na, nb) = (self.len as f64, v.len as f64);
+        let n = na + nb;
+        let delta = v.mean - self.mean;
+        let (d2, d3, d4) = (delta * delta, delta.powi(3), delta.powi(4));
+        let m4 = self.m4
+            + v.m4
+            + d4 * na * nb * (na * na - na * nb + nb * nb) / (n * n * n)
+            + 6.0 * d2 * (na * na * v.m2 + nb * nb * self.m2) / (n * n)
+            + 4.0 * delta * (na * v.m3 - nb * self.m3) / n;
+        let m3 = self.m3
+            + v.m3
+            + d3 * na * nb * (na - nb) / (n * n)
+            + 3.0 * delta * (na * v.m2 - nb * self.m2) / n;
+        self.m2 += v.m2 + d2 * na * nb / n;
+        self.m3 = m3;
+        self.m4 = m4;
+        self.mean += delta * nb / n;
+        self.len += v.len;
+    }
+}
+
+impl fmt::Debug for Moments {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_rounded(&RoundPolicy::new(), f)
+    }
+}
+
+impl RoundedDisplay for Moments {
+    fn fmt_rounded(&self, policy: &RoundPolicy, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "mean {}, variance {}, skewness {}, kurtosis {}",
+            policy.format(self.mean()),
+            policy.format(self.variance()),
+            policy.format(self.skewness()),
+            policy.format(self.kurtosis())
+        )
+    }
+}
+
+impl<T: ToPrimitive> FromIterator<T> for Moments {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = T>>(it: I) -> Moments {
+        let mut v = Moments::new();
+        v.extend(it);
+        v
+    }
+}
+
+impl<T: ToPrimitive> Extend<T> for Moments {
+    #[inline]
+    fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
+        for sample in it {
+            self.add(&sample);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Moments;
+    use crate::Commute;
+
+    fn two_pass(data: &[f64]) -> (f64, f64) {
+        let n = data.len() as f64;
+        let mean = data.iter().sum::<f64>() / n;
+        let m = |p| data.iter().map(|x| (x - mean).powi(p)).sum::<f64>() / n;
+        (m(3) / m(2).powf(1.5), m(4) / (m(2) * m(2)) - 3.0)
+    }
+
+    #[test]
+    fn skewness_kurtosis() {
+        let data: Vec<f64> = (1..=40)
+            .map(|i| f64::from(i * i % 17) + f64::from(i).sqrt())
+            .collect();
+        let (skew, kurt) = two_pass(&data);
+        let all: Moments = data.iter().copied().collect();
+        assert!((all.skewness() - skew).abs() < 1e-12);
+        assert!((all.kurtosis() - kurt).abs() < 1e-12);
+
+        let mut merged: Moments = data[..13].iter().copied().collect();
+        merged.merge(data[13..].iter().copied().collect());
+        merged.merge(Moments::new());
+        assert!((merged.mean() - all.mean()).abs() < 1e-12);
+        assert!((merged.variance() - all.variance()).abs() < 1e-12);
+        assert!((merged.skewness() - skew).abs() < 1e-12);
+        assert!((merged.kurtosis() - kurt).abs() < 1e-12);
+    }
+
+    #[test]
+    fn cornish_fisher() {
+        // exponential data: skewness 2, excess kurtosis 6
+        let data: Vec<f64> = (0..10_000)
+            .map(|i| -(1.0 - (f64::from(i) + 0.5) / 10_000.0).ln())
+            .collect();
+        let moments: Moments = data.iter().copied().collect();
+        // the exact median and p90 are ln(2) and ln(10)
+        let median = moments.approx_quantile(0.5).unwrap();
+        assert!((median - 2f64.ln()).abs() < 0.05);
+        let p90 = moments.approx_quantile(0.9).unwrap();
+        assert!((p90 - 10f64.ln()).abs() < 0.1);
+        assert_eq!(moments.approx_quantile(1.0), None);
+
+        let constant: Moments = [7, 7].into_iter().collect();
+        assert_eq!(constant.approx_quantile(0.3), Some(7.0));
+    }
+
+    #[test]
+    fn jarque_bera() {
+        // a symmetric two-point distribution: skewness 0, excess kurtosis -2
+        let data: Moments = (0..100).map(|i| i % 2).collect();
+        let r = data.jarque_bera().unwrap();
+        assert!((r.statistic - 100.0 / 6.0).abs() < 1e-9);
+        assert!((r.p_value - (-100.0f64 / 12.0).exp()).abs() < 1e-12);
+        assert!(data.is_clearly_non_normal(0.05));
+
+        let constant: Moments = [3, 3, 3].into_iter().collect();
+        assert!(constant.jarque_bera().is_none());
+        assert!(!constant.is_clearly_non_normal(0.05));
+    }
+}
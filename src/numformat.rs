@@ -0,0 +1,503 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Commute;
+
+/// How a raw number was written, as classified by `NumberFormat`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NumberKind {
+    /// Digits only, e.g. `-42` or `1,000`.
+    Integer,
+    /// Digits with a decimal point, e.g. `3.14` or `.5`.
+    Float,
+    /// A mantissa with an exponent, e.g. `6.02e23`.
+    Scientific,
+}
+
+/// The parts of a number written in plain or scientific decimal notation.
+pub(crate) struct NumberParts<'a> {
+    pub(crate) kind: NumberKind,
+    /// The digits before the decimal point, with any separators removed
+    /// from the count but not from the slice.
+    pub(crate) int: &'a [u8],
+    pub(crate) int_digits: usize,
+    pub(crate) frac: &'a [u8],
+    pub(crate) exponent: i64,
+    pub(crate) thousands: bool,
+}
+
+/// Parses `v` (without surrounding whitespace) as a decimal number with an
+/// optional sign, `,` thousands separators, fraction and exponent.
+///
+/// `None` is returned if `v` is not such a number. Separators must group
+/// the integer digits by three.
+pub(crate) fn parse_number(v: &[u8]) -> Option<NumberParts<'_>> {
+    let mut i = usize::from(matches!(v.first(), Some(b'+' | b'-')));
+    let start = i;
+    while i < v.len() && (v[i].is_ascii_digit() || v[i] == b',') {
+        i += 1;
+    }
+    let int = &v[start..i];
+    let thousands = int.contains(&b',');
+    if thousands {
+        let mut groups = int.split(|&b| b == b',');
+        let first = groups.next()?.len();
+        if !(1..=3).contains(&first) || !groups.all(|g| g.len() == 3) {
+            return None;
+        }
+    }
+    let int_digits = int.iter().filter(|b| b.is_ascii_digit()).count();
+
+    let mut kind = NumberKind::Integer;
+    let mut frac: &[u8] = &[];
+    if v.get(i) == Some(&b'.') {
+        kind = NumberKind::Float;
+        i += 1;
+        let start = i;
+        while i < v.len() && v[i].is_ascii_digit() {
+            i += 1;
+        }
+        frac = &v[start..i];
+    }
+    if int_digits == 0 && frac.is_empty() {
+        return None;
+    }
+
+    let mut exponent = 0i64;
+    if matches!(v.get(i), Some(b'e' | b'E')) {
+        kind = NumberKind::Scientific;
+        i += 1;
+        let negative = v.get(i) == Some(&b'-');
+        i += usize::from(matches!(v.get(i), Some(b'+' | b'-')));
+        let start = i;
+        while i < v.len() && v[i].is_ascii_digit() {
+            exponent = exponent
+                .saturating_mul(10)
+                .saturating_add(i64::from(v[i] - b'0'));
+            i += 1;
+        }
+        if i == start {
+            return None;
+        }
+        if negative {
+            exponent = -exponent;
+        }
+    }
+    if i != v.len() {
+        return None;
+    }
+    Some(NumberParts {
+        kind,
+        int,
+        int_digits,
+        frac,
+        exponent,
+        thousands,
+    })
+}
+
+/// Returns `v` without leading and trailing ASCII whitespace.
+#[inline]
+pub(crate) fn trim_ascii_whitespace(v: &[u8]) -> &[u8] {
+    let start = v
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(v.len());
+    let end = v
+        .iter()
+        .rposition(|b| !b.is_ascii_whitespace())
+        .map_or(start, |i| i + 1);
+    &v[start..end]
+}
+
+/// A commutative profile of how the numbers in a column of raw text are
+/// written, for choosing an output schema or parser.
+///
+/// Each value (with surrounding ASCII whitespace trimmed) is classified as
+/// an integer, a float or in scientific notation, and checked for
+/// thousands separators and leading zeros (which an integer type would
+/// lose, e.g. in ZIP codes). Values that are not numbers in this notation,
+/// including `inf` and `NaN`, are counted as non-numeric, and empty values
+/// are counted separately.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct NumberFormat {
+    integers: u64,
+    floats: u64,
+    scientific: u64,
+    thousands: u64,
+    leading_zeros: u64,
+    non_numeric: u64,
+    empty: u64,
+    max_decimal_places: u32,
+}
+
+impl NumberFormat {
+    /// Create initial empty state.
+    #[must_use]
+    pub fn new() -> NumberFormat {
+        Default::default()
+    }
+
+    /// Add a raw value, returning how it was written, or `None` if it is
+    /// empty or not a number.
+    pub fn add<S: AsRef<[u8]>>(&mut self, value: S) -> Option<NumberKind> {
+        let v = trim_ascii_whitespace(value.as_ref());
+        if v.is_empty() {
+            self.empty += 1;
+            return None;
+        }
+        let Some(parts) = parse_number(v) else {
+            self.non_numeric += 1;
+            return None;
+        };
+        match parts.kind {
+            NumberKind::Integer => self.integers += 1,
+            NumberKind::Float => self.floats += 1,
+            NumberKind::Scientific => self.scientific += 1,
+        }
+        self.thousands += u64::from(parts.thousands);
+        self.leading_zeros += u64::from(parts.int_digits > 1 && parts.int[0] == b'0');
+        let places = u32::try_from(parts.frac.len()).unwrap_or(u32::MAX);
+        self.max_decimal_places = self.max_decimal_places.max(places);
+        Some(parts.kind)
+    }
+
+    /// Returns the number of integers.
+    #[inline]
+    #[must_use]
+    pub const fn integer_count(&self) -> u64 {
+        self.integers
+    }
+
+    /// Returns the number of floats without an exponent.
+    #[inline]
+    #[must_use]
+    pub const fn float_count(&self) -> u64 {
+        self.floats
+    }
+
+    /// Returns the number of values in scientific notation.
+    #[inline]
+    #[must_use]
+    pub const fn scientific_count(&self) -> u64 {
+        self.scientific
+    }
+
+    /// Returns the number of values with thousands separators.
+    #[inline]
+    #[must_use]
+    pub const fn thousands_separator_count(&self) -> u64 {
+        self.thousands
+    }
+
+    /// Returns the number of values whose integer part has a leading zero,
+    /// e.g. `007` (but not `0` or `0.5`).
+    #[inline]
+    #[must_use]
+    pub const fn leading_zero_count(&self) -> u64 {
+        self.leading_zeros
+    }
+
+    /// Returns the number of non-empty values that are not numbers.
+    #[inline]
+    #[must_use]
+    pub const fn non_numeric_count(&self) -> u64 {
+        self.non_numeric
+    }
+
+    /// Returns the number of empty (or all whitespace) values.
+    #[inline]
+    #[must_use]
+    pub const fn empty_count(&self) -> u64 {
+        self.empty
+    }
+
+    /// Returns the largest number of digits after a decimal point.
+    #[inline]
+    #[must_use]
+    pub const fn max_decimal_places(&self) -> u32 {
+        self.max_decimal_places
+    }
+
+    /// Returns the number of values that are numbers.
+    #[inline]
+    #[must_use]
+    pub const fn numeric_count(&self) -> u64 {
+        self.integers + self.floats + self.scientific
+    }
+}
+
+impl Commute for NumberFormat {
+    #[inline]
+    fn merge(&mut self, v: NumberFormat) {
+        self.integers += v.integers;
+        self.floats += v.floats;
+        self.scientific += v.scientific;
+        self.thousands += v.thousands;
+        self.leading_zeros += v.leading_zeros;
+        self.non_numeric += v.non_numeric;
+        self.empty += v.empty;
+        self.max_decimal_places = self.max_decimal_places.max(v.max_decimal_places);
+    }
+}
+
+impl<S: AsRef<[u8]>> FromIterator<S> for NumberFormat {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = S>>(it: I) -> NumberFormat {
+        let mut v = NumberFormat::new();
+        v.extend(it);
+        v
+    }
+}
+
+impl<S: AsRef<[u8]>> Extend<S> for NumberFormat {
+    #[inline]
+    fn extend<I: IntoIterator<Item = S>>(&mut self, it: I) {
+        for value in it {
+            self.add(value);
+        }
+    }
+}
+
+/// A commutative tracker of the integer digits, decimal places and
+/// precision of numbers, for picking a `DECIMAL(p, s)` column type that
+/// holds every value exactly.
+///
+/// Raw text is parsed as by `NumberFormat`, and scientific notation is
+/// expanded, so `1.5e3` needs four integer digits and no decimal places.
+/// Trailing zeros after the decimal point count towards the scale, since
+/// they are usually significant in text (e.g. `2.50` for money), while
+/// leading zeros do not count towards the precision.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DecimalPrecision {
+    max_integer_digits: u32,
+    max_scale: u32,
+    max_precision: u32,
+    len: u64,
+    non_numeric: u64,
+}
+
+impl DecimalPrecision {
+    /// Create initial empty state.
+    #[must_use]
+    pub fn new() -> DecimalPrecision {
+        Default::default()
+    }
+
+    /// Add a raw value, returning false (and counting it as non-numeric)
+    /// if it is not a number. Empty values are ignored and return false.
+    pub fn add<S: AsRef<[u8]>>(&mut self, value: S) -> bool {
+        let v = trim_ascii_whitespace(value.as_ref());
+        if v.is_empty() {
+            return false;
+        }
+        let Some(parts) = parse_number(v) else {
+            self.non_numeric += 1;
+            return false;
+        };
+        let mut digits = parts
+            .int
+            .iter()
+            .chain(parts.frac)
+            .filter(|b| b.is_ascii_digit())
+            .peekable();
+        // the position of the decimal point, in digits from the left
+        let mut point = (parts.int_digits as i64).saturating_add(parts.exponent);
+        while digits.next_if_eq(&&b'0').is_some() {
+            point -= 1;
+        }
+        let significant = digits.count() as i64;
+        let scale = significant.saturating_sub(point).max(0);
+        self.record(point.max(0), scale);
+        true
+    }
+
+    /// Add a `Decimal` value, using its scale, so `Decimal::new(250, 2)`
+    /// counts as two decimal places.
+    #[cfg(feature = "decimal")]
+    #[inline]
+    pub fn add_decimal(&mut self, value: rust_decimal::Decimal) {
+        let mantissa = value.mantissa().unsigned_abs();
+        let digits = mantissa.checked_ilog10().map_or(0, |d| i64::from(d) + 1);
+        let scale = i64::from(value.scale());
+        self.record((digits - scale).max(0), scale);
+    }
+
+    #[inline]
+    fn record(&mut self, integer_digits: i64, scale: i64) {
+        let clamp = |d: i64| u32::try_from(d).unwrap_or(u32::MAX);
+        let (integer_digits, scale) = (clamp(integer_digits), clamp(scale));
+        self.max_integer_digits = self.max_integer_digits.max(integer_digits);
+        self.max_scale = self.max_scale.max(scale);
+        self.max_precision = self.max_precision.max(integer_digits.saturating_add(scale));
+        self.len += 1;
+    }
+
+    /// Returns the largest number of digits before the decimal point.
+    #[inline]
+    #[must_use]
+    pub const fn max_integer_digits(&self) -> u32 {
+        self.max_integer_digits
+    }
+
+    /// Returns the largest number of digits after the decimal point.
+    #[inline]
+    #[must_use]
+    pub const fn max_scale(&self) -> u32 {
+        self.max_scale
+    }
+
+    /// Returns the largest number of digits in a single value, from its
+    /// first non-zero digit to its last decimal place.
+    #[inline]
+    #[must_use]
+    pub const fn max_precision(&self) -> u32 {
+        self.max_precision
+    }
+
+    /// Returns the `(precision, scale)` of the smallest `DECIMAL` type that
+    /// holds every value, or `None` if there are no numbers.
+    ///
+    /// This can need more digits than `max_precision`, because the
+    /// largest integer part and the most decimal places may come from
+    /// different values. The precision is at least `1`.
+    #[must_use]
+    pub fn decimal_type(&self) -> Option<(u32, u32)> {
+        if self.len == 0 {
+            return None;
+        }
+        let precision = self.max_integer_digits.saturating_add(self.max_scale);
+        Some((precision.max(1), self.max_scale))
+    }
+
+    /// Returns the number of numbers.
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns true if there are no numbers.
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of non-empty values that are not numbers.
+    #[inline]
+    #[must_use]
+    pub const fn non_numeric_count(&self) -> u64 {
+        self.non_numeric
+    }
+}
+
+impl Commute for DecimalPrecision {
+    #[inline]
+    fn merge(&mut self, v: DecimalPrecision) {
+        self.max_integer_digits = self.max_integer_digits.max(v.max_integer_digits);
+        self.max_scale = self.max_scale.max(v.max_scale);
+        self.max_precision = self.max_precision.max(v.max_precision);
+        self.len += v.len;
+        self.non_numeric += v.non_numeric;
+    }
+}
+
+impl<S: AsRef<[u8]>> FromIterator<S> for DecimalPrecision {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = S>>(it: I) -> DecimalPrecision {
+        let mut v = DecimalPrecision::new();
+        v.extend(it);
+        v
+    }
+}
+
+impl<S: AsRef<[u8]>> Extend<S> for DecimalPrecision {
+    #[inline]
+    fn extend<I: IntoIterator<Item = S>>(&mut self, it: I) {
+        for value in it {
+            self.add(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DecimalPrecision, NumberFormat, NumberKind};
+    use crate::Commute;
+
+    #[test]
+    fn classify() {
+        let mut format = NumberFormat::new();
+        assert_eq!(format.add("-42"), Some(NumberKind::Integer));
+        assert_eq!(format.add(" 1,234,567 "), Some(NumberKind::Integer));
+        assert_eq!(format.add("00501"), Some(NumberKind::Integer));
+        assert_eq!(format.add("3.14159"), Some(NumberKind::Float));
+        assert_eq!(format.add(".5"), Some(NumberKind::Float));
+        assert_eq!(format.add("6.02E+23"), Some(NumberKind::Scientific));
+        assert_eq!(format.add("1e-7"), Some(NumberKind::Scientific));
+        for bad in ["12,34", "1e", "abc", "NaN", "1.2.3", "-", "."] {
+            assert_eq!(format.add(bad), None, "{bad}");
+        }
+        assert_eq!(format.add("  "), None);
+
+        assert_eq!(format.integer_count(), 3);
+        assert_eq!(format.float_count(), 2);
+        assert_eq!(format.scientific_count(), 2);
+        assert_eq!(format.thousands_separator_count(), 1);
+        assert_eq!(format.leading_zero_count(), 1);
+        assert_eq!(format.non_numeric_count(), 7);
+        assert_eq!(format.empty_count(), 1);
+        assert_eq!(format.max_decimal_places(), 5);
+    }
+
+    #[test]
+    fn merge() {
+        let mut a: NumberFormat = ["1", "2.50"].into_iter().collect();
+        let b: NumberFormat = ["0.125", "x"].into_iter().collect();
+        a.merge(b);
+        assert_eq!(a.numeric_count(), 3);
+        assert_eq!(a.non_numeric_count(), 1);
+        assert_eq!(a.max_decimal_places(), 3);
+        assert_eq!(a.leading_zero_count(), 0);
+    }
+
+    #[test]
+    fn decimal_precision() {
+        let mut p = DecimalPrecision::new();
+        assert_eq!(p.decimal_type(), None);
+        for v in ["123.450", "-0.0050", "1,000", "0", "", "n/a"] {
+            p.add(v);
+        }
+        assert_eq!(p.len(), 4);
+        assert_eq!(p.non_numeric_count(), 1);
+        assert_eq!(p.max_integer_digits(), 4);
+        assert_eq!(p.max_scale(), 4);
+        assert_eq!(p.max_precision(), 6);
+        assert_eq!(p.decimal_type(), Some((8, 4)));
+
+        let sci: DecimalPrecision = ["1.5e3", "2.5E-3"].into_iter().collect();
+        assert_eq!(sci.max_integer_digits(), 4);
+        assert_eq!(sci.max_scale(), 4);
+        assert_eq!(sci.max_precision(), 4);
+
+        let mut merged: DecimalPrecision = ["0"].into_iter().collect();
+        assert_eq!(merged.decimal_type(), Some((1, 0)));
+        merged.merge(sci);
+        assert_eq!(merged.decimal_type(), Some((8, 4)));
+    }
+
+    #[cfg(feature = "decimal")]
+    #[test]
+    fn decimal_values() {
+        use rust_decimal::Decimal;
+
+        let mut p = DecimalPrecision::new();
+        p.add_decimal(Decimal::new(-12_345, 2));
+        p.add_decimal(Decimal::new(50, 4));
+        p.add_decimal(Decimal::ZERO);
+        assert_eq!(p.max_integer_digits(), 3);
+        assert_eq!(p.max_scale(), 4);
+        assert_eq!(p.max_precision(), 5);
+        assert_eq!(p.decimal_type(), Some((7, 4)));
+    }
+}
@@ -1,9 +1,16 @@
 use std::fmt;
 
-use num_traits::ToPrimitive;
+use num_traits::{Float, ToPrimitive};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 
-use crate::Commute;
+use crate::parallel::in_pool;
+use crate::round::{RoundPolicy, RoundedDisplay};
+use crate::special::normal_quantile;
+use crate::{
+    Commute, ExactSum, MinMax, Moments, OnlineStatsSnapshotV1, OnlineStatsSnapshotV2, Parallelism,
+    TestResult,
+};
 
 /// Compute the standard deviation of a stream in constant space.
 pub fn stddev<'a, I, T>(x: I) -> f64
@@ -11,8 +18,10 @@ where
     I: IntoIterator<Item = T>,
     T: Into<&'a f64>,
 {
-    let it = x.into_iter();
-    stddev(it)
+    x.into_iter()
+        .map(|v| *v.into())
+        .collect::<OnlineStats>()
+        .stddev()
 }
 
 /// Compute the variance of a stream in constant space.
@@ -21,8 +30,10 @@ where
     I: IntoIterator<Item = T>,
     T: Into<&'a f64>,
 {
-    let it = x.into_iter();
-    variance(it)
+    x.into_iter()
+        .map(|v| *v.into())
+        .collect::<OnlineStats>()
+        .variance()
 }
 
 /// Compute the mean of a stream in constant space.
@@ -31,16 +42,45 @@ where
     I: IntoIterator<Item = T>,
     T: Into<&'a f64>,
 {
-    let it = x.into_iter();
-    mean(it)
+    x.into_iter()
+        .map(|v| *v.into())
+        .collect::<OnlineStats>()
+        .mean()
 }
 
+/// The largest integer such that it and all smaller integers are exactly
+/// representable as an `f64`, i.e. `2^53 - 1`.
+pub const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_991.0;
+
 /// Online state for computing mean, variance and standard deviation.
+///
+/// Skewness, kurtosis and the Jarque-Bera test are only available on state
+/// created with `with_higher_moments`, which keeps a `Moments` alongside.
+///
+/// The accumulator is generic over its floating-point type `F`, which
+/// defaults to `f64`. `OnlineStats<f32>` (created with `default()` or by
+/// collecting an iterator) keeps the mean and the sum of squares in `f32`.
+/// The sample, NULL and sign counters are `u64`s either way, so this only
+/// shrinks the state by a few bytes.
 #[derive(Clone, Copy, Serialize, Deserialize, PartialEq)]
-pub struct OnlineStats {
+#[serde(bound(deserialize = "F: Deserialize<'de> + Float"))]
+pub struct OnlineStats<F = f64> {
     size: u64,
-    mean: f64,
-    q: f64,
+    mean: F,
+    q: F,
+    // the higher moments, if tracked; see `with_higher_moments`
+    #[serde(default)]
+    moments: Option<Moments>,
+    #[serde(default)]
+    pub(crate) nulls: u64,
+    #[serde(default)]
+    imprecise: u64,
+    #[serde(default)]
+    zeros: u64,
+    #[serde(default)]
+    negatives: u64,
+    #[serde(default)]
+    positives: u64,
 }
 
 impl OnlineStats {
@@ -58,15 +98,182 @@ impl OnlineStats {
         samples.iter().map(|n| n.to_f64().unwrap()).collect()
     }
 
+    /// Initializes variance from a sample, in parallel.
+    ///
+    /// The slice is split into chunks whose moments are computed on the
+    /// rayon thread pool and then combined with the same pairwise formula
+    /// as `merge` (Chan et al.). The result matches `from_slice` up to
+    /// floating-point rounding.
+    #[must_use]
+    pub fn par_from_slice<T: ToPrimitive + Sync>(samples: &[T]) -> OnlineStats {
+        const CHUNK_LEN: usize = 64 * 1024;
+        in_pool(|| {
+            samples
+                .par_chunks(CHUNK_LEN)
+                .map(OnlineStats::from_slice)
+                .reduce(OnlineStats::new, |mut acc, chunk| {
+                    acc.merge(chunk);
+                    acc
+                })
+        })
+    }
+
+    /// Initializes variance from a sample, in parallel if `parallelism`
+    /// says so for its length. See `par_from_slice`.
+    #[must_use]
+    pub fn from_slice_with<T: ToPrimitive + Sync>(
+        samples: &[T],
+        parallelism: Parallelism,
+    ) -> OnlineStats {
+        if parallelism.is_parallel(samples.len()) {
+            OnlineStats::par_from_slice(samples)
+        } else {
+            OnlineStats::from_slice(samples)
+        }
+    }
+
+    /// Initializes state from a slice of `f64`s, using vectorized kernels.
+    ///
+    /// This makes two passes over the data, one for the mean and one for
+    /// the sum of squared deviations, which is both faster and at least as
+    /// accurate as adding the samples one at a time. With the `simd`
+    /// feature, AVX2 is used on CPUs that support it.
+    #[must_use]
+    pub fn from_f64_slice(samples: &[f64]) -> OnlineStats {
+        if samples.is_empty() {
+            return OnlineStats::new();
+        }
+        let mean = crate::simd::sum(samples) / samples.len() as f64;
+        OnlineStats {
+            size: samples.len() as u64,
+            mean,
+            q: crate::simd::sum_sq_dev(samples, mean),
+            moments: None,
+            nulls: 0,
+            imprecise: crate::simd::count_abs_ge(samples, MAX_SAFE_INTEGER + 1.0),
+            zeros: samples.iter().filter(|&&x| x == 0.0).count() as u64,
+            negatives: samples.iter().filter(|&&x| x < 0.0).count() as u64,
+            positives: samples.iter().filter(|&&x| x > 0.0).count() as u64,
+        }
+    }
+
+    /// Add a slice of samples.
+    ///
+    /// The samples are converted to `f64` in fixed-size chunks, and each
+    /// chunk is summarized with the vectorized kernels of `from_f64_slice`
+    /// and merged in, which avoids the per-sample division and branches
+    /// of `add`. The result matches `extend` up to floating-point
+    /// rounding. Higher moments, if tracked, are still added one sample at
+    /// a time.
+    pub fn add_slice<T: ToPrimitive>(&mut self, samples: &[T]) {
+        const CHUNK_LEN: usize = 1024;
+        let mut buf = [0.0; CHUNK_LEN];
+        let mut moments = self.moments.take();
+        for chunk in samples.chunks(CHUNK_LEN) {
+            let buf = &mut buf[..chunk.len()];
+            for (b, x) in buf.iter_mut().zip(chunk) {
+                *b = x.to_f64().unwrap();
+            }
+            if let Some(m) = &mut moments {
+                m.extend(buf.iter().copied());
+            }
+            self.merge(OnlineStats::from_f64_slice(buf));
+        }
+        self.moments = moments;
+    }
+
+    /// Initializes state from a stream of optional samples.
+    ///
+    /// `None` values are counted as NULLs (see `null_count`) and do not
+    /// affect the population size, mean or variance.
+    ///
+    /// (`FromIterator<Option<T>>` cannot be implemented alongside
+    /// `FromIterator<T: ToPrimitive>`, since the two would overlap.)
+    #[must_use]
+    pub fn from_options<T, I>(it: I) -> OnlineStats
+    where
+        T: ToPrimitive,
+        I: IntoIterator<Item = Option<T>>,
+    {
+        let mut v = OnlineStats::new();
+        v.extend_options(it);
+        v
+    }
+}
+
+impl<F: Float> OnlineStats<F> {
+    /// Create initial state that also tracks skewness and kurtosis, for
+    /// `skewness`, `kurtosis` and `jarque_bera`.
+    ///
+    /// This keeps a `Moments` next to the mean and variance, so every
+    /// sample costs a few more operations.
+    #[must_use]
+    pub fn with_higher_moments() -> OnlineStats<F> {
+        OnlineStats {
+            moments: Some(Moments::new()),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the higher moments, if they are tracked.
+    #[inline]
+    #[must_use]
+    pub const fn moments(&self) -> Option<&Moments> {
+        self.moments.as_ref()
+    }
+
+    /// Restores state from its raw parts, e.g. as saved to an external
+    /// checkpoint store with `len`, `mean`, `m2`, `null_count`,
+    /// `imprecise_count` and `sign_counts`.
+    ///
+    /// `m2` is the sum of squared deviations from the mean. The skewness
+    /// and kurtosis of the restored state are unknown; use
+    /// `OnlineStatsSnapshotV2` to keep them.
+    #[must_use]
+    pub fn from_parts(
+        size: u64,
+        mean: F,
+        m2: F,
+        nulls: u64,
+        imprecise: u64,
+        (negatives, zeros, positives): (u64, u64, u64),
+    ) -> OnlineStats<F> {
+        OnlineStats {
+            size,
+            mean,
+            q: m2,
+            moments: None,
+            nulls,
+            imprecise,
+            zeros,
+            negatives,
+            positives,
+        }
+    }
+
+    /// Returns the sum of squared deviations from the mean, i.e. the
+    /// variance multiplied by the population size.
+    #[inline]
+    #[must_use]
+    pub const fn m2(&self) -> F {
+        self.q
+    }
+
+    /// Converts the population size to `F`.
+    #[inline]
+    fn size_f(&self) -> F {
+        F::from(self.size).unwrap()
+    }
+
     /// Return the current mean.
     #[must_use]
-    pub const fn mean(&self) -> f64 {
+    pub const fn mean(&self) -> F {
         self.mean
     }
 
     /// Return the current standard deviation.
     #[must_use]
-    pub fn stddev(&self) -> f64 {
+    pub fn stddev(&self) -> F {
         self.variance().sqrt()
     }
 
@@ -74,35 +281,265 @@ impl OnlineStats {
     // TODO: look into alternate algorithms for calculating variance
     // https://en.wikipedia.org/wiki/Algorithms_for_calculating_variance
     #[must_use]
-    pub fn variance(&self) -> f64 {
-        self.q / (self.size as f64)
+    pub fn variance(&self) -> F {
+        self.q / self.size_f()
     }
 
-    // TODO: Calculate kurtosis
-    // also see https://en.wikipedia.org/wiki/Algorithms_for_calculating_variance
+    /// Return the current sample variance (with Bessel's correction).
+    ///
+    /// `NaN` is returned if there are fewer than two samples.
+    #[must_use]
+    pub fn sample_variance(&self) -> F {
+        if self.size < 2 {
+            return F::nan();
+        }
+        self.q / (self.size_f() - F::one())
+    }
+
+    /// Return the current sample standard deviation (with Bessel's correction).
+    ///
+    /// `NaN` is returned if there are fewer than two samples.
+    #[must_use]
+    pub fn sample_stddev(&self) -> F {
+        self.sample_variance().sqrt()
+    }
+
+    /// Returns an approximation of the `q`th quantile, for `q` in `(0, 1)`,
+    /// assuming the data is normally distributed.
+    ///
+    /// This is a last resort when only the mean and variance were kept;
+    /// `Moments::approx_quantile` also corrects for skewness and kurtosis.
+    ///
+    /// `NaN` is returned if there are no samples or `q` is not in `(0, 1)`.
+    #[must_use]
+    pub fn approx_quantile(&self, q: f64) -> F {
+        if self.size == 0 || q <= 0.0 || q >= 1.0 {
+            return F::nan();
+        }
+        let z = F::from(normal_quantile(q)).unwrap();
+        z.mul_add(self.stddev(), self.mean)
+    }
+
+    /// Returns an approximation of the mean absolute deviation around the
+    /// mean, assuming the data is normally distributed, for which it is
+    /// `sqrt(2 / pi)` (about `0.798`) times the standard deviation.
+    ///
+    /// For the exact value, keep the data and use
+    /// `Unsorted::mean_abs_deviation`.
+    ///
+    /// `NaN` is returned if there are no samples.
+    #[must_use]
+    pub fn approx_mean_abs_deviation(&self) -> F {
+        let ratio = F::from(std::f64::consts::FRAC_2_PI.sqrt()).unwrap();
+        self.stddev() * ratio
+    }
+
+    /// Returns Cohen's d, the difference between this mean and the mean of
+    /// `other` in units of their pooled sample standard deviation.
+    ///
+    /// `NaN` is returned if either side has no samples, if there are fewer
+    /// than three samples in total (so the pooled variance has no degrees
+    /// of freedom) or if both populations are constant.
+    #[must_use]
+    pub fn cohens_d(&self, other: &OnlineStats<F>) -> F {
+        let n = self.size + other.size;
+        if self.size == 0 || other.size == 0 || n < 3 {
+            return F::nan();
+        }
+        let df = F::from(n - 2).unwrap();
+        let pooled = ((self.q + other.q) / df).sqrt();
+        if pooled.is_zero() {
+            return F::nan();
+        }
+        (self.mean - other.mean) / pooled
+    }
+
+    /// Returns Hedges' g, i.e. Cohen's d corrected for its upward bias in
+    /// small samples.
+    ///
+    /// `NaN` is returned in the same cases as `cohens_d`.
+    #[must_use]
+    pub fn hedges_g(&self, other: &OnlineStats<F>) -> F {
+        let n = F::from(self.size + other.size).unwrap();
+        let four = F::from(4.0).unwrap();
+        let correction = F::one() - F::from(3.0).unwrap() / (four * n - F::from(9.0).unwrap());
+        self.cohens_d(other) * correction
+    }
+
+    /// Returns the population skewness, `g1`. See `Moments::skewness`.
+    ///
+    /// `NaN` is returned if there are no samples, they are all equal, or
+    /// the higher moments are not tracked (see `with_higher_moments`).
+    #[must_use]
+    pub fn skewness(&self) -> F {
+        self.moments
+            .map_or(F::nan(), |m| F::from(m.skewness()).unwrap())
+    }
+
+    /// Returns the population excess kurtosis, `g2`, which is `0` for a
+    /// normal distribution. See `Moments::kurtosis`.
+    ///
+    /// `NaN` is returned in the same cases as `skewness`.
+    #[must_use]
+    pub fn kurtosis(&self) -> F {
+        self.moments
+            .map_or(F::nan(), |m| F::from(m.kurtosis()).unwrap())
+    }
+
+    /// Performs the Jarque-Bera test of whether the data is normally
+    /// distributed, based on its skewness and kurtosis. See
+    /// `Moments::jarque_bera`.
+    ///
+    /// `None` is returned if `skewness` is `NaN`, e.g. for constant data.
+    #[must_use]
+    pub fn jarque_bera(&self) -> Option<TestResult> {
+        self.moments?.jarque_bera()
+    }
+
+    /// Returns true if the Jarque-Bera test rejects normality at the
+    /// significance level `alpha`, e.g. `0.05`.
+    ///
+    /// Data without a test result, e.g. constant data, is not reported.
+    #[must_use]
+    pub fn is_clearly_non_normal(&self, alpha: f64) -> bool {
+        self.jarque_bera().is_some_and(|r| r.p_value < alpha)
+    }
 
     /// Add a new sample.
     #[inline]
     pub fn add<T: ToPrimitive>(&mut self, sample: &T) {
-        let sample = sample.to_f64().unwrap();
+        let sample: F = F::from(sample.to_f64().unwrap()).unwrap();
+        // integers at or beyond 2^mantissa_bits may have been rounded
+        if sample.abs() >= F::epsilon().recip() + F::epsilon().recip() {
+            self.imprecise += 1;
+        }
+        if sample.is_zero() {
+            self.zeros += 1;
+        } else if sample < F::zero() {
+            self.negatives += 1;
+        } else if sample > F::zero() {
+            self.positives += 1;
+        }
+        if let Some(m) = &mut self.moments {
+            m.add(&sample);
+        }
         // Taken from: https://en.wikipedia.org/wiki/Standard_deviation#Rapid_calculation_methods
         // See also: https://api.semanticscholar.org/CorpusID:120126049
         let oldmean = self.mean;
         self.size += 1;
         let delta = sample - oldmean;
-        self.mean += delta / (self.size as f64);
+        self.mean = self.mean + delta / self.size_f();
         let delta2 = sample - self.mean;
-        self.q += delta * delta2;
+        self.q = self.q + delta * delta2;
     }
 
     /// Add a new NULL value to the population.
     ///
-    /// This increases the population size by `1`.
+    /// This increases the population size by `1`, since the NULL is added
+    /// as a `0` sample. It is not counted in `null_count`; to count NULLs
+    /// without adding them as samples, use `extend_options`.
     #[inline]
     pub fn add_null(&mut self) {
         self.add(&0usize);
     }
 
+    /// Add a stream of optional samples.
+    ///
+    /// `None` values are counted as NULLs (see `null_count`) and do not
+    /// affect the population size, mean or variance.
+    #[inline]
+    pub fn extend_options<T, I>(&mut self, it: I)
+    where
+        T: ToPrimitive,
+        I: IntoIterator<Item = Option<T>>,
+    {
+        for sample in it {
+            match sample {
+                Some(v) => self.add(&v),
+                None => self.nulls += 1,
+            }
+        }
+    }
+
+    /// Returns the number of NULLs, i.e. `None` values ingested via
+    /// `extend_options`, which are not part of the population.
+    #[inline]
+    #[must_use]
+    pub const fn null_count(&self) -> u64 {
+        self.nulls
+    }
+
+    /// Returns the number of samples equal to zero, including NULLs added
+    /// with `add_null`.
+    #[inline]
+    #[must_use]
+    pub const fn zero_count(&self) -> u64 {
+        self.zeros
+    }
+
+    /// Returns the fraction of samples equal to zero, a measure of the
+    /// sparsity of the data.
+    ///
+    /// `NaN` is returned if there are no samples.
+    #[inline]
+    #[must_use]
+    pub fn zero_ratio(&self) -> f64 {
+        self.zeros as f64 / self.size as f64
+    }
+
+    /// Returns the number of negative samples.
+    #[inline]
+    #[must_use]
+    pub const fn negative_count(&self) -> u64 {
+        self.negatives
+    }
+
+    /// Returns the number of positive samples.
+    #[inline]
+    #[must_use]
+    pub const fn positive_count(&self) -> u64 {
+        self.positives
+    }
+
+    /// Returns the numbers of negative, zero and positive samples.
+    ///
+    /// `NaN` samples have no sign, so the counts add up to less than
+    /// `len` if there were any.
+    #[inline]
+    #[must_use]
+    pub const fn sign_counts(&self) -> (u64, u64, u64) {
+        (self.negatives, self.zeros, self.positives)
+    }
+
+    /// Returns the number of samples whose magnitude exceeds the range in
+    /// which `F` represents every integer exactly (`MAX_SAFE_INTEGER` for
+    /// `f64`).
+    ///
+    /// Integers beyond this range (e.g. large IDs) may have been rounded
+    /// when converted to `F`, so the mean and variance may be imprecise.
+    #[inline]
+    #[must_use]
+    pub const fn imprecise_count(&self) -> u64 {
+        self.imprecise
+    }
+
+    /// Returns true if no sample was beyond the exact integer range of `F`,
+    /// so no integer lost precision when converted.
+    #[inline]
+    #[must_use]
+    pub const fn is_precise(&self) -> bool {
+        self.imprecise == 0
+    }
+
+    /// Returns the number of bytes used by this state.
+    ///
+    /// `OnlineStats` uses constant space, so this never grows.
+    #[inline]
+    #[must_use]
+    pub const fn mem_usage(&self) -> usize {
+        std::mem::size_of::<Self>()
+    }
+
     /// Returns the number of data points.
     #[inline]
     #[must_use]
@@ -118,14 +555,35 @@ impl OnlineStats {
     }
 }
 
-impl Commute for OnlineStats {
+impl<F: Float> Commute for OnlineStats<F> {
     #[inline]
-    fn merge(&mut self, v: OnlineStats) {
+    fn merge(&mut self, v: OnlineStats<F>) {
+        if v.size == 0 {
+            // also keeps two empty states from producing a NaN mean
+            self.nulls += v.nulls;
+            return;
+        }
         // Taken from: https://en.wikipedia.org/wiki/Standard_deviation#Combining_standard_deviations
-        let (s1, s2) = (self.size as f64, v.size as f64);
+        let (s1, s2) = (self.size_f(), v.size_f());
         let meandiffsq = (self.mean - v.mean) * (self.mean - v.mean);
 
+        // the higher moments are kept only if both sides track them, or
+        // this side is empty
+        self.moments = match (self.moments, v.moments) {
+            (Some(mut a), Some(b)) => {
+                a.merge(b);
+                Some(a)
+            }
+            (_, b) if self.size == 0 => b,
+            _ => None,
+        };
+
         self.size += v.size;
+        self.nulls += v.nulls;
+        self.imprecise += v.imprecise;
+        self.zeros += v.zeros;
+        self.negatives += v.negatives;
+        self.positives += v.positives;
 
         //self.mean = ((s1 * self.mean) + (s2 * v.mean)) / (s1 + s2);
         /*
@@ -134,37 +592,544 @@ impl Commute for OnlineStats {
         */
         self.mean = s1.mul_add(self.mean, s2 * v.mean) / (s1 + s2);
 
-        self.q += v.q + meandiffsq * s1 * s2 / (s1 + s2);
+        self.q = self.q + v.q + meandiffsq * s1 * s2 / (s1 + s2);
     }
 }
 
-impl Default for OnlineStats {
-    fn default() -> OnlineStats {
+impl<F: Float> Default for OnlineStats<F> {
+    fn default() -> OnlineStats<F> {
         OnlineStats {
             size: 0,
-            mean: 0.0,
-            q: 0.0,
+            mean: F::zero(),
+            q: F::zero(),
+            moments: None,
+            nulls: 0,
+            imprecise: 0,
+            zeros: 0,
+            negatives: 0,
+            positives: 0,
+        }
+    }
+}
+
+impl From<&OnlineStats> for OnlineStatsSnapshotV1 {
+    fn from(v: &OnlineStats) -> OnlineStatsSnapshotV1 {
+        OnlineStatsSnapshotV1 {
+            size: v.size,
+            mean: v.mean,
+            m2: v.q,
+            nulls: v.nulls,
+            imprecise: v.imprecise,
+        }
+    }
+}
+
+impl From<&OnlineStats> for OnlineStatsSnapshotV2 {
+    fn from(v: &OnlineStats) -> OnlineStatsSnapshotV2 {
+        OnlineStatsSnapshotV2 {
+            size: v.size,
+            mean: v.mean,
+            m2: v.q,
+            m3: v.moments.map(|m| m.sums().0),
+            m4: v.moments.map(|m| m.sums().1),
+            nulls: v.nulls,
+            imprecise: v.imprecise,
+            negatives: v.negatives,
+            zeros: v.zeros,
+            positives: v.positives,
+        }
+    }
+}
+
+impl From<OnlineStatsSnapshotV2> for OnlineStats {
+    fn from(v: OnlineStatsSnapshotV2) -> OnlineStats {
+        OnlineStats {
+            size: v.size,
+            mean: v.mean,
+            q: v.m2,
+            moments: v
+                .m3
+                .zip(v.m4)
+                .map(|(m3, m4)| Moments::from_sums(v.size, v.mean, v.m2, m3, m4)),
+            nulls: v.nulls,
+            imprecise: v.imprecise,
+            zeros: v.zeros,
+            negatives: v.negatives,
+            positives: v.positives,
         }
     }
 }
 
-impl fmt::Debug for OnlineStats {
+impl From<OnlineStatsSnapshotV1> for OnlineStats {
+    fn from(v: OnlineStatsSnapshotV1) -> OnlineStats {
+        OnlineStatsSnapshotV2::from(v).into()
+    }
+}
+
+impl<F: Float> fmt::Debug for OnlineStats<F> {
     #[inline]
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:.10} +/- {:.10}", self.mean(), self.stddev())
+        let policy = RoundPolicy::new();
+        write!(
+            f,
+            "{} +/- {}",
+            policy.format(self.mean().to_f64().unwrap()),
+            policy.format(self.stddev().to_f64().unwrap())
+        )
     }
 }
 
-impl<T: ToPrimitive> FromIterator<T> for OnlineStats {
+impl<F: Float> fmt::Display for OnlineStats<F> {
     #[inline]
-    fn from_iter<I: IntoIterator<Item = T>>(it: I) -> OnlineStats {
-        let mut v = OnlineStats::new();
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_rounded(&RoundPolicy::new(), f)
+    }
+}
+
+impl<F: Float> RoundedDisplay for OnlineStats<F> {
+    fn fmt_rounded(&self, policy: &RoundPolicy, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "n {}, mean {}, stddev {}",
+            self.size,
+            policy.format(self.mean().to_f64().unwrap()),
+            policy.format(self.stddev().to_f64().unwrap())
+        )
+    }
+}
+
+impl<F: Float, T: ToPrimitive> FromIterator<T> for OnlineStats<F> {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = T>>(it: I) -> OnlineStats<F> {
+        let mut v = OnlineStats::default();
         v.extend(it);
         v
     }
 }
 
-impl<T: ToPrimitive> Extend<T> for OnlineStats {
+impl<F: Float, T: ToPrimitive> Extend<T> for OnlineStats<F> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
+        for sample in it {
+            self.add(&sample);
+        }
+    }
+}
+
+/// A running sum with a Neumaier correction term for lost low-order bits.
+#[derive(Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub(crate) struct NeumaierSum {
+    sum: f64,
+    c: f64,
+}
+
+impl NeumaierSum {
+    #[inline]
+    pub(crate) fn add(&mut self, x: f64) {
+        let t = self.sum + x;
+        if self.sum.abs() >= x.abs() {
+            self.c += (self.sum - t) + x;
+        } else {
+            self.c += (x - t) + self.sum;
+        }
+        self.sum = t;
+    }
+
+    #[inline]
+    pub(crate) fn value(self) -> f64 {
+        self.sum + self.c
+    }
+}
+
+/// Online state for computing mean, variance and standard deviation with
+/// compensated (Kahan-Neumaier) summation.
+///
+/// This is a drop-in alternative to `OnlineStats` for very long streams or
+/// values of mixed magnitudes, where the rounding errors of a plain `f64`
+/// accumulator add up. Each running sum keeps a correction term for the
+/// low-order bits lost in every addition, at the cost of a few extra
+/// floating-point operations per sample.
+///
+/// It also tracks the harmonic and geometric means, which are only defined
+/// for positive data. Zero, negative and `NaN` samples are counted (see
+/// `non_positive_count`), and once there is one, in this state or in any
+/// state merged into it, both means are `NaN`.
+#[derive(Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct CompensatedStats {
+    size: u64,
+    mean: NeumaierSum,
+    q: NeumaierSum,
+    recip_sum: NeumaierSum,
+    ln_sum: NeumaierSum,
+    non_positive: u64,
+}
+
+impl CompensatedStats {
+    /// Create initial state.
+    #[must_use]
+    pub fn new() -> CompensatedStats {
+        Default::default()
+    }
+
+    /// Add a new sample.
+    #[inline]
+    pub fn add<T: ToPrimitive>(&mut self, sample: &T) {
+        let sample = sample.to_f64().unwrap();
+        // Welford's update, as in `OnlineStats::add`, with compensated
+        // accumulation of the mean and the sum of squared deviations.
+        self.size += 1;
+        let delta = sample - self.mean.value();
+        self.mean.add(delta / (self.size as f64));
+        let delta2 = sample - self.mean.value();
+        self.q.add(delta * delta2);
+        if sample > 0.0 {
+            self.recip_sum.add(sample.recip());
+            self.ln_sum.add(sample.ln());
+        } else {
+            self.non_positive += 1;
+        }
+    }
+
+    /// Return the current mean.
+    #[must_use]
+    pub fn mean(&self) -> f64 {
+        self.mean.value()
+    }
+
+    /// Return the current variance.
+    #[must_use]
+    pub fn variance(&self) -> f64 {
+        self.q.value() / (self.size as f64)
+    }
+
+    /// Return the current standard deviation.
+    #[must_use]
+    pub fn stddev(&self) -> f64 {
+        self.variance().sqrt()
+    }
+
+    /// Return the current sample variance (with Bessel's correction).
+    ///
+    /// `NaN` is returned if there are fewer than two samples.
+    #[must_use]
+    pub fn sample_variance(&self) -> f64 {
+        if self.size < 2 {
+            return f64::NAN;
+        }
+        self.q.value() / ((self.size - 1) as f64)
+    }
+
+    /// Returns the number of samples that were zero, negative or `NaN`, for
+    /// which the harmonic and geometric means are undefined.
+    #[inline]
+    #[must_use]
+    pub const fn non_positive_count(&self) -> u64 {
+        self.non_positive
+    }
+
+    /// Return the current harmonic mean.
+    ///
+    /// `NaN` is returned if there is no data or any sample is not positive.
+    #[must_use]
+    pub fn harmonic_mean(&self) -> f64 {
+        if self.size == 0 || self.non_positive > 0 {
+            return f64::NAN;
+        }
+        self.size as f64 / self.recip_sum.value()
+    }
+
+    /// Return the current geometric mean.
+    ///
+    /// `NaN` is returned if there is no data or any sample is not positive.
+    #[must_use]
+    pub fn geometric_mean(&self) -> f64 {
+        if self.size == 0 || self.non_positive > 0 {
+            return f64::NAN;
+        }
+        (self.ln_sum.value() / self.size as f64).exp()
+    }
+
+    /// Returns the number of data points.
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.size as usize
+    }
+
+    /// Returns if empty.
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+}
+
+impl Commute for CompensatedStats {
+    #[inline]
+    fn merge(&mut self, v: CompensatedStats) {
+        if v.size == 0 {
+            return;
+        }
+        let (s1, s2) = (self.size as f64, v.size as f64);
+        let (m1, m2) = (self.mean(), v.mean());
+        let meandiffsq = (m1 - m2) * (m1 - m2);
+
+        self.size += v.size;
+        self.non_positive += v.non_positive;
+
+        self.mean = NeumaierSum::default();
+        self.mean.add(m1);
+        self.mean.add(s2 / (s1 + s2) * (m2 - m1));
+
+        self.q.add(v.q.sum);
+        self.q.add(v.q.c);
+        self.q.add(meandiffsq * s1 * s2 / (s1 + s2));
+        self.recip_sum.add(v.recip_sum.sum);
+        self.recip_sum.add(v.recip_sum.c);
+        self.ln_sum.add(v.ln_sum.sum);
+        self.ln_sum.add(v.ln_sum.c);
+    }
+}
+
+impl fmt::Debug for CompensatedStats {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_rounded(&RoundPolicy::new(), f)
+    }
+}
+
+impl RoundedDisplay for CompensatedStats {
+    fn fmt_rounded(&self, policy: &RoundPolicy, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} +/- {}",
+            policy.format(self.mean()),
+            policy.format(self.stddev())
+        )
+    }
+}
+
+impl<T: ToPrimitive> FromIterator<T> for CompensatedStats {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = T>>(it: I) -> CompensatedStats {
+        let mut v = CompensatedStats::new();
+        v.extend(it);
+        v
+    }
+}
+
+impl<T: ToPrimitive> Extend<T> for CompensatedStats {
+    #[inline]
+    fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
+        for sample in it {
+            self.add(&sample);
+        }
+    }
+}
+
+/// Online state for the common combination of mean, variance, standard
+/// deviation, minimum, maximum and sum.
+///
+/// Every `add` updates an `OnlineStats`, a `MinMax` and an `ExactSum`
+/// together, so callers need neither a second accumulator nor a second
+/// pass over the data. The sum of integer data is exact.
+#[derive(Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct OnlineSummary {
+    stats: OnlineStats,
+    minmax: MinMax<f64>,
+    #[serde(default)]
+    sum: ExactSum,
+}
+
+impl OnlineSummary {
+    /// Create initial empty state.
+    #[must_use]
+    pub fn new() -> OnlineSummary {
+        Default::default()
+    }
+
+    /// Add a new sample.
+    #[inline]
+    pub fn add<T: ToPrimitive>(&mut self, sample: &T) {
+        let x = sample.to_f64().unwrap();
+        self.stats.add(&x);
+        self.minmax.add(x);
+        self.sum.add_numeric(sample);
+    }
+
+    /// Splits this summary into its mean/variance and min/max parts.
+    #[inline]
+    #[must_use]
+    pub fn into_parts(self) -> (OnlineStats, MinMax<f64>) {
+        (self.stats, self.minmax)
+    }
+
+    /// Returns the mean, variance and standard deviation state.
+    #[inline]
+    #[must_use]
+    pub const fn stats(&self) -> &OnlineStats {
+        &self.stats
+    }
+
+    /// Returns the minimum and maximum state.
+    #[inline]
+    #[must_use]
+    pub const fn minmax(&self) -> &MinMax<f64> {
+        &self.minmax
+    }
+
+    /// Return the current mean.
+    #[inline]
+    #[must_use]
+    pub const fn mean(&self) -> f64 {
+        self.stats.mean()
+    }
+
+    /// Return the current variance.
+    #[inline]
+    #[must_use]
+    pub fn variance(&self) -> f64 {
+        self.stats.variance()
+    }
+
+    /// Return the current standard deviation.
+    #[inline]
+    #[must_use]
+    pub fn stddev(&self) -> f64 {
+        self.stats.stddev()
+    }
+
+    /// Returns the number of samples equal to zero.
+    #[inline]
+    #[must_use]
+    pub const fn zero_count(&self) -> u64 {
+        self.stats.zero_count()
+    }
+
+    /// Returns the fraction of samples equal to zero, or `NaN` if there
+    /// are no samples.
+    #[inline]
+    #[must_use]
+    pub fn zero_ratio(&self) -> f64 {
+        self.stats.zero_ratio()
+    }
+
+    /// Returns the numbers of negative, zero and positive samples.
+    #[inline]
+    #[must_use]
+    pub const fn sign_counts(&self) -> (u64, u64, u64) {
+        self.stats.sign_counts()
+    }
+
+    /// Returns the number of samples beyond `MAX_SAFE_INTEGER`, which may
+    /// have been rounded, so that the mean and variance may be imprecise.
+    #[inline]
+    #[must_use]
+    pub const fn imprecise_count(&self) -> u64 {
+        self.stats.imprecise_count()
+    }
+
+    /// Returns the exact sum, if every sample was an integer and the sum
+    /// did not overflow an `i128`.
+    #[inline]
+    #[must_use]
+    pub fn exact_sum(&self) -> Option<i128> {
+        // state saved before the sum was tracked has no sum of its samples
+        if self.sum.len() != self.stats.len() as u64 {
+            return None;
+        }
+        self.sum.sum()
+    }
+
+    /// Returns the sum, which is exact (up to the final rounding to `f64`)
+    /// if `exact_sum` is available, and the mean times the number of
+    /// samples otherwise.
+    #[inline]
+    #[must_use]
+    pub fn sum(&self) -> f64 {
+        self.exact_sum()
+            .map_or_else(|| self.mean() * self.len() as f64, |sum| sum as f64)
+    }
+
+    /// Returns the minimum, or `None` if there are no samples.
+    #[inline]
+    #[must_use]
+    pub fn min(&self) -> Option<f64> {
+        self.minmax.min().copied()
+    }
+
+    /// Returns the maximum, or `None` if there are no samples.
+    #[inline]
+    #[must_use]
+    pub fn max(&self) -> Option<f64> {
+        self.minmax.max().copied()
+    }
+
+    /// Returns the number of data points.
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.stats.len()
+    }
+
+    /// Returns if empty.
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.stats.is_empty()
+    }
+}
+
+impl Commute for OnlineSummary {
+    #[inline]
+    fn merge(&mut self, v: OnlineSummary) {
+        self.stats.merge(v.stats);
+        self.minmax.merge(v.minmax);
+        self.sum.merge(v.sum);
+    }
+}
+
+impl fmt::Debug for OnlineSummary {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:?} in [{:?}, {:?}]",
+            self.stats,
+            self.min(),
+            self.max()
+        )
+    }
+}
+
+/// Shows the minimum and maximum as they were added.
+impl fmt::Display for OnlineSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}, range {}", self.stats, self.minmax)
+    }
+}
+
+impl RoundedDisplay for OnlineSummary {
+    fn fmt_rounded(&self, policy: &RoundPolicy, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}, range {}",
+            policy.apply(&self.stats),
+            policy.apply(&self.minmax)
+        )
+    }
+}
+
+impl<T: ToPrimitive> FromIterator<T> for OnlineSummary {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = T>>(it: I) -> OnlineSummary {
+        let mut v = OnlineSummary::new();
+        v.extend(it);
+        v
+    }
+}
+
+impl<T: ToPrimitive> Extend<T> for OnlineSummary {
     #[inline]
     fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
         for sample in it {
@@ -175,9 +1140,191 @@ impl<T: ToPrimitive> Extend<T> for OnlineStats {
 
 #[cfg(test)]
 mod test {
-    use super::OnlineStats;
+    use super::{
+        CompensatedStats, OnlineStats, OnlineStatsSnapshotV1, OnlineStatsSnapshotV2, OnlineSummary,
+    };
     use {crate::merge_all, crate::Commute};
 
+    #[test]
+    fn normal_quantile() {
+        let stats = OnlineStats::from_slice(&[2, 4, 4, 4, 5, 5, 7, 9]);
+        assert!((stats.approx_quantile(0.5) - 5.0).abs() < 1e-9);
+        assert!((stats.approx_quantile(0.975) - 8.919_928).abs() < 1e-5);
+        assert!(stats.approx_quantile(0.0).is_nan());
+        assert!((stats.approx_mean_abs_deviation() - 1.595_769).abs() < 1e-6);
+        assert!(OnlineStats::new().approx_mean_abs_deviation().is_nan());
+    }
+
+    #[test]
+    fn free_functions() {
+        let data = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+        assert_eq!(crate::mean(&data), 5.0);
+        assert_eq!(crate::variance(&data), 4.0);
+        assert_eq!(crate::stddev(data.iter()), 2.0);
+    }
+
+    #[test]
+    fn normality() {
+        let data: Vec<f64> = (1..=40)
+            .map(|i| f64::from(i * i % 17) + f64::from(i).sqrt())
+            .collect();
+        let moments: crate::Moments = data.iter().copied().collect();
+        let tracked = |values: &[f64]| {
+            let mut stats = OnlineStats::with_higher_moments();
+            stats.extend(values.iter().copied());
+            stats
+        };
+        let mut merged = tracked(&data[..13]);
+        merged.merge(tracked(&data[13..]));
+        let mut sliced = OnlineStats::with_higher_moments();
+        sliced.add_slice(&data);
+        for stats in [tracked(&data), merged, sliced] {
+            assert!((stats.skewness() - moments.skewness()).abs() < 1e-12);
+            assert!((stats.kurtosis() - moments.kurtosis()).abs() < 1e-12);
+            assert_eq!(
+                stats.is_clearly_non_normal(0.05),
+                moments.is_clearly_non_normal(0.05)
+            );
+        }
+        // an empty state takes the moments of the other side
+        let mut empty = OnlineStats::new();
+        empty.merge(tracked(&data));
+        assert_eq!(empty.moments(), Some(&moments));
+
+        let two_point = tracked(&(0..100).map(|i| f64::from(i % 2)).collect::<Vec<_>>());
+        let r = two_point.jarque_bera().unwrap();
+        assert!((r.statistic - 100.0 / 6.0).abs() < 1e-9);
+        assert!(two_point.is_clearly_non_normal(0.05));
+
+        let constant = tracked(&[3.0, 3.0, 3.0]);
+        assert!(constant.jarque_bera().is_none());
+        assert!(!constant.is_clearly_non_normal(0.05));
+        // the higher moments are not tracked by default, nor after
+        // restoring from parts or merging with untracked state
+        assert!(OnlineStats::from_slice(&data).kurtosis().is_nan());
+        assert!(OnlineStats::from_slice(&data).jarque_bera().is_none());
+        let mut restored = OnlineStats::from_parts(3, 2.0f64, 2.0, 0, 0, (0, 0, 3));
+        assert!(restored.kurtosis().is_nan());
+        restored.merge(tracked(&[1.0, 5.0]));
+        assert!(restored.skewness().is_nan());
+    }
+
+    #[test]
+    fn effect_sizes() {
+        let a = OnlineStats::from_slice(&[5, 7, 6, 8, 9]);
+        let b = OnlineStats::from_slice(&[3, 4, 6, 2, 5]);
+        // pooled sample variance is (10 + 10) / 8
+        let d = 3.0 / 2.5f64.sqrt();
+        assert!((a.cohens_d(&b) - d).abs() < 1e-12);
+        assert!((b.cohens_d(&a) + d).abs() < 1e-12);
+        assert!((a.hedges_g(&b) - d * (1.0 - 3.0 / 31.0)).abs() < 1e-12);
+        let one = OnlineStats::from_slice(&[1]);
+        assert!(one.cohens_d(&one).is_nan());
+        let flat = OnlineStats::from_slice(&[2, 2]);
+        assert!(flat.cohens_d(&flat).is_nan());
+        // one side alone has a variance, but there is nothing to compare to
+        let empty = OnlineStats::new();
+        assert!(a.cohens_d(&empty).is_nan());
+        assert!(empty.hedges_g(&a).is_nan());
+        assert!(!one.cohens_d(&a).is_nan());
+    }
+
+    #[test]
+    fn from_parts() {
+        let mut stats = OnlineStats::from_slice(&[1, 0, 3, 2, 0, 6]);
+        stats.add_null();
+        let restored = OnlineStats::from_parts(
+            stats.len() as u64,
+            stats.mean(),
+            stats.m2(),
+            stats.null_count(),
+            stats.imprecise_count(),
+            stats.sign_counts(),
+        );
+        assert_eq!(
+            (restored.len(), restored.mean(), restored.m2()),
+            (stats.len(), stats.mean(), stats.m2())
+        );
+        assert_eq!(
+            (restored.null_count(), restored.sign_counts()),
+            (stats.null_count(), (0, 3, 4))
+        );
+        assert!((restored.m2() - stats.variance() * 7.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn snapshot_v1() {
+        let mut stats = OnlineStats::from_slice(&[1.5, 1e17, -3.0]);
+        stats.extend_options([None::<f64>]);
+        let snap = OnlineStatsSnapshotV1::from(&stats);
+        assert_eq!(snap.imprecise, 1);
+        let restored = OnlineStats::from(snap);
+        assert_eq!(
+            (restored.len(), restored.mean(), restored.m2()),
+            (stats.len(), stats.mean(), stats.m2())
+        );
+        assert_eq!(restored.null_count(), 1);
+        assert_eq!(restored.imprecise_count(), 1);
+        // the snapshot predates the higher moments
+        assert!(restored.kurtosis().is_nan());
+    }
+
+    #[test]
+    fn snapshot_v2() {
+        let mut stats = OnlineStats::with_higher_moments();
+        stats.extend([1.5, 0.0, 1e17, -3.0]);
+        stats.extend_options([None::<f64>]);
+        let restored = OnlineStats::from(OnlineStatsSnapshotV2::from(&stats));
+        assert_eq!(
+            (restored.len(), restored.mean(), restored.m2()),
+            (stats.len(), stats.mean(), stats.m2())
+        );
+        assert_eq!((restored.null_count(), restored.imprecise_count()), (1, 1));
+        assert_eq!(restored.sign_counts(), (1, 1, 2));
+        assert_eq!(restored.kurtosis(), stats.kurtosis());
+        assert_eq!(restored, stats);
+
+        let upgraded = OnlineStatsSnapshotV2::from(OnlineStatsSnapshotV1::from(&stats));
+        assert_eq!(
+            (upgraded.m3, upgraded.zeros, upgraded.positives),
+            (None, 0, 0)
+        );
+        assert!(OnlineStats::from(upgraded).kurtosis().is_nan());
+    }
+
+    #[test]
+    fn display() {
+        let summary: OnlineSummary = [2, 4, 4, 4, 5, 5, 7, 9].into_iter().collect();
+        assert_eq!(
+            summary.to_string(),
+            "n 8, mean 5.0000000000, stddev 2.0000000000, range [2, 9]"
+        );
+    }
+
+    #[test]
+    fn online_summary() {
+        let mut s1: OnlineSummary = [4u8, 1, 7].into_iter().collect();
+        let s2: OnlineSummary = [2.5f64, 9.0].into_iter().collect();
+        s1.merge(s2);
+        let expected = OnlineStats::from_slice(&[4.0, 1.0, 7.0, 2.5, 9.0]);
+        assert_eq!(s1.len(), 5);
+        assert!((s1.mean() - expected.mean()).abs() < 1e-12);
+        assert!((s1.stddev() - expected.stddev()).abs() < 1e-12);
+        assert_eq!((s1.min(), s1.max()), (Some(1.0), Some(9.0)));
+        assert_eq!(s1.minmax().range(), Some(8.0));
+        assert_eq!(OnlineSummary::new().min(), None);
+        // 2.5 is not an integer, so the sum is not exact
+        assert_eq!((s1.exact_sum(), s1.sum()), (None, 23.5));
+        let (stats, minmax) = s1.into_parts();
+        assert_eq!(stats.len(), minmax.len());
+
+        let big = (1i64 << 53) + 1;
+        let ints: OnlineSummary = [big, 1, 1].into_iter().collect();
+        assert_eq!(ints.exact_sum(), Some(i128::from(big) + 2));
+        assert_eq!(ints.sum(), (big + 2) as f64);
+        assert_eq!(ints.imprecise_count(), 1);
+    }
+
     #[test]
     fn online() {
         // TODO: Convert this to a quickcheck test.
@@ -221,4 +1368,156 @@ mod test {
             merge_all(vars.into_iter()).unwrap().variance()
         );
     }
+
+    #[test]
+    fn online_options() {
+        let expected = OnlineStats::from_slice(&[1usize, 2, 3, 4]);
+
+        let mut got = OnlineStats::from_options(vec![Some(1usize), None, Some(2)]);
+        got.extend_options(vec![None, Some(3usize), Some(4)]);
+        assert_eq!(got.len(), 4);
+        assert_eq!(got.null_count(), 2);
+        assert_eq!(expected.mean(), got.mean());
+        assert_eq!(expected.variance(), got.variance());
+
+        got.add_null();
+        assert_eq!((got.len(), got.null_count()), (5, 2));
+    }
+
+    #[test]
+    fn deserialize_without_counts() {
+        let stats: OnlineStats = serde_json::from_str(r#"{"size":2,"mean":1.5,"q":0.5}"#).unwrap();
+        assert_eq!((stats.len(), stats.mean(), stats.null_count()), (2, 1.5, 0));
+    }
+
+    #[test]
+    fn compensated_mixed_magnitudes() {
+        // one huge value followed by many small ones, whose contributions
+        // to the mean are mostly rounded away by a plain accumulator
+        let mut data = vec![1e8];
+        data.extend(std::iter::repeat(0.1).take(1_000_000));
+        let n = data.len() as f64;
+        let exact_mean = (1e8 + 0.1 * 1e6) / n;
+
+        let plain = OnlineStats::from_slice(&data);
+        let compensated: CompensatedStats = data.iter().copied().collect();
+        let plain_err = (plain.mean() - exact_mean).abs();
+        let compensated_err = (compensated.mean() - exact_mean).abs();
+        assert!(compensated_err <= plain_err);
+        assert!(compensated_err < 1e-9);
+    }
+
+    #[test]
+    fn compensated_merge_and_means() {
+        let mut c1: CompensatedStats = [1.0, 2.0].into_iter().collect();
+        let c2: CompensatedStats = [4.0, 8.0].into_iter().collect();
+        c1.merge(c2);
+        let expected = OnlineStats::from_slice(&[1.0, 2.0, 4.0, 8.0]);
+        assert_eq!(c1.len(), 4);
+        assert!((c1.mean() - expected.mean()).abs() < 1e-12);
+        assert!((c1.variance() - expected.variance()).abs() < 1e-12);
+        assert!((c1.geometric_mean() - 64f64.powf(0.25)).abs() < 1e-12);
+        assert!((c1.harmonic_mean() - 4.0 / 1.875).abs() < 1e-12);
+
+        c1.add(&0);
+        assert!(c1.harmonic_mean().is_nan());
+        assert!(CompensatedStats::new().geometric_mean().is_nan());
+    }
+
+    #[test]
+    fn imprecise_integers() {
+        let mut stats = OnlineStats::from_slice(&[1u64, (1 << 53) - 1]);
+        assert!(stats.is_precise());
+        stats.add(&((1u64 << 53) + 1));
+        stats.merge(OnlineStats::from_slice(&[-(1i64 << 60)]));
+        assert_eq!(stats.imprecise_count(), 2);
+        assert!(!stats.is_precise());
+    }
+
+    #[test]
+    fn online_par_from_slice() {
+        let data: Vec<u64> = (0..200_001).map(|i| i * 7 % 1013).collect();
+        let expected = OnlineStats::from_slice(&data);
+        let got = OnlineStats::par_from_slice(&data);
+        assert_eq!(got.len(), expected.len());
+        assert!((got.mean() - expected.mean()).abs() < 1e-9);
+        assert!((got.variance() - expected.variance()).abs() < 1e-6);
+        assert!(OnlineStats::par_from_slice::<u64>(&[]).is_empty());
+
+        let mut empty = OnlineStats::new();
+        empty.merge(OnlineStats::new());
+        assert_eq!(empty.mean(), 0.0);
+    }
+
+    #[test]
+    fn online_f64_slice() {
+        let data: Vec<f64> = (0..1001).map(|i| f64::from(i) * 0.5 - 7.0).collect();
+        let expected = OnlineStats::from_slice(&data);
+        let got = OnlineStats::from_f64_slice(&data);
+        assert_eq!(got.len(), expected.len());
+        assert!((got.mean() - expected.mean()).abs() < 1e-9);
+        assert!((got.variance() - expected.variance()).abs() < 1e-6);
+        assert_eq!(
+            OnlineStats::from_f64_slice(&[1e16, 1.0]).imprecise_count(),
+            1
+        );
+        assert!(OnlineStats::from_f64_slice(&[]).is_empty());
+        assert_eq!(got.zero_count(), expected.zero_count());
+    }
+
+    #[test]
+    fn online_add_slice() {
+        let data: Vec<i32> = (0..2500).map(|i| (i * 37) % 101 - 50).collect();
+        let mut expected = OnlineStats::from_slice(&[1e16]);
+        expected.extend(data.iter().copied());
+        let mut got = OnlineStats::from_slice(&[1e16]);
+        got.add_slice(&data);
+        assert_eq!(got.len(), expected.len());
+        assert!((got.mean() - expected.mean()).abs() / expected.mean() < 1e-12);
+        assert!((got.variance() - expected.variance()).abs() / expected.variance() < 1e-9);
+        assert_eq!(got.sign_counts(), expected.sign_counts());
+        assert_eq!(got.imprecise_count(), 1);
+    }
+
+    #[test]
+    fn zero_count() {
+        let mut stats = OnlineStats::from_slice(&[0, 3, 0, -1]);
+        stats.merge(OnlineStats::from_slice(&[0.0, -0.0, 2.0, 5.0]));
+        assert_eq!(stats.zero_count(), 4);
+        assert_eq!(stats.zero_ratio(), 0.5);
+        let restored = OnlineStats::from(OnlineStatsSnapshotV2::from(&stats));
+        assert_eq!(restored.sign_counts(), stats.sign_counts());
+        assert!(OnlineStats::new().zero_ratio().is_nan());
+
+        let summary: OnlineSummary = [0u8, 1, 2, 0].into_iter().collect();
+        assert_eq!(summary.zero_count(), 2);
+        assert_eq!(summary.zero_ratio(), 0.5);
+    }
+
+    #[test]
+    fn sign_counts() {
+        let mut stats = OnlineStats::from_slice(&[-2.0, 0.0, 3.5, f64::NAN]);
+        stats.merge(OnlineStats::from_f64_slice(&[-1.0, -4.0, 7.0]));
+        assert_eq!(stats.sign_counts(), (3, 1, 2));
+        assert_eq!((stats.negative_count(), stats.positive_count()), (3, 2));
+        assert_eq!(stats.len(), 7);
+
+        let summary: OnlineSummary = [-1i32, 5, -3].into_iter().collect();
+        assert_eq!(summary.sign_counts(), (2, 0, 1));
+    }
+
+    #[test]
+    fn online_f32() {
+        let single: OnlineStats<f32> = [1u8, 2, 3, 4].into_iter().collect();
+        let mut merged: OnlineStats<f32> = [1u8, 2].into_iter().collect();
+        merged.merge([3u8, 4].into_iter().collect());
+        assert_eq!(single.mean(), 2.5f32);
+        assert_eq!(merged.mean(), 2.5f32);
+        assert_eq!(merged.variance(), 1.25f32);
+        assert!(single.mem_usage() < OnlineStats::new().mem_usage());
+
+        // f32 is only exact for integers below 2^24
+        let big: OnlineStats<f32> = [(1u32 << 24) - 1, 1 << 24].into_iter().collect();
+        assert_eq!(big.imprecise_count(), 1);
+    }
 }
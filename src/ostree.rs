@@ -0,0 +1,419 @@
+use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+
+use crate::hashing::mix64;
+use crate::unsorted::quantile_at;
+use crate::{Commute, Partial, QuantileMethod};
+
+/// The index of a missing child.
+const NIL: usize = usize::MAX;
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+struct Node<T> {
+    value: Partial<T>,
+    priority: u64,
+    left: usize,
+    right: usize,
+    /// The number of nodes in the subtree rooted here.
+    size: usize,
+}
+
+/// A sorted multiset that supports `O(log n)` inserts and `O(log n)`
+/// queries for the `k`th smallest value, and so for any quantile.
+///
+/// `Unsorted` sorts its data once and answers every query after that in
+/// constant time, but has to re-sort (or insert in `O(n)` with
+/// `add_sorted`) when data arrives between queries. For workloads that
+/// interleave adds with frequent quantile queries, such as a running
+/// median, this tree keeps both costs logarithmic.
+///
+/// It is a treap, i.e. a binary search tree that is also a heap on
+/// pseudorandom priorities, with the nodes in one arena. The priorities
+/// are a fixed hash of the insertion count, so the shape of the tree, and
+/// therefore the cost of each operation, is the same in every run. The
+/// depth is logarithmic in expectation for data whose order is unrelated
+/// to the hash, but since the priorities are predictable, values ordered
+/// to match them (by an adversary who knows the hash) can still make the
+/// tree degenerate, with linear costs and deep recursion. Each value uses
+/// about `4 * size_of::<usize>()` bytes on top of its own size.
+///
+/// Like `Unsorted`, this works on types without a total ordering, such as
+/// `f64`, but the position of values like `NaN` is arbitrary.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(
+    try_from = "OrderStatTreeParts<T>",
+    bound(deserialize = "T: Deserialize<'de> + PartialOrd")
+)]
+pub struct OrderStatTree<T> {
+    nodes: Vec<Node<T>>,
+    root: usize,
+    method: QuantileMethod,
+}
+
+/// The serialized form of an `OrderStatTree`, which is checked before use.
+#[derive(Deserialize)]
+struct OrderStatTreeParts<T> {
+    nodes: Vec<Node<T>>,
+    root: usize,
+    method: QuantileMethod,
+}
+
+impl<T: PartialOrd> TryFrom<OrderStatTreeParts<T>> for OrderStatTree<T> {
+    type Error = String;
+
+    fn try_from(v: OrderStatTreeParts<T>) -> Result<OrderStatTree<T>, String> {
+        let nodes = &v.nodes;
+        if nodes.is_empty() != (v.root == NIL) || (v.root != NIL && v.root >= nodes.len()) {
+            return Err("the root of the order statistic tree is invalid".to_owned());
+        }
+        // every node must be reachable from the root exactly once, which
+        // also rules out cycles
+        let mut seen = vec![false; nodes.len()];
+        let mut preorder = Vec::with_capacity(nodes.len());
+        let mut stack = vec![v.root];
+        while let Some(node) = stack.pop() {
+            if node == NIL {
+                continue;
+            }
+            if node >= nodes.len() || std::mem::replace(&mut seen[node], true) {
+                return Err("the order statistic tree has an invalid child".to_owned());
+            }
+            preorder.push(node);
+            stack.extend([nodes[node].left, nodes[node].right]);
+        }
+        if preorder.len() != nodes.len() {
+            return Err("the order statistic tree has unreachable nodes".to_owned());
+        }
+        // children come after their parents in preorder
+        let size = |node: usize| if node == NIL { 0 } else { nodes[node].size };
+        for &node in preorder.iter().rev() {
+            let Node {
+                left,
+                right,
+                size: n,
+                ..
+            } = nodes[node];
+            if n != 1 + size(left) + size(right) {
+                return Err("the order statistic tree has a wrong subtree size".to_owned());
+            }
+        }
+        // an in-order walk must visit the values in order
+        let mut prev: Option<usize> = None;
+        let (mut stack, mut node) = (Vec::new(), v.root);
+        while node != NIL || !stack.is_empty() {
+            while node != NIL {
+                stack.push(node);
+                node = nodes[node].left;
+            }
+            let current = stack.pop().unwrap();
+            if prev.is_some_and(|p| nodes[current].value < nodes[p].value) {
+                return Err("the order statistic tree is not sorted".to_owned());
+            }
+            prev = Some(current);
+            node = nodes[current].right;
+        }
+        Ok(OrderStatTree {
+            nodes: v.nodes,
+            root: v.root,
+            method: v.method,
+        })
+    }
+}
+
+impl<T: PartialOrd> OrderStatTree<T> {
+    /// Create initial empty state.
+    #[must_use]
+    pub fn new() -> OrderStatTree<T> {
+        Default::default()
+    }
+
+    /// Sets how `quantile` picks a value between two data points.
+    #[inline]
+    pub fn set_quantile_method(&mut self, method: QuantileMethod) {
+        self.method = method;
+    }
+
+    /// Returns how `quantile` picks a value between two data points.
+    #[inline]
+    #[must_use]
+    pub const fn quantile_method(&self) -> QuantileMethod {
+        self.method
+    }
+
+    /// Returns the number of values.
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns true if there are no values.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Add a value.
+    ///
+    /// A value equal to existing ones is placed after them.
+    pub fn add(&mut self, value: T) {
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            value: Partial(value),
+            priority: mix64(index as u64),
+            left: NIL,
+            right: NIL,
+            size: 1,
+        });
+        let (left, right) = self.split(self.root, index);
+        let left = self.join(left, index);
+        self.root = self.join(left, right);
+    }
+
+    /// Returns the size of the subtree rooted at `node`.
+    #[inline]
+    fn size(&self, node: usize) -> usize {
+        if node == NIL {
+            0
+        } else {
+            self.nodes[node].size
+        }
+    }
+
+    #[inline]
+    fn update(&mut self, node: usize) {
+        let Node { left, right, .. } = self.nodes[node];
+        self.nodes[node].size = 1 + self.size(left) + self.size(right);
+    }
+
+    /// Splits the subtree rooted at `node` into the values that are at
+    /// most the value of `pivot`, and those that are greater.
+    fn split(&mut self, node: usize, pivot: usize) -> (usize, usize) {
+        if node == NIL {
+            return (NIL, NIL);
+        }
+        if self.nodes[node].value <= self.nodes[pivot].value {
+            let (left, right) = self.split(self.nodes[node].right, pivot);
+            self.nodes[node].right = left;
+            self.update(node);
+            (node, right)
+        } else {
+            let (left, right) = self.split(self.nodes[node].left, pivot);
+            self.nodes[node].left = right;
+            self.update(node);
+            (left, node)
+        }
+    }
+
+    /// Joins two subtrees, where every value in `left` is at most every
+    /// value in `right`.
+    fn join(&mut self, left: usize, right: usize) -> usize {
+        if left == NIL {
+            return right;
+        }
+        if right == NIL {
+            return left;
+        }
+        if self.nodes[left].priority > self.nodes[right].priority {
+            let joined = self.join(self.nodes[left].right, right);
+            self.nodes[left].right = joined;
+            self.update(left);
+            left
+        } else {
+            let joined = self.join(left, self.nodes[right].left);
+            self.nodes[right].left = joined;
+            self.update(right);
+            right
+        }
+    }
+
+    /// Returns the `k`th smallest value, counting from `0`.
+    ///
+    /// `None` is returned if there are not more than `k` values.
+    #[must_use]
+    pub fn select(&self, mut k: usize) -> Option<&T> {
+        let mut node = self.root;
+        while node != NIL {
+            let Node {
+                left, right, value, ..
+            } = &self.nodes[node];
+            let left_size = self.size(*left);
+            match k.cmp(&left_size) {
+                std::cmp::Ordering::Less => node = *left,
+                std::cmp::Ordering::Equal => return Some(&value.0),
+                std::cmp::Ordering::Greater => {
+                    k -= left_size + 1;
+                    node = *right;
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns the number of values less than `value`.
+    #[must_use]
+    pub fn rank(&self, value: &T) -> usize {
+        let mut rank = 0;
+        let mut node = self.root;
+        while node != NIL {
+            let current = &self.nodes[node];
+            if current.value.0 < *value {
+                rank += self.size(current.left) + 1;
+                node = current.right;
+            } else {
+                node = current.left;
+            }
+        }
+        rank
+    }
+
+    /// Returns the smallest value.
+    #[inline]
+    #[must_use]
+    pub fn min(&self) -> Option<&T> {
+        self.select(0)
+    }
+
+    /// Returns the largest value.
+    #[inline]
+    #[must_use]
+    pub fn max(&self) -> Option<&T> {
+        self.select(self.len().checked_sub(1)?)
+    }
+}
+
+impl<T: PartialOrd + ToPrimitive> OrderStatTree<T> {
+    /// Returns the `q`th quantile, for `q` in `[0, 1]`, with the same
+    /// result as `Unsorted::quantile_with` and this tree's quantile
+    /// method.
+    ///
+    /// `None` is returned if there is no data or `q` is not in `[0, 1]`.
+    #[must_use]
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        quantile_at(self.len(), q, self.method, |i| {
+            self.select(i).unwrap().to_f64().unwrap()
+        })
+    }
+
+    /// Returns the median, by this tree's quantile method.
+    #[inline]
+    #[must_use]
+    pub fn median(&self) -> Option<f64> {
+        self.quantile(0.5)
+    }
+}
+
+impl<T: PartialOrd> Commute for OrderStatTree<T> {
+    #[inline]
+    fn merge(&mut self, v: OrderStatTree<T>) {
+        self.nodes.reserve(v.nodes.len());
+        for node in v.nodes {
+            self.add(node.value.0);
+        }
+    }
+}
+
+impl<T> Default for OrderStatTree<T> {
+    #[inline]
+    fn default() -> OrderStatTree<T> {
+        OrderStatTree {
+            nodes: Vec::new(),
+            root: NIL,
+            method: QuantileMethod::default(),
+        }
+    }
+}
+
+impl<T: PartialOrd> FromIterator<T> for OrderStatTree<T> {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = T>>(it: I) -> OrderStatTree<T> {
+        let mut v = OrderStatTree::new();
+        v.extend(it);
+        v
+    }
+}
+
+impl<T: PartialOrd> Extend<T> for OrderStatTree<T> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
+        for value in it {
+            self.add(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::OrderStatTree;
+    use crate::{Commute, QuantileMethod, Unsorted};
+
+    #[test]
+    fn select_and_rank() {
+        let mut tree: OrderStatTree<u32> = [5, 1, 4, 4, 2].into_iter().collect();
+        assert_eq!(tree.len(), 5);
+        assert_eq!((tree.min(), tree.max()), (Some(&1), Some(&5)));
+        assert_eq!(tree.select(2), Some(&4));
+        assert_eq!(tree.select(5), None);
+        assert_eq!((tree.rank(&4), tree.rank(&6), tree.rank(&0)), (2, 5, 0));
+        assert_eq!(tree.median(), Some(4.0));
+        tree.add(0);
+        assert_eq!(tree.median(), Some(3.0));
+
+        let empty = OrderStatTree::<f64>::new();
+        assert_eq!((empty.max(), empty.median()), (None, None));
+    }
+
+    #[test]
+    fn deserialize_checks_structure() {
+        let tree: OrderStatTree<u32> = [5, 1, 4, 4, 2].into_iter().collect();
+        let json = serde_json::to_string(&tree).unwrap();
+        let back: OrderStatTree<u32> = serde_json::from_str(&json).unwrap();
+        assert!(back == tree);
+        assert_eq!(back.median(), Some(4.0));
+
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let invalid = |edit: &dyn Fn(&mut serde_json::Value)| {
+            let mut v = value.clone();
+            edit(&mut v);
+            serde_json::from_value::<OrderStatTree<u32>>(v).is_err()
+        };
+        assert!(invalid(&|v| v["root"] = 5.into()));
+        assert!(invalid(&|v| v["nodes"] = serde_json::json!([])));
+        let root = value["root"].as_u64().unwrap() as usize;
+        assert!(invalid(&|v| v["nodes"][root]["left"] = root.into()));
+        assert!(invalid(&|v| v["nodes"][root]["size"] = 4.into()));
+        // a root with a left subtree must not be smaller than all of it
+        let unordered = if value["nodes"][root]["left"] == usize::MAX {
+            9
+        } else {
+            0
+        };
+        assert!(invalid(&|v| v["nodes"][root]["value"] = unordered.into()));
+    }
+
+    #[test]
+    fn matches_unsorted() {
+        let data: Vec<i64> = (0..3000).map(|i| (i * 7919) % 1013 - 500).collect();
+        let mut tree: OrderStatTree<i64> = data[..1500].iter().copied().collect();
+        tree.merge(data[1500..].iter().copied().collect());
+        let mut unsorted: Unsorted<i64> = data.into_iter().collect();
+        assert_eq!(tree.len(), unsorted.len());
+        for (i, v) in unsorted.as_sorted_slice().iter().enumerate() {
+            assert_eq!(tree.select(i), Some(v));
+        }
+        for method in [
+            QuantileMethod::Linear,
+            QuantileMethod::Lower,
+            QuantileMethod::Higher,
+            QuantileMethod::Nearest,
+            QuantileMethod::Midpoint,
+        ] {
+            tree.set_quantile_method(method);
+            for q in [0.0, 0.1, 0.25, 0.5, 0.9, 0.999, 1.0] {
+                assert_eq!(tree.quantile(q), unsorted.quantile_with(q, method));
+            }
+        }
+        assert_eq!(tree.quantile(1.5), None);
+    }
+}
@@ -0,0 +1,156 @@
+use std::cmp::Ordering;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::sync::{Arc, PoisonError, RwLock};
+
+use rayon::slice::ParallelSliceMut;
+use rayon::ThreadPool;
+use serde::{Deserialize, Serialize};
+
+/// Controls whether a computation runs on the rayon thread pool.
+///
+/// Parallelism has a fixed overhead, so it only pays off on large inputs.
+/// Accumulators that support it (e.g. `Unsorted::set_parallelism`) use
+/// this for every step that can run in parallel, such as sorting and the
+/// MAD computation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Parallelism {
+    /// Always run on the current thread.
+    Sequential,
+    /// Run in parallel when the input has at least `AUTO_THRESHOLD` values.
+    #[default]
+    Auto,
+    /// Always run in parallel.
+    Force,
+    /// Run in parallel when the input has at least this many values.
+    Threshold(usize),
+}
+
+impl Parallelism {
+    /// The input length at which `Auto` switches to running in parallel.
+    pub const AUTO_THRESHOLD: usize = 10_000;
+
+    /// Returns true if an input of `len` values should be processed in
+    /// parallel.
+    #[inline]
+    #[must_use]
+    pub const fn is_parallel(self, len: usize) -> bool {
+        match self {
+            Parallelism::Sequential => false,
+            Parallelism::Auto => len >= Parallelism::AUTO_THRESHOLD,
+            Parallelism::Force => true,
+            Parallelism::Threshold(n) => len >= n,
+        }
+    }
+}
+
+static THREAD_POOL: RwLock<Option<Arc<ThreadPool>>> = RwLock::new(None);
+// lets `in_pool` skip the lock while no pool is set
+static HAS_THREAD_POOL: AtomicBool = AtomicBool::new(false);
+
+/// Run this crate's parallel work on `pool`, or on rayon's global pool if
+/// `None` (the default).
+///
+/// Embedding applications can use this to keep statistics from competing
+/// with their own rayon workers, e.g. by giving this crate a small pool of
+/// its own. Work started from a rayon worker thread, including inside a
+/// caller's `ThreadPool::install`, stays on that thread's pool instead.
+pub fn set_thread_pool(pool: Option<Arc<ThreadPool>>) {
+    let mut current = THREAD_POOL.write().unwrap_or_else(PoisonError::into_inner);
+    HAS_THREAD_POOL.store(pool.is_some(), AtomicOrdering::Release);
+    *current = pool;
+}
+
+/// Runs `f`, which does parallel work, on the pool set by `set_thread_pool`.
+#[inline]
+pub(crate) fn in_pool<R: Send, F: FnOnce() -> R + Send>(f: F) -> R {
+    if !HAS_THREAD_POOL.load(AtomicOrdering::Acquire) || rayon::current_thread_index().is_some() {
+        return f();
+    }
+    let pool = THREAD_POOL
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+    install(pool.as_deref(), f)
+}
+
+/// Runs `f` on `pool`, unless the current thread already belongs to a
+/// rayon pool, in which case `f` runs there.
+#[inline]
+fn install<R: Send, F: FnOnce() -> R + Send>(pool: Option<&ThreadPool>, f: F) -> R {
+    match pool {
+        Some(pool) if rayon::current_thread_index().is_none() => pool.install(f),
+        _ => f(),
+    }
+}
+
+/// Sorts `data` by `cmp`, in parallel if `parallelism` says so.
+#[inline]
+pub(crate) fn sort_unstable_by<T, F>(data: &mut [T], parallelism: Parallelism, cmp: F)
+where
+    T: Send,
+    F: Fn(&T, &T) -> Ordering + Send + Sync,
+{
+    if parallelism.is_parallel(data.len()) {
+        in_pool(|| data.par_sort_unstable_by(cmp));
+    } else {
+        data.sort_unstable_by(cmp);
+    }
+}
+
+/// Sorts `data` by `cmp`, preserving the order of equal elements, in
+/// parallel if `parallelism` says so.
+#[inline]
+pub(crate) fn sort_stable_by<T, F>(data: &mut [T], parallelism: Parallelism, cmp: F)
+where
+    T: Send,
+    F: Fn(&T, &T) -> Ordering + Send + Sync,
+{
+    if parallelism.is_parallel(data.len()) {
+        in_pool(|| data.par_sort_by(cmp));
+    } else {
+        data.sort_by(cmp);
+    }
+}
+
+/// Sorts `data` with `f64::total_cmp`, in parallel if `parallelism` says so.
+#[inline]
+pub(crate) fn sort_f64(data: &mut [f64], parallelism: Parallelism) {
+    let cmp = |a: &f64, b: &f64| -> Ordering { a.total_cmp(b) };
+    if parallelism.is_parallel(data.len()) {
+        in_pool(|| data.par_sort_unstable_by(cmp));
+    } else {
+        data.sort_unstable_by(cmp);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{install, Parallelism};
+
+    #[test]
+    fn thresholds() {
+        assert!(!Parallelism::Sequential.is_parallel(usize::MAX));
+        assert!(Parallelism::Force.is_parallel(0));
+        assert!(!Parallelism::Auto.is_parallel(Parallelism::AUTO_THRESHOLD - 1));
+        assert!(Parallelism::Auto.is_parallel(Parallelism::AUTO_THRESHOLD));
+        assert!(Parallelism::Threshold(5).is_parallel(5));
+        assert!(!Parallelism::Threshold(5).is_parallel(4));
+    }
+
+    #[test]
+    fn custom_thread_pool() {
+        let pool = |n| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(n)
+                .build()
+                .unwrap()
+        };
+        let (two, three) = (pool(2), pool(3));
+        assert_eq!(install(Some(&two), rayon::current_num_threads), 2);
+        assert!(install(Some(&two), rayon::current_thread_index).is_some());
+        assert!(install(None, rayon::current_thread_index).is_none());
+        // a caller's pool is kept rather than replaced
+        let nested = three.install(|| install(Some(&two), rayon::current_num_threads));
+        assert_eq!(nested, 3);
+    }
+}
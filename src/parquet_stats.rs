@@ -0,0 +1,103 @@
+use parquet::data_type::ByteArray;
+use parquet::file::statistics::Statistics;
+
+use crate::MinMax;
+
+/// Conversion of accumulated statistics into Parquet column chunk statistics.
+///
+/// This lets Parquet writers populate file metadata (min, max, null count and
+/// distinct count) from the same computation used for profiling.
+pub trait ToParquetStatistics {
+    /// Returns the Parquet statistics for this column.
+    ///
+    /// `distinct_count` is typically `Frequencies::cardinality`, or `None`
+    /// if it was not computed.
+    fn to_parquet_statistics(&self, distinct_count: Option<u64>) -> Statistics;
+}
+
+macro_rules! impl_to_parquet_statistics {
+    ($ty:ty, $ctor:ident) => {
+        impl ToParquetStatistics for MinMax<$ty> {
+            #[inline]
+            fn to_parquet_statistics(&self, distinct_count: Option<u64>) -> Statistics {
+                Statistics::$ctor(
+                    self.min().cloned(),
+                    self.max().cloned(),
+                    distinct_count,
+                    Some(self.null_count()),
+                    false,
+                )
+            }
+        }
+    };
+}
+
+impl_to_parquet_statistics!(bool, boolean);
+impl_to_parquet_statistics!(i32, int32);
+impl_to_parquet_statistics!(i64, int64);
+impl_to_parquet_statistics!(f32, float);
+impl_to_parquet_statistics!(f64, double);
+
+impl ToParquetStatistics for MinMax<Vec<u8>> {
+    #[inline]
+    fn to_parquet_statistics(&self, distinct_count: Option<u64>) -> Statistics {
+        Statistics::byte_array(
+            self.min().map(|v| ByteArray::from(v.clone())),
+            self.max().map(|v| ByteArray::from(v.clone())),
+            distinct_count,
+            Some(self.null_count()),
+            false,
+        )
+    }
+}
+
+impl ToParquetStatistics for MinMax<String> {
+    #[inline]
+    fn to_parquet_statistics(&self, distinct_count: Option<u64>) -> Statistics {
+        Statistics::byte_array(
+            self.min().map(|v| ByteArray::from(v.as_str())),
+            self.max().map(|v| ByteArray::from(v.as_str())),
+            distinct_count,
+            Some(self.null_count()),
+            false,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use parquet::basic::Type;
+    use parquet::file::statistics::Statistics;
+
+    use super::ToParquetStatistics;
+    use crate::{Frequencies, MinMax};
+
+    #[test]
+    fn int64_statistics() {
+        let data = [Some(4i64), None, Some(-2), Some(9), Some(4)];
+        let minmax: MinMax<i64> = data.iter().copied().collect();
+        let freqs: Frequencies<i64> = data.iter().flatten().copied().collect();
+        let stats = minmax.to_parquet_statistics(Some(freqs.cardinality()));
+
+        assert_eq!(stats.physical_type(), Type::INT64);
+        assert_eq!(stats.null_count_opt(), Some(1));
+        assert_eq!(stats.distinct_count_opt(), Some(3));
+        let Statistics::Int64(stats) = stats else {
+            unreachable!()
+        };
+        assert_eq!(stats.min_opt(), Some(&-2));
+        assert_eq!(stats.max_opt(), Some(&9));
+    }
+
+    #[test]
+    fn string_statistics() {
+        let minmax: MinMax<String> = vec!["b".to_string(), "a".to_string(), "c".to_string()]
+            .into_iter()
+            .collect();
+        let stats = minmax.to_parquet_statistics(None);
+        assert_eq!(stats.physical_type(), Type::BYTE_ARRAY);
+        assert_eq!(stats.null_count_opt(), Some(0));
+        assert_eq!(stats.min_bytes_opt(), Some("a".as_bytes()));
+        assert_eq!(stats.max_bytes_opt(), Some("c".as_bytes()));
+    }
+}
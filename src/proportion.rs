@@ -0,0 +1,177 @@
+use serde::{Deserialize, Serialize};
+
+use crate::special::{beta_quantile, normal_critical_value};
+use crate::{Commute, Estimate};
+
+/// A commutative accumulator of successes out of a number of trials, for
+/// estimating a binomial proportion such as the rate of a boolean flag.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Proportion {
+    successes: u64,
+    trials: u64,
+}
+
+impl Proportion {
+    /// Create initial empty state.
+    #[must_use]
+    pub fn new() -> Proportion {
+        Default::default()
+    }
+
+    /// Add the outcome of one trial.
+    #[inline]
+    pub fn add(&mut self, success: bool) {
+        self.trials += 1;
+        self.successes += u64::from(success);
+    }
+
+    /// Add the outcomes of many trials at once.
+    ///
+    /// # Panics
+    ///
+    /// If `successes` is larger than `trials`.
+    #[inline]
+    pub fn add_counts(&mut self, successes: u64, trials: u64) {
+        assert!(successes <= trials, "more successes than trials");
+        self.successes += successes;
+        self.trials += trials;
+    }
+
+    /// Returns the number of successes.
+    #[inline]
+    #[must_use]
+    pub const fn successes(&self) -> u64 {
+        self.successes
+    }
+
+    /// Returns the number of trials.
+    #[inline]
+    #[must_use]
+    pub const fn trials(&self) -> u64 {
+        self.trials
+    }
+
+    /// Returns the observed proportion of successes, or `None` if there
+    /// are no trials.
+    #[inline]
+    #[must_use]
+    pub fn proportion(&self) -> Option<f64> {
+        if self.trials == 0 {
+            None
+        } else {
+            Some(self.successes as f64 / self.trials as f64)
+        }
+    }
+
+    /// Returns the proportion with its Wilson score interval at the given
+    /// `confidence` level, e.g. `0.95`.
+    ///
+    /// Unlike the textbook normal interval, it stays within `[0, 1]` and
+    /// behaves well for proportions close to `0` or `1`.
+    ///
+    /// `None` is returned if there are no trials.
+    #[must_use]
+    pub fn wilson(&self, confidence: f64) -> Option<Estimate> {
+        let p = self.proportion()?;
+        let n = self.trials as f64;
+        let z = normal_critical_value(confidence);
+        let z2 = z * z;
+        let denom = 1.0 + z2 / n;
+        let center = (p + z2 / (2.0 * n)) / denom;
+        let half = z / denom * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt();
+        Some(Estimate {
+            value: p,
+            lower: (center - half).max(0.0),
+            upper: (center + half).min(1.0),
+        })
+    }
+
+    /// Returns the proportion with its Clopper-Pearson ("exact") interval
+    /// at the given `confidence` level, e.g. `0.95`.
+    ///
+    /// The interval is conservative: its coverage is at least `confidence`
+    /// for every true proportion.
+    ///
+    /// `None` is returned if there are no trials.
+    #[must_use]
+    pub fn clopper_pearson(&self, confidence: f64) -> Option<Estimate> {
+        let p = self.proportion()?;
+        let alpha = 1.0 - confidence;
+        let (x, n) = (self.successes as f64, self.trials as f64);
+        let lower = if self.successes == 0 {
+            0.0
+        } else {
+            beta_quantile(alpha / 2.0, x, n - x + 1.0)
+        };
+        let upper = if self.successes == self.trials {
+            1.0
+        } else {
+            beta_quantile(1.0 - alpha / 2.0, x + 1.0, n - x)
+        };
+        Some(Estimate {
+            value: p,
+            lower,
+            upper,
+        })
+    }
+}
+
+impl Commute for Proportion {
+    #[inline]
+    fn merge(&mut self, v: Proportion) {
+        self.successes += v.successes;
+        self.trials += v.trials;
+    }
+}
+
+impl FromIterator<bool> for Proportion {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = bool>>(it: I) -> Proportion {
+        let mut v = Proportion::new();
+        v.extend(it);
+        v
+    }
+}
+
+impl Extend<bool> for Proportion {
+    #[inline]
+    fn extend<I: IntoIterator<Item = bool>>(&mut self, it: I) {
+        for success in it {
+            self.add(success);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Proportion;
+    use crate::Commute;
+
+    #[test]
+    fn intervals() {
+        let mut p: Proportion = (0..20).map(|i| i < 8).collect();
+        p.merge([false; 5].into_iter().collect());
+        assert_eq!((p.successes(), p.trials()), (8, 25));
+        assert_eq!(p.proportion(), Some(0.32));
+
+        // the Clopper-Pearson bounds solve P(X >= 8) = 0.025 and P(X <= 8) = 0.025
+        let w = p.wilson(0.95).unwrap();
+        assert!((w.lower - 0.172_052).abs() < 1e-6);
+        assert!((w.upper - 0.515_897).abs() < 1e-6);
+        let cp = p.clopper_pearson(0.95).unwrap();
+        assert!((cp.lower - 0.149_495).abs() < 1e-6);
+        assert!((cp.upper - 0.535_001).abs() < 1e-6);
+    }
+
+    #[test]
+    fn extremes() {
+        let mut p = Proportion::new();
+        assert!(p.wilson(0.95).is_none());
+        p.add_counts(0, 10);
+        let cp = p.clopper_pearson(0.95).unwrap();
+        assert_eq!(cp.lower, 0.0);
+        // (alpha / 2)^(1 / n) = 0.025^0.1
+        assert!((cp.upper - (1.0 - 0.025f64.powf(0.1))).abs() < 1e-9);
+        assert_eq!(p.wilson(0.95).unwrap().lower, 0.0);
+    }
+}
@@ -0,0 +1,229 @@
+use std::hash::Hash;
+
+use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+
+use crate::sql::percentile_cont_on_sorted;
+use crate::{merge_all, Grouped, OnlineStats, Unsorted};
+
+/// Process capability indices of data against lower and upper
+/// specification limits.
+///
+/// The `cp` and `cpk` indices use the short-term (within-subgroup)
+/// standard deviation, and `pp` and `ppk` the long-term (overall) one.
+/// `cp` and `pp` compare the width of the specification to six standard
+/// deviations, while `cpk` and `ppk` also account for how far the mean is
+/// off center. A value of at least `1.33` is a common target.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Capability {
+    /// The potential capability, from the short-term standard deviation.
+    pub cp: f64,
+    /// The capability accounting for centering, from the short-term
+    /// standard deviation.
+    pub cpk: f64,
+    /// The potential performance, from the overall standard deviation.
+    pub pp: f64,
+    /// The performance accounting for centering, from the overall standard
+    /// deviation.
+    pub ppk: f64,
+}
+
+impl Capability {
+    fn new(lower: f64, upper: f64, mean: f64, within: f64, overall: f64) -> Capability {
+        let spread = (upper - lower) / 6.0;
+        let nearest = (upper - mean).min(mean - lower) / 3.0;
+        Capability {
+            cp: spread / within,
+            cpk: nearest / within,
+            pp: spread / overall,
+            ppk: nearest / overall,
+        }
+    }
+}
+
+impl OnlineStats {
+    /// Returns the process capability indices for the specification limits
+    /// `lower_spec` and `upper_spec`, using the sample standard deviation.
+    ///
+    /// A single accumulator cannot tell short-term from long-term
+    /// variation, so `cp` equals `pp` and `cpk` equals `ppk`. Use
+    /// `Grouped::capability` with rational subgroups (e.g. one group per
+    /// batch or shift) to tell them apart.
+    ///
+    /// `None` is returned if there are fewer than two samples or the
+    /// limits are not in ascending order.
+    #[must_use]
+    pub fn capability(&self, lower_spec: f64, upper_spec: f64) -> Option<Capability> {
+        let n = self.len() as f64;
+        if n < 2.0 || lower_spec >= upper_spec {
+            return None;
+        }
+        let sigma = (self.m2() / (n - 1.0)).sqrt();
+        Some(Capability::new(
+            lower_spec,
+            upper_spec,
+            self.mean(),
+            sigma,
+            sigma,
+        ))
+    }
+}
+
+impl<K: Eq + Hash> Grouped<K, OnlineStats> {
+    /// Returns the process capability indices for the specification limits
+    /// `lower_spec` and `upper_spec`, treating each group as a subgroup.
+    ///
+    /// The short-term standard deviation is pooled from the variation
+    /// within the subgroups, and the overall one is computed over all
+    /// samples. Groups without samples are ignored.
+    ///
+    /// `None` is returned if there are no more samples than groups, or
+    /// fewer than two samples, or the limits are not in ascending order.
+    #[must_use]
+    pub fn capability(&self, lower_spec: f64, upper_spec: f64) -> Option<Capability> {
+        let groups: Vec<&OnlineStats> = self
+            .iter()
+            .map(|(_, s)| s)
+            .filter(|s| !s.is_empty())
+            .collect();
+        let k = groups.len() as f64;
+        let total = merge_all(groups.iter().copied().copied())?;
+        let n = total.len() as f64;
+        if n <= k || n < 2.0 || lower_spec >= upper_spec {
+            return None;
+        }
+        let ss_within: f64 = groups.iter().map(|s| s.m2()).sum();
+        Some(Capability::new(
+            lower_spec,
+            upper_spec,
+            total.mean(),
+            (ss_within / (n - k)).sqrt(),
+            (total.m2() / (n - 1.0)).sqrt(),
+        ))
+    }
+}
+
+/// Lower and upper control limits around a center line, for flagging
+/// values that are out of the ordinary for a monitored process.
+///
+/// Limits are derived from historical data, either as the mean plus or
+/// minus a number of standard deviations (Shewhart limits, which assume
+/// roughly normal data), or as empirical percentiles (which make no such
+/// assumption but need the buffered data).
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ControlLimits {
+    /// The lower control limit.
+    pub lower: f64,
+    /// The center line: the mean or the median.
+    pub center: f64,
+    /// The upper control limit.
+    pub upper: f64,
+}
+
+impl ControlLimits {
+    /// Returns limits at `k` sample standard deviations around the mean,
+    /// e.g. `k = 3.0` for the classic three-sigma limits.
+    ///
+    /// `None` is returned if there are fewer than two samples.
+    #[must_use]
+    pub fn from_sigma(stats: &OnlineStats, k: f64) -> Option<ControlLimits> {
+        if stats.len() < 2 {
+            return None;
+        }
+        let (center, width) = (stats.mean(), k * stats.sample_stddev());
+        Some(ControlLimits {
+            lower: center - width,
+            center,
+            upper: center + width,
+        })
+    }
+
+    /// Returns limits at the `lower` and `upper` percentiles of the data
+    /// (as fractions in `[0, 1]`), around the median.
+    ///
+    /// Percentiles are linearly interpolated, as `sql::percentile_cont`.
+    ///
+    /// `None` is returned if there is no data, or the fractions are not in
+    /// `[0, 1]` or not in ascending order.
+    pub fn from_percentiles<T: PartialOrd + ToPrimitive>(
+        data: &mut Unsorted<T>,
+        lower: f64,
+        upper: f64,
+    ) -> Option<ControlLimits> {
+        if !(0.0 <= lower && lower <= upper && upper <= 1.0) {
+            return None;
+        }
+        let sorted = data.as_sorted_slice();
+        Some(ControlLimits {
+            lower: percentile_cont_on_sorted(sorted, lower)?,
+            center: percentile_cont_on_sorted(sorted, 0.5)?,
+            upper: percentile_cont_on_sorted(sorted, upper)?,
+        })
+    }
+
+    /// Returns true if `sample` is within the limits (inclusive).
+    #[inline]
+    #[must_use]
+    pub fn contains<T: ToPrimitive>(&self, sample: &T) -> bool {
+        let x = sample.to_f64().unwrap();
+        self.lower <= x && x <= self.upper
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ControlLimits;
+    use crate::{Grouped, OnlineStats, Unsorted};
+
+    #[test]
+    fn control_limits() {
+        let stats = OnlineStats::from_slice(&[2, 4, 4, 4, 5, 5, 7, 9]);
+        // sample variance 32 / 7
+        let limits = ControlLimits::from_sigma(&stats, 3.0).unwrap();
+        let width = 3.0 * (32.0f64 / 7.0).sqrt();
+        assert_eq!(limits.center, 5.0);
+        assert!((limits.upper - (5.0 + width)).abs() < 1e-12);
+        assert!((limits.lower - (5.0 - width)).abs() < 1e-12);
+        assert!(limits.contains(&11) && !limits.contains(&12));
+        assert!(ControlLimits::from_sigma(&OnlineStats::from_slice(&[1]), 3.0).is_none());
+
+        let mut data: Unsorted<u32> = (0..=100).collect();
+        let limits = ControlLimits::from_percentiles(&mut data, 0.05, 0.95).unwrap();
+        assert_eq!(
+            (limits.lower, limits.center, limits.upper),
+            (5.0, 50.0, 95.0)
+        );
+        assert!(!limits.contains(&96u32));
+        assert!(ControlLimits::from_percentiles(&mut data, 0.9, 0.1).is_none());
+        assert!(ControlLimits::from_percentiles(&mut Unsorted::<u32>::new(), 0.0, 1.0).is_none());
+    }
+
+    #[test]
+    fn single_stream() {
+        let stats = OnlineStats::from_slice(&[3, 5, 7, 3, 5, 7, 5, 5]);
+        let sigma = (stats.m2() / 7.0).sqrt();
+        let cap = stats.capability(-1.0, 9.0).unwrap();
+        assert!((cap.cp - 10.0 / (6.0 * sigma)).abs() < 1e-12);
+        assert!((cap.cpk - 4.0 / (3.0 * sigma)).abs() < 1e-12);
+        assert_eq!((cap.cp, cap.cpk), (cap.pp, cap.ppk));
+        assert!(stats.capability(9.0, -1.0).is_none());
+        assert!(OnlineStats::from_slice(&[1]).capability(0.0, 2.0).is_none());
+    }
+
+    #[test]
+    fn subgroups() {
+        // the batches are tight, but their means drift apart
+        let mut groups: Grouped<u8, OnlineStats> = Grouped::new();
+        for (batch, values) in [(1, [9.0, 10.0, 11.0]), (2, [11.0, 12.0, 13.0])] {
+            for v in values {
+                groups.add(batch, v);
+            }
+        }
+        let cap = groups.capability(5.0, 16.0).unwrap();
+        // pooled within variance (2 + 2) / (6 - 2) = 1, overall 10 / 5 = 2
+        assert!((cap.cp - 11.0 / 6.0).abs() < 1e-12);
+        assert!((cap.cpk - 5.0 / 3.0).abs() < 1e-12);
+        assert!((cap.pp - 11.0 / 6.0 / 2f64.sqrt()).abs() < 1e-12);
+        assert!(cap.ppk < cap.cpk);
+    }
+}
@@ -0,0 +1,341 @@
+use num_traits::ToPrimitive;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::parallel::in_pool;
+use crate::rng::SplitMix64;
+use crate::{Estimate, TestResult, Unsorted};
+
+/// The statistic compared between two samples by `permutation_test`.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum PermutationStatistic {
+    /// The difference of the means.
+    MeanDifference,
+    /// The difference of the medians.
+    MedianDifference,
+}
+
+impl PermutationStatistic {
+    /// Evaluates the statistic for the split of `pooled` into its first
+    /// `n1` values and the rest.
+    fn eval(self, pooled: &[f64], n1: usize) -> f64 {
+        let (a, b) = pooled.split_at(n1);
+        match self {
+            PermutationStatistic::MeanDifference => crate::mean(a) - crate::mean(b),
+            PermutationStatistic::MedianDifference => {
+                let median = |data: &[f64]| crate::median(data.iter().copied()).unwrap();
+                median(a) - median(b)
+            }
+        }
+    }
+}
+
+/// A jackknife estimate of the bias and variance of a statistic.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Jackknife {
+    /// The statistic computed on all of the data.
+    pub value: f64,
+    /// The estimated bias of the statistic.
+    pub bias: f64,
+    /// The estimated variance of the statistic.
+    pub variance: f64,
+}
+
+impl Jackknife {
+    /// Returns the statistic with its estimated bias removed.
+    #[inline]
+    #[must_use]
+    pub fn bias_corrected(&self) -> f64 {
+        self.value - self.bias
+    }
+
+    /// Returns the estimated standard error of the statistic.
+    #[inline]
+    #[must_use]
+    pub fn std_error(&self) -> f64 {
+        self.variance.sqrt()
+    }
+}
+
+impl<T: PartialOrd + Clone + Send + Sync> Unsorted<T> {
+    /// Estimates a confidence interval for `stat_fn` (e.g. the median or
+    /// the MAD) with the percentile bootstrap.
+    ///
+    /// The data is resampled with replacement `iterations` times, and the
+    /// interval is formed by the `(1 - confidence) / 2` and
+    /// `(1 + confidence) / 2` quantiles of the statistic over the
+    /// resamples. The resamples are drawn in parallel (subject to
+    /// `set_parallelism`), each from its own stream of a generator seeded
+    /// with `seed`, so the result is reproducible for a given `seed`
+    /// regardless of the number of threads.
+    ///
+    /// Resamples for which `stat_fn` returns `None` are skipped. `None` is
+    /// returned if `confidence` is not in `(0, 1)`, there is no data,
+    /// `stat_fn` is undefined on the data, or no resample gives a value.
+    pub fn bootstrap_ci<F>(
+        &mut self,
+        stat_fn: F,
+        iterations: usize,
+        confidence: f64,
+        seed: u64,
+    ) -> Option<Estimate>
+    where
+        F: Fn(&mut Unsorted<T>) -> Option<f64> + Sync,
+    {
+        if !(confidence > 0.0 && confidence < 1.0) {
+            return None;
+        }
+        let value = stat_fn(self)?;
+        let parallel = self
+            .parallelism()
+            .is_parallel(iterations.saturating_mul(self.len()));
+        let data = self.as_sorted_slice();
+        if data.is_empty() {
+            return None;
+        }
+        let resample = |i: usize| {
+            let mut rng = SplitMix64::stream(seed, i as u64);
+            let mut sample: Unsorted<T> = (0..data.len())
+                .map(|_| data[rng.next_below(data.len())].clone())
+                .collect();
+            stat_fn(&mut sample)
+        };
+        let mut stats: Vec<f64> = if parallel {
+            in_pool(|| {
+                (0..iterations)
+                    .into_par_iter()
+                    .filter_map(resample)
+                    .collect()
+            })
+        } else {
+            (0..iterations).filter_map(resample).collect()
+        };
+        if stats.is_empty() {
+            return None;
+        }
+        stats.sort_unstable_by(f64::total_cmp);
+        let alpha = (1.0 - confidence) / 2.0;
+        let at = |q: f64| stats[(q * (stats.len() - 1) as f64).round() as usize];
+        Some(Estimate {
+            value,
+            lower: at(alpha),
+            upper: at(1.0 - alpha),
+        })
+    }
+
+    /// Estimates the bias and variance of `stat_fn` with the leave-one-out
+    /// jackknife.
+    ///
+    /// The statistic is recomputed once without each value, so this costs
+    /// `len` evaluations on `len - 1` values each, but unlike the bootstrap
+    /// it involves no randomness, which suits small samples. The
+    /// evaluations run in parallel, subject to `set_parallelism`.
+    ///
+    /// `None` is returned if there are fewer than two values, or if
+    /// `stat_fn` is undefined on the data or on any leave-one-out subset.
+    pub fn jackknife<F>(&mut self, stat_fn: F) -> Option<Jackknife>
+    where
+        F: Fn(&mut Unsorted<T>) -> Option<f64> + Sync,
+    {
+        let value = stat_fn(self)?;
+        let n = self.len();
+        let parallel = self.parallelism().is_parallel(n.saturating_mul(n));
+        let data = self.as_sorted_slice();
+        if n < 2 {
+            return None;
+        }
+        let leave_out = |i: usize| {
+            let mut subset: Unsorted<T> = data[..i].iter().chain(&data[i + 1..]).cloned().collect();
+            stat_fn(&mut subset)
+        };
+        let values: Option<Vec<f64>> = if parallel {
+            in_pool(|| (0..n).into_par_iter().map(leave_out).collect())
+        } else {
+            (0..n).map(leave_out).collect()
+        };
+        let values = values?;
+        let nf = n as f64;
+        let mean = values.iter().sum::<f64>() / nf;
+        let ss: f64 = values.iter().map(|v| (v - mean) * (v - mean)).sum();
+        Some(Jackknife {
+            value,
+            bias: (nf - 1.0) * (mean - value),
+            variance: (nf - 1.0) / nf * ss,
+        })
+    }
+}
+
+impl<T: PartialOrd + ToPrimitive> Unsorted<T> {
+    /// Performs a two-sided permutation test of whether this data and
+    /// `other` differ in location, without assuming any distribution.
+    ///
+    /// The observed `statistic` (this sample's minus `other`'s) is compared
+    /// to its values over `iterations` random reassignments of the pooled
+    /// values to the two samples. The p-value is the fraction of
+    /// reassignments at least as extreme as observed, counting the observed
+    /// assignment itself, so it is never `0`. The reassignments run in
+    /// parallel (subject to this data's `set_parallelism`), each from its
+    /// own stream of a generator seeded with `seed`, so the result is
+    /// reproducible.
+    ///
+    /// `None` is returned if either sample is empty.
+    pub fn permutation_test(
+        &mut self,
+        other: &mut Unsorted<T>,
+        statistic: PermutationStatistic,
+        iterations: usize,
+        seed: u64,
+    ) -> Option<TestResult> {
+        let (n1, n2) = (self.len(), other.len());
+        if n1 == 0 || n2 == 0 {
+            return None;
+        }
+        let parallel = self
+            .parallelism()
+            .is_parallel(iterations.saturating_mul(n1 + n2));
+        let pooled: Vec<f64> = self
+            .as_sorted_slice()
+            .iter()
+            .chain(other.as_sorted_slice())
+            .map(|v| v.to_f64().unwrap())
+            .collect();
+        let observed = statistic.eval(&pooled, n1);
+        // tolerate rounding, so exchangeable ties count as extreme
+        let threshold = observed.abs() * (1.0 - 1e-12);
+
+        let permuted = |i: usize| {
+            let mut rng = SplitMix64::stream(seed, i as u64);
+            let mut data = pooled.clone();
+            // a partial Fisher-Yates shuffle picks a random first sample
+            for j in 0..n1 {
+                let k = j + rng.next_below(data.len() - j);
+                data.swap(j, k);
+            }
+            statistic.eval(&data, n1).abs() >= threshold
+        };
+        let extreme = if parallel {
+            in_pool(|| {
+                (0..iterations)
+                    .into_par_iter()
+                    .filter(|&i| permuted(i))
+                    .count()
+            })
+        } else {
+            (0..iterations).filter(|&i| permuted(i)).count()
+        };
+        Some(TestResult {
+            statistic: observed,
+            p_value: (extreme + 1) as f64 / (iterations + 1) as f64,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::PermutationStatistic;
+    use crate::{OnlineStats, Parallelism, Unsorted};
+
+    #[test]
+    fn permutation_shifted() {
+        let mut a: Unsorted<u32> = (0..20).collect();
+        let mut b: Unsorted<u32> = (15..35).collect();
+        let r = a
+            .permutation_test(&mut b, PermutationStatistic::MeanDifference, 999, 3)
+            .unwrap();
+        assert_eq!(r.statistic, -15.0);
+        assert_eq!(r.p_value, 0.001);
+
+        let r = a
+            .permutation_test(&mut b, PermutationStatistic::MedianDifference, 999, 3)
+            .unwrap();
+        assert_eq!(r.statistic, -15.0);
+        assert!(r.p_value < 0.01);
+    }
+
+    #[test]
+    fn permutation_same() {
+        let mut a: Unsorted<u32> = (0..30).map(|i| i * 2).collect();
+        let mut b: Unsorted<u32> = (0..30).map(|i| i * 2 + 1).collect();
+        a.set_parallelism(Parallelism::Sequential);
+        let seq = a
+            .permutation_test(&mut b, PermutationStatistic::MeanDifference, 500, 9)
+            .unwrap();
+        assert!(seq.p_value > 0.5);
+        a.set_parallelism(Parallelism::Force);
+        let par = a
+            .permutation_test(&mut b, PermutationStatistic::MeanDifference, 500, 9)
+            .unwrap();
+        assert_eq!(seq, par);
+        assert!(a
+            .permutation_test(
+                &mut Unsorted::new(),
+                PermutationStatistic::MeanDifference,
+                9,
+                9
+            )
+            .is_none());
+    }
+
+    fn mean(data: &mut Unsorted<f64>) -> Option<f64> {
+        let stats = OnlineStats::from(&*data);
+        (!stats.is_empty()).then(|| stats.mean())
+    }
+
+    fn population_variance(data: &mut Unsorted<f64>) -> Option<f64> {
+        Some(OnlineStats::from(&*data).variance())
+    }
+
+    #[test]
+    fn jackknife_mean() {
+        let mut data: Unsorted<f64> = [2.0, 4.0, 4.0, 5.0, 9.0].into_iter().collect();
+        let j = data.jackknife(mean).unwrap();
+        // the mean is unbiased, and its jackknife variance is s^2 / n
+        assert_eq!(j.value, 4.8);
+        assert!(j.bias.abs() < 1e-12);
+        let s2 = OnlineStats::from(&data).sample_variance();
+        assert!((j.variance - s2 / 5.0).abs() < 1e-12);
+        assert!((j.std_error() - (s2 / 5.0).sqrt()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn jackknife_bias() {
+        // the jackknife corrects the population variance to the sample variance
+        let mut data: Unsorted<f64> = [1.0, 3.0, 4.0, 8.0].into_iter().collect();
+        data.set_parallelism(Parallelism::Force);
+        let j = data.jackknife(population_variance).unwrap();
+        let s2 = OnlineStats::from(&data).sample_variance();
+        assert!((j.bias_corrected() - s2).abs() < 1e-12);
+
+        let mut one: Unsorted<f64> = [1.0].into_iter().collect();
+        assert!(one.jackknife(mean).is_none());
+    }
+
+    #[test]
+    fn bootstrap_median() {
+        let mut data: Unsorted<u32> = (1..=101).collect();
+        let ci = data.bootstrap_ci(Unsorted::median, 500, 0.9, 42).unwrap();
+        assert_eq!(ci.value, 51.0);
+        assert!(ci.lower < 51.0 && ci.upper > 51.0);
+        assert!(ci.lower > 35.0 && ci.upper < 67.0);
+
+        // the result depends only on the seed, not on the threads
+        data.set_parallelism(Parallelism::Sequential);
+        let seq = data.bootstrap_ci(Unsorted::median, 500, 0.9, 42).unwrap();
+        data.set_parallelism(Parallelism::Force);
+        assert_eq!(data.bootstrap_ci(Unsorted::median, 500, 0.9, 42), Some(seq));
+        assert_eq!(seq, ci);
+    }
+
+    #[test]
+    fn bootstrap_undefined() {
+        let mut empty: Unsorted<f64> = Unsorted::new();
+        assert!(empty.bootstrap_ci(Unsorted::median, 10, 0.95, 1).is_none());
+        let mut data: Unsorted<f64> = [1.0, 2.0].into_iter().collect();
+        assert!(data.bootstrap_ci(|_| None, 10, 0.95, 1).is_none());
+        for confidence in [0.0, 1.0, 1.5, -0.9, f64::NAN] {
+            assert!(data
+                .bootstrap_ci(Unsorted::median, 10, confidence, 1)
+                .is_none());
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+
+/// A small, seedable pseudo-random number generator (SplitMix64).
+///
+/// This is not cryptographically secure. It exists so that sampling and
+/// resampling routines are reproducible across runs and platforms without
+/// pulling in an external RNG crate.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub(crate) struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    /// Create a new generator from `seed`.
+    #[inline]
+    pub(crate) const fn new(seed: u64) -> SplitMix64 {
+        SplitMix64 { state: seed }
+    }
+
+    /// Return the next 64 random bits.
+    #[inline]
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        // Taken from: https://prng.di.unimi.it/splitmix64.c
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Return a uniformly distributed `f64` in `[0, 1)`.
+    #[inline]
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        // use the top 53 bits, which is the precision of an f64 mantissa
+        (self.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+
+    /// Return a uniformly distributed index in `0..n`.
+    ///
+    /// This uses a multiply-shift instead of a modulo. The bias is below
+    /// `n / 2^64`, which is negligible for any slice length.
+    #[inline]
+    pub(crate) fn next_below(&mut self, n: usize) -> usize {
+        ((u128::from(self.next_u64()) * n as u128) >> 64) as usize
+    }
+
+    /// Create the generator for the `index`th of many independent streams
+    /// derived from `seed`, e.g. one per resampling iteration, so results
+    /// do not depend on how the iterations are spread over threads.
+    #[inline]
+    pub(crate) fn stream(seed: u64, index: u64) -> SplitMix64 {
+        SplitMix64::new(seed ^ SplitMix64::new(index).next_u64())
+    }
+}
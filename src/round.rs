@@ -0,0 +1,555 @@
+use std::fmt;
+
+use serde::ser::{
+    SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple,
+    SerializeTupleStruct, SerializeTupleVariant,
+};
+use serde::{Deserialize, Serialize, Serializer};
+
+/// How a value exactly halfway between two rounded values is rounded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RoundMode {
+    /// Round ties to the even neighbor, e.g. `2.5` to `2` and `3.5` to `4`
+    /// (banker's rounding), which does not bias sums of rounded values.
+    #[default]
+    HalfEven,
+    /// Round ties away from zero, e.g. `2.5` to `3` and `-2.5` to `-3`, as
+    /// most people round by hand.
+    HalfUp,
+}
+
+/// How floating-point statistics are rounded for output.
+///
+/// `apply` pairs a statistic with a policy, to display it with its floats
+/// formatted by the policy (for statistics that implement
+/// `RoundedDisplay`) or to serialize it with every float rounded.
+/// Consumers can use `round` and `format` to present their own values the
+/// same way.
+///
+/// Without `apply`, the `Display` and `Debug` output of statistics formats
+/// the values they compute, such as the mean of an `OnlineStats`, with the
+/// default policy, and shows stored values, such as the minimum of a
+/// `MinMax`, as they are. State that is serialized directly is never
+/// rounded, so it still merges exactly.
+///
+/// The default keeps 10 decimal places with ties rounded to even.
+///
+/// Rounding works on the binary value, so a decimal literal that is not
+/// exactly representable (such as `2.675`, which is stored as slightly
+/// less) is rounded according to its stored value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoundPolicy {
+    mode: RoundMode,
+    decimal_places: Option<u32>,
+    significant_digits: Option<u32>,
+}
+
+impl RoundPolicy {
+    /// Create the default policy: 10 decimal places, ties to even.
+    #[must_use]
+    pub const fn new() -> RoundPolicy {
+        RoundPolicy {
+            mode: RoundMode::HalfEven,
+            decimal_places: Some(10),
+            significant_digits: None,
+        }
+    }
+
+    /// Create a policy that does not round, and formats every value with
+    /// the shortest representation that reads back as the same `f64`.
+    #[must_use]
+    pub const fn exact() -> RoundPolicy {
+        RoundPolicy {
+            mode: RoundMode::HalfEven,
+            decimal_places: None,
+            significant_digits: None,
+        }
+    }
+
+    /// Sets how ties are rounded.
+    #[must_use]
+    pub const fn with_mode(mut self, mode: RoundMode) -> RoundPolicy {
+        self.mode = mode;
+        self
+    }
+
+    /// Sets the maximum number of digits after the decimal point, or
+    /// `None` for no limit.
+    #[must_use]
+    pub const fn with_decimal_places(mut self, places: Option<u32>) -> RoundPolicy {
+        self.decimal_places = places;
+        self
+    }
+
+    /// Sets the maximum number of significant digits, or `None` for no
+    /// limit. If decimal places are limited too, the stricter limit wins.
+    ///
+    /// # Panics
+    ///
+    /// If `digits` is `Some(0)` or more than `i32::MAX`.
+    #[must_use]
+    pub const fn with_significant_digits(mut self, digits: Option<u32>) -> RoundPolicy {
+        assert!(
+            !matches!(digits, Some(d) if d == 0 || d > i32::MAX as u32),
+            "significant digits must be in [1, i32::MAX]"
+        );
+        self.significant_digits = digits;
+        self
+    }
+
+    /// Returns how ties are rounded.
+    #[inline]
+    #[must_use]
+    pub const fn mode(&self) -> RoundMode {
+        self.mode
+    }
+
+    /// Returns the maximum number of digits after the decimal point.
+    #[inline]
+    #[must_use]
+    pub const fn decimal_places(&self) -> Option<u32> {
+        self.decimal_places
+    }
+
+    /// Returns the maximum number of significant digits.
+    #[inline]
+    #[must_use]
+    pub const fn significant_digits(&self) -> Option<u32> {
+        self.significant_digits
+    }
+
+    /// Returns the number of decimal places to round `x` to, which is
+    /// negative to round to tens, hundreds etc., or `None` to not round.
+    fn places_for(&self, x: f64) -> Option<i32> {
+        let by_digits = self.significant_digits.filter(|_| x != 0.0).map(|d| {
+            let magnitude = x.abs().log10().floor() as i32;
+            (d as i32 - 1).saturating_sub(magnitude)
+        });
+        let by_places = self.decimal_places.map(|p| p.min(i32::MAX as u32) as i32);
+        match (by_digits, by_places) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Returns `x` rounded by this policy. Non-finite values are returned
+    /// as they are.
+    #[must_use]
+    pub fn round(&self, x: f64) -> f64 {
+        match self.places_for(x) {
+            Some(places) if x.is_finite() => self.round_to(x, places),
+            _ => x,
+        }
+    }
+
+    fn round_to(&self, x: f64, places: i32) -> f64 {
+        // beyond this, every f64 is already an integer multiple of the unit
+        if places > 325 {
+            return x;
+        }
+        let scale = 10f64.powi(places.abs());
+        let scaled = if places >= 0 { x * scale } else { x / scale };
+        if !scaled.is_finite() {
+            return x;
+        }
+        let rounded = match self.mode {
+            RoundMode::HalfEven => scaled.round_ties_even(),
+            RoundMode::HalfUp => scaled.round(),
+        };
+        if places >= 0 {
+            rounded / scale
+        } else {
+            rounded * scale
+        }
+    }
+
+    /// Returns `x` rounded and formatted by this policy, with trailing
+    /// zeros up to the number of decimal places rounded to.
+    #[must_use]
+    pub fn format(&self, x: f64) -> String {
+        match self.places_for(x) {
+            Some(places) if x.is_finite() => {
+                let places = places.clamp(0, 325) as usize;
+                format!("{:.*}", places, self.round(x))
+            }
+            _ => format!("{x}"),
+        }
+    }
+
+    /// Returns a wrapper that displays or serializes `value` with its
+    /// floats rounded by this policy, e.g. `policy.apply(&stats).to_string()`.
+    #[inline]
+    #[must_use]
+    pub const fn apply<'a, T: ?Sized>(&self, value: &'a T) -> Rounded<'a, T> {
+        Rounded {
+            policy: *self,
+            value,
+        }
+    }
+}
+
+impl Default for RoundPolicy {
+    #[inline]
+    fn default() -> RoundPolicy {
+        RoundPolicy::new()
+    }
+}
+
+/// Statistics whose text output can be rounded by a `RoundPolicy`.
+pub trait RoundedDisplay {
+    /// Writes the `Display` output of the statistic (or its `Debug`
+    /// output, if it has no `Display`) with every float formatted by
+    /// `policy`.
+    fn fmt_rounded(&self, policy: &RoundPolicy, f: &mut fmt::Formatter) -> fmt::Result;
+}
+
+/// A value paired with a `RoundPolicy`, as returned by `RoundPolicy::apply`.
+///
+/// `Display` writes a statistic that implements `RoundedDisplay` with its
+/// floats formatted by the policy, and `Serialize` serializes any value
+/// with every `f32` and `f64` in it rounded by the policy.
+#[derive(Clone, Copy)]
+pub struct Rounded<'a, T: ?Sized> {
+    policy: RoundPolicy,
+    value: &'a T,
+}
+
+impl<T: RoundedDisplay + ?Sized> fmt::Display for Rounded<'_, T> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.value.fmt_rounded(&self.policy, f)
+    }
+}
+
+impl<T: Serialize + ?Sized> Serialize for Rounded<'_, T> {
+    #[inline]
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize(RoundingSerializer {
+            policy: self.policy,
+            inner: serializer,
+        })
+    }
+}
+
+/// Forwards everything to `inner`, rounding floats on the way.
+struct RoundingSerializer<S> {
+    policy: RoundPolicy,
+    inner: S,
+}
+
+macro_rules! forward {
+    ($($method:ident($ty:ty)),* $(,)?) => {
+        $(
+            #[inline]
+            fn $method(self, v: $ty) -> Result<S::Ok, S::Error> {
+                self.inner.$method(v)
+            }
+        )*
+    };
+}
+
+impl<S: Serializer> Serializer for RoundingSerializer<S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+    type SerializeSeq = RoundingSerializer<S::SerializeSeq>;
+    type SerializeTuple = RoundingSerializer<S::SerializeTuple>;
+    type SerializeTupleStruct = RoundingSerializer<S::SerializeTupleStruct>;
+    type SerializeTupleVariant = RoundingSerializer<S::SerializeTupleVariant>;
+    type SerializeMap = RoundingSerializer<S::SerializeMap>;
+    type SerializeStruct = RoundingSerializer<S::SerializeStruct>;
+    type SerializeStructVariant = RoundingSerializer<S::SerializeStructVariant>;
+
+    forward!(
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_i128(i128),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_u128(u128),
+        serialize_char(char),
+        serialize_str(&str),
+        serialize_bytes(&[u8]),
+        serialize_unit_struct(&'static str),
+    );
+
+    #[inline]
+    fn serialize_f32(self, v: f32) -> Result<S::Ok, S::Error> {
+        self.inner
+            .serialize_f32(self.policy.round(f64::from(v)) as f32)
+    }
+
+    #[inline]
+    fn serialize_f64(self, v: f64) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_f64(self.policy.round(v))
+    }
+
+    #[inline]
+    fn serialize_none(self) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_none()
+    }
+
+    #[inline]
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_some(&self.policy.apply(value))
+    }
+
+    #[inline]
+    fn serialize_unit(self) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_unit()
+    }
+
+    #[inline]
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+    ) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_unit_variant(name, index, variant)
+    }
+
+    #[inline]
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        self.inner
+            .serialize_newtype_struct(name, &self.policy.apply(value))
+    }
+
+    #[inline]
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        self.inner
+            .serialize_newtype_variant(name, index, variant, &self.policy.apply(value))
+    }
+
+    #[inline]
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, S::Error> {
+        Ok(RoundingSerializer {
+            policy: self.policy,
+            inner: self.inner.serialize_seq(len)?,
+        })
+    }
+
+    #[inline]
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, S::Error> {
+        Ok(RoundingSerializer {
+            policy: self.policy,
+            inner: self.inner.serialize_tuple(len)?,
+        })
+    }
+
+    #[inline]
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, S::Error> {
+        Ok(RoundingSerializer {
+            policy: self.policy,
+            inner: self.inner.serialize_tuple_struct(name, len)?,
+        })
+    }
+
+    #[inline]
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, S::Error> {
+        Ok(RoundingSerializer {
+            policy: self.policy,
+            inner: self
+                .inner
+                .serialize_tuple_variant(name, index, variant, len)?,
+        })
+    }
+
+    #[inline]
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, S::Error> {
+        Ok(RoundingSerializer {
+            policy: self.policy,
+            inner: self.inner.serialize_map(len)?,
+        })
+    }
+
+    #[inline]
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, S::Error> {
+        Ok(RoundingSerializer {
+            policy: self.policy,
+            inner: self.inner.serialize_struct(name, len)?,
+        })
+    }
+
+    #[inline]
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, S::Error> {
+        Ok(RoundingSerializer {
+            policy: self.policy,
+            inner: self
+                .inner
+                .serialize_struct_variant(name, index, variant, len)?,
+        })
+    }
+
+    #[inline]
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+}
+
+// The compound serializers round the elements, keys and fields they are
+// given and forward them to the compound serializer of `inner`.
+macro_rules! forward_compound {
+    ($trait:ident, $method:ident($($key:ident: $key_ty:ty),*)) => {
+        impl<S: $trait> $trait for RoundingSerializer<S> {
+            type Ok = S::Ok;
+            type Error = S::Error;
+
+            #[inline]
+            fn $method<T: Serialize + ?Sized>(
+                &mut self,
+                $($key: $key_ty,)*
+                value: &T,
+            ) -> Result<(), S::Error> {
+                self.inner.$method($($key,)* &self.policy.apply(value))
+            }
+
+            #[inline]
+            fn end(self) -> Result<S::Ok, S::Error> {
+                self.inner.end()
+            }
+        }
+    };
+}
+
+forward_compound!(SerializeSeq, serialize_element());
+forward_compound!(SerializeTuple, serialize_element());
+forward_compound!(SerializeTupleStruct, serialize_field());
+forward_compound!(SerializeTupleVariant, serialize_field());
+forward_compound!(SerializeStruct, serialize_field(key: &'static str));
+forward_compound!(SerializeStructVariant, serialize_field(key: &'static str));
+
+impl<S: SerializeMap> SerializeMap for RoundingSerializer<S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+
+    #[inline]
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), S::Error> {
+        self.inner.serialize_key(&self.policy.apply(key))
+    }
+
+    #[inline]
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), S::Error> {
+        self.inner.serialize_value(&self.policy.apply(value))
+    }
+
+    #[inline]
+    fn end(self) -> Result<S::Ok, S::Error> {
+        self.inner.end()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{RoundMode, RoundPolicy};
+    use crate::{MinMax, OnlineSummary, TestResult, Unsorted};
+
+    #[test]
+    fn round_modes() {
+        let even = RoundPolicy::new().with_decimal_places(Some(0));
+        let up = even.with_mode(RoundMode::HalfUp);
+        assert_eq!((even.round(2.5), even.round(3.5)), (2.0, 4.0));
+        assert_eq!((up.round(2.5), up.round(-2.5)), (3.0, -3.0));
+
+        let cents = RoundPolicy::new().with_decimal_places(Some(2));
+        assert_eq!(cents.round(0.125), 0.12);
+        assert_eq!(cents.with_mode(RoundMode::HalfUp).round(0.125), 0.13);
+        assert_eq!(cents.format(1.0), "1.00");
+        assert_eq!(cents.format(f64::NAN), "NaN");
+    }
+
+    #[test]
+    fn significant_digits() {
+        let sig = RoundPolicy::exact().with_significant_digits(Some(3));
+        assert_eq!(sig.round(123_456.0), 123_000.0);
+        assert_eq!(sig.format(123_456.0), "123000");
+        assert_eq!(sig.format(0.000_123_456), "0.000123");
+        assert_eq!(sig.format(0.0), "0");
+
+        // the stricter of the two limits wins
+        let both = sig.with_decimal_places(Some(1));
+        assert_eq!(both.format(0.000_123_456), "0.0");
+        assert_eq!(both.format(12.345), "12.3");
+        assert_eq!(RoundPolicy::exact().format(0.1), "0.1");
+        assert_eq!(RoundPolicy::new().format(1.0 / 3.0), "0.3333333333");
+        let huge = RoundPolicy::exact().with_significant_digits(Some(i32::MAX as u32));
+        assert_eq!(huge.round(1e-300), 1e-300);
+    }
+
+    #[test]
+    #[should_panic(expected = "significant digits")]
+    fn too_many_significant_digits() {
+        let _ = RoundPolicy::new().with_significant_digits(Some(i32::MAX as u32 + 1));
+    }
+
+    #[test]
+    fn apply_display() {
+        let cents = RoundPolicy::new().with_decimal_places(Some(2));
+        let summary: OnlineSummary = [1.0, 2.0, 2.125].into_iter().collect();
+        assert_eq!(
+            cents.apply(&summary).to_string(),
+            "n 3, mean 1.71, stddev 0.50, range [1.00, 2.12]"
+        );
+        // without a policy, stored values are shown as they were added
+        assert!(summary.to_string().ends_with("range [1, 2.125]"));
+
+        let minmax: MinMax<f32> = [0.5f32, 1.0 / 3.0].into_iter().collect();
+        assert_eq!(cents.apply(&minmax).to_string(), "[0.33, 0.50]");
+        let mut data: Unsorted<f64> = Unsorted::new();
+        data.extend([Some(2.0 / 3.0), None, Some(0.1)]);
+        assert_eq!(
+            cents.apply(&data).to_string(),
+            "2 values in [0.10, 0.67], 1 nulls"
+        );
+    }
+
+    #[test]
+    fn apply_serialize() {
+        let cents = RoundPolicy::new().with_decimal_places(Some(2));
+        let result = TestResult {
+            statistic: 1.0 / 3.0,
+            p_value: 0.125,
+        };
+        let json = serde_json::to_string(&cents.apply(&result)).unwrap();
+        assert_eq!(json, r#"{"statistic":0.33,"p_value":0.12}"#);
+        let nested = (Some(2.0f32 / 3.0), vec![1.005f64], 7u8);
+        let json = serde_json::to_string(&cents.apply(&nested)).unwrap();
+        assert_eq!(json, "[0.67,[1.0],7]");
+        // the value itself is not rounded
+        assert_eq!(result.statistic, 1.0 / 3.0);
+    }
+}
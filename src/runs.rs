@@ -0,0 +1,289 @@
+use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+
+use crate::special::normal_two_sided_p;
+use crate::unsorted::median_with;
+use crate::{TestResult, Unsorted};
+
+/// Online state for runs (streaks) of identical consecutive values.
+///
+/// Long runs of one value reveal constant-filled regions, such as a stuck
+/// sensor or a default value copied down a column. This tracks the number
+/// of runs and the longest one, in constant space.
+///
+/// Since runs depend on the order of the values, this does not implement
+/// `Commute`. Chunks of a stream can instead be combined, in stream order,
+/// with `append`, which joins a run that crosses the boundary.
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
+pub struct Runs<T> {
+    len: u64,
+    runs: u64,
+    first: Option<(T, u64)>,
+    last: Option<(T, u64)>,
+    longest: Option<(T, u64)>,
+}
+
+impl<T: PartialEq + Clone> Runs<T> {
+    /// Create initial empty state.
+    #[must_use]
+    pub fn new() -> Runs<T> {
+        Default::default()
+    }
+
+    /// Add the next value of the stream.
+    #[inline]
+    pub fn add(&mut self, sample: T) {
+        self.len += 1;
+        match &mut self.last {
+            Some((v, n)) if *v == sample => *n += 1,
+            _ => {
+                self.runs += 1;
+                self.last = Some((sample, 1));
+            }
+        }
+        if self.runs == 1 {
+            self.first.clone_from(&self.last);
+        }
+        self.update_longest();
+    }
+
+    /// Keeps the last run as the longest if it is strictly longer, so the
+    /// earliest of equally long runs is reported.
+    #[inline]
+    fn update_longest(&mut self) {
+        let (Some((v, n)), longest) = (&self.last, &mut self.longest) else {
+            return;
+        };
+        match longest {
+            Some((_, m)) if *m >= *n => {}
+            _ => *longest = Some((v.clone(), *n)),
+        }
+    }
+
+    /// Appends the state of the chunk of the stream that immediately
+    /// follows this one.
+    pub fn append(&mut self, v: Runs<T>) {
+        if v.len == 0 {
+            return;
+        }
+        if self.len == 0 {
+            *self = v;
+            return;
+        }
+        let joined = match (&self.last, &v.first) {
+            (Some((a, n)), Some((b, m))) if a == b => Some((a.clone(), n + m)),
+            _ => None,
+        };
+        // candidates for the longest run, in stream order, so the earliest
+        // of equally long runs is kept
+        let mut longest = self.longest.take();
+        for run in [joined.clone(), v.longest] {
+            match (&longest, run) {
+                (Some((_, m)), Some(run)) if run.1 > *m => longest = Some(run),
+                (None, run) => longest = run,
+                _ => {}
+            }
+        }
+        self.longest = longest;
+
+        let own_runs = self.runs;
+        self.len += v.len;
+        self.runs += v.runs;
+        self.last = v.last;
+        if let Some(run) = joined {
+            self.runs -= 1;
+            if own_runs == 1 {
+                self.first = Some(run.clone());
+            }
+            if v.runs == 1 {
+                self.last = Some(run);
+            }
+        }
+    }
+
+    /// Returns the number of runs.
+    #[inline]
+    #[must_use]
+    pub const fn runs(&self) -> u64 {
+        self.runs
+    }
+
+    /// Returns the value and length of the longest run, or `None` if there
+    /// are no values. Of equally long runs, the earliest is returned.
+    #[inline]
+    #[must_use]
+    pub fn longest_run(&self) -> Option<(&T, u64)> {
+        self.longest.as_ref().map(|(v, n)| (v, *n))
+    }
+
+    /// Returns the mean length of a run, or `None` if there are no values.
+    #[inline]
+    #[must_use]
+    pub fn mean_run_length(&self) -> Option<f64> {
+        if self.runs == 0 {
+            None
+        } else {
+            Some(self.len as f64 / self.runs as f64)
+        }
+    }
+
+    /// Returns the number of values.
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns true if there are no values.
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<T> Default for Runs<T> {
+    #[inline]
+    fn default() -> Runs<T> {
+        Runs {
+            len: 0,
+            runs: 0,
+            first: None,
+            last: None,
+            longest: None,
+        }
+    }
+}
+
+impl<T: PartialEq + Clone> FromIterator<T> for Runs<T> {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = T>>(it: I) -> Runs<T> {
+        let mut v = Runs::new();
+        v.extend(it);
+        v
+    }
+}
+
+impl<T: PartialEq + Clone> Extend<T> for Runs<T> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
+        for sample in it {
+            self.add(sample);
+        }
+    }
+}
+
+impl<T: PartialOrd + ToPrimitive> Unsorted<T> {
+    /// Performs the Wald-Wolfowitz runs test of whether the data is in
+    /// random order.
+    ///
+    /// Values are classified as above or below the median, and values
+    /// equal to the median are dropped. The statistic is the number of
+    /// runs of values on the same side. Too few runs suggest trends or
+    /// clustering, and too many suggest oscillation. The two-sided p-value
+    /// uses the normal approximation, which is accurate once there are
+    /// more than about 10 values on each side.
+    ///
+    /// Runs are counted in the order in which the values were added. The
+    /// data is not sorted by this method.
+    ///
+    /// `None` is returned if there are no values on one of the sides, or if
+    /// the input order has been lost, i.e. order statistics sorted the data
+    /// while `Unsorted::set_track_insertion_order` was off.
+    #[must_use]
+    pub fn runs_test(&self) -> Option<TestResult> {
+        let data = self.stored_slice();
+        let positions = self.sorted_positions();
+        let median = median_with(data.len(), |i| {
+            positions.get(i).and_then(|&p| data[p].to_f64())
+        })?;
+
+        let (mut above, mut below, mut runs) = (0u64, 0u64, 0u64);
+        let mut side = None;
+        for x in self.insertion_ordered()? {
+            let x = x.to_f64().unwrap();
+            if x == median || x.is_nan() {
+                continue;
+            }
+            let up = x > median;
+            if up {
+                above += 1;
+            } else {
+                below += 1;
+            }
+            if side != Some(up) {
+                runs += 1;
+                side = Some(up);
+            }
+        }
+        if above == 0 || below == 0 {
+            return None;
+        }
+        let (n1, n2) = (above as f64, below as f64);
+        let n = n1 + n2;
+        let expected = 2.0 * n1 * n2 / n + 1.0;
+        let variance = 2.0 * n1 * n2 * (2.0 * n1 * n2 - n) / (n * n * (n - 1.0));
+        let p_value = if variance > 0.0 {
+            normal_two_sided_p((runs as f64 - expected) / variance.sqrt())
+        } else {
+            1.0
+        };
+        Some(TestResult {
+            statistic: runs as f64,
+            p_value,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Runs;
+    use crate::Unsorted;
+
+    #[test]
+    fn streaks() {
+        let runs: Runs<u8> = [1, 1, 2, 2, 2, 3, 1, 1, 1].into_iter().collect();
+        assert_eq!(runs.runs(), 4);
+        assert_eq!(runs.longest_run(), Some((&2, 3)));
+        assert_eq!(runs.mean_run_length(), Some(2.25));
+        assert_eq!(Runs::<u8>::new().longest_run(), None);
+    }
+
+    #[test]
+    fn append_chunks() {
+        let data = [4, 4, 5, 5, 5, 5, 5, 6, 4, 4];
+        let whole: Runs<i32> = data.iter().copied().collect();
+        for split in [(2, 4), (3, 7), (0, 9), (4, 5)] {
+            let mut runs: Runs<i32> = data[..split.0].iter().copied().collect();
+            runs.append(data[split.0..split.1].iter().copied().collect());
+            runs.append(data[split.1..].iter().copied().collect());
+            assert_eq!(runs.runs(), whole.runs(), "{split:?}");
+            assert_eq!(runs.longest_run(), Some((&5, 5)), "{split:?}");
+            assert_eq!(runs.len(), 10);
+        }
+    }
+
+    #[test]
+    fn runs_test() {
+        // alternating above/below the median: far too many runs
+        let mut data: Unsorted<i32> = (0..40).map(|i| if i % 2 == 0 { i } else { -i }).collect();
+        let test = data.runs_test().unwrap();
+        assert_eq!(test.statistic, 40.0);
+        assert!(test.p_value < 1e-6);
+        let mut untracked = data.clone();
+        data.set_track_insertion_order(true);
+        data.median();
+        assert_eq!(data.runs_test(), Some(test));
+        untracked.median();
+        assert_eq!(untracked.runs_test(), None);
+
+        // a trend: only two runs
+        let trend: Unsorted<i32> = (0..40).collect();
+        let test = trend.runs_test().unwrap();
+        assert_eq!(test.statistic, 2.0);
+        assert!(test.p_value < 1e-6);
+
+        let constant: Unsorted<i32> = [3, 3, 3].into_iter().collect();
+        assert!(constant.runs_test().is_none());
+    }
+}
@@ -0,0 +1,508 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+
+use crate::rng::SplitMix64;
+use crate::special::normal_critical_value;
+use crate::{Commute, OnlineStats};
+
+/// The strategy used to decide which records are sampled.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub enum SamplingMethod {
+    /// Sample every `n`th record, starting with the first.
+    Systematic(u64),
+    /// Sample each record independently with probability `p`.
+    Bernoulli(f64),
+}
+
+/// A point estimate together with its confidence interval.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Estimate {
+    /// The extrapolated value.
+    pub value: f64,
+    /// The lower bound of the confidence interval.
+    pub lower: f64,
+    /// The upper bound of the confidence interval.
+    pub upper: f64,
+}
+
+/// Online state for profiling a sample of a stream and extrapolating
+/// totals to the whole stream.
+///
+/// Every record is offered to `add` (or `add_null`), but only the records
+/// selected by the `SamplingMethod` are accumulated. This permits quick
+/// profiles of very large inputs where reading and parsing every field
+/// would be too expensive.
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
+#[serde(try_from = "SampledStatsParts")]
+pub struct SampledStats {
+    method: SamplingMethod,
+    rng: SplitMix64,
+    seen: u64,
+    sampled: u64,
+    sampled_nulls: u64,
+    stats: OnlineStats,
+}
+
+/// The serialized form of a `SampledStats`, which is checked before use.
+#[derive(Deserialize)]
+struct SampledStatsParts {
+    method: SamplingMethod,
+    rng: SplitMix64,
+    seen: u64,
+    sampled: u64,
+    sampled_nulls: u64,
+    stats: OnlineStats,
+}
+
+impl TryFrom<SampledStatsParts> for SampledStats {
+    type Error = String;
+
+    fn try_from(v: SampledStatsParts) -> Result<SampledStats, String> {
+        match v.method {
+            SamplingMethod::Systematic(0) => {
+                return Err("systematic sampling step must be positive".to_owned());
+            }
+            SamplingMethod::Bernoulli(p) if !(0.0..=1.0).contains(&p) => {
+                return Err(format!("sampling probability {p} is not in [0, 1]"));
+            }
+            _ => {}
+        }
+        Ok(SampledStats {
+            method: v.method,
+            rng: v.rng,
+            seen: v.seen,
+            sampled: v.sampled,
+            sampled_nulls: v.sampled_nulls,
+            stats: v.stats,
+        })
+    }
+}
+
+impl SampledStats {
+    /// Create a sampler that accumulates every `step`th record.
+    ///
+    /// A `step` of `0` is treated as `1`, i.e. every record is sampled.
+    #[must_use]
+    pub fn systematic(step: u64) -> SampledStats {
+        SampledStats::with_method(SamplingMethod::Systematic(step.max(1)), 0)
+    }
+
+    /// Create a sampler that accumulates each record with probability `p`.
+    ///
+    /// The `seed` makes the selection reproducible.
+    ///
+    /// # Panics
+    ///
+    /// If `p` is not in `[0, 1]`, e.g. if it is NaN.
+    #[must_use]
+    pub fn bernoulli(p: f64, seed: u64) -> SampledStats {
+        assert!(
+            (0.0..=1.0).contains(&p),
+            "sampling probability {p} is not in [0, 1]"
+        );
+        SampledStats::with_method(SamplingMethod::Bernoulli(p), seed)
+    }
+
+    fn with_method(method: SamplingMethod, seed: u64) -> SampledStats {
+        SampledStats {
+            method,
+            rng: SplitMix64::new(seed),
+            seen: 0,
+            sampled: 0,
+            sampled_nulls: 0,
+            stats: OnlineStats::new(),
+        }
+    }
+
+    /// Returns the sampling method in use.
+    #[inline]
+    #[must_use]
+    pub const fn method(&self) -> SamplingMethod {
+        self.method
+    }
+
+    /// Decides whether the next record is sampled and counts it as seen.
+    #[inline]
+    fn select(&mut self) -> bool {
+        let selected = match self.method {
+            SamplingMethod::Systematic(step) => self.seen % step == 0,
+            SamplingMethod::Bernoulli(p) => self.rng.next_f64() < p,
+        };
+        self.seen += 1;
+        if selected {
+            self.sampled += 1;
+        }
+        selected
+    }
+
+    /// Offer a new sample.
+    ///
+    /// Returns `true` if the sample was selected and accumulated.
+    #[inline]
+    pub fn add<T: ToPrimitive>(&mut self, sample: &T) -> bool {
+        let selected = self.select();
+        if selected {
+            self.stats.add(sample);
+        }
+        selected
+    }
+
+    /// Offer a new NULL value.
+    ///
+    /// Returns `true` if the NULL was selected and counted.
+    #[inline]
+    pub fn add_null(&mut self) -> bool {
+        let selected = self.select();
+        if selected {
+            self.sampled_nulls += 1;
+        }
+        selected
+    }
+
+    /// Returns the statistics of the sampled, non-NULL values.
+    #[inline]
+    #[must_use]
+    pub const fn stats(&self) -> &OnlineStats {
+        &self.stats
+    }
+
+    /// Returns the number of records offered, sampled or not.
+    #[inline]
+    #[must_use]
+    pub const fn records_seen(&self) -> u64 {
+        self.seen
+    }
+
+    /// Returns the number of records selected, including NULLs.
+    #[inline]
+    #[must_use]
+    pub const fn sample_size(&self) -> u64 {
+        self.sampled
+    }
+
+    /// Returns the fraction of records that were selected.
+    #[inline]
+    #[must_use]
+    pub fn sampling_fraction(&self) -> f64 {
+        if self.seen == 0 {
+            return 0.0;
+        }
+        self.sampled as f64 / self.seen as f64
+    }
+
+    /// Returns the finite population correction for the variance of an estimate.
+    #[inline]
+    fn fpc(&self) -> f64 {
+        1.0 - self.sampling_fraction()
+    }
+
+    /// Estimate the number of non-NULL values in all records seen.
+    ///
+    /// The interval is the normal approximation of the binomial proportion
+    /// at the requested `confidence` level (e.g. `0.95`).
+    ///
+    /// `None` is returned if no records were sampled or `confidence` is not
+    /// in `(0, 1)`.
+    #[must_use]
+    pub fn estimated_count(&self, confidence: f64) -> Option<Estimate> {
+        self.estimate_count(self.stats.len() as u64, confidence)
+    }
+
+    /// Estimate the number of NULL values in all records seen.
+    ///
+    /// `None` is returned if no records were sampled or `confidence` is not
+    /// in `(0, 1)`.
+    #[must_use]
+    pub fn estimated_nulls(&self, confidence: f64) -> Option<Estimate> {
+        self.estimate_count(self.sampled_nulls, confidence)
+    }
+
+    fn estimate_count(&self, hits: u64, confidence: f64) -> Option<Estimate> {
+        if self.sampled == 0 || !valid_confidence(confidence) {
+            return None;
+        }
+        let (n, population) = (self.sampled as f64, self.seen as f64);
+        let p = hits as f64 / n;
+        let se = population * (p * (1.0 - p) / n * self.fpc()).sqrt();
+        let z = normal_critical_value(confidence);
+        let value = population * p;
+        Some(Estimate {
+            value,
+            lower: (z.mul_add(-se, value)).max(0.0),
+            upper: (z.mul_add(se, value)).min(population),
+        })
+    }
+
+    /// Estimate the sum of all non-NULL values in all records seen.
+    ///
+    /// NULLs are treated as contributing `0` to the total. The interval
+    /// uses the sample standard deviation with a finite population
+    /// correction at the requested `confidence` level (e.g. `0.95`).
+    ///
+    /// `None` is returned if fewer than two records were sampled or
+    /// `confidence` is not in `(0, 1)`.
+    #[must_use]
+    pub fn estimated_sum(&self, confidence: f64) -> Option<Estimate> {
+        if self.sampled < 2 || !valid_confidence(confidence) {
+            return None;
+        }
+        let (n, population) = (self.sampled as f64, self.seen as f64);
+        // Treat sampled NULLs as zeros, so the per-record mean and variance
+        // cover every sampled record rather than only the non-NULL ones.
+        // Pooling with a group of zeros (mean 0, no spread) shrinks the mean
+        // by n1 / n and adds n1 * k / n * mean^2 to the squared deviations.
+        let (n1, k) = (self.stats.len() as f64, self.sampled_nulls as f64);
+        let mean = self.stats.mean();
+        let m2 = (n1 * k / n).mul_add(mean * mean, self.stats.m2());
+        let sample_variance = m2 / (n - 1.0);
+        let se = population * (sample_variance / n * self.fpc()).sqrt();
+        let z = normal_critical_value(confidence);
+        let value = population * mean * n1 / n;
+        Some(Estimate {
+            value,
+            lower: z.mul_add(-se, value),
+            upper: z.mul_add(se, value),
+        })
+    }
+
+    /// Estimate the mean of all non-NULL values in all records seen.
+    ///
+    /// `None` is returned if fewer than two non-NULL values were sampled or
+    /// `confidence` is not in `(0, 1)`.
+    #[must_use]
+    pub fn estimated_mean(&self, confidence: f64) -> Option<Estimate> {
+        let n = self.stats.len() as f64;
+        if n < 2.0 || !valid_confidence(confidence) {
+            return None;
+        }
+        let sample_variance = self.stats.sample_variance();
+        let se = (sample_variance / n * self.fpc()).sqrt();
+        let z = normal_critical_value(confidence);
+        let value = self.stats.mean();
+        Some(Estimate {
+            value,
+            lower: z.mul_add(-se, value),
+            upper: z.mul_add(se, value),
+        })
+    }
+}
+
+/// Returns whether `confidence` is a level for which an interval exists.
+#[inline]
+fn valid_confidence(confidence: f64) -> bool {
+    confidence > 0.0 && confidence < 1.0
+}
+
+impl Commute for SampledStats {
+    /// # Panics
+    ///
+    /// If the samplers use different sampling methods.
+    #[inline]
+    fn merge(&mut self, v: SampledStats) {
+        assert!(
+            self.method == v.method,
+            "cannot merge samples taken with different sampling methods"
+        );
+        self.seen += v.seen;
+        self.sampled += v.sampled;
+        self.sampled_nulls += v.sampled_nulls;
+        self.stats.merge(v.stats);
+    }
+}
+
+impl<T: ToPrimitive> Extend<T> for SampledStats {
+    #[inline]
+    fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
+        for sample in it {
+            self.add(&sample);
+        }
+    }
+}
+
+/// Returns every `step`th item of `it`, starting at a random offset in
+/// `0..step` chosen with `seed`.
+///
+/// This works on streams of unknown length in a single pass, and gives a
+/// sample that is spread evenly over the input. A `step` of `0` is treated
+/// as `1`, i.e. every item is returned.
+pub fn sample_systematic<T, I>(it: I, step: usize, seed: u64) -> Vec<T>
+where
+    I: IntoIterator<Item = T>,
+{
+    let step = step.max(1);
+    let start = SplitMix64::new(seed).next_below(step);
+    it.into_iter().skip(start).step_by(step).collect()
+}
+
+/// Returns a uniform random sample of up to `k` values from each group of
+/// a stream of `(group, value)` pairs.
+///
+/// Each group is sampled with its own reservoir (Algorithm R), so this
+/// needs a single pass and memory for `k` values per group, and every
+/// value of a group is equally likely to be selected. The samples are
+/// reproducible for a given `seed`, and a group's sample is in input order.
+pub fn sample_stratified<K, T, I>(it: I, k: usize, seed: u64) -> HashMap<K, Vec<T>>
+where
+    K: Eq + Hash,
+    I: IntoIterator<Item = (K, T)>,
+{
+    let mut rng = SplitMix64::new(seed);
+    let mut groups: HashMap<K, (u64, Vec<(u64, T)>)> = HashMap::new();
+    for (key, value) in it {
+        let (seen, reservoir) = groups.entry(key).or_default();
+        if reservoir.len() < k {
+            reservoir.push((*seen, value));
+        } else {
+            let j = rng.next_below(*seen as usize + 1);
+            if j < k {
+                reservoir[j] = (*seen, value);
+            }
+        }
+        *seen += 1;
+    }
+    groups
+        .into_iter()
+        .map(|(key, (_, mut reservoir))| {
+            reservoir.sort_unstable_by_key(|&(i, _)| i);
+            (key, reservoir.into_iter().map(|(_, v)| v).collect())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{sample_stratified, sample_systematic, SampledStats};
+    use crate::Commute;
+
+    #[test]
+    fn systematic() {
+        let sample = sample_systematic(0..100, 10, 7);
+        assert_eq!(sample.len(), 10);
+        assert!(sample.windows(2).all(|w| w[1] - w[0] == 10));
+        assert_eq!(sample, sample_systematic(0..100, 10, 7));
+        assert_eq!(sample_systematic(0..3, 0, 7), [0, 1, 2]);
+    }
+
+    #[test]
+    fn stratified() {
+        let data = (0..1000).map(|i| (i % 3, i));
+        let sample = sample_stratified(data.clone(), 5, 11);
+        assert_eq!(sample.len(), 3);
+        for (key, values) in &sample {
+            assert_eq!(values.len(), 5);
+            assert!(values.iter().all(|v| v % 3 == *key));
+            assert!(values.windows(2).all(|w| w[0] < w[1]));
+        }
+        assert_eq!(sample, sample_stratified(data, 5, 11));
+
+        let small = sample_stratified([("a", 1), ("b", 2), ("a", 3)], 5, 0);
+        assert_eq!(small["a"], [1, 3]);
+        assert_eq!(small["b"], [2]);
+    }
+
+    #[test]
+    fn systematic_every_nth() {
+        let mut sampled = SampledStats::systematic(10);
+        sampled.extend(0usize..1000);
+        assert_eq!(sampled.records_seen(), 1000);
+        assert_eq!(sampled.sample_size(), 100);
+        assert_eq!(sampled.stats().mean(), 495.0);
+
+        let sum = sampled.estimated_sum(0.95).unwrap();
+        assert_eq!(sum.value, 495_000.0);
+        assert!(sum.lower < 499_500.0 && 499_500.0 < sum.upper);
+    }
+
+    #[test]
+    fn bernoulli_is_reproducible() {
+        let mut s1 = SampledStats::bernoulli(0.1, 42);
+        let mut s2 = SampledStats::bernoulli(0.1, 42);
+        s1.extend(0usize..10_000);
+        s2.extend(0usize..10_000);
+        assert_eq!(s1.sample_size(), s2.sample_size());
+        assert_eq!(s1.stats().mean(), s2.stats().mean());
+
+        let count = s1.estimated_count(0.95).unwrap();
+        assert_eq!(count.value, 10_000.0);
+        let mean = s1.estimated_mean(0.95).unwrap();
+        assert!(mean.lower < 4999.5 && 4999.5 < mean.upper);
+        for confidence in [0.0, 1.0, 1.5, -0.9, f64::NAN] {
+            assert!(s1.estimated_count(confidence).is_none());
+            assert!(s1.estimated_nulls(confidence).is_none());
+            assert!(s1.estimated_sum(confidence).is_none());
+            assert!(s1.estimated_mean(confidence).is_none());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "sampling probability")]
+    fn bernoulli_rejects_nan() {
+        let _ = SampledStats::bernoulli(f64::NAN, 0);
+    }
+
+    #[test]
+    fn nulls_extrapolated() {
+        let mut sampled = SampledStats::systematic(2);
+        for i in 0..1000 {
+            if i % 4 == 0 {
+                sampled.add_null();
+            } else {
+                sampled.add(&1usize);
+            }
+        }
+        let nulls = sampled.estimated_nulls(0.95).unwrap();
+        assert_eq!(nulls.value, 500.0);
+        assert_eq!(sampled.estimated_count(0.95).unwrap().value, 500.0);
+
+        // NULLs count as zeros in the sum, as if they had been added as 0
+        let mut zeros = SampledStats::systematic(2);
+        zeros.extend((0..1000usize).map(|i| usize::from(i % 4 != 0)));
+        let (sum, expected) = (
+            sampled.estimated_sum(0.95).unwrap(),
+            zeros.estimated_sum(0.95).unwrap(),
+        );
+        assert_eq!(sum.value, 500.0);
+        assert!((sum.lower - expected.lower).abs() < 1e-9);
+        assert!((sum.upper - expected.upper).abs() < 1e-9);
+    }
+
+    #[test]
+    fn merge_shards() {
+        let mut s1 = SampledStats::systematic(1);
+        let mut s2 = SampledStats::systematic(1);
+        s1.extend([1usize, 2, 3]);
+        s2.extend([4usize, 5, 6]);
+        s1.merge(s2);
+        assert_eq!(s1.records_seen(), 6);
+        let sum = s1.estimated_sum(0.95).unwrap();
+        // every record was sampled, so the interval collapses to the exact sum
+        assert_eq!(sum.value, 21.0);
+        assert_eq!(sum.lower, 21.0);
+        assert_eq!(sum.upper, 21.0);
+    }
+
+    #[test]
+    fn deserialize_checks_method() {
+        let mut sampler = SampledStats::systematic(2);
+        sampler.extend([1usize, 2, 3]);
+        let json = serde_json::to_string(&sampler).unwrap();
+        let back: SampledStats = serde_json::from_str(&json).unwrap();
+        assert!(back == sampler);
+
+        let zero = json.replace(r#"{"Systematic":2}"#, r#"{"Systematic":0}"#);
+        assert_ne!(zero, json);
+        assert!(serde_json::from_str::<SampledStats>(&zero).is_err());
+        let p = json.replace(r#"{"Systematic":2}"#, r#"{"Bernoulli":1.5}"#);
+        assert!(serde_json::from_str::<SampledStats>(&p).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "different sampling methods")]
+    fn merge_rejects_other_methods() {
+        let mut s1 = SampledStats::systematic(10);
+        s1.merge(SampledStats::bernoulli(0.1, 0));
+    }
+}
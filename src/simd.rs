@@ -0,0 +1,234 @@
+//! Vectorized kernels for the hot loops over `f64` slices.
+//!
+//! Every kernel accumulates in four independent lanes, where lane `j` sees
+//! the elements `j, j + 4, j + 8, ...`, and combines the lanes in a fixed
+//! order. With the `simd` feature on `x86_64`, the lanes are AVX2 registers
+//! when the CPU supports them. Otherwise, the portable fallback uses the
+//! same lane layout, so both paths return bit-identical results.
+
+const LANES: usize = 4;
+
+/// Returns the sum of `data`.
+pub(crate) fn sum(data: &[f64]) -> f64 {
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    if is_x86_feature_detected!("avx2") {
+        // safety: AVX2 support was just detected
+        return unsafe { avx2::sum(data) };
+    }
+    let chunks = data.chunks_exact(LANES);
+    let rest = chunks.remainder();
+    let mut acc = [0.0; LANES];
+    for c in chunks {
+        for j in 0..LANES {
+            acc[j] += c[j];
+        }
+    }
+    hsum(acc) + rest.iter().sum::<f64>()
+}
+
+/// Returns the sum of the squared deviations of `data` from `center`.
+pub(crate) fn sum_sq_dev(data: &[f64], center: f64) -> f64 {
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    if is_x86_feature_detected!("avx2") {
+        // safety: AVX2 support was just detected
+        return unsafe { avx2::sum_sq_dev(data, center) };
+    }
+    let chunks = data.chunks_exact(LANES);
+    let rest = chunks.remainder();
+    let mut acc = [0.0; LANES];
+    for c in chunks {
+        for j in 0..LANES {
+            let d = c[j] - center;
+            acc[j] += d * d;
+        }
+    }
+    hsum(acc)
+        + rest
+            .iter()
+            .map(|x| (x - center) * (x - center))
+            .sum::<f64>()
+}
+
+/// Returns the number of values whose magnitude is at least `limit`.
+pub(crate) fn count_abs_ge(data: &[f64], limit: f64) -> u64 {
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    if is_x86_feature_detected!("avx2") {
+        // safety: AVX2 support was just detected
+        return unsafe { avx2::count_abs_ge(data, limit) };
+    }
+    data.iter().filter(|x| x.abs() >= limit).count() as u64
+}
+
+/// Replaces every value `x` of `data` with `|x - center|`.
+pub(crate) fn abs_dev_in_place(data: &mut [f64], center: f64) {
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    if is_x86_feature_detected!("avx2") {
+        // safety: AVX2 support was just detected
+        unsafe { avx2::abs_dev_in_place(data, center) };
+        return;
+    }
+    for x in data {
+        *x = (*x - center).abs();
+    }
+}
+
+/// Returns the smallest and largest values of `data`, ignoring `NaN`s.
+///
+/// `None` is returned if `data` has no values other than `NaN`.
+pub(crate) fn min_max(data: &[f64]) -> Option<(f64, f64)> {
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    if is_x86_feature_detected!("avx2") {
+        // safety: AVX2 support was just detected
+        return unsafe { avx2::min_max(data) };
+    }
+    let (min, max) = data
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &x| {
+            (min.min(x), max.max(x))
+        });
+    (min <= max).then_some((min, max))
+}
+
+#[inline]
+fn hsum(acc: [f64; LANES]) -> f64 {
+    (acc[0] + acc[1]) + (acc[2] + acc[3])
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+mod avx2 {
+    use std::arch::x86_64::{
+        __m256d, _mm256_add_pd, _mm256_andnot_pd, _mm256_cmp_pd, _mm256_loadu_pd, _mm256_max_pd,
+        _mm256_min_pd, _mm256_movemask_pd, _mm256_mul_pd, _mm256_set1_pd, _mm256_setzero_pd,
+        _mm256_storeu_pd, _mm256_sub_pd, _CMP_GE_OQ,
+    };
+
+    use super::{hsum, LANES};
+
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn lanes(v: __m256d) -> [f64; LANES] {
+        let mut out = [0.0; LANES];
+        _mm256_storeu_pd(out.as_mut_ptr(), v);
+        out
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn sum(data: &[f64]) -> f64 {
+        let chunks = data.chunks_exact(LANES);
+        let rest = chunks.remainder();
+        let mut acc = _mm256_setzero_pd();
+        for c in chunks {
+            acc = _mm256_add_pd(acc, _mm256_loadu_pd(c.as_ptr()));
+        }
+        hsum(lanes(acc)) + rest.iter().sum::<f64>()
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn sum_sq_dev(data: &[f64], center: f64) -> f64 {
+        let chunks = data.chunks_exact(LANES);
+        let rest = chunks.remainder();
+        let m = _mm256_set1_pd(center);
+        let mut acc = _mm256_setzero_pd();
+        for c in chunks {
+            let d = _mm256_sub_pd(_mm256_loadu_pd(c.as_ptr()), m);
+            acc = _mm256_add_pd(acc, _mm256_mul_pd(d, d));
+        }
+        hsum(lanes(acc))
+            + rest
+                .iter()
+                .map(|x| (x - center) * (x - center))
+                .sum::<f64>()
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn count_abs_ge(data: &[f64], limit: f64) -> u64 {
+        let chunks = data.chunks_exact(LANES);
+        let rest = chunks.remainder();
+        let (sign, lim) = (_mm256_set1_pd(-0.0), _mm256_set1_pd(limit));
+        let mut count = 0u64;
+        for c in chunks {
+            let abs = _mm256_andnot_pd(sign, _mm256_loadu_pd(c.as_ptr()));
+            let ge = _mm256_cmp_pd::<_CMP_GE_OQ>(abs, lim);
+            count += u64::from(_mm256_movemask_pd(ge).count_ones());
+        }
+        count + rest.iter().filter(|x| x.abs() >= limit).count() as u64
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn abs_dev_in_place(data: &mut [f64], center: f64) {
+        let (sign, m) = (_mm256_set1_pd(-0.0), _mm256_set1_pd(center));
+        let mut chunks = data.chunks_exact_mut(LANES);
+        for c in &mut chunks {
+            let d = _mm256_sub_pd(_mm256_loadu_pd(c.as_ptr()), m);
+            _mm256_storeu_pd(c.as_mut_ptr(), _mm256_andnot_pd(sign, d));
+        }
+        for x in chunks.into_remainder() {
+            *x = (*x - center).abs();
+        }
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn min_max(data: &[f64]) -> Option<(f64, f64)> {
+        let chunks = data.chunks_exact(LANES);
+        let rest = chunks.remainder();
+        let mut mn = _mm256_set1_pd(f64::INFINITY);
+        let mut mx = _mm256_set1_pd(f64::NEG_INFINITY);
+        for c in chunks {
+            let x = _mm256_loadu_pd(c.as_ptr());
+            // when `x` is NaN, these return the second operand unchanged
+            mn = _mm256_min_pd(x, mn);
+            mx = _mm256_max_pd(x, mx);
+        }
+        let (mn, mx) = (lanes(mn), lanes(mx));
+        let (min, max) = mn
+            .iter()
+            .zip(mx.iter())
+            .chain(rest.iter().zip(rest.iter()))
+            .fold(
+                (f64::INFINITY, f64::NEG_INFINITY),
+                |(min, max), (&a, &b)| (min.min(a), max.max(b)),
+            );
+        (min <= max).then_some((min, max))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{abs_dev_in_place, count_abs_ge, min_max, sum, sum_sq_dev};
+
+    fn data() -> Vec<f64> {
+        (0..103)
+            .map(|i| f64::from(i).mul_add(0.37, -11.0))
+            .collect()
+    }
+
+    #[test]
+    fn kernels_match_scalar() {
+        let data = data();
+        let total: f64 = data.iter().sum();
+        assert!((sum(&data) - total).abs() < 1e-9);
+        let mean = total / data.len() as f64;
+        let q: f64 = data.iter().map(|x| (x - mean) * (x - mean)).sum();
+        assert!((sum_sq_dev(&data, mean) - q).abs() < 1e-9);
+        assert_eq!(
+            count_abs_ge(&data, 10.0),
+            data.iter().filter(|x| x.abs() >= 10.0).count() as u64
+        );
+
+        let mut devs = data.clone();
+        abs_dev_in_place(&mut devs, 1.5);
+        for (d, x) in devs.iter().zip(&data) {
+            assert_eq!(*d, (x - 1.5).abs());
+        }
+    }
+
+    #[test]
+    fn min_max_skips_nan() {
+        let mut data = data();
+        data[5] = f64::NAN;
+        data[100] = f64::NAN;
+        assert_eq!(min_max(&data), Some((-11.0, 102f64.mul_add(0.37, -11.0))));
+        assert_eq!(min_max(&[f64::NAN; 6]), None);
+        assert_eq!(min_max(&[]), None);
+    }
+}
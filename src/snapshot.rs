@@ -0,0 +1,146 @@
+//! Versioned snapshot formats for persisting accumulators.
+//!
+//! The serde derives on the accumulators themselves mirror their internal
+//! fields, which may change between crate versions. The snapshot types in
+//! this module are a stable format instead: a released version is never
+//! changed, and a new layout is added as a new `V2` type. Convert with
+//! `From`, e.g. `OnlineStatsSnapshotV1::from(&stats)` before serializing
+//! and `OnlineStats::from(snapshot)` after deserializing.
+//!
+//! With the `rkyv` feature, the snapshots also implement rkyv's `Archive`,
+//! so a checkpoint can be memory-mapped and read in place (e.g. the values
+//! of an `ArchivedUnsortedSnapshotV1`) without deserializing a copy.
+
+use serde::{Deserialize, Serialize};
+
+/// Version 1 snapshot of an `OnlineStats`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+pub struct OnlineStatsSnapshotV1 {
+    /// The number of samples.
+    pub size: u64,
+    /// The mean of the samples.
+    pub mean: f64,
+    /// The sum of squared deviations from the mean.
+    pub m2: f64,
+    /// The number of NULL values.
+    pub nulls: u64,
+    /// The number of samples that may have lost precision.
+    pub imprecise: u64,
+}
+
+/// Version 2 snapshot of an `OnlineStats`.
+///
+/// In addition to version 1, this keeps the higher moments and the numbers
+/// of negative, zero and positive samples. A version 1 snapshot converts to version 2 with `From`,
+/// leaving the new fields unknown or zero.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+pub struct OnlineStatsSnapshotV2 {
+    /// The number of samples.
+    pub size: u64,
+    /// The mean of the samples.
+    pub mean: f64,
+    /// The sum of squared deviations from the mean.
+    pub m2: f64,
+    /// The sum of cubed deviations from the mean, or `None` if the higher
+    /// moments were not tracked.
+    pub m3: Option<f64>,
+    /// The sum of fourth-power deviations from the mean, or `None` if the
+    /// higher moments were not tracked.
+    pub m4: Option<f64>,
+    /// The number of NULL values.
+    pub nulls: u64,
+    /// The number of samples that may have lost precision.
+    pub imprecise: u64,
+    /// The number of negative samples.
+    pub negatives: u64,
+    /// The number of samples equal to zero.
+    pub zeros: u64,
+    /// The number of positive samples.
+    pub positives: u64,
+}
+
+impl From<OnlineStatsSnapshotV1> for OnlineStatsSnapshotV2 {
+    fn from(v: OnlineStatsSnapshotV1) -> OnlineStatsSnapshotV2 {
+        OnlineStatsSnapshotV2 {
+            size: v.size,
+            mean: v.mean,
+            m2: v.m2,
+            m3: None,
+            m4: None,
+            nulls: v.nulls,
+            imprecise: v.imprecise,
+            negatives: 0,
+            zeros: 0,
+            positives: 0,
+        }
+    }
+}
+
+/// Version 1 snapshot of a `MinMax`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+pub struct MinMaxSnapshotV1<T> {
+    /// The number of samples.
+    pub len: u64,
+    /// The number of NULL values.
+    pub nulls: u64,
+    /// The minimum, or `None` if there are no samples.
+    pub min: Option<T>,
+    /// The maximum, or `None` if there are no samples.
+    pub max: Option<T>,
+}
+
+/// Version 1 snapshot of an `Unsorted`.
+///
+/// Only the values and the NULL count are kept. Settings such as
+/// `set_stable` and `set_parallelism` are not part of the snapshot.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+pub struct UnsortedSnapshotV1<T> {
+    /// The values, in no particular order.
+    pub data: Vec<T>,
+    /// The number of NULL values.
+    pub nulls: u64,
+}
+
+#[cfg(all(test, feature = "rkyv"))]
+mod test {
+    use rkyv::rancor::Error;
+
+    use super::{ArchivedUnsortedSnapshotV1, OnlineStatsSnapshotV2, UnsortedSnapshotV1};
+    use crate::{OnlineStats, Unsorted};
+
+    #[test]
+    fn rkyv_round_trip() {
+        let unsorted: Unsorted<u32> = [4, 1, 3].into_iter().collect();
+        let bytes = rkyv::to_bytes::<Error>(&UnsortedSnapshotV1::from(unsorted)).unwrap();
+        let archived = rkyv::access::<ArchivedUnsortedSnapshotV1<u32>, Error>(&bytes).unwrap();
+        assert_eq!(archived.data.len(), 3);
+        assert_eq!(archived.nulls, 0);
+
+        let snap: UnsortedSnapshotV1<u32> = rkyv::deserialize::<_, Error>(archived).unwrap();
+        assert_eq!(Unsorted::from(snap).median(), Some(3.0));
+
+        let mut stats = OnlineStats::with_higher_moments();
+        stats.extend([1.0, 2.0, 6.0]);
+        let bytes = rkyv::to_bytes::<Error>(&OnlineStatsSnapshotV2::from(&stats)).unwrap();
+        let snap = rkyv::from_bytes::<OnlineStatsSnapshotV2, Error>(&bytes).unwrap();
+        let restored = OnlineStats::from(snap);
+        assert_eq!((restored.mean(), restored.m2()), (stats.mean(), stats.m2()));
+        assert_eq!(restored.skewness(), stats.skewness());
+    }
+}
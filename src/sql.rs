@@ -0,0 +1,304 @@
+//! Aggregate functions that mirror SQL semantics exactly.
+//!
+//! Every function takes a stream of `Option<T>`, where `None` is a SQL
+//! `NULL`. As in SQL, NULLs are ignored, and an aggregate over no non-NULL
+//! values is `NULL` (`None`), except for `count`, which is `0`.
+//!
+//! The results match the reference outputs of DuckDB and PostgreSQL.
+
+use num_traits::ToPrimitive;
+
+use crate::{OnlineStats, Unsorted};
+
+/// `COUNT(x)`: the number of non-NULL values.
+pub fn count<T, I>(it: I) -> u64
+where
+    I: IntoIterator<Item = Option<T>>,
+{
+    it.into_iter().filter(Option::is_some).count() as u64
+}
+
+/// A column type that `sum` can add up.
+///
+/// Integer types are summed exactly, floating point types in `f64`.
+pub trait Summable: ToPrimitive {
+    /// Whether values of this type are integers.
+    const INTEGER: bool;
+}
+
+macro_rules! impl_summable {
+    ($integer:expr; $($ty:ty),*) => {
+        $(impl Summable for $ty {
+            const INTEGER: bool = $integer;
+        })*
+    };
+}
+
+impl_summable!(true; i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+impl_summable!(false; f32, f64);
+
+#[cfg(feature = "decimal")]
+impl Summable for rust_decimal::Decimal {
+    const INTEGER: bool = false;
+}
+
+/// `SUM(x)`: the sum of the non-NULL values.
+///
+/// Integer columns are summed exactly in an `i128`, so the result is only
+/// rounded once, when it is converted to `f64`. Use `sum_int` to get the
+/// exact sum of an integer column. Floating point columns are summed in
+/// `f64`, even when their values happen to be integral.
+///
+/// `None` is returned if all values are NULL, and `Some(Err(_))` if an
+/// integer column's sum overflows an `i128`, where SQL raises an error.
+pub fn sum<T, I>(it: I) -> Option<Result<f64, String>>
+where
+    T: Summable,
+    I: IntoIterator<Item = Option<T>>,
+{
+    let mut total: Option<(i128, f64)> = None;
+    for v in it.into_iter().flatten() {
+        let (ints, floats) = total.get_or_insert((0, 0.0));
+        if !T::INTEGER {
+            *floats += v.to_f64().unwrap();
+            continue;
+        }
+        match v.to_i128().and_then(|i| ints.checked_add(i)) {
+            Some(exact) => *ints = exact,
+            None => return Some(Err("sum overflowed i128".to_string())),
+        }
+    }
+    total.map(|(ints, floats)| Ok(ints as f64 + floats))
+}
+
+/// `SUM(x)` of an integer column, computed exactly.
+///
+/// `None` is returned if all values are NULL, and `Some(Err(_))` if the sum
+/// overflows an `i128`, where SQL raises an error. This cannot happen with
+/// fewer than 2^64 `i64` or `u64` values.
+pub fn sum_int<T, I>(it: I) -> Option<Result<i128, String>>
+where
+    T: Into<i128>,
+    I: IntoIterator<Item = Option<T>>,
+{
+    let mut total: Option<i128> = None;
+    for v in it.into_iter().flatten() {
+        match total.unwrap_or(0).checked_add(v.into()) {
+            Some(sum) => total = Some(sum),
+            None => return Some(Err("sum overflowed i128".to_string())),
+        }
+    }
+    total.map(Ok)
+}
+
+/// `AVG(x)`: the mean of the non-NULL values.
+pub fn avg<T, I>(it: I) -> Option<f64>
+where
+    T: ToPrimitive,
+    I: IntoIterator<Item = Option<T>>,
+{
+    non_null_stats(it, 1).map(|s| s.mean())
+}
+
+/// `VAR_POP(x)`: the population variance of the non-NULL values.
+pub fn var_pop<T, I>(it: I) -> Option<f64>
+where
+    T: ToPrimitive,
+    I: IntoIterator<Item = Option<T>>,
+{
+    non_null_stats(it, 1).map(|s| s.variance())
+}
+
+/// `VAR_SAMP(x)` (a.k.a. `VARIANCE(x)`): the sample variance of the
+/// non-NULL values.
+///
+/// `None` is returned if there are fewer than two non-NULL values.
+pub fn var_samp<T, I>(it: I) -> Option<f64>
+where
+    T: ToPrimitive,
+    I: IntoIterator<Item = Option<T>>,
+{
+    non_null_stats(it, 2).map(|s| s.sample_variance())
+}
+
+/// `STDDEV_POP(x)`: the population standard deviation of the non-NULL values.
+pub fn stddev_pop<T, I>(it: I) -> Option<f64>
+where
+    T: ToPrimitive,
+    I: IntoIterator<Item = Option<T>>,
+{
+    non_null_stats(it, 1).map(|s| s.stddev())
+}
+
+/// `STDDEV_SAMP(x)` (a.k.a. `STDDEV(x)`): the sample standard deviation of
+/// the non-NULL values.
+///
+/// `None` is returned if there are fewer than two non-NULL values.
+pub fn stddev_samp<T, I>(it: I) -> Option<f64>
+where
+    T: ToPrimitive,
+    I: IntoIterator<Item = Option<T>>,
+{
+    non_null_stats(it, 2).map(|s| s.sample_stddev())
+}
+
+/// `PERCENTILE_CONT(fraction) WITHIN GROUP (ORDER BY x)`: the percentile of
+/// the non-NULL values, linearly interpolated between adjacent values.
+///
+/// `None` is returned if `fraction` is not in `[0, 1]`.
+pub fn percentile_cont<T, I>(it: I, fraction: f64) -> Option<f64>
+where
+    T: PartialOrd + ToPrimitive,
+    I: IntoIterator<Item = Option<T>>,
+{
+    if !(0.0..=1.0).contains(&fraction) {
+        return None;
+    }
+    let mut unsorted: Unsorted<T> = it.into_iter().collect();
+    percentile_cont_on_sorted(unsorted.sorted_data(), fraction)
+}
+
+/// `PERCENTILE_DISC(fraction) WITHIN GROUP (ORDER BY x)`: the first
+/// non-NULL value whose cumulative distribution is at least `fraction`.
+///
+/// `None` is returned if `fraction` is not in `[0, 1]`.
+pub fn percentile_disc<T, I>(it: I, fraction: f64) -> Option<T>
+where
+    T: PartialOrd + Clone,
+    I: IntoIterator<Item = Option<T>>,
+{
+    if !(0.0..=1.0).contains(&fraction) {
+        return None;
+    }
+    let mut unsorted: Unsorted<T> = it.into_iter().collect();
+    percentile_disc_on_sorted(unsorted.sorted_data(), fraction).map(|p| p.0.clone())
+}
+
+/// `MEDIAN(x)`: equivalent to `PERCENTILE_CONT(0.5)`.
+pub fn median<T, I>(it: I) -> Option<f64>
+where
+    T: PartialOrd + ToPrimitive,
+    I: IntoIterator<Item = Option<T>>,
+{
+    percentile_cont(it, 0.5)
+}
+
+fn non_null_stats<T, I>(it: I, min_len: usize) -> Option<OnlineStats>
+where
+    T: ToPrimitive,
+    I: IntoIterator<Item = Option<T>>,
+{
+    let stats = OnlineStats::from_options(it);
+    if stats.len() < min_len {
+        return None;
+    }
+    Some(stats)
+}
+
+pub(crate) fn percentile_cont_on_sorted<T: ToPrimitive>(data: &[T], fraction: f64) -> Option<f64> {
+    let last = data.len().checked_sub(1)?;
+    let rank = fraction * last as f64;
+    let (lo, hi) = (rank.floor() as usize, rank.ceil() as usize);
+    let lo_val = data.get(lo)?.to_f64().unwrap();
+    if lo == hi {
+        return Some(lo_val);
+    }
+    let hi_val = data.get(hi)?.to_f64().unwrap();
+    Some((rank - lo as f64).mul_add(hi_val - lo_val, lo_val))
+}
+
+fn percentile_disc_on_sorted<T>(data: &[T], fraction: f64) -> Option<&T> {
+    let idx = (fraction * data.len() as f64).ceil() as usize;
+    data.get(idx.saturating_sub(1))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Reference values from DuckDB:
+    //   SELECT agg(x) FROM (VALUES (2), (4), (NULL), (4), (4), (5), (5), (7), (9)) t(x);
+    const DATA: [Option<i64>; 9] = [
+        Some(2),
+        Some(4),
+        None,
+        Some(4),
+        Some(4),
+        Some(5),
+        Some(5),
+        Some(7),
+        Some(9),
+    ];
+
+    #[test]
+    fn aggregates() {
+        assert_eq!(count(DATA), 8);
+        assert_eq!(sum(DATA), Some(Ok(40.0)));
+        assert_eq!(avg(DATA), Some(5.0));
+        assert_eq!(var_pop(DATA), Some(4.0));
+        assert_eq!(stddev_pop(DATA), Some(2.0));
+        assert!((var_samp(DATA).unwrap() - 4.571_428_571_428_571).abs() < 1e-12);
+        assert!((stddev_samp(DATA).unwrap() - 2.138_089_935_299_395).abs() < 1e-12);
+    }
+
+    #[test]
+    fn sum_large_integers() {
+        // every partial sum past 2^53 would be rounded in f64
+        let big = [Some(1i64 << 53), Some(1), Some(1), Some(-(1i64 << 53))];
+        assert_eq!(sum(big), Some(Ok(2.0)));
+        assert_eq!(sum_int(big), Some(Ok(2)));
+        assert_eq!(sum([Some(0.5), Some(3.0), Some(0.25)]), Some(Ok(3.75)));
+        assert_eq!(sum_int([None::<u64>]), None);
+        assert_eq!(
+            sum_int([Some(u64::MAX), Some(u64::MAX)]),
+            Some(Ok(2 * i128::from(u64::MAX)))
+        );
+        assert!(sum_int([Some(i128::MAX), Some(1)]).unwrap().is_err());
+        assert!(sum([Some(i128::MAX), Some(1)]).unwrap().is_err());
+        assert!(sum([Some(u128::MAX)]).unwrap().is_err());
+        // floats never overflow the exact sum
+        assert_eq!(
+            sum([Some(f64::MAX), Some(f64::MAX)]),
+            Some(Ok(f64::INFINITY))
+        );
+    }
+
+    #[test]
+    fn sum_floats_in_f64() {
+        // integral floats are not moved to the exact integer path
+        let big = 2f64.powi(53);
+        let floats = [Some(big), Some(1.0), Some(1.0), Some(-big)];
+        assert_eq!(sum(floats), Some(Ok(0.0)));
+        assert_eq!(sum(floats.map(|v| v.map(|x| x as i64))), Some(Ok(2.0)));
+    }
+
+    #[test]
+    fn all_nulls() {
+        let nulls = [None::<i64>, None];
+        assert_eq!(count(nulls), 0);
+        assert_eq!(sum(nulls), None);
+        assert_eq!(avg(nulls), None);
+        assert_eq!(var_pop(nulls), None);
+        assert_eq!(percentile_cont(nulls, 0.5), None);
+        assert_eq!(percentile_disc(nulls, 0.5), None);
+        // a single value has a population but no sample variance
+        assert_eq!(var_pop([Some(1)]), Some(0.0));
+        assert_eq!(var_samp([Some(1)]), None);
+        assert_eq!(stddev_samp([Some(1)]), None);
+    }
+
+    #[test]
+    fn percentiles() {
+        let data = [Some(1), None, Some(2), Some(3), Some(4)];
+        assert_eq!(percentile_cont(data, 0.0), Some(1.0));
+        assert_eq!(percentile_cont(data, 0.25), Some(1.75));
+        assert_eq!(percentile_cont(data, 0.5), Some(2.5));
+        assert_eq!(percentile_cont(data, 1.0), Some(4.0));
+        assert_eq!(percentile_disc(data, 0.0), Some(1));
+        assert_eq!(percentile_disc(data, 0.25), Some(1));
+        assert_eq!(percentile_disc(data, 0.5), Some(2));
+        assert_eq!(percentile_disc(data, 1.0), Some(4));
+        assert_eq!(median(DATA), Some(4.5));
+        assert_eq!(percentile_cont(data, 1.5), None);
+    }
+}
@@ -0,0 +1,123 @@
+use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+
+use crate::{OnlineStats, Unsorted};
+
+/// Standardizes values to z-scores, `(x - mean) / stddev`, with a fixed
+/// mean and standard deviation.
+///
+/// This is typically built from an `OnlineStats` computed over training
+/// data, and then applied to new values one at a time, e.g. to normalize
+/// features for machine learning without a second pass over the data.
+///
+/// When the standard deviation is `0` (constant data), every value is
+/// mapped to `0`.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Standardizer {
+    mean: f64,
+    stddev: f64,
+}
+
+impl Standardizer {
+    /// Create a standardizer with the given mean and standard deviation.
+    #[must_use]
+    pub const fn new(mean: f64, stddev: f64) -> Standardizer {
+        Standardizer { mean, stddev }
+    }
+
+    /// Returns the z-score of `sample`.
+    #[inline]
+    #[must_use]
+    pub fn transform<T: ToPrimitive>(&self, sample: &T) -> f64 {
+        let x = sample.to_f64().unwrap();
+        if self.stddev == 0.0 {
+            0.0
+        } else {
+            (x - self.mean) / self.stddev
+        }
+    }
+
+    /// Returns the value whose z-score is `z`.
+    #[inline]
+    #[must_use]
+    pub fn inverse(&self, z: f64) -> f64 {
+        z.mul_add(self.stddev, self.mean)
+    }
+
+    /// Returns the mean that is subtracted.
+    #[inline]
+    #[must_use]
+    pub const fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// Returns the standard deviation that is divided by.
+    #[inline]
+    #[must_use]
+    pub const fn stddev(&self) -> f64 {
+        self.stddev
+    }
+}
+
+impl From<&OnlineStats> for Standardizer {
+    /// Uses the mean and (population) standard deviation of `stats`.
+    fn from(stats: &OnlineStats) -> Standardizer {
+        Standardizer::new(stats.mean(), stats.stddev())
+    }
+}
+
+impl<T: PartialOrd + ToPrimitive> Unsorted<T> {
+    /// Returns the z-score of every value, using the mean and (population)
+    /// standard deviation of the data.
+    ///
+    /// The z-scores are in the order in which the values were added.
+    /// `None` is returned if that order has been lost, i.e. order
+    /// statistics sorted the data while
+    /// `Unsorted::set_track_insertion_order` was off.
+    ///
+    /// Use a `Standardizer` to standardize values that are not part of the
+    /// data.
+    #[must_use]
+    pub fn zscores(&self) -> Option<Vec<f64>> {
+        let standardizer = Standardizer::from(&OnlineStats::from(self));
+        Some(
+            self.insertion_ordered()?
+                .into_iter()
+                .map(|x| standardizer.transform(x))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Standardizer;
+    use crate::{OnlineStats, Unsorted};
+
+    #[test]
+    fn zscores() {
+        let mut data: Unsorted<u32> = [9, 2, 4, 4, 5, 5, 4, 7].into_iter().collect();
+        let expected = vec![2.0, -1.5, -0.5, -0.5, 0.0, 0.0, -0.5, 1.0];
+        assert_eq!(data.zscores(), Some(expected.clone()));
+        // still in input order after a sort, unless that is not tracked
+        let mut untracked = data.clone();
+        data.set_track_insertion_order(true);
+        assert_eq!(data.median(), Some(4.5));
+        assert_eq!(data.zscores(), Some(expected));
+        untracked.median();
+        assert_eq!(untracked.zscores(), None);
+        let constant: Unsorted<u32> = [3, 3].into_iter().collect();
+        assert_eq!(constant.zscores(), Some(vec![0.0, 0.0]));
+        assert_eq!(Unsorted::<u32>::new().zscores(), Some(vec![]));
+    }
+
+    #[test]
+    fn standardizer() {
+        let stats = OnlineStats::from_slice(&[2, 4, 4, 4, 5, 5, 7, 9]);
+        let standardizer = Standardizer::from(&stats);
+        assert_eq!((standardizer.mean(), standardizer.stddev()), (5.0, 2.0));
+        assert_eq!(standardizer.transform(&11), 3.0);
+        assert_eq!(standardizer.transform(&4.0), -0.5);
+        assert_eq!(standardizer.inverse(-0.5), 4.0);
+    }
+}
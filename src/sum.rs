@@ -0,0 +1,169 @@
+use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+
+use crate::Commute;
+
+/// Online state for the exact sum of integers.
+///
+/// Accumulating in `f64`, as `OnlineStats` does, silently loses precision
+/// once the sum passes 2^53. This keeps the sum in an `i128` instead, which
+/// cannot overflow for fewer than 2^64 `i64` or `u64` values. Overflow is
+/// still detected, in which case the sum is no longer available.
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ExactSum {
+    len: u64,
+    sum: Option<i128>,
+}
+
+impl ExactSum {
+    /// Create initial empty state.
+    #[must_use]
+    pub fn new() -> ExactSum {
+        Default::default()
+    }
+
+    /// Add a new sample.
+    #[inline]
+    pub fn add<T: Into<i128>>(&mut self, sample: T) {
+        self.len += 1;
+        self.sum = self.sum.and_then(|sum| sum.checked_add(sample.into()));
+    }
+
+    /// Add a sample of any numeric type, e.g. from a column that has not
+    /// been checked to hold only integers.
+    ///
+    /// A sample that is not an integer (such as `2.5` or `NaN`) makes the
+    /// sum unavailable, just as an overflow does.
+    #[inline]
+    pub fn add_numeric<T: ToPrimitive>(&mut self, sample: &T) {
+        self.len += 1;
+        let integer = sample
+            .to_i128()
+            .filter(|_| sample.to_f64().is_some_and(|x| x.fract() == 0.0));
+        self.sum = self
+            .sum
+            .zip(integer)
+            .and_then(|(sum, x)| sum.checked_add(x));
+    }
+
+    /// Returns the number of data points.
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns true if there are no data points.
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the exact sum, or `None` if it overflowed an `i128` or a
+    /// non-integer was added with `add_numeric`.
+    #[inline]
+    #[must_use]
+    pub const fn sum(&self) -> Option<i128> {
+        self.sum
+    }
+
+    /// Returns true if the sum overflowed an `i128`.
+    #[inline]
+    #[must_use]
+    pub const fn overflowed(&self) -> bool {
+        self.sum.is_none()
+    }
+
+    /// Returns the mean, computed from the exact sum.
+    ///
+    /// `None` is returned if there is no data or the sum overflowed.
+    #[inline]
+    #[must_use]
+    pub fn mean(&self) -> Option<f64> {
+        if self.len == 0 {
+            return None;
+        }
+        Some(self.sum? as f64 / self.len as f64)
+    }
+}
+
+impl Commute for ExactSum {
+    #[inline]
+    fn merge(&mut self, v: ExactSum) {
+        self.len += v.len;
+        self.sum = match (self.sum, v.sum) {
+            (Some(a), Some(b)) => a.checked_add(b),
+            _ => None,
+        };
+    }
+}
+
+impl Default for ExactSum {
+    #[inline]
+    fn default() -> ExactSum {
+        ExactSum {
+            len: 0,
+            sum: Some(0),
+        }
+    }
+}
+
+impl<T: Into<i128>> FromIterator<T> for ExactSum {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = T>>(it: I) -> ExactSum {
+        let mut v = ExactSum::new();
+        v.extend(it);
+        v
+    }
+}
+
+impl<T: Into<i128>> Extend<T> for ExactSum {
+    #[inline]
+    fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
+        for sample in it {
+            self.add(sample);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ExactSum;
+    use crate::{Commute, OnlineStats};
+
+    #[test]
+    fn exact_past_f64_precision() {
+        let big = (1i64 << 53) + 1;
+        let data = [big, 1, 1];
+        let exact: ExactSum = data.into_iter().collect();
+        assert_eq!(exact.sum(), Some(i128::from(big) + 2));
+
+        // accumulated in `f64`, each `+ 1` is lost to rounding
+        let float = OnlineStats::from_slice(&data);
+        assert_ne!(float.mean() * 3.0, (big + 2) as f64);
+        assert_eq!(exact.mean(), Some((big + 2) as f64 / 3.0));
+    }
+
+    #[test]
+    fn overflow_detected() {
+        let mut sum: ExactSum = [i128::MAX].into_iter().collect();
+        assert!(!sum.overflowed());
+        sum.merge([1u8].into_iter().collect());
+        assert!(sum.overflowed());
+        assert_eq!(sum.len(), 2);
+        assert_eq!(sum.sum(), None);
+        assert_eq!(sum.mean(), None);
+        assert_eq!(ExactSum::new().mean(), None);
+    }
+
+    #[test]
+    fn numeric_samples() {
+        let mut sum = ExactSum::new();
+        sum.add_numeric(&3u64);
+        sum.add_numeric(&-2.0f64);
+        assert_eq!(sum.sum(), Some(1));
+        sum.add_numeric(&0.5f64);
+        assert_eq!((sum.len(), sum.sum()), (3, None));
+    }
+}
@@ -0,0 +1,508 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Commute;
+
+/// How text values are normalized before they are compared.
+///
+/// Raw CSV data commonly has variants such as `"Yes"`, `"yes "` and
+/// `"YES"` that should usually be counted as one value. Normalized values
+/// can be added with `add_normalized` on `Unsorted`, `MinMax` and
+/// `Frequencies`, so cardinality, modes and min/max see them as equal.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextNormalization {
+    /// Strip leading and trailing whitespace.
+    pub trim: bool,
+    /// Convert to lowercase, so comparisons ignore case.
+    pub case_insensitive: bool,
+}
+
+impl TextNormalization {
+    /// Leave values unchanged.
+    pub const NONE: TextNormalization = TextNormalization {
+        trim: false,
+        case_insensitive: false,
+    };
+
+    /// Strip surrounding whitespace and ignore case.
+    pub const ALL: TextNormalization = TextNormalization {
+        trim: true,
+        case_insensitive: true,
+    };
+}
+
+/// Values that can be normalized according to a `TextNormalization`.
+///
+/// Values that are already normalized are returned without reallocating.
+pub trait Normalize: Sized {
+    /// Returns the normalized value.
+    #[must_use]
+    fn normalize(self, how: TextNormalization) -> Self;
+}
+
+impl Normalize for String {
+    fn normalize(mut self, how: TextNormalization) -> String {
+        if how.trim {
+            let end = self.trim_end().len();
+            self.truncate(end);
+            let start = self.len() - self.trim_start().len();
+            self.drain(..start);
+        }
+        if how.case_insensitive {
+            if self.is_ascii() {
+                self.make_ascii_lowercase();
+            } else {
+                self = self.to_lowercase();
+            }
+        }
+        self
+    }
+}
+
+impl Normalize for Vec<u8> {
+    /// Valid UTF-8 is normalized like a `String`. Otherwise, only ASCII
+    /// whitespace and ASCII letters are affected.
+    fn normalize(self, how: TextNormalization) -> Vec<u8> {
+        match String::from_utf8(self) {
+            Ok(s) => s.normalize(how).into_bytes(),
+            Err(e) => {
+                let mut bytes = e.into_bytes();
+                if how.trim {
+                    let end = bytes
+                        .iter()
+                        .rposition(|b| !b.is_ascii_whitespace())
+                        .map_or(0, |i| i + 1);
+                    bytes.truncate(end);
+                    let start = bytes
+                        .iter()
+                        .position(|b| !b.is_ascii_whitespace())
+                        .unwrap_or(bytes.len());
+                    bytes.drain(..start);
+                }
+                if how.case_insensitive {
+                    bytes.make_ascii_lowercase();
+                }
+                bytes
+            }
+        }
+    }
+}
+
+/// A commutative data structure for profiling a column of text in one pass.
+///
+/// Lengths are measured in bytes. Values are compared bytewise, which for
+/// UTF-8 is the same as comparing code points.
+///
+/// Besides lengths and encodings, values with common formatting anomalies
+/// are counted: surrounding whitespace, control characters, invisible
+/// formatting characters and replacement characters left by a lossy
+/// decoding. For values that are not valid UTF-8, only ASCII whitespace
+/// and control bytes are recognized.
+#[derive(Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct StringStats {
+    len: u64,
+    empty: u64,
+    ascii: u64,
+    non_utf8: u64,
+    total_bytes: u64,
+    min_length: u64,
+    max_length: u64,
+    min: Option<Vec<u8>>,
+    max: Option<Vec<u8>>,
+    #[serde(default)]
+    leading_whitespace: u64,
+    #[serde(default)]
+    trailing_whitespace: u64,
+    #[serde(default)]
+    control: u64,
+    #[serde(default)]
+    invisible: u64,
+    #[serde(default)]
+    replacement: u64,
+}
+
+/// Returns true for zero-width and other invisible formatting characters,
+/// such as a byte order mark or a zero-width space.
+#[inline]
+fn is_invisible(c: char) -> bool {
+    matches!(
+        c,
+        '\u{ad}' | '\u{200b}'..='\u{200f}' | '\u{2028}'..='\u{202e}' | '\u{2060}'..='\u{2064}' | '\u{feff}'
+    )
+}
+
+impl StringStats {
+    /// Create initial empty state.
+    #[must_use]
+    pub fn new() -> StringStats {
+        Default::default()
+    }
+
+    /// Add a new value, given as a `&str`, `String`, `&[u8]` or `Vec<u8>`.
+    #[inline]
+    pub fn add<S: AsRef<[u8]>>(&mut self, value: S) {
+        let v = value.as_ref();
+        let n = v.len() as u64;
+        if self.len == 0 {
+            (self.min_length, self.max_length) = (n, n);
+        } else {
+            self.min_length = self.min_length.min(n);
+            self.max_length = self.max_length.max(n);
+        }
+        self.len += 1;
+        self.total_bytes += n;
+        if v.is_empty() {
+            self.empty += 1;
+        }
+        if v.is_ascii() {
+            self.ascii += 1;
+            self.count_anomalies_bytes(v);
+        } else if let Ok(s) = std::str::from_utf8(v) {
+            self.count_anomalies_str(s);
+        } else {
+            self.non_utf8 += 1;
+            self.count_anomalies_bytes(v);
+        }
+        if self.min.as_deref().map_or(true, |m| v < m) {
+            set_bytes(&mut self.min, v);
+        }
+        if self.max.as_deref().map_or(true, |m| v > m) {
+            set_bytes(&mut self.max, v);
+        }
+    }
+
+    #[inline]
+    fn count_anomalies_bytes(&mut self, v: &[u8]) {
+        self.leading_whitespace += u64::from(v.first().is_some_and(u8::is_ascii_whitespace));
+        self.trailing_whitespace += u64::from(v.last().is_some_and(u8::is_ascii_whitespace));
+        self.control += u64::from(v.iter().any(u8::is_ascii_control));
+    }
+
+    #[inline]
+    fn count_anomalies_str(&mut self, s: &str) {
+        let starts = s.chars().next().is_some_and(char::is_whitespace);
+        let ends = s.chars().next_back().is_some_and(char::is_whitespace);
+        self.leading_whitespace += u64::from(starts);
+        self.trailing_whitespace += u64::from(ends);
+        let (mut control, mut invisible, mut replacement) = (false, false, false);
+        for c in s.chars() {
+            control |= c.is_control();
+            invisible |= is_invisible(c);
+            replacement |= c == char::REPLACEMENT_CHARACTER;
+        }
+        self.control += u64::from(control);
+        self.invisible += u64::from(invisible);
+        self.replacement += u64::from(replacement);
+    }
+
+    /// Returns the number of values.
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns the number of values that start with whitespace.
+    #[inline]
+    #[must_use]
+    pub const fn leading_whitespace_count(&self) -> u64 {
+        self.leading_whitespace
+    }
+
+    /// Returns the number of values that end with whitespace.
+    #[inline]
+    #[must_use]
+    pub const fn trailing_whitespace_count(&self) -> u64 {
+        self.trailing_whitespace
+    }
+
+    /// Returns the number of values that contain a control character,
+    /// including tabs and line breaks.
+    #[inline]
+    #[must_use]
+    pub const fn control_count(&self) -> u64 {
+        self.control
+    }
+
+    /// Returns the number of values that contain an invisible formatting
+    /// character, such as a zero-width space or a byte order mark.
+    #[inline]
+    #[must_use]
+    pub const fn invisible_count(&self) -> u64 {
+        self.invisible
+    }
+
+    /// Returns the number of values that contain the Unicode replacement
+    /// character, a sign that text in another encoding was decoded lossily.
+    #[inline]
+    #[must_use]
+    pub const fn replacement_char_count(&self) -> u64 {
+        self.replacement
+    }
+
+    /// Returns true if there are no values.
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of empty values.
+    #[inline]
+    #[must_use]
+    pub const fn empty_count(&self) -> u64 {
+        self.empty
+    }
+
+    /// Returns the total number of bytes over all values.
+    #[inline]
+    #[must_use]
+    pub const fn total_bytes(&self) -> u64 {
+        self.total_bytes
+    }
+
+    /// Returns the length of the shortest value, or `None` if there are
+    /// no values.
+    #[inline]
+    #[must_use]
+    pub const fn min_length(&self) -> Option<u64> {
+        if self.len == 0 {
+            None
+        } else {
+            Some(self.min_length)
+        }
+    }
+
+    /// Returns the length of the longest value, or `None` if there are
+    /// no values.
+    #[inline]
+    #[must_use]
+    pub const fn max_length(&self) -> Option<u64> {
+        if self.len == 0 {
+            None
+        } else {
+            Some(self.max_length)
+        }
+    }
+
+    /// Returns the difference between the longest and shortest lengths, or
+    /// `None` if there are no values.
+    #[inline]
+    #[must_use]
+    pub const fn length_range(&self) -> Option<u64> {
+        if self.len == 0 {
+            None
+        } else {
+            Some(self.max_length - self.min_length)
+        }
+    }
+
+    /// Returns the mean length, or `None` if there are no values.
+    #[inline]
+    #[must_use]
+    pub fn mean_length(&self) -> Option<f64> {
+        if self.len == 0 {
+            None
+        } else {
+            Some(self.total_bytes as f64 / self.len as f64)
+        }
+    }
+
+    /// Returns the number of values that are pure ASCII.
+    #[inline]
+    #[must_use]
+    pub const fn ascii_count(&self) -> u64 {
+        self.ascii
+    }
+
+    /// Returns the number of values that are valid UTF-8 but not ASCII.
+    #[inline]
+    #[must_use]
+    pub const fn utf8_count(&self) -> u64 {
+        self.len - self.ascii - self.non_utf8
+    }
+
+    /// Returns the number of values that are not valid UTF-8.
+    #[inline]
+    #[must_use]
+    pub const fn non_utf8_count(&self) -> u64 {
+        self.non_utf8
+    }
+
+    /// Returns the fraction of values that are pure ASCII, or `None` if
+    /// there are no values.
+    #[inline]
+    #[must_use]
+    pub fn ascii_fraction(&self) -> Option<f64> {
+        if self.len == 0 {
+            None
+        } else {
+            Some(self.ascii as f64 / self.len as f64)
+        }
+    }
+
+    /// Returns the lexically smallest value.
+    #[inline]
+    #[must_use]
+    pub fn min(&self) -> Option<&[u8]> {
+        self.min.as_deref()
+    }
+
+    /// Returns the lexically largest value.
+    #[inline]
+    #[must_use]
+    pub fn max(&self) -> Option<&[u8]> {
+        self.max.as_deref()
+    }
+}
+
+/// Replace the contents of `slot` with `v`, reusing its allocation.
+#[inline]
+fn set_bytes(slot: &mut Option<Vec<u8>>, v: &[u8]) {
+    let buf = slot.get_or_insert_with(Vec::new);
+    buf.clear();
+    buf.extend_from_slice(v);
+}
+
+impl Commute for StringStats {
+    #[inline]
+    fn merge(&mut self, v: StringStats) {
+        if v.len == 0 {
+            return;
+        }
+        if self.len == 0 {
+            *self = v;
+            return;
+        }
+        self.len += v.len;
+        self.empty += v.empty;
+        self.ascii += v.ascii;
+        self.non_utf8 += v.non_utf8;
+        self.total_bytes += v.total_bytes;
+        self.leading_whitespace += v.leading_whitespace;
+        self.trailing_whitespace += v.trailing_whitespace;
+        self.control += v.control;
+        self.invisible += v.invisible;
+        self.replacement += v.replacement;
+        self.min_length = self.min_length.min(v.min_length);
+        self.max_length = self.max_length.max(v.max_length);
+        if v.min < self.min {
+            self.min = v.min;
+        }
+        if v.max > self.max {
+            self.max = v.max;
+        }
+    }
+}
+
+impl<S: AsRef<[u8]>> FromIterator<S> for StringStats {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = S>>(it: I) -> StringStats {
+        let mut v = StringStats::new();
+        v.extend(it);
+        v
+    }
+}
+
+impl<S: AsRef<[u8]>> Extend<S> for StringStats {
+    #[inline]
+    fn extend<I: IntoIterator<Item = S>>(&mut self, it: I) {
+        for value in it {
+            self.add(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Normalize, StringStats, TextNormalization};
+    use crate::{Commute, Frequencies, MinMax, Unsorted};
+
+    #[test]
+    fn normalize_strings() {
+        let trim = TextNormalization {
+            trim: true,
+            case_insensitive: false,
+        };
+        assert_eq!(" Yes \t".to_owned().normalize(trim), "Yes");
+        assert_eq!("ÉTÉ ".to_owned().normalize(TextNormalization::ALL), "été");
+        assert_eq!("YES".to_owned().normalize(TextNormalization::NONE), "YES");
+        assert_eq!(
+            b"\xff NO ".to_vec().normalize(TextNormalization::ALL),
+            b"\xff no"
+        );
+    }
+
+    #[test]
+    fn normalized_stats() {
+        let data = ["Yes", "yes ", "YES", " no", "No"];
+        let how = TextNormalization::ALL;
+
+        let mut unsorted = Unsorted::new();
+        let mut minmax = MinMax::new();
+        let mut freqs = Frequencies::new();
+        for v in data {
+            unsorted.add_normalized(v.to_owned(), how);
+            minmax.add_normalized(v.as_bytes().to_vec(), how);
+            freqs.add_normalized(v.to_owned(), how);
+        }
+        assert_eq!(unsorted.cardinality(), 2);
+        assert_eq!(unsorted.mode(), Some("yes".to_owned()));
+        assert_eq!(minmax.min(), Some(&b"no".to_vec()));
+        assert_eq!(minmax.max(), Some(&b"yes".to_vec()));
+        assert_eq!(freqs.count(&"no".to_owned()), 2);
+    }
+
+    #[test]
+    fn string_stats() {
+        let stats: StringStats = ["pear", "", "éclair", "apple"].into_iter().collect();
+        assert_eq!(stats.len(), 4);
+        assert_eq!(stats.empty_count(), 1);
+        assert_eq!(stats.total_bytes(), 16);
+        assert_eq!(stats.min_length(), Some(0));
+        assert_eq!(stats.max_length(), Some(7));
+        assert_eq!(stats.length_range(), Some(7));
+        assert_eq!(stats.mean_length(), Some(4.0));
+        assert_eq!(stats.ascii_count(), 3);
+        assert_eq!(stats.utf8_count(), 1);
+        assert_eq!(stats.min(), Some(&b""[..]));
+        assert_eq!(stats.max(), Some("éclair".as_bytes()));
+    }
+
+    #[test]
+    fn formatting_anomalies() {
+        let data: [&[u8]; 7] = [
+            b" padded\t",
+            b"line\nbreak",
+            "\u{feff}bom".as_bytes(),
+            "caf\u{fffd}".as_bytes(),
+            "\u{3000}wide".as_bytes(),
+            b"\xff\x01 ",
+            b"clean",
+        ];
+        let stats: StringStats = data.into_iter().collect();
+        assert_eq!(stats.leading_whitespace_count(), 2);
+        assert_eq!(stats.trailing_whitespace_count(), 2);
+        assert_eq!(stats.control_count(), 3);
+        assert_eq!(stats.invisible_count(), 1);
+        assert_eq!(stats.replacement_char_count(), 1);
+
+        let mut merged: StringStats = [" a"].into_iter().collect();
+        merged.merge(stats);
+        assert_eq!(merged.leading_whitespace_count(), 3);
+    }
+
+    #[test]
+    fn string_stats_merge() {
+        let mut s1: StringStats = ["b", "cc"].into_iter().collect();
+        let s2: StringStats = [&b"a\xff"[..], b"zzzz"].into_iter().collect();
+        s1.merge(s2);
+        s1.merge(StringStats::new());
+        assert_eq!(s1.len(), 4);
+        assert_eq!(s1.non_utf8_count(), 1);
+        assert_eq!(s1.min(), Some(&b"a\xff"[..]));
+        assert_eq!(s1.max(), Some(&b"zzzz"[..]));
+        assert_eq!(s1.min_length(), Some(1));
+        assert_eq!(s1.max_length(), Some(4));
+        assert!(StringStats::new().mean_length().is_none());
+    }
+}
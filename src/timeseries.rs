@@ -0,0 +1,253 @@
+use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+
+use crate::Unsorted;
+
+/// The overall order of a sequence, as reported by `Unsorted::sortedness`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Monotonicity {
+    /// Every value is equal to the previous one (or there are fewer than
+    /// two values).
+    Constant,
+    /// Every value is greater than the previous one.
+    StrictlyIncreasing,
+    /// No value is less than the previous one.
+    NonDecreasing,
+    /// Every value is less than the previous one.
+    StrictlyDecreasing,
+    /// No value is greater than the previous one.
+    NonIncreasing,
+    /// The sequence goes both up and down.
+    NonMonotonic,
+}
+
+/// How close a sequence is to being sorted, as reported by
+/// `Unsorted::sortedness`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sortedness {
+    /// The overall order of the sequence.
+    pub monotonicity: Monotonicity,
+    /// The number of pairs of values that are out of ascending order.
+    pub inversions: u64,
+    /// The length of the longest run of consecutive non-decreasing values.
+    pub longest_ascending_run: usize,
+    /// The length of the longest run of consecutive non-increasing values.
+    pub longest_descending_run: usize,
+    /// The number of values.
+    pub len: usize,
+}
+
+impl Sortedness {
+    /// Returns the fraction of pairs of values that are in ascending
+    /// order, from `0` (descending) to `1` (ascending).
+    ///
+    /// Sequences with fewer than two values score `1`.
+    #[must_use]
+    pub fn score(&self) -> f64 {
+        let n = self.len as f64;
+        let pairs = n * (n - 1.0) / 2.0;
+        if pairs == 0.0 {
+            1.0
+        } else {
+            1.0 - self.inversions as f64 / pairs
+        }
+    }
+}
+
+/// Returns the sample autocorrelation of `data` at each of `lags`.
+///
+/// The autocorrelation at lag `k` is the covariance of the series with
+/// itself shifted by `k` positions, divided by its variance, using the
+/// mean of the whole series (the usual ACF estimator). A strong positive
+/// value at a lag reveals periodicity with that period.
+///
+/// The autocorrelation is `NaN` at lags that are not smaller than the
+/// length of the data, and at every lag if the data is constant or empty.
+pub fn autocorrelation<T: ToPrimitive>(data: &[T], lags: &[usize]) -> Vec<f64> {
+    let xs: Vec<f64> = data.iter().map(|x| x.to_f64().unwrap()).collect();
+    let mean = xs.iter().sum::<f64>() / xs.len() as f64;
+    let devs: Vec<f64> = xs.iter().map(|x| x - mean).collect();
+    let denom: f64 = devs.iter().map(|d| d * d).sum();
+    lags.iter()
+        .map(|&lag| {
+            if lag >= devs.len() || denom == 0.0 {
+                return f64::NAN;
+            }
+            let num: f64 = devs.iter().zip(&devs[lag..]).map(|(a, b)| a * b).sum();
+            num / denom
+        })
+        .collect()
+}
+
+impl<T: PartialOrd + ToPrimitive> Unsorted<T> {
+    /// Returns the sample autocorrelation of the data at each of `lags`.
+    ///
+    /// See `autocorrelation`. The series is the data in the order in which
+    /// it was added.
+    ///
+    /// `None` is returned if that order has been lost, i.e. order
+    /// statistics sorted the data while
+    /// `Unsorted::set_track_insertion_order` was off.
+    #[must_use]
+    pub fn autocorrelation(&self, lags: &[usize]) -> Option<Vec<f64>> {
+        let series: Vec<f64> = self
+            .insertion_ordered()?
+            .into_iter()
+            .map(|x| x.to_f64().unwrap())
+            .collect();
+        Some(autocorrelation(&series, lags))
+    }
+}
+
+impl<T: PartialOrd> Unsorted<T> {
+    /// Reports how close the data is to being sorted: whether it is
+    /// monotonic, how many pairs are out of order and its longest runs.
+    ///
+    /// Index and timestamp columns are typically (nearly) monotonic. The
+    /// inversions are counted exactly, with a merge sort over positions, in
+    /// `O(n log n)` time. Values that cannot be compared (e.g. `NaN`) are
+    /// never counted as out of order and do not break runs.
+    ///
+    /// The data is taken in the order in which it was added. `None` is
+    /// returned if that order has been lost, i.e. order statistics sorted
+    /// the data while `Unsorted::set_track_insertion_order` was off.
+    #[must_use]
+    pub fn sortedness(&self) -> Option<Sortedness> {
+        let data = self.insertion_ordered()?;
+        let (mut up, mut down) = (false, false);
+        let (mut strict_up, mut strict_down) = (true, true);
+        let (mut asc, mut desc) = (1, 1);
+        let (mut longest_asc, mut longest_desc) = (data.len().min(1), data.len().min(1));
+        for w in data.windows(2) {
+            let (a, b) = (&w[0], &w[1]);
+            up |= b > a;
+            down |= b < a;
+            strict_up &= b > a;
+            strict_down &= b < a;
+            asc = if b < a { 1 } else { asc + 1 };
+            desc = if b > a { 1 } else { desc + 1 };
+            longest_asc = longest_asc.max(asc);
+            longest_desc = longest_desc.max(desc);
+        }
+        let monotonicity = match (up, down) {
+            (false, false) => Monotonicity::Constant,
+            (true, false) if strict_up => Monotonicity::StrictlyIncreasing,
+            (true, false) => Monotonicity::NonDecreasing,
+            (false, true) if strict_down => Monotonicity::StrictlyDecreasing,
+            (false, true) => Monotonicity::NonIncreasing,
+            (true, true) => Monotonicity::NonMonotonic,
+        };
+        let mut refs = data.clone();
+        let mut buf = Vec::with_capacity(refs.len());
+        Some(Sortedness {
+            monotonicity,
+            inversions: count_inversions(&mut refs, &mut buf),
+            longest_ascending_run: longest_asc,
+            longest_descending_run: longest_desc,
+            len: data.len(),
+        })
+    }
+}
+
+/// Sorts `v` with a merge sort, returning the number of pairs that were
+/// out of order. `buf` is scratch space.
+fn count_inversions<'a, T: PartialOrd>(v: &mut [&'a T], buf: &mut Vec<&'a T>) -> u64 {
+    if v.len() < 2 {
+        return 0;
+    }
+    let mid = v.len() / 2;
+    let mut count = count_inversions(&mut v[..mid], buf) + count_inversions(&mut v[mid..], buf);
+    buf.clear();
+    let (mut i, mut j) = (0, mid);
+    while i < mid && j < v.len() {
+        if v[j] < v[i] {
+            // every remaining left value is greater than v[j]
+            count += (mid - i) as u64;
+            buf.push(v[j]);
+            j += 1;
+        } else {
+            buf.push(v[i]);
+            i += 1;
+        }
+    }
+    buf.extend_from_slice(&v[i..mid]);
+    buf.extend_from_slice(&v[j..]);
+    v.copy_from_slice(buf);
+    count
+}
+
+#[cfg(test)]
+mod test {
+    use super::{autocorrelation, Monotonicity};
+    use crate::Unsorted;
+
+    #[test]
+    fn periodic() {
+        let data: Vec<i32> = (0..120).map(|i| [1, 5, 2, -3][i % 4]).collect();
+        let acf = autocorrelation(&data, &[0, 2, 4, 8, 120]);
+        assert!((acf[0] - 1.0).abs() < 1e-12);
+        assert!(acf[1] < 0.0);
+        assert!(acf[2] > 0.9 && acf[3] > 0.9);
+        assert!(acf[4].is_nan());
+        assert!(autocorrelation(&[3, 3, 3], &[1])[0].is_nan());
+
+        let mut unsorted: Unsorted<i32> = data.iter().copied().collect();
+        assert_eq!(unsorted.autocorrelation(&[4]), Some(vec![acf[2]]));
+        let mut untracked = unsorted.clone();
+        unsorted.set_track_insertion_order(true);
+        unsorted.median();
+        assert_eq!(unsorted.autocorrelation(&[4]), Some(vec![acf[2]]));
+        untracked.median();
+        assert_eq!(untracked.autocorrelation(&[4]), None);
+    }
+
+    #[test]
+    fn known_values() {
+        // 1..=5: the lag-1 cross products of deviations sum to 4 and the
+        // squared deviations to 10
+        let acf = autocorrelation(&[1, 2, 3, 4, 5], &[1, 2]);
+        assert!((acf[0] - 0.4).abs() < 1e-12);
+        assert!((acf[1] + 0.1).abs() < 1e-12);
+    }
+
+    #[test]
+    fn sortedness() {
+        let data: Unsorted<u32> = [1, 2, 2, 5, 3, 4, 9, 8].into_iter().collect();
+        let s = data.sortedness().unwrap();
+        assert_eq!(s.monotonicity, Monotonicity::NonMonotonic);
+        // (5, 3), (5, 4) and (9, 8)
+        assert_eq!(s.inversions, 3);
+        assert_eq!((s.longest_ascending_run, s.longest_descending_run), (4, 2));
+        assert!((s.score() - (1.0 - 3.0 / 28.0)).abs() < 1e-12);
+        let mut sorted = data.clone();
+        sorted.set_track_insertion_order(true);
+        sorted.median();
+        assert_eq!(sorted.sortedness(), Some(s));
+        let mut untracked = data.clone();
+        untracked.median();
+        assert_eq!(untracked.sortedness(), None);
+
+        let order = |v: &[i32]| {
+            v.iter()
+                .copied()
+                .collect::<Unsorted<_>>()
+                .sortedness()
+                .unwrap()
+        };
+        assert_eq!(
+            order(&[1, 2, 3]).monotonicity,
+            Monotonicity::StrictlyIncreasing
+        );
+        assert_eq!(order(&[1, 1, 3]).monotonicity, Monotonicity::NonDecreasing);
+        assert_eq!(
+            order(&[3, 2, 1]).monotonicity,
+            Monotonicity::StrictlyDecreasing
+        );
+        assert_eq!(order(&[3, 3, 1]).monotonicity, Monotonicity::NonIncreasing);
+        assert_eq!(order(&[4, 4]).monotonicity, Monotonicity::Constant);
+        assert_eq!(order(&[3, 2, 1]).inversions, 3);
+        assert_eq!(order(&[3, 2, 1]).score(), 0.0);
+        let empty = order(&[]);
+        assert_eq!((empty.longest_ascending_run, empty.score()), (0, 1.0));
+    }
+}
@@ -0,0 +1,164 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Commute, Partial};
+
+/// A commutative data structure for tracking the `k` largest and `k`
+/// smallest values.
+///
+/// Each side is a bounded heap, so adding a sample takes `O(log k)` time
+/// and the whole state takes `O(k)` space, regardless of the number of
+/// samples.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "T: Serialize + PartialOrd",
+    deserialize = "T: Deserialize<'de> + PartialOrd"
+))]
+pub struct TopN<T> {
+    k: usize,
+    len: u64,
+    // a max-heap, so the largest of the smallest values is evicted first
+    smallest: BinaryHeap<Partial<T>>,
+    // a min-heap, so the smallest of the largest values is evicted first
+    largest: BinaryHeap<Reverse<Partial<T>>>,
+}
+
+impl<T: PartialOrd + Clone> TopN<T> {
+    /// Create initial empty state, keeping the `k` largest and `k`
+    /// smallest values.
+    #[must_use]
+    pub fn new(k: usize) -> TopN<T> {
+        TopN {
+            k,
+            len: 0,
+            smallest: BinaryHeap::with_capacity(k),
+            largest: BinaryHeap::with_capacity(k),
+        }
+    }
+
+    /// Add a new sample.
+    #[inline]
+    pub fn add(&mut self, sample: T) {
+        self.len += 1;
+        if self.k == 0 {
+            return;
+        }
+        self.add_smallest(sample.clone());
+        self.add_largest(sample);
+    }
+
+    /// Returns the number of values kept on each side.
+    #[inline]
+    #[must_use]
+    pub const fn k(&self) -> usize {
+        self.k
+    }
+
+    /// Returns the number of samples added.
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns true if no samples were added.
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns up to `k` of the smallest values, in ascending order.
+    #[must_use]
+    pub fn smallest(&self) -> Vec<&T> {
+        let mut values: Vec<&Partial<T>> = self.smallest.iter().collect();
+        values.sort_unstable();
+        values.into_iter().map(|p| &p.0).collect()
+    }
+
+    /// Returns up to `k` of the largest values, in descending order.
+    #[must_use]
+    pub fn largest(&self) -> Vec<&T> {
+        let mut values: Vec<&Partial<T>> = self.largest.iter().map(|r| &r.0).collect();
+        values.sort_unstable_by(|a, b| b.cmp(a));
+        values.into_iter().map(|p| &p.0).collect()
+    }
+}
+
+impl<T: PartialOrd> TopN<T> {
+    fn add_smallest(&mut self, sample: T) {
+        if self.smallest.len() < self.k {
+            self.smallest.push(Partial(sample));
+        } else if self.smallest.peek().is_some_and(|top| sample < top.0) {
+            self.smallest.pop();
+            self.smallest.push(Partial(sample));
+        }
+    }
+
+    fn add_largest(&mut self, sample: T) {
+        if self.largest.len() < self.k {
+            self.largest.push(Reverse(Partial(sample)));
+        } else if self.largest.peek().is_some_and(|top| sample > top.0 .0) {
+            self.largest.pop();
+            self.largest.push(Reverse(Partial(sample)));
+        }
+    }
+}
+
+impl<T: PartialOrd + Clone> Commute for TopN<T> {
+    #[inline]
+    fn merge(&mut self, v: TopN<T>) {
+        let len = self.len + v.len;
+        for Partial(sample) in v.smallest {
+            self.add_smallest(sample);
+        }
+        for Reverse(Partial(sample)) in v.largest {
+            self.add_largest(sample);
+        }
+        self.len = len;
+    }
+}
+
+impl<T: PartialOrd + Clone> Extend<T> for TopN<T> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
+        for sample in it {
+            self.add(sample);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TopN;
+    use crate::Commute;
+
+    #[test]
+    fn top_and_bottom() {
+        let mut top = TopN::new(3);
+        top.extend([5, 1, 9, 7, 3, 9, 2, 8]);
+        assert_eq!(top.len(), 8);
+        assert_eq!(top.smallest(), vec![&1, &2, &3]);
+        assert_eq!(top.largest(), vec![&9, &9, &8]);
+
+        let mut few = TopN::new(5);
+        few.extend([2.5, 1.5]);
+        assert_eq!(few.smallest(), vec![&1.5, &2.5]);
+        assert_eq!(few.largest(), vec![&2.5, &1.5]);
+        assert!(TopN::<u8>::new(0).largest().is_empty());
+    }
+
+    #[test]
+    fn merge() {
+        let mut t1 = TopN::new(2);
+        t1.extend([10, 4, 6]);
+        let mut t2 = TopN::new(2);
+        t2.extend([1, 12, 5]);
+        t1.merge(t2);
+        assert_eq!(t1.len(), 6);
+        assert_eq!(t1.smallest(), vec![&1, &4]);
+        assert_eq!(t1.largest(), vec![&12, &10]);
+    }
+}
@@ -1,10 +1,237 @@
-use num_traits::ToPrimitive;
+use std::fmt;
+use std::hash::Hash;
+
+use ahash::AHashSet;
+use num_traits::{Float, PrimInt, ToPrimitive};
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-use rayon::slice::ParallelSliceMut;
+use rayon::slice::{ParallelSlice, ParallelSliceMut};
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::parallel::{in_pool, sort_f64, sort_stable_by, sort_unstable_by};
+use crate::round::{RoundPolicy, RoundedDisplay};
+use crate::sql::percentile_cont_on_sorted;
+use {
+    crate::Commute, crate::Frequencies, crate::MinMax, crate::Normalize, crate::OnlineStats,
+    crate::Parallelism, crate::Partial, crate::TextNormalization, crate::UnsortedSnapshotV1,
+};
+
+/// How close two floating-point values must be to be counted as equal by
+/// the approximate mode and cardinality methods of `Unsorted`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Tolerance {
+    /// Values at most this far apart are equal.
+    Absolute(f64),
+    /// Values at most this fraction of the smaller magnitude apart are equal.
+    Relative(f64),
+    /// Values at most this many representable `f64`s apart are equal.
+    Ulps(u64),
+}
+
+impl Tolerance {
+    /// Returns true if `b` is close enough to `a` to be grouped with it,
+    /// assuming `a <= b`.
+    #[inline]
+    fn contains(self, a: f64, b: f64) -> bool {
+        match self {
+            Tolerance::Absolute(eps) => b - a <= eps,
+            Tolerance::Relative(rel) => b - a <= rel * a.abs().min(b.abs()),
+            Tolerance::Ulps(ulps) => {
+                if a.is_nan() || b.is_nan() {
+                    return false;
+                }
+                // map the bits to integers ordered like the floats, with
+                // both zeros at 0, so the distance is the number of steps
+                let ordered = |x: f64| {
+                    let bits = x.to_bits() as i64;
+                    i128::from(if bits < 0 { i64::MIN - bits } else { bits })
+                };
+                (ordered(b) - ordered(a)).unsigned_abs() <= u128::from(ulps)
+            }
+        }
+    }
+}
+
+/// How tied values are ranked by `Unsorted::ranks`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RankMethod {
+    /// Tied values get the mean of the ranks they span, e.g. `2.5`.
+    #[default]
+    Average,
+    /// Tied values get the lowest rank they span.
+    Min,
+    /// Tied values get the highest rank they span.
+    Max,
+    /// Like `Min`, but the next distinct value gets the next rank, so the
+    /// ranks have no gaps.
+    Dense,
+    /// Every value gets a distinct rank, with ties ranked in the order in
+    /// which they are stored.
+    Ordinal,
+}
+
+/// How `Unsorted::quantile_with` picks a value for a fraction `q` whose
+/// rank `q * (len - 1)` falls between two data points.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QuantileMethod {
+    /// Interpolate linearly between the two points, as SQL's
+    /// `PERCENTILE_CONT`.
+    #[default]
+    Linear,
+    /// Take the lower point.
+    Lower,
+    /// Take the higher point.
+    Higher,
+    /// Take the nearer point, and the even-ranked one on a tie.
+    Nearest,
+    /// Take the mean of the two points.
+    Midpoint,
+}
+
+/// Returns the `q`th quantile of `len` sorted values, for `q` in `[0, 1]`,
+/// where `value(i)` is the `i`th smallest value.
+///
+/// `None` is returned if `len` is zero or `q` is not in `[0, 1]`.
+pub(crate) fn quantile_at<F: Fn(usize) -> f64>(
+    len: usize,
+    q: f64,
+    method: QuantileMethod,
+    value: F,
+) -> Option<f64> {
+    if !(0.0..=1.0).contains(&q) {
+        return None;
+    }
+    let rank = q * len.checked_sub(1)? as f64;
+    let (lo, hi) = (rank.floor() as usize, rank.ceil() as usize);
+    Some(match method {
+        // as `percentile_cont_on_sorted`
+        QuantileMethod::Linear if lo == hi => value(lo),
+        QuantileMethod::Linear => {
+            let lo_val = value(lo);
+            (rank - lo as f64).mul_add(value(hi) - lo_val, lo_val)
+        }
+        QuantileMethod::Lower => value(lo),
+        QuantileMethod::Higher => value(hi),
+        QuantileMethod::Nearest => value(rank.round_ties_even() as usize),
+        QuantileMethod::Midpoint => (value(lo) + value(hi)) / 2.0,
+    })
+}
+
+/// The number of antimodes that `Unsorted::antimodes` returns.
+pub(crate) const ANTIMODES_LIMIT: usize = 10;
+
+/// A compact uniqueness profile of the data, as reported by
+/// `Unsorted::duplicate_stats`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DuplicateStats {
+    /// The number of distinct values.
+    pub distinct: u64,
+    /// The number of distinct values that occur more than once.
+    pub duplicated: u64,
+    /// The number of occurrences of the most frequent value.
+    pub max_count: u64,
+    /// The fraction of values that repeat an earlier value, i.e.
+    /// `1 - distinct / len`, from `0` (all unique) towards `1`.
+    pub duplicate_ratio: f64,
+}
+
+/// An outlier-resistant profile of the data, as reported by
+/// `Unsorted::robust_describe`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RobustSummary {
+    /// The number of values.
+    pub len: usize,
+    /// The fraction of values trimmed (or winsorized) from each end.
+    pub trim_fraction: f64,
+    /// The mean of the values left after trimming.
+    pub trimmed_mean: f64,
+    /// The standard deviation after replacing the trimmed values with the
+    /// nearest value that was kept.
+    pub winsorized_stddev: f64,
+    /// The median.
+    pub median: f64,
+    /// The median absolute deviation around the median.
+    pub mad: f64,
+    /// The first quartile.
+    pub q1: f64,
+    /// The third quartile.
+    pub q3: f64,
+    /// The interquartile range, `q3 - q1`.
+    pub iqr: f64,
+    /// Tukey's lower fence, `q1 - 1.5 * iqr`, below which values are
+    /// outliers.
+    pub lower_fence: f64,
+    /// Tukey's upper fence, `q3 + 1.5 * iqr`, above which values are
+    /// outliers.
+    pub upper_fence: f64,
+}
+
+impl fmt::Display for RobustSummary {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_rounded(&RoundPolicy::new(), f)
+    }
+}
 
-use serde::{Deserialize, Serialize};
+impl RoundedDisplay for RobustSummary {
+    fn fmt_rounded(&self, policy: &RoundPolicy, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "n {}, median {}, MAD {}, IQR {} in [{}, {}], trimmed mean {}, winsorized stddev {}",
+            self.len,
+            policy.format(self.median),
+            policy.format(self.mad),
+            policy.format(self.iqr),
+            policy.format(self.q1),
+            policy.format(self.q3),
+            policy.format(self.trimmed_mean),
+            policy.format(self.winsorized_stddev)
+        )
+    }
+}
+
+/// How integer data covers the range between its minimum and maximum, as
+/// reported by `Unsorted::gaps`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Gaps<T> {
+    /// The smallest value.
+    pub min: T,
+    /// The largest value.
+    pub max: T,
+    /// The number of integers in `[min, max]` that do not occur.
+    pub missing: u128,
+    /// The total number of gaps, i.e. maximal runs of missing integers.
+    pub gap_count: u64,
+    /// The first gaps in ascending order, as inclusive `(start, end)`
+    /// ranges of missing integers, up to the requested limit.
+    pub ranges: Vec<(T, T)>,
+}
+
+impl<T> Gaps<T> {
+    /// Returns true if every integer in `[min, max]` occurs.
+    #[inline]
+    #[must_use]
+    pub const fn is_contiguous(&self) -> bool {
+        self.missing == 0
+    }
+}
 
-use {crate::Commute, crate::Partial};
+/// How occurrences are counted when computing modes and cardinality.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FrequencyStrategy {
+    /// Count on the sorted data if it is already sorted. Otherwise,
+    /// estimate the cardinality from an evenly spaced sample of the data,
+    /// and count with a hash map if it is low, or sort if it is high.
+    #[default]
+    Auto,
+    /// Sort the data and count runs of equal values. This is fastest when
+    /// the cardinality is close to the number of samples, and the sort is
+    /// reused by later order statistics.
+    Sort,
+    /// Count with a `Frequencies` hash map, without sorting. This is
+    /// fastest for low-cardinality data.
+    Hash,
+}
 
 /// Compute the exact median on a stream of data.
 ///
@@ -54,7 +281,7 @@ where
 ///
 /// If there is a single mode, then only that value is returned in the `Vec`
 /// however, if there are multiple values tied for occurring the most amount of times
-/// those values are returned.
+/// those values are returned, in ascending order.
 ///
 /// ## Example
 /// ```
@@ -67,7 +294,7 @@ where
 /// This has time complexity `O(n)`
 ///
 /// If the data does not have a mode, then an empty `Vec` is returned.
-pub fn modes<T, I>(it: I) -> (Vec<T>, usize, u32)
+pub fn modes<T, I>(it: I) -> (Vec<T>, usize, u64)
 where
     T: PartialOrd + Clone,
     I: Iterator<Item = T>,
@@ -81,9 +308,9 @@ where
 ///
 /// If there is a single antimode, then only that value is returned in the `Vec`
 /// however, if there are multiple values tied for occurring the least amount of times
-/// those values are returned.
+/// those values are returned, in ascending order.
 ///
-/// Only the first 10 antimodes are returned to prevent returning the whole set
+/// Only the first (smallest) 10 antimodes are returned to prevent returning the whole set
 /// when cardinality = number of records (i.e. all unique values).
 ///
 /// ## Example
@@ -97,7 +324,7 @@ where
 /// This has time complexity `O(n)`
 ///
 /// If the data does not have an antimode, then an empty `Vec` is returned.
-pub fn antimodes<T, I>(it: I) -> (Vec<T>, usize, u32)
+pub fn antimodes<T, I>(it: I) -> (Vec<T>, usize, u64)
 where
     T: PartialOrd + Clone,
     I: Iterator<Item = T>,
@@ -111,38 +338,49 @@ fn median_on_sorted<T>(data: &[T]) -> Option<f64>
 where
     T: PartialOrd + ToPrimitive,
 {
-    Some(match data.len() {
+    median_with(data.len(), |i| data.get(i).map(|v| v.to_f64().unwrap()))
+}
+
+/// Computes the median of `len` sorted values, where `get(i)` returns the
+/// `i`th smallest value.
+pub(crate) fn median_with<F>(len: usize, mut get: F) -> Option<f64>
+where
+    F: FnMut(usize) -> Option<f64>,
+{
+    Some(match len {
         0 => return None,
-        1 => data.first()?.to_f64().unwrap(),
+        1 => get(0)?,
         len if len % 2 == 0 => {
             let idx = len / 2;
-            let v1 = data.get(idx - 1)?.to_f64().unwrap();
-            let v2 = data.get(idx)?.to_f64().unwrap();
+            let v1 = get(idx - 1)?;
+            let v2 = get(idx)?;
             (v1 + v2) / 2.0
         }
-        len => data.get(len / 2)?.to_f64().unwrap(),
+        len => get(len / 2)?,
     })
 }
 
-fn mad_on_sorted<T>(data: &[T], precalc_median: Option<f64>) -> Option<f64>
+fn mad_on_sorted<T>(
+    data: &[T],
+    precalc_median: Option<f64>,
+    parallelism: Parallelism,
+) -> Option<f64>
 where
     T: Sync + PartialOrd + ToPrimitive,
 {
     if data.is_empty() {
         return None;
     }
-    let median_obs =
-        precalc_median.map_or_else(|| median_on_sorted(data).unwrap(), |precalc| precalc);
+    let median_obs = precalc_median.unwrap_or_else(|| median_on_sorted(data).unwrap());
 
-    let mut abs_diff_vec: Vec<f64> = data
-        .par_iter()
-        .map(|x| {
-            let val: f64 = x.to_f64().unwrap();
-            (median_obs - val).abs()
-        })
-        .collect();
+    let mut abs_diff_vec: Vec<f64> = if parallelism.is_parallel(data.len()) {
+        in_pool(|| data.par_iter().map(|x| x.to_f64().unwrap()).collect())
+    } else {
+        data.iter().map(|x| x.to_f64().unwrap()).collect()
+    };
+    crate::simd::abs_dev_in_place(&mut abs_diff_vec, median_obs);
 
-    abs_diff_vec.par_sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
+    sort_f64(&mut abs_diff_vec, parallelism);
     median_on_sorted(&abs_diff_vec)
 }
 
@@ -150,13 +388,18 @@ fn quartiles_on_sorted<T>(data: &[T]) -> Option<(f64, f64, f64)>
 where
     T: PartialOrd + ToPrimitive,
 {
-    Some(match data.len() {
+    quartiles_with(data.len(), |i| data.get(i).map(|v| v.to_f64().unwrap()))
+}
+
+/// Computes the quartiles of `len` sorted values, where `get(i)` returns the
+/// `i`th smallest value.
+pub(crate) fn quartiles_with<F>(len: usize, mut get: F) -> Option<(f64, f64, f64)>
+where
+    F: FnMut(usize) -> Option<f64>,
+{
+    Some(match len {
         0..=2 => return None,
-        3 => (
-            data.first()?.to_f64().unwrap(),
-            data.get(1)?.to_f64().unwrap(),
-            data.last()?.to_f64().unwrap(),
-        ),
+        3 => (get(0)?, get(1)?, get(2)?),
         len => {
             let r = len % 4;
             let k = (len - r) / 4;
@@ -169,12 +412,12 @@ where
                 // q1 = (x_{k-1} + x_{k}) / 2 and q3 = (x_{3k-1} + x_{3k}) / 2.
                 0 => {
                     let (q1_l, q1_r, q2_l, q2_r, q3_l, q3_r) = (
-                        data.get(k - 1)?.to_f64().unwrap(),
-                        data.get(k)?.to_f64().unwrap(),
-                        data.get(2 * k - 1)?.to_f64().unwrap(),
-                        data.get(2 * k)?.to_f64().unwrap(),
-                        data.get(3 * k - 1)?.to_f64().unwrap(),
-                        data.get(3 * k)?.to_f64().unwrap(),
+                        get(k - 1)?,
+                        get(k)?,
+                        get(2 * k - 1)?,
+                        get(2 * k)?,
+                        get(3 * k - 1)?,
+                        get(3 * k)?,
                     );
 
                     ((q1_l + q1_r) / 2., (q2_l + q2_r) / 2., (q3_l + q3_r) / 2.)
@@ -186,11 +429,11 @@ where
                 // q1 = (x_{k-1} + x_{k}) / 2 and q3 = (x_{3k} + x_{3k+1}) / 2.
                 1 => {
                     let (q1_l, q1_r, q2, q3_l, q3_r) = (
-                        data.get(k - 1)?.to_f64().unwrap(),
-                        data.get(k)?.to_f64().unwrap(),
-                        data.get(2 * k)?.to_f64().unwrap(),
-                        data.get(3 * k)?.to_f64().unwrap(),
-                        data.get(3 * k + 1)?.to_f64().unwrap(),
+                        get(k - 1)?,
+                        get(k)?,
+                        get(2 * k)?,
+                        get(3 * k)?,
+                        get(3 * k + 1)?,
                     );
                     ((q1_l + q1_r) / 2., q2, (q3_l + q3_r) / 2.)
                 }
@@ -200,12 +443,8 @@ where
                 // {x_i > q2} as R, it's true that #L == #R == 2k+1.
                 // Thus, q1 = x_{k} and q3 = x_{3k+1}.
                 2 => {
-                    let (q1, q2_l, q2_r, q3) = (
-                        data.get(k)?.to_f64().unwrap(),
-                        data.get(2 * k)?.to_f64().unwrap(),
-                        data.get(2 * k + 1)?.to_f64().unwrap(),
-                        data.get(3 * k + 1)?.to_f64().unwrap(),
-                    );
+                    let (q1, q2_l, q2_r, q3) =
+                        (get(k)?, get(2 * k)?, get(2 * k + 1)?, get(3 * k + 1)?);
                     (q1, (q2_l + q2_r) / 2., q3)
                 }
                 // Let data = {x_i}_{i=0..4k+3} where k is positive integer.
@@ -214,11 +453,7 @@ where
                 // as L and {x_i > q2} as R, #L == #R == 2k+1 holds true.
                 // Thus, q1 = x_{k} and q3 = x_{3k+2}.
                 _ => {
-                    let (q1, q2, q3) = (
-                        data.get(k)?.to_f64().unwrap(),
-                        data.get(2 * k + 1)?.to_f64().unwrap(),
-                        data.get(3 * k + 2)?.to_f64().unwrap(),
-                    );
+                    let (q1, q2, q3) = (get(k)?, get(2 * k + 1)?, get(3 * k + 2)?);
                     (q1, q2, q3)
                 }
             }
@@ -226,50 +461,49 @@ where
     })
 }
 
-fn mode_on_sorted<T, I>(it: I) -> Option<T>
+pub(crate) fn mode_on_sorted<T, I>(it: I) -> Option<T>
 where
     T: PartialOrd,
     I: Iterator<Item = T>,
 {
     // This approach to computing the mode works very nicely when the
     // number of samples is large and is close to its cardinality.
-    // In other cases, a hashmap would be much better.
-    // But really, how can we know this when given an arbitrary stream?
-    // Might just switch to a hashmap to track frequencies. That would also
-    // be generally useful for discovering the cardinality of a sample.
-    let (mut mode, mut next) = (None, None);
-    let (mut mode_count, mut next_count) = (0usize, 0usize);
+    // For low-cardinality data, `FrequencyStrategy::Hash` avoids the sort.
+    //
+    // Each run of equal values is compared with the longest run so far
+    // once it ends. Values that occur only once are never a mode.
+    let (mut mode, mut mode_count, mut tied) = (None, 1usize, false);
+    let (mut run, mut run_count): (Option<T>, usize) = (None, 0);
+    let mut close_run = |run: Option<T>, run_count: usize| {
+        #[allow(clippy::comparison_chain)]
+        if run_count > mode_count {
+            (mode, mode_count, tied) = (run, run_count, false);
+        } else if run_count == mode_count {
+            tied = true;
+        }
+    };
     for x in it {
-        if mode.as_ref().map_or(false, |y| y == &x) {
-            mode_count += 1;
-        } else if next.as_ref().map_or(false, |y| y == &x) {
-            next_count += 1;
+        if run.as_ref().is_some_and(|y| y == &x) {
+            run_count += 1;
         } else {
-            next = Some(x);
-            next_count = 0;
-        }
-
-        #[allow(clippy::comparison_chain)]
-        if next_count > mode_count {
-            mode = next;
-            mode_count = next_count;
-            next = None;
-            next_count = 0;
-        } else if next_count == mode_count {
-            mode = None;
-            mode_count = 0;
+            close_run(run.replace(x), run_count);
+            run_count = 1;
         }
     }
+    close_run(run, run_count);
+    if tied {
+        return None;
+    }
     mode
 }
 
-fn modes_on_sorted<T, I>(mut it: I, size: usize) -> (Vec<T>, usize, u32)
+pub(crate) fn modes_on_sorted<T, I>(mut it: I, size: usize) -> (Vec<T>, usize, u64)
 where
     T: PartialOrd,
     I: Iterator<Item = T>,
 {
-    let mut highest_mode = 0_u32;
-    let mut modes: Vec<(T, u32)> = Vec::with_capacity(usize::min(size / 3, 10_000));
+    let mut highest_mode = 0_u64;
+    let mut modes: Vec<(T, u64)> = Vec::with_capacity(usize::min(size / 3, 10_000));
     let mut mode;
     let mut count = 0;
 
@@ -304,15 +538,19 @@ where
     (modes_result, modes_count, highest_mode)
 }
 
-fn antimodes_on_sorted<T, I>(mut it: I, size: usize) -> (Vec<T>, usize, u32)
+pub(crate) fn antimodes_on_sorted<T, I>(
+    mut it: I,
+    size: usize,
+    limit: usize,
+) -> (Vec<T>, usize, u64)
 where
     T: PartialOrd,
     I: Iterator<Item = T>,
 {
-    let mut lowest_mode = u32::MAX;
+    let mut lowest_mode = u64::MAX;
     // to do some prealloc, without taking up too much memory
     let capacity = usize::min(size / 3, 10_000);
-    let mut antimodes: Vec<u32> = Vec::with_capacity(capacity);
+    let mut antimodes: Vec<u64> = Vec::with_capacity(capacity);
     let mut values = Vec::with_capacity(capacity);
     let mut count = 0;
     let mut curr_antimode;
@@ -343,30 +581,26 @@ where
         lowest_mode = unsafe { *antimodes.get_unchecked(count) };
     }
 
-    let mut antimodes_result: Vec<T> = Vec::with_capacity(10);
-    let mut antimodes_result_ctr: u8 = 0;
-    let mut keep_count = true;
+    let mut antimodes_result: Vec<T> = Vec::with_capacity(limit.min(capacity));
+    let mut keep_count = limit > 0;
 
     let antimodes_count = antimodes
         .into_iter()
         .zip(values)
-        .filter(|(cnt, _val)| *cnt == lowest_mode && lowest_mode < u32::MAX)
+        .filter(|(cnt, _val)| *cnt == lowest_mode && lowest_mode < u64::MAX)
         .map(|(_, val)| {
-            // we only keep the first 10 antimodes and we do this as we do not want to store
-            // antimode values more than 10 we'll throw away immediately anyway,
+            // we only keep the first `limit` antimodes and we do this as we do not want to store
+            // antimode values we'll throw away immediately anyway,
             // especially if the cardinality of a column is high,
             // where there will be a lot of antimodes
             if keep_count {
                 antimodes_result.push(val);
-                antimodes_result_ctr += 1;
-                if antimodes_result_ctr == 10 {
-                    keep_count = false;
-                }
+                keep_count = antimodes_result.len() < limit;
             }
         })
         .count();
 
-    if lowest_mode == u32::MAX {
+    if lowest_mode == u64::MAX {
         lowest_mode = 0;
     }
 
@@ -375,227 +609,1923 @@ where
 
 /// A commutative data structure for lazily sorted sequences of data.
 ///
-/// The sort does not occur until statistics need to be computed.
+/// The sort does not occur until statistics need to be computed. The order
+/// of the data is tracked as it is added, so input that is already in
+/// ascending order is never re-sorted, and input in descending order is
+/// simply reversed.
 ///
 /// Note that this works on types that do not define a total ordering like
 /// `f32` and `f64`. When an ordering is not defined, an arbitrary order
 /// is returned.
+///
+/// Tied modes and antimodes are always returned in ascending order. Values
+/// that compare equal but are distinguishable (e.g. `0.0` and `-0.0`) are
+/// represented by whichever one the sort puts first; see `set_stable` to
+/// make that choice reproducible.
+///
+/// Methods that depend on the order of the values (e.g. `ranks` or
+/// `autocorrelation`) see them in the order in which they were added. That
+/// order is only remembered when a statistic sorts the data if tracking is
+/// turned on with `set_track_insertion_order`. Otherwise those methods
+/// return `None` once the data has been reordered.
 #[derive(Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(try_from = "UnsortedParts<T>")]
 pub struct Unsorted<T> {
     data: Vec<Partial<T>>,
     sorted: bool,
+    #[serde(default)]
+    reverse_sorted: bool,
+    #[serde(default)]
+    nulls: u64,
+    #[serde(default)]
+    stable: bool,
+    #[serde(default)]
+    parallelism: Parallelism,
+    #[serde(default)]
+    track_order: bool,
+    // the insertion index of each stored value, once sorting has moved
+    // them; empty while the values are stored in insertion order or the
+    // order is not tracked
+    #[serde(default)]
+    order: Vec<usize>,
+    // set when the values were reordered while the order was not tracked
+    #[serde(default)]
+    order_lost: bool,
+}
+
+/// The serialized form of an `Unsorted`, which is checked before use.
+#[derive(Deserialize)]
+struct UnsortedParts<T> {
+    data: Vec<Partial<T>>,
+    sorted: bool,
+    #[serde(default)]
+    reverse_sorted: bool,
+    #[serde(default)]
+    nulls: u64,
+    #[serde(default)]
+    stable: bool,
+    #[serde(default)]
+    parallelism: Parallelism,
+    // these are missing from JSON written before insertion order could be
+    // tracked, which then loads untracked, as `new` creates it
+    #[serde(default)]
+    track_order: bool,
+    #[serde(default)]
+    order: Vec<usize>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    order_lost: Option<bool>,
+}
+
+/// Deserializes a field that is always written, so that `Option` only
+/// tells whether an older payload omitted it.
+fn deserialize_some<'de, D, V>(deserializer: D) -> Result<Option<V>, D::Error>
+where
+    D: Deserializer<'de>,
+    V: Deserialize<'de>,
+{
+    V::deserialize(deserializer).map(Some)
+}
+
+impl<T> TryFrom<UnsortedParts<T>> for Unsorted<T> {
+    type Error = String;
+
+    fn try_from(v: UnsortedParts<T>) -> Result<Unsorted<T>, String> {
+        // older payloads do not say whether sorted data was reordered by a
+        // statistic, so the order of untracked sorted data is assumed lost
+        let order_lost = v
+            .order_lost
+            .unwrap_or(!v.track_order && v.sorted && v.data.len() > 1);
+        if !v.order.is_empty() {
+            if !v.track_order {
+                return Err("an insertion order is stored but not tracked".to_owned());
+            }
+            // every insertion index must occur exactly once
+            let mut seen = vec![false; v.data.len()];
+            let valid = v.order.len() == v.data.len()
+                && v.order
+                    .iter()
+                    .all(|&i| i < seen.len() && !std::mem::replace(&mut seen[i], true));
+            if !valid {
+                return Err("the insertion order is not a permutation of the data".to_owned());
+            }
+        }
+        Ok(Unsorted {
+            data: v.data,
+            sorted: v.sorted,
+            reverse_sorted: v.reverse_sorted,
+            nulls: v.nulls,
+            stable: v.stable,
+            parallelism: v.parallelism,
+            track_order: v.track_order,
+            order: v.order,
+            order_lost,
+        })
+    }
 }
 
 impl<T: PartialOrd> Unsorted<T> {
     /// Create initial empty state.
+    ///
+    /// No memory is allocated until the first element is added.
     #[inline]
     #[must_use]
     pub fn new() -> Unsorted<T> {
         Default::default()
     }
 
-    /// Add a new element to the set.
+    /// Create initial empty state with space for at least `capacity` elements.
     #[inline]
-    pub fn add(&mut self, v: T) {
-        self.sorted = false;
-        self.data.push(Partial(v));
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Unsorted<T> {
+        Unsorted {
+            data: Vec::with_capacity(capacity),
+            ..Default::default()
+        }
     }
 
-    /// Return the number of data points.
+    /// Returns the number of bytes allocated by this state.
+    ///
+    /// This counts the buffer capacity, not just its length, but does not
+    /// follow heap allocations owned by the elements themselves
+    /// (e.g. the contents of a `String`).
     #[inline]
     #[must_use]
-    #[allow(clippy::len_without_is_empty)]
-    pub fn len(&self) -> usize {
-        self.data.len()
+    pub fn mem_usage(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.data.capacity() * std::mem::size_of::<Partial<T>>()
+            + self.order.capacity() * std::mem::size_of::<usize>()
     }
 
+    /// Shrink the capacity of the underlying buffer as much as possible.
     #[inline]
-    fn sort(&mut self) {
-        if !self.sorted {
-            self.data.par_sort_unstable();
-            self.sorted = true;
+    pub fn shrink_to_fit(&mut self) {
+        self.data.shrink_to_fit();
+        self.order.shrink_to_fit();
+    }
+
+    /// Create state from an existing buffer, reusing its allocation.
+    #[must_use]
+    pub fn from_vec(data: Vec<T>) -> Unsorted<T> {
+        let data: Vec<Partial<T>> = data.into_iter().map(Partial).collect();
+        let sorted = data.windows(2).all(|w| w[0].0 <= w[1].0);
+        let reverse_sorted = data.windows(2).all(|w| w[0].0 >= w[1].0);
+        Unsorted {
+            data,
+            sorted,
+            reverse_sorted,
+            nulls: 0,
+            stable: false,
+            parallelism: Parallelism::Auto,
+            track_order: false,
+            order: Vec::new(),
+            order_lost: false,
         }
     }
-}
 
-impl<T: PartialOrd + Eq + Clone> Unsorted<T> {
+    /// Returns the underlying buffer, in no particular order.
+    ///
+    /// Together with `Vec::clear` and `from_vec`, this lets a streaming
+    /// profiler recycle one allocation across many columns.
     #[inline]
-    pub fn cardinality(&mut self) -> usize {
-        self.sort();
-        let mut set = self.data.clone();
-        set.dedup();
-        set.len()
+    #[must_use]
+    pub fn into_vec(self) -> Vec<T> {
+        self.data.into_iter().map(|p| p.0).collect()
     }
-}
 
-impl<T: PartialOrd + Clone> Unsorted<T> {
-    /// Returns the mode of the data.
+    /// Remove all data, keeping the allocated capacity.
     #[inline]
-    pub fn mode(&mut self) -> Option<T> {
-        self.sort();
-        mode_on_sorted(self.data.iter()).map(|p| p.0.clone())
+    pub fn clear(&mut self) {
+        self.data.clear();
+        self.order.clear();
+        self.order_lost = false;
+        self.sorted = true;
+        self.reverse_sorted = true;
+        self.nulls = 0;
     }
 
-    /// Returns the modes of the data.
+    /// Remove all data and return it as an iterator, keeping the allocated
+    /// capacity.
+    ///
+    /// The values are yielded in sorted order if the data has been sorted,
+    /// and in insertion order otherwise.
     #[inline]
-    pub fn modes(&mut self) -> (Vec<T>, usize, u32) {
-        self.sort();
-        let (modes_vec, modes_count, occurrences) = modes_on_sorted(self.data.iter(), self.len());
-        let modes_result = modes_vec.into_iter().map(|p| p.0.clone()).collect();
-        (modes_result, modes_count, occurrences)
+    pub fn drain(&mut self) -> impl Iterator<Item = T> + '_ {
+        self.order.clear();
+        self.order_lost = false;
+        self.sorted = true;
+        self.reverse_sorted = true;
+        self.nulls = 0;
+        self.data.drain(..).map(|p| p.0)
     }
 
-    /// Returns the antimodes of the data.
+    /// Keep only the elements for which `f` returns `true`.
+    ///
+    /// This is useful to drop sentinel values (e.g. `-9999` or empty
+    /// strings) before computing statistics. The relative order of the
+    /// remaining elements is preserved, so sorted data stays sorted.
     #[inline]
-    pub fn antimodes(&mut self) -> (Vec<T>, usize, u32) {
-        self.sort();
-        let (antimodes_vec, antimodes_count, occurrences) =
-            antimodes_on_sorted(self.data.iter(), self.len());
-        let antimodes_result: Vec<T> = antimodes_vec.into_iter().map(|p| p.0.clone()).collect();
-
-        (antimodes_result, antimodes_count, occurrences)
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut f: F) {
+        if self.order.is_empty() {
+            self.data.retain(|p| f(&p.0));
+            return;
+        }
+        let mut kept = vec![false; self.data.len()];
+        let mut order = self.order.iter();
+        self.data.retain(|p| {
+            let keep = f(&p.0);
+            kept[*order.next().unwrap()] = keep;
+            keep
+        });
+        // renumber the remaining insertion indices to `0..len`
+        let mut next = 0;
+        let renumbered: Vec<usize> = kept
+            .iter()
+            .map(|&keep| {
+                let i = next;
+                next += usize::from(keep);
+                i
+            })
+            .collect();
+        self.order.retain(|&i| kept[i]);
+        for i in &mut self.order {
+            *i = renumbered[*i];
+        }
     }
-}
 
-impl<T: PartialOrd + ToPrimitive> Unsorted<T> {
-    /// Returns the median of the data.
+    /// Add a new element to the set.
     #[inline]
-    pub fn median(&mut self) -> Option<f64> {
+    pub fn add(&mut self, v: T) {
+        self.push(Partial(v));
+    }
+
+    /// Add a slice of elements.
+    ///
+    /// This is faster than `extend` for large chunks, since the order is
+    /// checked in one pass over the slice and the values are copied in
+    /// bulk rather than pushed one at a time.
+    pub fn extend_from_slice(&mut self, values: &[T])
+    where
+        T: Copy,
+    {
+        let Some(first) = values.first() else {
+            return;
+        };
+        let last = self.data.last().map(|p| p.0);
+        // comparisons are on the inner values, as in `push`
+        self.sorted = self.sorted
+            && last.map_or(true, |l| l <= *first)
+            && values.windows(2).all(|w| w[0] <= w[1]);
+        self.reverse_sorted = self.reverse_sorted
+            && last.map_or(true, |l| l >= *first)
+            && values.windows(2).all(|w| w[0] >= w[1]);
+        if !self.order.is_empty() {
+            let len = self.data.len();
+            self.order.extend(len..len + values.len());
+        }
+        self.data.extend(values.iter().map(|&v| Partial(v)));
+    }
+
+    /// Add a new element at its place in sorted order.
+    ///
+    /// The data is sorted first if it is not already, and then stays
+    /// sorted, so statistics computed between adds never need to re-sort.
+    /// Each insert is a binary search and a shift of the larger elements,
+    /// i.e. `O(n)`, so this suits workloads that interleave a few adds with
+    /// frequent queries. For bulk loads, `add` followed by one sort is
+    /// faster.
+    ///
+    /// A value equal to existing ones is inserted after them, as a stable
+    /// sort would place it.
+    pub fn add_sorted(&mut self, v: T) {
         self.sort();
-        median_on_sorted(&self.data)
+        let v = Partial(v);
+        let index = self.data.partition_point(|p| *p <= v);
+        if index < self.data.len() && self.order.is_empty() {
+            if self.track_order {
+                self.order = (0..self.data.len()).collect();
+            } else {
+                self.order_lost = true;
+            }
+        }
+        if !self.order.is_empty() {
+            self.order.insert(index, self.data.len());
+        }
+        self.data.insert(index, v);
+        self.reverse_sorted = self.data.len() <= 1;
     }
-}
 
-impl<T: PartialOrd + ToPrimitive> Unsorted<T> {
-    /// Returns the MAD of the data.
+    /// Push a new element, keeping track of whether the data is still
+    /// in ascending or descending order.
     #[inline]
-    pub fn mad(&mut self, existing_median: Option<f64>) -> Option<f64> {
-        if existing_median.is_none() {
-            self.sort();
+    fn push(&mut self, v: Partial<T>) {
+        if let Some(last) = self.data.last() {
+            // comparisons are on the inner values, so an undefined
+            // ordering (e.g. NaN) clears both flags
+            self.sorted = self.sorted && last.0 <= v.0;
+            self.reverse_sorted = self.reverse_sorted && last.0 >= v.0;
         }
-        mad_on_sorted(&self.data, existing_median)
+        if !self.order.is_empty() {
+            self.order.push(self.data.len());
+        }
+        self.data.push(v);
     }
-}
 
-impl<T: PartialOrd + ToPrimitive> Unsorted<T> {
-    /// Returns the quartiles of the data.
+    /// Use a stable sort, so that values which compare equal keep the order
+    /// in which they were added.
+    ///
+    /// The default unstable sort is faster, but when equal values are
+    /// distinguishable (e.g. `0.0` and `-0.0`, or strings compared with a
+    /// custom ordering) it may pick a different representative for a mode
+    /// from run to run. With a stable sort, the first one added is used.
     #[inline]
-    pub fn quartiles(&mut self) -> Option<(f64, f64, f64)> {
-        self.sort();
-        quartiles_on_sorted(&self.data)
+    pub fn set_stable(&mut self, stable: bool) {
+        self.stable = stable;
     }
-}
 
-impl<T: PartialOrd> Commute for Unsorted<T> {
+    /// Set whether sorting and other heavy steps run on the rayon thread
+    /// pool. The default, `Parallelism::Auto`, only goes parallel on large
+    /// inputs.
     #[inline]
-    fn merge(&mut self, v: Unsorted<T>) {
-        self.sorted = false;
-        self.data.extend(v.data);
+    pub fn set_parallelism(&mut self, parallelism: Parallelism) {
+        self.parallelism = parallelism;
     }
-}
 
-impl<T: PartialOrd> Default for Unsorted<T> {
+    /// Returns the parallelism setting.
     #[inline]
-    fn default() -> Unsorted<T> {
-        Unsorted {
-            data: Vec::with_capacity(10_000),
-            sorted: true, // empty is sorted
-        }
+    #[must_use]
+    pub const fn parallelism(&self) -> Parallelism {
+        self.parallelism
     }
-}
 
-impl<T: PartialOrd> FromIterator<T> for Unsorted<T> {
+    /// Returns true if the data is known to be in ascending order,
+    /// so computing statistics will not need to sort it.
     #[inline]
-    fn from_iter<I: IntoIterator<Item = T>>(it: I) -> Unsorted<T> {
-        let mut v = Unsorted::new();
-        v.extend(it);
-        v
+    #[must_use]
+    pub const fn is_sorted(&self) -> bool {
+        self.sorted
     }
-}
 
-impl<T: PartialOrd> Extend<T> for Unsorted<T> {
+    /// Return the number of data points.
     #[inline]
-    fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
-        self.sorted = false;
-        self.data.extend(it.into_iter().map(Partial));
+    #[must_use]
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        self.data.len()
     }
-}
-
-#[cfg(test)]
-mod test {
-    use super::{antimodes, mad, median, mode, modes, quartiles};
 
-    #[test]
-    fn median_stream() {
-        assert_eq!(median(vec![3usize, 5, 7, 9].into_iter()), Some(6.0));
-        assert_eq!(median(vec![3usize, 5, 7].into_iter()), Some(5.0));
+    /// Return the number of `None` values ingested via `Extend<Option<T>>`.
+    ///
+    /// NULLs are not part of the data and do not affect any statistic.
+    #[inline]
+    #[must_use]
+    pub const fn null_count(&self) -> u64 {
+        self.nulls
     }
 
-    #[test]
-    fn mad_stream() {
-        assert_eq!(mad(vec![3usize, 5, 7, 9].into_iter(), None), Some(2.0));
-        assert_eq!(
-            mad(
-                vec![
-                    86usize, 60, 95, 39, 49, 12, 56, 82, 92, 24, 33, 28, 46, 34, 100, 39, 100, 38,
-                    50, 61, 39, 88, 5, 13, 64
-                ]
-                .into_iter(),
-                None
-            ),
-            Some(16.0)
-        );
+    /// Set whether sorting remembers the order in which the values were
+    /// added, so methods that depend on it (e.g. `ranks` or
+    /// `autocorrelation`) see the input order even after order statistics
+    /// have been computed. This is off by default.
+    ///
+    /// Tracking costs one `usize` per value, allocated the first time the
+    /// data is reordered, and makes sorting several times slower. With it
+    /// off, those
+    /// methods return `None` once a sort has reordered the data, and turning
+    /// it back on does not recover the order until the data is cleared.
+    #[inline]
+    pub fn set_track_insertion_order(&mut self, track: bool) {
+        self.track_order = track;
+        if !track {
+            self.order_lost |= !self.order.is_empty();
+            self.order = Vec::new();
+        }
     }
 
-    #[test]
-    fn mad_stream_precalc_median() {
-        let data = vec![3usize, 5, 7, 9].into_iter();
-        let median1 = median(data.clone());
-        assert_eq!(mad(data, median1), Some(2.0));
+    #[inline]
+    fn sort(&mut self) {
+        if self.sorted {
+            return;
+        }
+        self.order_lost |= !self.track_order;
+        if self.track_order {
+            self.sort_tracked();
+        } else if self.stable {
+            // reversing would also reverse runs of equal values
+            sort_stable_by(&mut self.data, self.parallelism, Ord::cmp);
+        } else if self.reverse_sorted {
+            self.data.reverse();
+        } else {
+            sort_unstable_by(&mut self.data, self.parallelism, Ord::cmp);
+        }
+        self.sorted = true;
+        self.reverse_sorted = self.data.len() <= 1;
+    }
 
-        let data2 = vec![
-            86usize, 60, 95, 39, 49, 12, 56, 82, 92, 24, 33, 28, 46, 34, 100, 39, 100, 38, 50, 61,
-            39, 88, 5, 13, 64,
-        ]
-        .into_iter();
-        let median2 = median(data2.clone());
-        assert_eq!(mad(data2, median2), Some(16.0));
+    /// Sorts the data in place, moving the insertion indices along with
+    /// the values.
+    fn sort_tracked(&mut self) {
+        let mut order = self.take_order();
+        // reversing would also reverse runs of equal values
+        if self.reverse_sorted && !self.stable {
+            self.data.reverse();
+            order.reverse();
+            self.order = order;
+            return;
+        }
+        let mut positions: Vec<usize> = (0..self.data.len()).collect();
+        let data = &self.data;
+        let by_value = |&i: &usize, &j: &usize| data[i].cmp(&data[j]);
+        if self.stable {
+            sort_stable_by(&mut positions, self.parallelism, by_value);
+        } else {
+            sort_unstable_by(&mut positions, self.parallelism, by_value);
+        }
+        self.order = positions.iter().map(|&p| order[p]).collect();
+        // move the value at `positions[i]` to `i`, one cycle at a time
+        for start in 0..positions.len() {
+            let mut i = start;
+            while positions[i] != start {
+                let next = positions[i];
+                self.data.swap(i, next);
+                positions[i] = i;
+                i = next;
+            }
+            positions[i] = i;
+        }
     }
 
-    #[test]
-    fn mode_stream() {
-        assert_eq!(mode(vec![3usize, 5, 7, 9].into_iter()), None);
-        assert_eq!(mode(vec![3usize, 3, 3, 3].into_iter()), Some(3));
-        assert_eq!(mode(vec![3usize, 3, 3, 4].into_iter()), Some(3));
-        assert_eq!(mode(vec![4usize, 3, 3, 3].into_iter()), Some(3));
-        assert_eq!(mode(vec![1usize, 1, 2, 3, 3].into_iter()), None);
+    /// Takes the insertion index of each stored value, leaving the order
+    /// empty.
+    fn take_order(&mut self) -> Vec<usize> {
+        if self.order.is_empty() {
+            (0..self.data.len()).collect()
+        } else {
+            std::mem::take(&mut self.order)
+        }
     }
 
-    #[test]
-    fn median_floats() {
-        assert_eq!(median(vec![3.0f64, 5.0, 7.0, 9.0].into_iter()), Some(6.0));
-        assert_eq!(median(vec![3.0f64, 5.0, 7.0].into_iter()), Some(5.0));
-        assert_eq!(median(vec![1.0f64, 2.5, 3.0].into_iter()), Some(2.5));
+    /// Returns the values in the order in which they were added, without
+    /// sorting them, or `None` if that order has been lost.
+    pub(crate) fn insertion_ordered(&self) -> Option<Vec<&T>> {
+        if self.order_lost {
+            return None;
+        }
+        if self.order.is_empty() {
+            return Some(self.data.iter().map(|p| &p.0).collect());
+        }
+        Some(
+            self.insertion_positions()
+                .into_iter()
+                .map(|p| &self.data[p].0)
+                .collect(),
+        )
     }
 
-    #[test]
-    fn mode_floats() {
-        assert_eq!(mode(vec![3.0f64, 5.0, 7.0, 9.0].into_iter()), None);
-        assert_eq!(mode(vec![3.0f64, 3.0, 3.0, 3.0].into_iter()), Some(3.0));
-        assert_eq!(mode(vec![3.0f64, 3.0, 3.0, 4.0].into_iter()), Some(3.0));
-        assert_eq!(mode(vec![4.0f64, 3.0, 3.0, 3.0].into_iter()), Some(3.0));
-        assert_eq!(mode(vec![1.0f64, 1.0, 2.0, 3.0, 3.0].into_iter()), None);
+    /// Returns the stored position of each value, in the order in which the
+    /// values were added unless that order has been lost.
+    fn insertion_positions(&self) -> Vec<usize> {
+        if self.order.is_empty() {
+            return (0..self.data.len()).collect();
+        }
+        let mut positions = vec![0; self.data.len()];
+        for (p, &i) in self.order.iter().enumerate() {
+            positions[i] = p;
+        }
+        positions
     }
 
-    #[test]
-    fn modes_stream() {
-        assert_eq!(modes(vec![3usize, 5, 7, 9].into_iter()), (vec![], 0, 0));
-        assert_eq!(modes(vec![3usize, 3, 3, 3].into_iter()), (vec![3], 1, 4));
-        assert_eq!(modes(vec![3usize, 3, 4, 4].into_iter()), (vec![3, 4], 2, 2));
-        assert_eq!(modes(vec![4usize, 3, 3, 3].into_iter()), (vec![3], 1, 3));
-        assert_eq!(modes(vec![1usize, 1, 2, 2].into_iter()), (vec![1, 2], 2, 2));
-        let vec: Vec<u32> = vec![];
-        assert_eq!(modes(vec.into_iter()), (vec![], 0, 0));
+    /// Reorders one result per stored value into the order in which the
+    /// values were added, unless that order has been lost.
+    pub(crate) fn to_insertion_order<R: Copy + Default>(&self, stored: Vec<R>) -> Vec<R> {
+        if self.order.is_empty() {
+            return stored;
+        }
+        let mut results = vec![R::default(); stored.len()];
+        for (r, &i) in stored.into_iter().zip(&self.order) {
+            results[i] = r;
+        }
+        results
+    }
+
+    /// Sorts the data if needed and returns a view of it.
+    ///
+    /// This lets callers run their own algorithms (e.g. other percentile
+    /// conventions) on the sorted data without copying it.
+    #[inline]
+    pub fn as_sorted_slice(&mut self) -> &[T] {
+        let data = self.sorted_data();
+        // safety: `Partial<T>` is `repr(transparent)`, so it has the same
+        // layout as `T`
+        unsafe { std::slice::from_raw_parts(data.as_ptr().cast::<T>(), data.len()) }
+    }
+
+    /// Returns a view of the data in the order it is stored, without
+    /// sorting it.
+    #[inline]
+    pub(crate) fn stored_slice(&self) -> &[T] {
+        // safety: see `as_sorted_slice`
+        unsafe { std::slice::from_raw_parts(self.data.as_ptr().cast::<T>(), self.data.len()) }
+    }
+
+    /// Sorts the data if needed and returns it, consuming `self`.
+    #[inline]
+    #[must_use]
+    pub fn into_sorted_vec(self) -> Vec<T> {
+        self.into_sorted_partials()
+            .into_iter()
+            .map(|p| p.0)
+            .collect()
+    }
+
+    /// Sorts the data if needed and returns it.
+    #[inline]
+    pub(crate) fn sorted_data(&mut self) -> &[Partial<T>] {
+        self.sort();
+        &self.data
+    }
+
+    /// Sorts the data if needed and returns the underlying buffer.
+    #[inline]
+    pub(crate) fn into_sorted_partials(mut self) -> Vec<Partial<T>> {
+        self.sort();
+        self.data
+    }
+}
+
+impl<T: PartialOrd> Unsorted<T> {
+    /// Returns the positions of the stored values, ordered by value.
+    ///
+    /// Ties keep their stored order. The data itself is not sorted.
+    pub(crate) fn sorted_positions(&self) -> Vec<usize> {
+        let mut positions: Vec<usize> = (0..self.data.len()).collect();
+        self.sort_positions(&mut positions);
+        positions
+    }
+
+    /// Sorts `positions` by the values stored at them, keeping ties in
+    /// their given order.
+    fn sort_positions(&self, positions: &mut [usize]) {
+        if self.parallelism.is_parallel(positions.len()) {
+            in_pool(|| positions.par_sort_by(|&i, &j| self.data[i].cmp(&self.data[j])));
+        } else {
+            positions.sort_by(|&i, &j| self.data[i].cmp(&self.data[j]));
+        }
+    }
+
+    /// Returns the 1-based rank of every value, with ties ranked by
+    /// `method`.
+    ///
+    /// Values that are not equal to themselves (i.e. `NaN`) cannot be
+    /// ranked: they get a rank of `NaN` and do not take up a rank.
+    ///
+    /// The ranks line up with the input: the `i`th rank is that of the
+    /// `i`th value added. The data is not sorted by this method.
+    ///
+    /// `None` is returned if the input order has been lost, i.e. a
+    /// statistic sorted the data while `set_track_insertion_order` was off.
+    #[must_use]
+    pub fn ranks(&self, method: RankMethod) -> Option<Vec<f64>> {
+        if self.order_lost {
+            return None;
+        }
+        let mut ranks = vec![f64::NAN; self.data.len()];
+        // ties keep the order of `positions`, i.e. the insertion order
+        let mut positions: Vec<usize> = self
+            .insertion_positions()
+            .into_iter()
+            .filter(|&i| {
+                let v = &self.data[i];
+                v.partial_cmp(v).is_some()
+            })
+            .collect();
+        self.sort_positions(&mut positions);
+
+        let (mut start, mut dense) = (0, 0);
+        while start < positions.len() {
+            let first = &self.data[positions[start]];
+            let end = start
+                + positions[start..]
+                    .iter()
+                    .take_while(|&&i| self.data[i] == *first)
+                    .count();
+            dense += 1;
+            for (offset, &i) in positions[start..end].iter().enumerate() {
+                ranks[i] = match method {
+                    RankMethod::Average => (start + end + 1) as f64 / 2.0,
+                    RankMethod::Min => (start + 1) as f64,
+                    RankMethod::Max => end as f64,
+                    RankMethod::Dense => f64::from(dense),
+                    RankMethod::Ordinal => (start + offset + 1) as f64,
+                };
+            }
+            start = end;
+        }
+        Some(self.to_insertion_order(ranks))
+    }
+
+    /// Assigns every value to one of `k` equal-frequency (quantile) bins,
+    /// numbered `0` to `k - 1` in ascending order of value.
+    ///
+    /// A value's bin is `k` times the fraction of values strictly smaller
+    /// than it, rounded down, so equal values always share a bin and bins
+    /// may be uneven when there are many ties.
+    ///
+    /// The bins are returned in the order in which the values were added.
+    /// The data is not sorted by this method. A `k` of `0` is treated as
+    /// `1`.
+    ///
+    /// `None` is returned if that order has been lost, i.e. a statistic
+    /// sorted the data while `set_track_insertion_order` was off.
+    #[must_use]
+    pub fn bin_assignments(&self, k: usize) -> Option<Vec<usize>> {
+        if self.order_lost {
+            return None;
+        }
+        let (n, k) = (self.data.len(), k.max(1));
+        let mut bins = vec![0; n];
+        let positions = self.sorted_positions();
+        let mut smaller = 0;
+        for (rank, &i) in positions.iter().enumerate() {
+            if rank > 0 && self.data[positions[rank - 1]] != self.data[i] {
+                smaller = rank;
+            }
+            bins[i] = ((smaller as u128 * k as u128) / n as u128) as usize;
+        }
+        Some(self.to_insertion_order(bins))
+    }
+}
+
+impl<T: PartialOrd> Unsorted<T> {
+    /// Returns the number of distinct values, how many of them repeat, the
+    /// largest number of repeats and the fraction of duplicate values.
+    ///
+    /// `None` is returned if there is no data.
+    pub fn duplicate_stats(&mut self) -> Option<DuplicateStats> {
+        let data = self.sorted_data();
+        if data.is_empty() {
+            return None;
+        }
+        let mut stats = DuplicateStats::default();
+        for run in data.chunk_by(|a, b| a == b) {
+            let count = run.len() as u64;
+            stats.distinct += 1;
+            stats.duplicated += u64::from(count > 1);
+            stats.max_count = stats.max_count.max(count);
+        }
+        stats.duplicate_ratio = 1.0 - stats.distinct as f64 / data.len() as f64;
+        Some(stats)
+    }
+}
+
+impl<T: PartialOrd + PrimInt> Unsorted<T> {
+    /// Returns whether the integers form a contiguous range, how many
+    /// integers in `[min, max]` are missing, and the first `max_ranges`
+    /// gaps, e.g. to validate surrogate keys or sequence numbers.
+    ///
+    /// Duplicates are ignored; see `duplicate_stats` for those.
+    ///
+    /// `None` is returned if there is no data.
+    pub fn gaps(&mut self, max_ranges: usize) -> Option<Gaps<T>> {
+        let data = self.sorted_data();
+        let mut gaps = Gaps {
+            min: data.first()?.0,
+            max: data.last()?.0,
+            missing: 0,
+            gap_count: 0,
+            ranges: Vec::new(),
+        };
+        for w in data.windows(2) {
+            let (a, b) = (w[0].0, w[1].0);
+            // every primitive integer fits in i128 except the upper half
+            // of u128, which is never negative
+            let diff = match (a.to_i128(), b.to_i128()) {
+                (Some(a), Some(b)) => b.abs_diff(a),
+                _ => b.to_u128().unwrap() - a.to_u128().unwrap(),
+            };
+            if diff > 1 {
+                gaps.missing += diff - 1;
+                gaps.gap_count += 1;
+                if gaps.ranges.len() < max_ranges {
+                    gaps.ranges.push((a + T::one(), b - T::one()));
+                }
+            }
+        }
+        Some(gaps)
+    }
+}
+
+impl<T: PartialOrd + Eq + Clone> Unsorted<T> {
+    #[inline]
+    pub fn cardinality(&mut self) -> usize {
+        self.sort();
+        if self.data.is_empty() {
+            return 0;
+        }
+        // count the boundaries between runs of equal values
+        let boundaries = if self.parallelism.is_parallel(self.data.len()) {
+            in_pool(|| self.data.par_windows(2).filter(|w| w[0] != w[1]).count())
+        } else {
+            self.data.windows(2).filter(|w| w[0] != w[1]).count()
+        };
+        boundaries + 1
+    }
+}
+
+impl<T: PartialOrd + Normalize> Unsorted<T> {
+    /// Add a new element to the set after normalizing it, e.g. trimming
+    /// whitespace and ignoring case.
+    #[inline]
+    pub fn add_normalized(&mut self, v: T, how: TextNormalization) {
+        self.add(v.normalize(how));
+    }
+}
+
+impl<T: PartialOrd + Eq + Hash + Clone> Unsorted<T> {
+    /// Returns true if `strategy` resolves to counting with a hash map.
+    #[inline]
+    fn use_hash(&self, strategy: FrequencyStrategy) -> bool {
+        match strategy {
+            FrequencyStrategy::Auto => !self.sorted && self.low_cardinality(),
+            FrequencyStrategy::Sort => false,
+            FrequencyStrategy::Hash => true,
+        }
+    }
+
+    /// Returns true if at most half of an evenly spaced sample of up to
+    /// `AUTO_SAMPLE_LEN` values are distinct, in which case a hash map is
+    /// small and counting with it beats sorting.
+    fn low_cardinality(&self) -> bool {
+        const AUTO_SAMPLE_LEN: usize = 1024;
+        let len = self.data.len();
+        let sample_len = len.min(AUTO_SAMPLE_LEN);
+        let distinct: AHashSet<&T> = (0..sample_len)
+            .map(|i| &self.data[i * len / sample_len].0)
+            .collect();
+        distinct.len() * 2 <= sample_len
+    }
+
+    /// Count the occurrences of each value without sorting.
+    fn frequencies(&self) -> Frequencies<&T> {
+        self.data.iter().map(|p| &p.0).collect()
+    }
+
+    /// Returns the mode of the data, counting with `strategy`.
+    ///
+    /// The result is the same as `mode` for every strategy.
+    #[inline]
+    pub fn mode_with(&mut self, strategy: FrequencyStrategy) -> Option<T> {
+        if !self.use_hash(strategy) {
+            return self.mode();
+        }
+        let freqs = self.frequencies();
+        let (mut mode, mut mode_count, mut tied) = (None, 1, false);
+        for (v, count) in freqs.iter() {
+            if count > mode_count {
+                (mode, mode_count, tied) = (Some(v), count, false);
+            } else if count == mode_count {
+                tied = true;
+            }
+        }
+        if tied {
+            return None;
+        }
+        mode.map(|v| (*v).clone())
+    }
+
+    /// Returns the modes of the data, counting with `strategy`.
+    ///
+    /// The result is the same as `modes` for every strategy: the modes are
+    /// returned in ascending order.
+    #[inline]
+    pub fn modes_with(&mut self, strategy: FrequencyStrategy) -> (Vec<T>, usize, u64) {
+        if !self.use_hash(strategy) {
+            return self.modes();
+        }
+        let freqs = self.frequencies();
+        let highest = freqs.iter().map(|(_, c)| c).max().unwrap_or(0);
+        if highest < 2 {
+            // as with `modes`, values occurring only once are not modes
+            return (Vec::new(), 0, 0);
+        }
+        let mut modes: Vec<&T> = freqs
+            .iter()
+            .filter(|&(_, c)| c == highest)
+            .map(|(v, _)| *v)
+            .collect();
+        modes.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Less));
+        let count = modes.len();
+        (modes.into_iter().cloned().collect(), count, highest)
+    }
+
+    /// Returns the cardinality of the data, counting with `strategy`.
+    #[inline]
+    pub fn cardinality_with(&mut self, strategy: FrequencyStrategy) -> usize {
+        if self.use_hash(strategy) {
+            self.frequencies().len()
+        } else {
+            self.cardinality()
+        }
+    }
+}
+
+impl<T: PartialOrd + Clone> Unsorted<T> {
+    /// Returns the mode of the data.
+    #[inline]
+    pub fn mode(&mut self) -> Option<T> {
+        self.sort();
+        mode_on_sorted(self.data.iter()).map(|p| p.0.clone())
+    }
+
+    /// Returns the modes of the data.
+    #[inline]
+    pub fn modes(&mut self) -> (Vec<T>, usize, u64) {
+        self.sort();
+        let (modes_vec, modes_count, occurrences) = modes_on_sorted(self.data.iter(), self.len());
+        let modes_result = modes_vec.into_iter().map(|p| p.0.clone()).collect();
+        (modes_result, modes_count, occurrences)
+    }
+
+    /// Returns the antimodes of the data.
+    ///
+    /// Only the first 10 antimodes are returned, but the count includes
+    /// all of them; see `antimodes_with_limit` for a different limit.
+    #[inline]
+    pub fn antimodes(&mut self) -> (Vec<T>, usize, u64) {
+        self.antimodes_with_limit(ANTIMODES_LIMIT)
+    }
+
+    /// Returns the antimodes of the data, keeping at most `limit` of them.
+    /// The count still includes all of them.
+    #[inline]
+    pub fn antimodes_with_limit(&mut self, limit: usize) -> (Vec<T>, usize, u64) {
+        self.sort();
+        let (antimodes_vec, antimodes_count, occurrences) =
+            antimodes_on_sorted(self.data.iter(), self.len(), limit);
+        let antimodes_result: Vec<T> = antimodes_vec.into_iter().map(|p| p.0.clone()).collect();
+
+        (antimodes_result, antimodes_count, occurrences)
+    }
+
+    /// Returns the modes of the data, each paired with its number of
+    /// occurrences.
+    ///
+    /// If the data does not have a mode, then an empty `Vec` is returned.
+    #[inline]
+    pub fn modes_with_counts(&mut self) -> Vec<(T, u64)> {
+        let (modes, _, occurrences) = self.modes();
+        modes.into_iter().map(|v| (v, occurrences)).collect()
+    }
+
+    /// Returns the antimodes of the data, each paired with its number of
+    /// occurrences.
+    ///
+    /// As with `antimodes`, only the first 10 antimodes are returned.
+    #[inline]
+    pub fn antimodes_with_counts(&mut self) -> Vec<(T, u64)> {
+        let (antimodes, _, occurrences) = self.antimodes();
+        antimodes.into_iter().map(|v| (v, occurrences)).collect()
+    }
+}
+
+impl<T: PartialOrd + ToPrimitive> Unsorted<T> {
+    /// Returns a trimmed mean, winsorized standard deviation, median, MAD
+    /// and quartile-based range of the data in one call, none of which
+    /// are moved far by a few outliers.
+    ///
+    /// `trim_fraction` of the values are trimmed from each end for the
+    /// trimmed mean (rounded down to a whole number of values), e.g.
+    /// `0.1` for the 10% trimmed mean, and the same values are winsorized
+    /// for the standard deviation. With `0.0`, these are the ordinary mean
+    /// and population standard deviation.
+    ///
+    /// `None` is returned if `trim_fraction` is not in `[0, 0.5)`, or if
+    /// there are fewer than three values, as for `quartiles`.
+    pub fn robust_describe(&mut self, trim_fraction: f64) -> Option<RobustSummary> {
+        if !(0.0..0.5).contains(&trim_fraction) {
+            return None;
+        }
+        let parallelism = self.parallelism;
+        let data = self.sorted_data();
+        let (q1, median, q3) = quartiles_on_sorted(data)?;
+        let n = data.len();
+        let k = (trim_fraction * n as f64).floor() as usize;
+        let value = |i: usize| data[i].0.to_f64().unwrap();
+
+        let trimmed_mean = (k..n - k).map(value).sum::<f64>() / (n - 2 * k) as f64;
+        let mut winsorized = OnlineStats::new();
+        for i in 0..n {
+            winsorized.add(&value(i.clamp(k, n - 1 - k)));
+        }
+        let iqr = q3 - q1;
+        Some(RobustSummary {
+            len: n,
+            trim_fraction,
+            trimmed_mean,
+            winsorized_stddev: winsorized.stddev(),
+            median,
+            mad: mad_on_sorted(data, Some(median), parallelism)?,
+            q1,
+            q3,
+            iqr,
+            lower_fence: 1.5f64.mul_add(-iqr, q1),
+            upper_fence: 1.5f64.mul_add(iqr, q3),
+        })
+    }
+
+    /// Returns the median of the data.
+    #[inline]
+    pub fn median(&mut self) -> Option<f64> {
+        self.sort();
+        median_on_sorted(&self.data)
+    }
+}
+
+impl<T: PartialOrd + ToPrimitive> Unsorted<T> {
+    /// Returns the MAD of the data.
+    #[inline]
+    pub fn mad(&mut self, existing_median: Option<f64>) -> Option<f64> {
+        if existing_median.is_none() {
+            self.sort();
+        }
+        mad_on_sorted(&self.data, existing_median, self.parallelism)
+    }
+
+    /// Returns the exact mean absolute deviation around the mean, i.e. the
+    /// mean of `|x - mean|`, in two passes over the data.
+    ///
+    /// This is less robust to outliers than the MAD (the median absolute
+    /// deviation around the median), but is what some consumers expect.
+    ///
+    /// `None` is returned if there is no data.
+    pub fn mean_abs_deviation(&self) -> Option<f64> {
+        if self.data.is_empty() {
+            return None;
+        }
+        let data = self.stored_slice();
+        let n = data.len() as f64;
+        let mean = data.iter().map(|x| x.to_f64().unwrap()).sum::<f64>() / n;
+        let total: f64 = data
+            .iter()
+            .map(|x| (x.to_f64().unwrap() - mean).abs())
+            .sum();
+        Some(total / n)
+    }
+
+    /// Returns the `q`th quantile of the data, for `q` in `[0, 1]`, picking
+    /// between the data points around rank `q * (len - 1)` with `method`.
+    ///
+    /// `None` is returned if there is no data or `q` is not in `[0, 1]`.
+    pub fn quantile_with(&mut self, q: f64, method: QuantileMethod) -> Option<f64> {
+        if !(0.0..=1.0).contains(&q) {
+            return None;
+        }
+        let data = self.sorted_data();
+        quantile_at(data.len(), q, method, |i| data[i].0.to_f64().unwrap())
+    }
+
+    /// Returns the quantiles of the absolute deviations `|x - center|` at
+    /// each of the fractions in `probs`, interpolated as by
+    /// `Quantiles::value_at`.
+    ///
+    /// This generalizes the MAD, which is the `0.5` quantile around the
+    /// median, to other quantiles (e.g. `0.9` for the spread that covers
+    /// most of the data) and other centers, such as the mean or a target
+    /// value. Fractions outside `[0, 1]` give `NaN`.
+    ///
+    /// `None` is returned if there is no data.
+    pub fn abs_deviation_quantiles(&self, probs: &[f64], center: f64) -> Option<Vec<f64>> {
+        if self.data.is_empty() {
+            return None;
+        }
+        let mut deviations: Vec<f64> = self
+            .stored_slice()
+            .iter()
+            .map(|x| x.to_f64().unwrap())
+            .collect();
+        crate::simd::abs_dev_in_place(&mut deviations, center);
+        sort_f64(&mut deviations, self.parallelism);
+        Some(
+            probs
+                .iter()
+                .map(|&p| {
+                    if (0.0..=1.0).contains(&p) {
+                        percentile_cont_on_sorted(&deviations, p).unwrap()
+                    } else {
+                        f64::NAN
+                    }
+                })
+                .collect(),
+        )
+    }
+}
+
+impl<T: PartialOrd + ToPrimitive> Unsorted<T> {
+    /// Returns a view for computing statistics of `f(x)` over the data,
+    /// e.g. `v.map_stats(f64::ln).median()`.
+    ///
+    /// When `f` is monotonic over the data (like `ln`, `sqrt` or `exp`),
+    /// statistics are read directly from the sorted buffer, without
+    /// allocating a transformed copy. Otherwise (e.g. `abs` over data with
+    /// both signs), the transformed values are collected and sorted once.
+    #[inline]
+    pub fn map_stats<F: Fn(f64) -> f64>(&mut self, f: F) -> MappedStats<'_, T, F> {
+        let parallelism = self.parallelism;
+        MappedStats::new(self.sorted_data(), f, parallelism)
+    }
+
+    /// Sorts the data if needed and returns a view that answers percentile
+    /// queries in both directions, `value_at` in `O(1)` and
+    /// `fraction_below` in `O(log n)`, without sorting again.
+    #[inline]
+    pub fn quantiles(&mut self) -> Quantiles<'_, T> {
+        Quantiles {
+            data: self.as_sorted_slice(),
+        }
+    }
+
+    /// Returns the quartiles of the data.
+    #[inline]
+    pub fn quartiles(&mut self) -> Option<(f64, f64, f64)> {
+        self.sort();
+        quartiles_on_sorted(&self.data)
+    }
+}
+
+impl<T: PartialOrd> Commute for Unsorted<T> {
+    #[inline]
+    fn merge(&mut self, mut v: Unsorted<T>) {
+        self.nulls += v.nulls;
+        if v.data.is_empty() {
+            return;
+        }
+        let ordered = |u: &Unsorted<T>| u.sorted || u.reverse_sorted;
+        if !(ordered(self) && ordered(&v)) {
+            self.sorted = false;
+            self.reverse_sorted = false;
+            self.append(v);
+            return;
+        }
+
+        // Both sides are already ordered (e.g. per-chunk stats that were
+        // already queried), so a linear merge keeps the result sorted
+        // without a full re-sort. Reversing descending data is cheap.
+        self.sort();
+        v.sort();
+        let in_order = match (self.data.last(), v.data.first()) {
+            (Some(last), Some(first)) => last <= first,
+            _ => true,
+        };
+        if in_order {
+            self.append(v);
+        } else {
+            if self.track_order {
+                // the values of `v` were added after those of `self`
+                let a_order = self.take_order();
+                let b_order: Vec<usize> = v
+                    .take_order()
+                    .into_iter()
+                    .map(|i| i + self.data.len())
+                    .collect();
+                self.order = merge_order(&self.data, &v.data, &a_order, &b_order);
+            }
+            let data = std::mem::take(&mut self.data);
+            self.order_lost |= !self.track_order || v.order_lost;
+            self.data = merge_sorted(data, v.data);
+        }
+        self.reverse_sorted = self.data.len() <= 1;
+    }
+}
+
+impl<T: PartialOrd> Unsorted<T> {
+    /// Appends the values of `v` after those of `self`, without changing
+    /// the sort flags.
+    fn append(&mut self, mut v: Unsorted<T>) {
+        // the order of `v` is dropped if `self` does not track it
+        self.order_lost |= v.order_lost || !(self.track_order || v.order.is_empty());
+        if self.track_order && !(self.order.is_empty() && v.order.is_empty()) {
+            let offset = self.data.len();
+            let mut order = self.take_order();
+            order.extend(v.take_order().into_iter().map(|i| i + offset));
+            self.order = order;
+        }
+        self.data.extend(v.data);
+    }
+}
+
+impl<T: PartialOrd + ToPrimitive> Unsorted<T> {
+    /// Groups the sorted data into runs of values within `tolerance` of the
+    /// first value of the run, returning each run's mean and length.
+    ///
+    /// Runs are anchored on their first value, so a slowly drifting sequence
+    /// is split up instead of being chained into a single group.
+    fn approx_groups(&mut self, tolerance: Tolerance) -> Vec<(f64, u64)> {
+        let mut groups = Vec::new();
+        let (mut start, mut sum, mut count) = (f64::NAN, 0.0, 0u64);
+        for v in self.sorted_data() {
+            let x = v.to_f64().unwrap();
+            if count > 0 && tolerance.contains(start, x) {
+                sum += x;
+                count += 1;
+            } else {
+                if count > 0 {
+                    groups.push((sum / count as f64, count));
+                }
+                (start, sum, count) = (x, x, 1);
+            }
+        }
+        if count > 0 {
+            groups.push((sum / count as f64, count));
+        }
+        groups
+    }
+
+    /// Returns the mode of the data, counting values within `tolerance` of
+    /// each other as equal.
+    ///
+    /// This makes the mode meaningful for floating-point data, where e.g.
+    /// `3.0000001` and `3.0` would otherwise be distinct. The mode is
+    /// reported as the mean of its group of near-equal values.
+    ///
+    /// As with `mode`, `None` is returned if there is a tie or if no group
+    /// has more than one value.
+    #[must_use]
+    pub fn mode_approx(&mut self, tolerance: Tolerance) -> Option<f64> {
+        let (modes, count, _) = self.modes_approx(tolerance);
+        if count == 1 {
+            modes.first().copied()
+        } else {
+            None
+        }
+    }
+
+    /// Returns the modes of the data, counting values within `tolerance` of
+    /// each other as equal.
+    ///
+    /// Each mode is the mean of its group of near-equal values. The result
+    /// has the same shape as `modes`: the modes in ascending order, their
+    /// number, and the number of occurrences of each.
+    #[must_use]
+    pub fn modes_approx(&mut self, tolerance: Tolerance) -> (Vec<f64>, usize, u64) {
+        let groups = self.approx_groups(tolerance);
+        let highest = groups.iter().map(|&(_, c)| c).max().unwrap_or(0);
+        if highest < 2 {
+            return (Vec::new(), 0, 0);
+        }
+        let modes: Vec<f64> = groups
+            .into_iter()
+            .filter(|&(_, c)| c == highest)
+            .map(|(v, _)| v)
+            .collect();
+        let count = modes.len();
+        (modes, count, highest)
+    }
+
+    /// Returns the number of groups of values within `tolerance` of each
+    /// other.
+    #[must_use]
+    pub fn cardinality_approx(&mut self, tolerance: Tolerance) -> usize {
+        self.approx_groups(tolerance).len()
+    }
+}
+
+/// The order of the transformed values relative to the sorted data.
+enum MappedOrder {
+    Ascending,
+    Descending,
+    Unordered(Vec<f64>),
+}
+
+/// A view computing statistics of `f(x)` over the sorted data of an
+/// `Unsorted`. See `Unsorted::map_stats`.
+pub struct MappedStats<'a, T, F> {
+    data: &'a [Partial<T>],
+    f: F,
+    order: MappedOrder,
+    parallelism: Parallelism,
+}
+
+impl<'a, T, F> MappedStats<'a, T, F>
+where
+    T: PartialOrd + ToPrimitive,
+    F: Fn(f64) -> f64,
+{
+    fn new(data: &'a [Partial<T>], f: F, parallelism: Parallelism) -> MappedStats<'a, T, F> {
+        let mapped = |p: &Partial<T>| f(p.0.to_f64().unwrap());
+        let (mut ascending, mut descending) = (true, true);
+        let mut prev: Option<f64> = None;
+        for v in data.iter().map(mapped) {
+            if let Some(prev) = prev {
+                // NaN compares false either way, so it forces a sort
+                ascending = ascending && prev <= v;
+                descending = descending && prev >= v;
+                if !ascending && !descending {
+                    break;
+                }
+            }
+            prev = Some(v);
+        }
+        let order = if ascending {
+            MappedOrder::Ascending
+        } else if descending {
+            MappedOrder::Descending
+        } else {
+            let mut values: Vec<f64> = data.iter().map(mapped).collect();
+            sort_f64(&mut values, parallelism);
+            MappedOrder::Unordered(values)
+        };
+        MappedStats {
+            data,
+            f,
+            order,
+            parallelism,
+        }
+    }
+
+    /// Returns the `i`th smallest transformed value.
+    #[inline]
+    fn get(&self, i: usize) -> Option<f64> {
+        match self.order {
+            MappedOrder::Ascending => self.data.get(i).map(|p| (self.f)(p.0.to_f64().unwrap())),
+            MappedOrder::Descending => {
+                let idx = self.data.len().checked_sub(i + 1)?;
+                self.data.get(idx).map(|p| (self.f)(p.0.to_f64().unwrap()))
+            }
+            MappedOrder::Unordered(ref values) => values.get(i).copied(),
+        }
+    }
+
+    /// Returns the median of the transformed data.
+    #[inline]
+    #[must_use]
+    pub fn median(&self) -> Option<f64> {
+        median_with(self.data.len(), |i| self.get(i))
+    }
+
+    /// Returns the quartiles of the transformed data.
+    #[inline]
+    #[must_use]
+    pub fn quartiles(&self) -> Option<(f64, f64, f64)> {
+        quartiles_with(self.data.len(), |i| self.get(i))
+    }
+
+    /// Returns the MAD of the transformed data.
+    #[inline]
+    #[must_use]
+    pub fn mad(&self, existing_median: Option<f64>) -> Option<f64> {
+        let median = existing_median.or_else(|| self.median())?;
+        let mut abs_diff_vec: Vec<f64> = (0..self.data.len())
+            .map(|i| (median - self.get(i).unwrap()).abs())
+            .collect();
+        sort_f64(&mut abs_diff_vec, self.parallelism);
+        median_on_sorted(&abs_diff_vec)
+    }
+}
+
+/// A view answering repeated percentile queries in both directions over
+/// the sorted data of an `Unsorted`. See `Unsorted::quantiles`.
+pub struct Quantiles<'a, T> {
+    data: &'a [T],
+}
+
+impl<T: PartialOrd + ToPrimitive> Quantiles<'_, T> {
+    /// Returns the value at `fraction` (in `[0, 1]`) of the data, linearly
+    /// interpolated between adjacent values, as `sql::percentile_cont`.
+    ///
+    /// `None` is returned if there is no data or `fraction` is not in
+    /// `[0, 1]`.
+    #[inline]
+    #[must_use]
+    pub fn value_at(&self, fraction: f64) -> Option<f64> {
+        if !(0.0..=1.0).contains(&fraction) {
+            return None;
+        }
+        percentile_cont_on_sorted(self.data, fraction)
+    }
+
+    /// Returns the fraction of values that are less than `value`, or `NaN`
+    /// if there is no data.
+    #[inline]
+    #[must_use]
+    pub fn fraction_below(&self, value: &T) -> f64 {
+        self.data.partition_point(|x| x < value) as f64 / self.data.len() as f64
+    }
+
+    /// Returns the fraction of values that are less than or equal to
+    /// `value`, i.e. the empirical CDF at `value`, or `NaN` if there is no
+    /// data.
+    #[inline]
+    #[must_use]
+    pub fn fraction_at_or_below(&self, value: &T) -> f64 {
+        self.data.partition_point(|x| x <= value) as f64 / self.data.len() as f64
+    }
+
+    /// Returns the number of values.
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns true if there is no data.
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+/// Merges two sorted buffers into one sorted buffer in linear time.
+fn merge_sorted<T: PartialOrd>(a: Vec<Partial<T>>, b: Vec<Partial<T>>) -> Vec<Partial<T>> {
+    let mut merged = Vec::with_capacity(a.len() + b.len());
+    let (mut a, mut b) = (a.into_iter().peekable(), b.into_iter().peekable());
+    loop {
+        let take_b = match (a.peek(), b.peek()) {
+            (Some(x), Some(y)) => y < x,
+            (Some(_), None) => {
+                merged.extend(a);
+                break;
+            }
+            (None, _) => {
+                merged.extend(b);
+                break;
+            }
+        };
+        merged.push(if take_b { b.next() } else { a.next() }.unwrap());
+    }
+    merged
+}
+
+/// Returns the insertion indices of the values that `merge_sorted` merges
+/// from `a` and `b`, which have the indices `a_order` and `b_order`.
+fn merge_order<T: PartialOrd>(
+    a: &[Partial<T>],
+    b: &[Partial<T>],
+    a_order: &[usize],
+    b_order: &[usize],
+) -> Vec<usize> {
+    let mut order = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if b[j] < a[i] {
+            order.push(b_order[j]);
+            j += 1;
+        } else {
+            order.push(a_order[i]);
+            i += 1;
+        }
+    }
+    order.extend_from_slice(&a_order[i..]);
+    order.extend_from_slice(&b_order[j..]);
+    order
+}
+
+/// Summarizes the state, without the values.
+impl<T> fmt::Debug for Unsorted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Unsorted")
+            .field("len", &self.data.len())
+            .field("nulls", &self.nulls)
+            .field("sorted", &self.sorted)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: PartialOrd> Unsorted<T> {
+    /// Writes the number of values and their range, found with a scan if
+    /// the data is not sorted, with each bound written by `write_value`.
+    fn fmt_range(
+        &self,
+        f: &mut fmt::Formatter,
+        write_value: impl Fn(&T, &mut fmt::Formatter) -> fmt::Result,
+    ) -> fmt::Result {
+        let (min, max) = if self.sorted {
+            (self.data.first(), self.data.last())
+        } else {
+            (self.data.iter().min(), self.data.iter().max())
+        };
+        match (min, max) {
+            (Some(min), Some(max)) => {
+                write!(f, "{} values in [", self.data.len())?;
+                write_value(&min.0, f)?;
+                write!(f, ", ")?;
+                write_value(&max.0, f)?;
+                write!(f, "]")?;
+            }
+            _ => write!(f, "no values")?,
+        }
+        if self.nulls > 0 {
+            write!(f, ", {} nulls", self.nulls)?;
+        }
+        Ok(())
+    }
+}
+
+/// Shows the number of values and their range, found with a scan if the
+/// data is not sorted.
+impl<T: PartialOrd + fmt::Display> fmt::Display for Unsorted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_range(f, |x, f| write!(f, "{x}"))
+    }
+}
+
+impl<T: PartialOrd + Float> RoundedDisplay for Unsorted<T> {
+    fn fmt_rounded(&self, policy: &RoundPolicy, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_range(f, |x, f| f.write_str(&policy.format(x.to_f64().unwrap())))
+    }
+}
+
+impl<T: PartialOrd> Default for Unsorted<T> {
+    #[inline]
+    fn default() -> Unsorted<T> {
+        Unsorted {
+            data: Vec::new(),
+            sorted: true, // empty is sorted
+            reverse_sorted: true,
+            nulls: 0,
+            stable: false,
+            parallelism: Parallelism::Auto,
+            track_order: false,
+            order: Vec::new(),
+            order_lost: false,
+        }
+    }
+}
+
+impl<T: PartialOrd + ToPrimitive> From<&Unsorted<T>> for OnlineStats {
+    /// Computes mean and variance from the buffered values, without
+    /// sorting them. NULLs are carried over.
+    fn from(v: &Unsorted<T>) -> OnlineStats {
+        let mut stats = OnlineStats::new();
+        for p in &v.data {
+            stats.add(&p.0);
+        }
+        stats.nulls = v.nulls;
+        stats
+    }
+}
+
+impl<T: PartialOrd + Clone> From<&Unsorted<T>> for MinMax<T> {
+    /// Finds the minimum and maximum of the buffered values, without
+    /// sorting them. NULLs are carried over.
+    fn from(v: &Unsorted<T>) -> MinMax<T> {
+        let mut minmax: MinMax<T> = v.data.iter().map(|p| p.0.clone()).collect();
+        minmax.extend((0..v.nulls).map(|_| None));
+        minmax
+    }
+}
+
+impl<T> From<Unsorted<T>> for UnsortedSnapshotV1<T> {
+    /// The snapshot holds the values in the order in which they were added
+    /// if that is tracked, and in stored order otherwise.
+    fn from(v: Unsorted<T>) -> UnsortedSnapshotV1<T> {
+        let stored = v.data.into_iter().map(|p| p.0);
+        let data = if v.order.is_empty() {
+            stored.collect()
+        } else {
+            let mut data: Vec<Option<T>> = std::iter::repeat_with(|| None)
+                .take(v.order.len())
+                .collect();
+            for (x, i) in stored.zip(v.order) {
+                data[i] = Some(x);
+            }
+            data.into_iter().map(Option::unwrap).collect()
+        };
+        UnsortedSnapshotV1 {
+            data,
+            nulls: v.nulls,
+        }
+    }
+}
+
+impl<T: PartialOrd> From<UnsortedSnapshotV1<T>> for Unsorted<T> {
+    fn from(v: UnsortedSnapshotV1<T>) -> Unsorted<T> {
+        let mut unsorted: Unsorted<T> = v.data.into_iter().collect();
+        unsorted.nulls = v.nulls;
+        unsorted
+    }
+}
+
+impl<T: PartialOrd> FromIterator<T> for Unsorted<T> {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = T>>(it: I) -> Unsorted<T> {
+        let mut v = Unsorted::new();
+        v.extend(it);
+        v
+    }
+}
+
+impl<T: PartialOrd> Extend<T> for Unsorted<T> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
+        let it = it.into_iter();
+        self.data.reserve(it.size_hint().0);
+        for sample in it {
+            self.push(Partial(sample));
+        }
+    }
+}
+
+impl<T: PartialOrd> FromIterator<Option<T>> for Unsorted<T> {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = Option<T>>>(it: I) -> Unsorted<T> {
+        let mut v = Unsorted::new();
+        v.extend(it);
+        v
+    }
+}
+
+impl<T: PartialOrd> Extend<Option<T>> for Unsorted<T> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = Option<T>>>(&mut self, it: I) {
+        for sample in it {
+            match sample {
+                Some(v) => self.push(Partial(v)),
+                None => self.nulls += 1,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        antimodes, mad, median, mode, modes, quartiles, FrequencyStrategy, Gaps, RankMethod,
+        Tolerance, Unsorted,
+    };
+    use crate::{Commute, MinMax, OnlineStats, Parallelism, UnsortedSnapshotV1};
+
+    #[test]
+    fn duplicate_stats() {
+        let mut data: Unsorted<u32> = [4, 1, 4, 2, 4, 3, 3, 5].into_iter().collect();
+        let stats = data.duplicate_stats().unwrap();
+        assert_eq!(
+            (stats.distinct, stats.duplicated, stats.max_count),
+            (5, 2, 3)
+        );
+        assert_eq!(stats.duplicate_ratio, 0.375);
+        let mut unique: Unsorted<u32> = (0..4).collect();
+        assert_eq!(unique.duplicate_stats().unwrap().duplicate_ratio, 0.0);
+        assert!(Unsorted::<u32>::new().duplicate_stats().is_none());
+    }
+
+    #[test]
+    fn gaps() {
+        let mut keys: Unsorted<i64> = [7, 1, 2, 2, 3, 10, 12, 4].into_iter().collect();
+        let gaps = keys.gaps(2).unwrap();
+        assert!(!gaps.is_contiguous());
+        assert_eq!((gaps.min, gaps.max), (1, 12));
+        assert_eq!(gaps.missing, 5);
+        assert_eq!(gaps.gap_count, 3);
+        assert_eq!(gaps.ranges, vec![(5, 6), (8, 9)]);
+
+        let mut extremes: Unsorted<u64> = [0, u64::MAX].into_iter().collect();
+        assert_eq!(extremes.gaps(0).unwrap().missing, u128::from(u64::MAX) - 1);
+        let mut sequence: Unsorted<u8> = (0..=255).rev().collect();
+        assert!(sequence.gaps(10).unwrap().is_contiguous());
+        assert_eq!(Unsorted::<i32>::new().gaps(1), None::<Gaps<i32>>);
+
+        let mut signed: Unsorted<i128> = [i128::MAX, i128::MIN].into_iter().collect();
+        let gaps = signed.gaps(1).unwrap();
+        assert_eq!(gaps.missing, u128::MAX - 1);
+        assert_eq!(gaps.ranges, vec![(i128::MIN + 1, i128::MAX - 1)]);
+        let mut unsigned: Unsorted<u128> = [u128::MAX, 1, u128::MAX - 2].into_iter().collect();
+        let gaps = unsigned.gaps(2).unwrap();
+        assert_eq!((gaps.min, gaps.max), (1, u128::MAX));
+        assert_eq!(gaps.missing, u128::MAX - 3);
+        assert_eq!(
+            gaps.ranges,
+            vec![(2, u128::MAX - 3), (u128::MAX - 1, u128::MAX - 1)]
+        );
+    }
+
+    #[test]
+    fn quantiles() {
+        let mut data: Unsorted<u32> = [40, 10, 30, 20, 20].into_iter().collect();
+        let q = data.quantiles();
+        assert_eq!(q.len(), 5);
+        assert_eq!(q.value_at(0.0), Some(10.0));
+        assert_eq!(q.value_at(0.5), Some(20.0));
+        assert_eq!(q.value_at(0.875), Some(35.0));
+        assert_eq!(q.value_at(1.5), None);
+        assert_eq!(q.fraction_below(&20), 0.2);
+        assert_eq!(q.fraction_at_or_below(&20), 0.6);
+        assert_eq!(q.fraction_below(&5), 0.0);
+        assert_eq!(q.fraction_at_or_below(&99), 1.0);
+        assert!(Unsorted::<u32>::new()
+            .quantiles()
+            .fraction_below(&1)
+            .is_nan());
+    }
+
+    #[test]
+    fn ranks() {
+        let data: Unsorted<f64> = [3.0, 1.0, f64::NAN, 3.0, 2.0, 3.0].into_iter().collect();
+        let ranks = |method| {
+            data.ranks(method)
+                .unwrap()
+                .into_iter()
+                .map(|r| if r.is_nan() { -1.0 } else { r })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ranks(RankMethod::Average), vec![4., 1., -1., 4., 2., 4.]);
+        assert_eq!(ranks(RankMethod::Min), vec![3., 1., -1., 3., 2., 3.]);
+        assert_eq!(ranks(RankMethod::Max), vec![5., 1., -1., 5., 2., 5.]);
+        assert_eq!(ranks(RankMethod::Dense), vec![3., 1., -1., 3., 2., 3.]);
+        assert_eq!(ranks(RankMethod::Ordinal), vec![3., 1., -1., 4., 2., 5.]);
+        assert_eq!(
+            Unsorted::<u8>::new().ranks(RankMethod::Average),
+            Some(vec![])
+        );
+
+        // ranks line up with the input positions after a sort
+        let mut untracked: Unsorted<u32> = [30, 10, 20, 10].into_iter().collect();
+        let mut sorted = untracked.clone();
+        sorted.set_track_insertion_order(true);
+        assert_eq!(sorted.median(), Some(15.0));
+        assert_eq!(
+            sorted.ranks(RankMethod::Min),
+            Some(vec![4.0, 1.0, 3.0, 1.0])
+        );
+        assert_eq!(
+            sorted.ranks(RankMethod::Ordinal),
+            Some(vec![4.0, 1.0, 3.0, 2.0])
+        );
+        untracked.median();
+        assert_eq!(untracked.ranks(RankMethod::Min), None);
+    }
+
+    #[test]
+    fn bin_assignments() {
+        let data: Unsorted<u32> = [7, 1, 9, 3, 3, 5, 8, 2].into_iter().collect();
+        assert_eq!(data.bin_assignments(4), Some(vec![2, 0, 3, 1, 1, 2, 3, 0]));
+        assert_eq!(data.bin_assignments(0), Some(vec![0; 8]));
+        assert_eq!(data.bin_assignments(8), Some(vec![5, 0, 7, 2, 2, 4, 6, 1]));
+        assert!(!data.is_sorted());
+        assert_eq!(Unsorted::<u32>::new().bin_assignments(3), Some(vec![]));
+    }
+
+    #[test]
+    fn assign_after_median() {
+        let input = [7, 1, 9, 3, 3, 5, 8, 2];
+        let expected = |values: &[u32]| {
+            let fresh: Unsorted<u32> = values.iter().copied().collect();
+            fresh.bin_assignments(4)
+        };
+        let mut untracked: Unsorted<u32> = input.into_iter().collect();
+        assert_eq!(untracked.bin_assignments(4), expected(&input));
+        untracked.median();
+        // without tracking, the order is lost once the data is sorted
+        assert_eq!(untracked.bin_assignments(4), None);
+        untracked.set_track_insertion_order(true);
+        assert_eq!(untracked.bin_assignments(4), None);
+        untracked.clear();
+        untracked.extend(input);
+        assert_eq!(untracked.bin_assignments(4), expected(&input));
+
+        // sorted input is never reordered
+        let mut ascending: Unsorted<u32> = (0..8).collect();
+        ascending.median();
+        assert_eq!(
+            ascending.bin_assignments(4),
+            expected(&[0, 1, 2, 3, 4, 5, 6, 7])
+        );
+        let json = serde_json::to_string(&ascending).unwrap();
+        let back: Unsorted<u32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.bin_assignments(4), ascending.bin_assignments(4));
+
+        let mut data = Unsorted::new();
+        data.set_track_insertion_order(true);
+        data.extend(input);
+        assert_eq!(data.median(), Some(4.0));
+        assert!(data.is_sorted());
+        assert_eq!(data.bin_assignments(4), expected(&input));
+
+        // the order survives adding, merging and filtering after the sort
+        data.add(4);
+        data.extend_from_slice(&[0, 6]);
+        data.add_sorted(10);
+        let mut other = Unsorted::new();
+        other.set_track_insertion_order(true);
+        other.extend([11, 3]);
+        other.median();
+        data.merge(other);
+        data.median();
+        let mut added = vec![7, 1, 9, 3, 3, 5, 8, 2, 4, 0, 6, 10, 11, 3];
+        assert_eq!(data.bin_assignments(4), expected(&added));
+        data.retain(|&x| x != 3);
+        added.retain(|&x| x != 3);
+        assert_eq!(data.bin_assignments(4), expected(&added));
+
+        let json = serde_json::to_string(&data).unwrap();
+        let back: Unsorted<u32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.bin_assignments(4), expected(&added));
+        assert_eq!(UnsortedSnapshotV1::from(data).data, added);
+
+        // an untracked side loses the order of the merge
+        let mut sorted: Unsorted<u32> = [3, 1, 2].into_iter().collect();
+        sorted.median();
+        let mut tracked: Unsorted<u32> = [9, 8].into_iter().collect();
+        tracked.set_track_insertion_order(true);
+        tracked.merge(sorted);
+        assert_eq!(tracked.bin_assignments(2), None);
+        // payloads from before the order could be tracked may have been
+        // sorted
+        let old: Unsorted<u32> = serde_json::from_str(r#"{"data":[1,2],"sorted":true}"#).unwrap();
+        assert_eq!(old.bin_assignments(2), None);
+
+        for invalid in [
+            r#"{"data":[1,2],"sorted":true,"track_order":true,"order":[0,0]}"#,
+            r#"{"data":[1,2],"sorted":true,"track_order":false,"order":[1,0]}"#,
+        ] {
+            assert!(serde_json::from_str::<Unsorted<u32>>(invalid).is_err());
+        }
+    }
+
+    #[test]
+    fn tracked_sort_in_place() {
+        let input: Vec<u32> = (0..1000).map(|i| (i * 7919) % 101).collect();
+        for (stable, parallelism) in [(false, Parallelism::Sequential), (true, Parallelism::Force)]
+        {
+            let mut data = Unsorted::with_capacity(2000);
+            data.set_stable(stable);
+            data.set_parallelism(parallelism);
+            data.set_track_insertion_order(true);
+            data.extend(input.iter().copied());
+            let buffer = data.data.as_ptr();
+            assert!(data.as_sorted_slice().windows(2).all(|w| w[0] <= w[1]));
+            assert_eq!(data.data.as_ptr(), buffer);
+            assert_eq!(
+                data.insertion_ordered(),
+                Some(input.iter().collect::<Vec<_>>())
+            );
+        }
+    }
+
+    #[test]
+    fn into_other_accumulators() {
+        let unsorted: Unsorted<i32> = [Some(4), None, Some(-2), Some(7)].into_iter().collect();
+        let stats = OnlineStats::from(&unsorted);
+        assert_eq!(stats.len(), 3);
+        assert_eq!(stats.mean(), 3.0);
+        assert_eq!(stats.null_count(), 1);
+        let minmax = MinMax::from(&unsorted);
+        assert_eq!((minmax.min(), minmax.max()), (Some(&-2), Some(&7)));
+        assert_eq!(minmax.len(), 3);
+    }
+
+    #[test]
+    fn snapshot_v1() {
+        let unsorted: Unsorted<u32> = [Some(5), None, Some(1), Some(3)].into_iter().collect();
+        let snap = UnsortedSnapshotV1::from(unsorted.clone());
+        assert_eq!(snap.nulls, 1);
+        let mut restored = Unsorted::from(snap);
+        assert_eq!(restored.len(), unsorted.len());
+        assert_eq!(restored.null_count(), 1);
+        assert_eq!(restored.median(), Some(3.0));
+    }
+
+    #[test]
+    fn median_stream() {
+        assert_eq!(median(vec![3usize, 5, 7, 9].into_iter()), Some(6.0));
+        assert_eq!(median(vec![3usize, 5, 7].into_iter()), Some(5.0));
+    }
+
+    #[test]
+    fn mad_stream() {
+        assert_eq!(mad(vec![3usize, 5, 7, 9].into_iter(), None), Some(2.0));
+        assert_eq!(
+            mad(
+                vec![
+                    86usize, 60, 95, 39, 49, 12, 56, 82, 92, 24, 33, 28, 46, 34, 100, 39, 100, 38,
+                    50, 61, 39, 88, 5, 13, 64
+                ]
+                .into_iter(),
+                None
+            ),
+            Some(16.0)
+        );
+    }
+
+    #[test]
+    fn mad_stream_precalc_median() {
+        let data = vec![3usize, 5, 7, 9].into_iter();
+        let median1 = median(data.clone());
+        assert_eq!(mad(data, median1), Some(2.0));
+
+        let data2 = vec![
+            86usize, 60, 95, 39, 49, 12, 56, 82, 92, 24, 33, 28, 46, 34, 100, 39, 100, 38, 50, 61,
+            39, 88, 5, 13, 64,
+        ]
+        .into_iter();
+        let median2 = median(data2.clone());
+        assert_eq!(mad(data2, median2), Some(16.0));
+    }
+
+    #[test]
+    fn abs_deviation_quantiles() {
+        let mut data: Unsorted<i32> = [1, 2, 3, 4, 100].into_iter().collect();
+        let median = data.median().unwrap();
+        let q = data
+            .abs_deviation_quantiles(&[0.0, 0.5, 0.75, 1.0, 1.5], median)
+            .unwrap();
+        assert_eq!(q[..4], [0.0, 1.0, 2.0, 97.0]);
+        assert!(q[4].is_nan());
+        assert_eq!(Some(q[1]), data.mad(None));
+
+        // around the mean of 22
+        let q = data.abs_deviation_quantiles(&[0.5], 22.0).unwrap();
+        assert_eq!(q, vec![20.0]);
+        assert_eq!(
+            Unsorted::<i32>::new().abs_deviation_quantiles(&[0.5], 0.0),
+            None
+        );
+    }
+
+    #[test]
+    fn robust_describe() {
+        let mut data: Unsorted<i32> = [1, 2, 3, 4, 5, 6, 7, 8, 9, 1000].into_iter().collect();
+        let robust = data.robust_describe(0.1).unwrap();
+        assert_eq!(robust.len, 10);
+        // 2..=9 after trimming one value from each end
+        assert_eq!(robust.trimmed_mean, 5.5);
+        // 2, 2..=9, 9 after winsorizing
+        let expected = OnlineStats::from_slice(&[2, 2, 3, 4, 5, 6, 7, 8, 9, 9]);
+        assert!((robust.winsorized_stddev - expected.stddev()).abs() < 1e-12);
+        assert_eq!(robust.median, 5.5);
+        assert_eq!(robust.mad, 2.5);
+        assert_eq!((robust.q1, robust.q3, robust.iqr), (3.0, 8.0, 5.0));
+        assert_eq!((robust.lower_fence, robust.upper_fence), (-4.5, 15.5));
+
+        let plain = data.robust_describe(0.0).unwrap();
+        assert_eq!(plain.trimmed_mean, 104.5);
+        assert!(Unsorted::from_vec(vec![1, 2])
+            .robust_describe(0.1)
+            .is_none());
+        for trim_fraction in [-0.1, 0.5, 1.0, f64::NAN] {
+            assert!(data.robust_describe(trim_fraction).is_none());
+        }
+    }
+
+    #[test]
+    fn display() {
+        let mut data: Unsorted<i32> = [3, 1, 2].into_iter().collect();
+        data.extend([None]);
+        assert_eq!(data.to_string(), "3 values in [1, 3], 1 nulls");
+        assert_eq!(
+            format!("{data:?}"),
+            "Unsorted { len: 3, nulls: 1, sorted: false, .. }"
+        );
+        data.median();
+        assert_eq!(data.to_string(), "3 values in [1, 3], 1 nulls");
+        assert_eq!(Unsorted::<i32>::new().to_string(), "no values");
+    }
+
+    #[test]
+    fn mean_abs_deviation() {
+        let data: Unsorted<i32> = [2, 4, 4, 4, 5, 5, 7, 9].into_iter().collect();
+        // |x - 5| = 3, 1, 1, 1, 0, 0, 2, 4
+        assert_eq!(data.mean_abs_deviation(), Some(1.5));
+        assert_eq!(Unsorted::<f64>::new().mean_abs_deviation(), None);
+    }
+
+    #[test]
+    fn mode_stream() {
+        assert_eq!(mode(vec![3usize, 5, 7, 9].into_iter()), None);
+        assert_eq!(mode(vec![3usize, 3, 3, 3].into_iter()), Some(3));
+        assert_eq!(mode(vec![3usize, 3, 3, 4].into_iter()), Some(3));
+        assert_eq!(mode(vec![4usize, 3, 3, 3].into_iter()), Some(3));
+        assert_eq!(mode(vec![1usize, 1, 2, 3, 3].into_iter()), None);
+        // a shorter run after a tie must not become the mode
+        assert_eq!(mode(vec![1usize, 1, 1, 2, 2, 2, 3, 3].into_iter()), None);
+        assert_eq!(mode(vec![1usize, 1, 2, 2, 3, 3, 3].into_iter()), Some(3));
+        assert_eq!(mode(vec![1usize, 1, 1, 2, 2, 3, 3].into_iter()), Some(1));
+    }
+
+    #[test]
+    fn median_floats() {
+        assert_eq!(median(vec![3.0f64, 5.0, 7.0, 9.0].into_iter()), Some(6.0));
+        assert_eq!(median(vec![3.0f64, 5.0, 7.0].into_iter()), Some(5.0));
+        assert_eq!(median(vec![1.0f64, 2.5, 3.0].into_iter()), Some(2.5));
+    }
+
+    #[test]
+    fn mode_floats() {
+        assert_eq!(mode(vec![3.0f64, 5.0, 7.0, 9.0].into_iter()), None);
+        assert_eq!(mode(vec![3.0f64, 3.0, 3.0, 3.0].into_iter()), Some(3.0));
+        assert_eq!(mode(vec![3.0f64, 3.0, 3.0, 4.0].into_iter()), Some(3.0));
+        assert_eq!(mode(vec![4.0f64, 3.0, 3.0, 3.0].into_iter()), Some(3.0));
+        assert_eq!(mode(vec![1.0f64, 1.0, 2.0, 3.0, 3.0].into_iter()), None);
+    }
+
+    #[test]
+    fn modes_stream() {
+        assert_eq!(modes(vec![3usize, 5, 7, 9].into_iter()), (vec![], 0, 0));
+        assert_eq!(modes(vec![3usize, 3, 3, 3].into_iter()), (vec![3], 1, 4));
+        assert_eq!(modes(vec![3usize, 3, 4, 4].into_iter()), (vec![3, 4], 2, 2));
+        assert_eq!(modes(vec![4usize, 3, 3, 3].into_iter()), (vec![3], 1, 3));
+        assert_eq!(modes(vec![1usize, 1, 2, 2].into_iter()), (vec![1, 2], 2, 2));
+        let vec: Vec<u32> = vec![];
+        assert_eq!(modes(vec.into_iter()), (vec![], 0, 0));
     }
 
     #[test]
@@ -756,4 +2686,312 @@ mod test {
             Some((5., 9., 20.))
         );
     }
+
+    #[test]
+    fn options_as_nulls() {
+        let mut v1: Unsorted<usize> = vec![Some(3usize), None, Some(5), None]
+            .into_iter()
+            .collect();
+        assert_eq!(v1.len(), 2);
+        assert_eq!(v1.null_count(), 2);
+        assert_eq!(v1.median(), Some(4.0));
+
+        let v2: Unsorted<usize> = vec![None, Some(7usize)].into_iter().collect();
+        v1.merge(v2);
+        assert_eq!(v1.len(), 3);
+        assert_eq!(v1.null_count(), 3);
+
+        let mut old: Unsorted<u32> =
+            serde_json::from_str(r#"{"data":[2,1,3],"sorted":false}"#).unwrap();
+        assert_eq!((old.null_count(), old.median()), (0, Some(2.0)));
+        assert_eq!(v1.median(), Some(5.0));
+    }
+
+    #[test]
+    fn capacity() {
+        let mut v: Unsorted<usize> = Unsorted::with_capacity(100);
+        v.extend(vec![3usize, 5, 7, 9]);
+        assert!(v.mem_usage() >= 100 * std::mem::size_of::<usize>());
+        v.shrink_to_fit();
+        assert!(v.mem_usage() < 100 * std::mem::size_of::<usize>());
+        assert_eq!(v.len(), 4);
+        assert_eq!(v.median(), Some(6.0));
+        assert_eq!(Unsorted::<usize>::new().len(), 0);
+    }
+
+    #[test]
+    fn presorted_input() {
+        let mut asc: Unsorted<usize> = (0..100).collect();
+        assert!(asc.is_sorted());
+        assert_eq!(asc.median(), Some(49.5));
+
+        let mut desc: Unsorted<usize> = (0..100).rev().collect();
+        assert!(!desc.is_sorted());
+        assert_eq!(desc.quartiles(), asc.quartiles());
+        assert!(desc.is_sorted());
+        assert_eq!(desc.mode(), None);
+
+        let mut ties: Unsorted<f64> = vec![3.0, 3.0, 2.0, 2.0, 1.0].into_iter().collect();
+        assert_eq!(ties.modes(), (vec![2.0, 3.0], 2, 2));
+
+        let mut mixed: Unsorted<usize> = vec![1usize, 3, 2].into_iter().collect();
+        assert!(!mixed.is_sorted());
+        mixed.add(0);
+        assert_eq!(mixed.median(), Some(1.5));
+
+        let nan: Unsorted<f64> = vec![1.0, f64::NAN, 2.0].into_iter().collect();
+        assert!(!nan.is_sorted());
+    }
+
+    #[test]
+    fn extend_from_slice() {
+        let mut v: Unsorted<u32> = Unsorted::new();
+        v.extend_from_slice(&[1, 2, 2, 5]);
+        v.extend_from_slice(&[]);
+        v.extend_from_slice(&[5, 9]);
+        assert!(v.is_sorted());
+        v.extend_from_slice(&[3]);
+        assert!(!v.is_sorted());
+        assert_eq!(v.as_sorted_slice(), &[1, 2, 2, 3, 5, 5, 9]);
+
+        let mut nan: Unsorted<f64> = Unsorted::new();
+        nan.extend_from_slice(&[1.0, f64::NAN]);
+        assert!(!nan.is_sorted());
+        assert_eq!(nan.len(), 2);
+    }
+
+    #[test]
+    fn add_sorted() {
+        let mut v: Unsorted<u32> = vec![5, 1, 3].into_iter().collect();
+        assert!(!v.is_sorted());
+        v.add_sorted(4);
+        assert!(v.is_sorted());
+        assert_eq!(v.median(), Some(3.5));
+        v.add_sorted(0);
+        v.add_sorted(9);
+        v.add_sorted(3);
+        assert!(v.is_sorted());
+        assert_eq!(v.as_sorted_slice(), &[0, 1, 3, 3, 4, 5, 9]);
+        assert_eq!(v.median(), Some(3.0));
+
+        let mut empty: Unsorted<f64> = Unsorted::new();
+        empty.add_sorted(2.0);
+        assert_eq!(empty.median(), Some(2.0));
+    }
+
+    #[test]
+    fn merge_sorted_sets() {
+        let mut v1: Unsorted<usize> = vec![1usize, 4, 6, 9].into_iter().collect();
+        let v2: Unsorted<usize> = vec![8usize, 5, 3, 2].into_iter().collect();
+        v1.merge(v2);
+        assert!(v1.is_sorted());
+        assert_eq!(v1.median(), Some(4.5));
+        assert_eq!(v1.as_sorted_slice(), &[1, 2, 3, 4, 5, 6, 8, 9]);
+
+        // disjoint ranges are appended without interleaving
+        let v3: Unsorted<usize> = vec![10usize, 11].into_iter().collect();
+        v1.merge(v3);
+        assert!(v1.is_sorted());
+        assert_eq!(v1.len(), 10);
+
+        let v4: Unsorted<usize> = vec![7usize, 0, 12].into_iter().collect();
+        v1.merge(v4);
+        assert!(!v1.is_sorted());
+        assert_eq!(v1.quartiles(), Some((2.5, 6., 9.5)));
+    }
+
+    #[test]
+    fn sorted_views() {
+        let mut v: Unsorted<String> = vec!["b", "c", "a"].into_iter().map(String::from).collect();
+        assert_eq!(v.as_sorted_slice(), &["a", "b", "c"]);
+        assert_eq!(v.into_sorted_vec(), vec!["a", "b", "c"]);
+
+        let v: Unsorted<f64> = vec![3.0, 1.0, 2.0].into_iter().collect();
+        assert_eq!(v.into_sorted_vec(), vec![1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn buffer_reuse() {
+        let mut v: Unsorted<usize> = vec![3usize, 1, 2].into_iter().collect();
+        assert_eq!(v.median(), Some(2.0));
+        let capacity = v.mem_usage();
+        assert_eq!(v.drain().collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(v.len(), 0);
+        assert_eq!(v.mem_usage(), capacity);
+
+        v.extend(vec![5usize, 4]);
+        v.clear();
+        assert_eq!(v.len(), 0);
+        assert_eq!(v.median(), None);
+
+        v.extend(vec![9usize, 7]);
+        let mut buf = v.into_vec();
+        buf.clear();
+        buf.extend([4usize, 5, 6]);
+        let mut recycled = Unsorted::from_vec(buf);
+        assert!(recycled.is_sorted());
+        assert_eq!(recycled.median(), Some(5.0));
+    }
+
+    #[test]
+    fn retain_sentinels() {
+        let mut v: Unsorted<i64> = vec![3i64, -9999, 5, 7, -9999, 9].into_iter().collect();
+        v.retain(|&x| x != -9999);
+        assert_eq!(v.len(), 4);
+        assert_eq!(v.median(), Some(6.0));
+
+        v.retain(|&x| x > 3);
+        assert!(v.is_sorted());
+        assert_eq!(v.as_sorted_slice(), &[5, 7, 9]);
+    }
+
+    #[test]
+    fn mapped_stats() {
+        let mut v: Unsorted<f64> = vec![1.0, 4.0, 9.0, 16.0].into_iter().collect();
+        assert_eq!(v.map_stats(f64::sqrt).median(), Some(2.5));
+        assert_eq!(v.map_stats(|x| -x).median(), Some(-6.5));
+        assert_eq!(v.map_stats(f64::sqrt).quartiles(), Some((1.5, 2.5, 3.5)));
+        assert_eq!(v.map_stats(f64::sqrt).mad(None), Some(1.0));
+
+        // abs is not monotonic over data with both signs
+        let mut v: Unsorted<i64> = vec![-3i64, -1, 2, 5].into_iter().collect();
+        assert_eq!(v.map_stats(f64::abs).median(), Some(2.5));
+        assert_eq!(v.map_stats(f64::abs).quartiles(), Some((1.5, 2.5, 4.)));
+    }
+
+    #[test]
+    fn modes_antimodes_with_counts() {
+        let mut v: Unsorted<usize> = vec![1usize, 1, 2, 2, 3, 4, 4].into_iter().collect();
+        assert_eq!(v.modes_with_counts(), vec![(1, 2), (2, 2), (4, 2)]);
+        assert_eq!(v.antimodes_with_counts(), vec![(3, 1)]);
+
+        let mut empty: Unsorted<usize> = Unsorted::new();
+        assert_eq!(empty.modes_with_counts(), vec![]);
+        assert_eq!(empty.antimodes_with_counts(), vec![]);
+    }
+
+    #[test]
+    fn tied_modes_ascending() {
+        let expected = (vec![1, 4, 7], 3, 2);
+        for data in [[7, 1, 4, 7, 1, 4], [4, 4, 1, 7, 7, 1], [7, 7, 4, 4, 1, 1]] {
+            assert_eq!(modes(data.into_iter()), expected);
+            assert_eq!(antimodes(data.into_iter()), expected);
+        }
+    }
+
+    #[test]
+    fn stable_sort_keeps_first_equal() {
+        let mut v: Unsorted<f64> = Unsorted::new();
+        v.set_stable(true);
+        v.extend([1.0, 0.0, -0.0, -0.0, 0.0]);
+        let (modes, _, count) = v.modes();
+        assert_eq!(count, 4);
+        assert!(modes[0].is_sign_positive());
+        assert_eq!(v.mode().map(f64::is_sign_positive), Some(true));
+
+        // descending input is not simply reversed
+        let mut v: Unsorted<f64> = Unsorted::new();
+        v.set_stable(true);
+        v.extend([1.0, -0.0, 0.0]);
+        assert!(v.as_sorted_slice()[0].is_sign_negative());
+    }
+
+    #[test]
+    fn frequency_strategies_agree() {
+        let cases: [&[u32]; 6] = [
+            &[],
+            &[1],
+            &[3, 1, 2, 3, 1],
+            &[5, 5, 5, 2, 9, 2],
+            &[4, 1, 4, 1, 7, 7, 2],
+            &[1, 2, 2, 3],
+        ];
+        for data in cases {
+            for strategy in [
+                FrequencyStrategy::Auto,
+                FrequencyStrategy::Sort,
+                FrequencyStrategy::Hash,
+            ] {
+                let mut sorted: Unsorted<u32> = data.iter().copied().collect();
+                let mut v: Unsorted<u32> = data.iter().copied().collect();
+                assert_eq!(v.mode_with(strategy), sorted.mode(), "{data:?}");
+                assert_eq!(v.modes_with(strategy), sorted.modes(), "{data:?}");
+                assert_eq!(
+                    v.cardinality_with(strategy),
+                    sorted.cardinality(),
+                    "{data:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn hash_strategy_does_not_sort() {
+        let mut v: Unsorted<u32> = vec![3, 1, 3, 2].into_iter().collect();
+        assert_eq!(v.mode_with(FrequencyStrategy::Hash), Some(3));
+        assert_eq!(v.cardinality_with(FrequencyStrategy::Hash), 3);
+        assert!(!v.is_sorted());
+    }
+
+    #[test]
+    fn auto_strategy_adapts() {
+        // low cardinality is counted with a hash map, without sorting
+        let mut low: Unsorted<u32> = (0..10_000).map(|i| (i * 7) % 5).collect();
+        assert_eq!(low.cardinality_with(FrequencyStrategy::Auto), 5);
+        assert!(!low.is_sorted());
+
+        // high cardinality is sorted, and the sort is kept for later
+        let mut high: Unsorted<u32> = (0..10_000).map(|i| (i * 7919) % 10_007).collect();
+        assert_eq!(high.cardinality_with(FrequencyStrategy::Auto), 10_000);
+        assert!(high.is_sorted());
+    }
+
+    #[test]
+    fn approx_modes() {
+        let mut v: Unsorted<f64> = vec![3.000_000_1, 1.5, 3.0, 2.0, 2.999_999_9, 1.5]
+            .into_iter()
+            .collect();
+        assert_eq!(v.mode(), Some(1.5));
+        assert_eq!(v.cardinality_approx(Tolerance::Absolute(0.0)), 5);
+
+        let (modes, count, occurrences) = v.modes_approx(Tolerance::Absolute(1e-6));
+        assert_eq!((count, occurrences), (1, 3));
+        assert!((modes[0] - 3.0).abs() < 1e-9);
+        assert!((v.mode_approx(Tolerance::Relative(1e-6)).unwrap() - 3.0).abs() < 1e-9);
+        assert_eq!(v.cardinality_approx(Tolerance::Absolute(1e-6)), 3);
+
+        // groups are anchored on their first value, so they do not chain
+        let mut v: Unsorted<f64> = vec![1.0, 1.4, 1.8, 2.2].into_iter().collect();
+        assert_eq!(v.cardinality_approx(Tolerance::Absolute(0.5)), 2);
+    }
+
+    #[test]
+    fn approx_modes_ulps() {
+        let x = 0.1_f64 + 0.2;
+        let mut v: Unsorted<f64> = vec![0.3, x, -0.0, 0.0, 7.0].into_iter().collect();
+        assert_eq!(v.cardinality_approx(Tolerance::Ulps(0)), 4);
+        assert_eq!(v.cardinality_approx(Tolerance::Ulps(1)), 3);
+        assert_eq!(v.modes_approx(Tolerance::Ulps(1)).1, 2);
+        assert_eq!(v.mode_approx(Tolerance::Ulps(1)), None);
+    }
+
+    #[test]
+    fn parallelism_settings_agree() {
+        let data: Vec<u32> = (0..20_000).map(|i| i * 7919 % 1009).collect();
+        let mut expected: Unsorted<u32> = data.iter().copied().collect();
+        expected.set_parallelism(Parallelism::Sequential);
+        for parallelism in [
+            Parallelism::Auto,
+            Parallelism::Force,
+            Parallelism::Threshold(100_000),
+        ] {
+            let mut v: Unsorted<u32> = data.iter().copied().collect();
+            v.set_parallelism(parallelism);
+            assert_eq!(v.parallelism(), parallelism);
+            assert_eq!(v.cardinality(), expected.cardinality());
+            assert_eq!(v.median(), expected.median());
+            assert_eq!(v.mad(None), expected.mad(None));
+        }
+        assert_eq!(expected.cardinality(), 1009);
+    }
 }
@@ -0,0 +1,226 @@
+use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+
+use crate::{Commute, Partial};
+
+/// A commutative data structure for robust statistics of weighted samples.
+///
+/// Each sample carries a weight, e.g. a survey weight or the number of
+/// records it stands for. The median is the value at which half of the
+/// total weight lies on either side, the MAD is the weighted median of the
+/// absolute deviations from it, and the mode is the value with the
+/// largest total weight. With every weight equal to `1`, these match the
+/// statistics of `Unsorted`.
+///
+/// Samples are buffered, like `Unsorted`, and sorted when a statistic is
+/// computed. Samples with a weight that is not positive and finite are
+/// ignored.
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
+pub struct WeightedUnsorted<T> {
+    data: Vec<(Partial<T>, f64)>,
+    sorted: bool,
+    total: f64,
+}
+
+impl<T: PartialOrd> WeightedUnsorted<T> {
+    /// Create initial empty state.
+    #[must_use]
+    pub fn new() -> WeightedUnsorted<T> {
+        Default::default()
+    }
+
+    /// Add a sample with the given weight.
+    #[inline]
+    pub fn add(&mut self, sample: T, weight: f64) {
+        if weight > 0.0 && weight.is_finite() {
+            self.sorted = false;
+            self.total += weight;
+            self.data.push((Partial(sample), weight));
+        }
+    }
+
+    /// Returns the number of samples.
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns true if there are no samples.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Returns the total weight of the samples.
+    #[inline]
+    #[must_use]
+    pub const fn total_weight(&self) -> f64 {
+        self.total
+    }
+
+    fn sort(&mut self) {
+        if !self.sorted {
+            self.data.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+            self.sorted = true;
+        }
+    }
+}
+
+impl<T: PartialOrd + ToPrimitive> WeightedUnsorted<T> {
+    /// Returns the weighted median of the data.
+    ///
+    /// If the values below some point hold exactly half of the total
+    /// weight, the median is the midpoint between the values on either
+    /// side, as for an even number of unweighted samples.
+    pub fn median(&mut self) -> Option<f64> {
+        self.sort();
+        weighted_median(
+            self.data.iter().map(|(x, w)| (x.0.to_f64().unwrap(), *w)),
+            self.total,
+        )
+    }
+
+    /// Returns the weighted MAD of the data: the weighted median of the
+    /// absolute deviations from the weighted median.
+    pub fn mad(&mut self, existing_median: Option<f64>) -> Option<f64> {
+        let median = match existing_median {
+            Some(median) => median,
+            None => self.median()?,
+        };
+        let mut deviations: Vec<(f64, f64)> = self
+            .data
+            .iter()
+            .map(|(x, w)| ((x.0.to_f64().unwrap() - median).abs(), *w))
+            .collect();
+        deviations.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+        weighted_median(deviations.into_iter(), self.total)
+    }
+}
+
+impl<T: PartialOrd + Clone> WeightedUnsorted<T> {
+    /// Returns the value with the largest total weight.
+    ///
+    /// As with `Unsorted::mode`, `None` is returned if there is a tie for
+    /// the largest total weight.
+    pub fn mode(&mut self) -> Option<T> {
+        self.sort();
+        let mut best: Option<(&T, f64)> = None;
+        let mut tied = false;
+        let mut i = 0;
+        while i < self.data.len() {
+            let value = &self.data[i].0;
+            let mut weight = 0.0;
+            while i < self.data.len() && self.data[i].0 == *value {
+                weight += self.data[i].1;
+                i += 1;
+            }
+            match best {
+                Some((_, w)) if weight < w => {}
+                Some((_, w)) if weight == w => tied = true,
+                _ => {
+                    best = Some((&value.0, weight));
+                    tied = false;
+                }
+            }
+        }
+        if tied {
+            None
+        } else {
+            best.map(|(v, _)| v.clone())
+        }
+    }
+}
+
+/// Returns the weighted median of `(value, weight)` pairs that are sorted
+/// by value, with weights summing to `total`.
+fn weighted_median<I: Iterator<Item = (f64, f64)>>(mut it: I, total: f64) -> Option<f64> {
+    let half = total / 2.0;
+    let mut cumulative = 0.0;
+    while let Some((x, w)) = it.next() {
+        cumulative += w;
+        if cumulative > half {
+            return Some(x);
+        }
+        if cumulative == half {
+            // exactly half of the weight is at or below `x`
+            return Some(it.next().map_or(x, |(next, _)| (x + next) / 2.0));
+        }
+    }
+    None
+}
+
+impl<T: PartialOrd> Commute for WeightedUnsorted<T> {
+    #[inline]
+    fn merge(&mut self, v: WeightedUnsorted<T>) {
+        self.sorted = false;
+        self.total += v.total;
+        self.data.extend(v.data);
+    }
+}
+
+impl<T: PartialOrd> Default for WeightedUnsorted<T> {
+    #[inline]
+    fn default() -> WeightedUnsorted<T> {
+        WeightedUnsorted {
+            data: Vec::new(),
+            sorted: true,
+            total: 0.0,
+        }
+    }
+}
+
+impl<T: PartialOrd> FromIterator<(T, f64)> for WeightedUnsorted<T> {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = (T, f64)>>(it: I) -> WeightedUnsorted<T> {
+        let mut v = WeightedUnsorted::new();
+        v.extend(it);
+        v
+    }
+}
+
+impl<T: PartialOrd> Extend<(T, f64)> for WeightedUnsorted<T> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = (T, f64)>>(&mut self, it: I) {
+        for (sample, weight) in it {
+            self.add(sample, weight);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::WeightedUnsorted;
+    use crate::{mad, median, Commute};
+
+    #[test]
+    fn unit_weights_match_unsorted() {
+        for data in [vec![3, 1, 4, 1, 5, 9, 2, 6], vec![7, 2, 2, 8, 1]] {
+            let mut weighted: WeightedUnsorted<i32> = data.iter().map(|&x| (x, 1.0)).collect();
+            assert_eq!(weighted.median(), median(data.iter().copied()));
+            assert_eq!(weighted.mad(None), mad(data.iter().copied(), None));
+        }
+    }
+
+    #[test]
+    fn weighted_stats() {
+        let mut weighted: WeightedUnsorted<u32> =
+            [(1, 1.0), (2, 1.0), (10, 5.0), (20, 0.0), (30, 1.0)]
+                .into_iter()
+                .collect();
+        assert_eq!(weighted.len(), 4);
+        assert_eq!(weighted.total_weight(), 8.0);
+        assert_eq!(weighted.median(), Some(10.0));
+        // deviations 9, 8, 0 (weight 5) and 20
+        assert_eq!(weighted.mad(None), Some(0.0));
+        assert_eq!(weighted.mode(), Some(10));
+
+        let mut other = WeightedUnsorted::new();
+        other.add(2, 4.0);
+        weighted.merge(other);
+        assert_eq!(weighted.mode(), None);
+        assert_eq!(weighted.median(), Some(6.0));
+        assert_eq!(WeightedUnsorted::<u32>::new().median(), None);
+    }
+}